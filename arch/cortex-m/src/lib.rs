@@ -5,6 +5,7 @@
 #![feature(asm, const_fn, lang_items)]
 #![no_std]
 
+pub mod mpu;
 pub mod nvic;
 pub mod scb;
 pub mod support;
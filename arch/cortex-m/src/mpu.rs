@@ -1,5 +1,13 @@
-//! Implementation of the memory protection unit for the Cortex-M3 and
-//! Cortex-M4..
+//! Implementation of the memory protection unit for the Cortex-M0+, Cortex-M3
+//! and Cortex-M4.
+//!
+//! The ARMv6-M MPU, an optional part of the Cortex-M0+, has the same registers
+//! as the ARMv7-M MPU of the Cortex-M3 and Cortex-M4, but its regions must be
+//! at least 256 bytes instead of 32. The smallest region is therefore a
+//! parameter of the MPU: `MPU::new()` creates an ARMv7-M MPU and ARMv6-M chips
+//! use `MPU::with_min_region_size(256)`. Regions smaller than that are made
+//! with subregions, which are at least 32 bytes. The Cortex-M0 has no MPU, and
+//! chips with it should use `()` as their MPU.
 
 use core::cmp;
 use kernel;
@@ -8,9 +16,11 @@ use kernel::common::registers::{register_bitfields, FieldValue, ReadOnly, ReadWr
 use kernel::common::StaticRef;
 use kernel::mpu;
 
-/// MPU Registers for the Cortex-M3 and Cortex-M4 families
+/// MPU Registers for the Cortex-M0+, Cortex-M3 and Cortex-M4 families
 /// Described in section 4.5 of
 /// <http://infocenter.arm.com/help/topic/com.arm.doc.dui0553a/DUI0553A_cortex_m4_dgug.pdf>
+/// and
+/// <http://infocenter.arm.com/help/topic/com.arm.doc.dui0662b/DUI0662B_cortex_m0p_r0p1_dgug.pdf>
 #[repr(C)]
 pub struct MpuRegisters {
     /// Indicates whether the MPU is present and, if so, how many regions it
@@ -44,7 +54,7 @@ register_bitfields![u32,
         DREGION OFFSET(8) NUMBITS(8) [],
         /// Indicates whether the processor support unified (0) or separate
         /// (1) instruction and data regions. Always reads 0 on the
-        /// Cortex-M4.
+        /// Cortex-M0+ and Cortex-M4.
         SEPARATE OFFSET(0) NUMBITS(1) []
     ],
 
@@ -75,7 +85,9 @@ register_bitfields![u32,
     ],
 
     RegionBaseAddress [
-        /// Base address of the currently selected MPU region.
+        /// Base address of the currently selected MPU region. Bits below the
+        /// region size are ignored, which on ARMv6-M is always at least bits
+        /// 7:5.
         ADDR OFFSET(5) NUMBITS(27) [],
         /// MPU Region Number valid bit.
         VALID OFFSET(4) NUMBITS(1) [
@@ -109,7 +121,8 @@ register_bitfields![u32,
         ],
         /// Subregion disable bits
         SRD OFFSET(8) NUMBITS(8) [],
-        /// Specifies the region size, being 2^(SIZE+1) (minimum 3)
+        /// Specifies the region size, being 2^(SIZE+1) (minimum 4 on ARMv7-M and 7 on
+        /// ARMv6-M)
         SIZE OFFSET(1) NUMBITS(5) [],
         /// Enables the region
         ENABLE OFFSET(0) NUMBITS(1) []
@@ -119,12 +132,26 @@ register_bitfields![u32,
 const MPU_BASE_ADDRESS: StaticRef<MpuRegisters> =
     unsafe { StaticRef::new(0xE000ED90 as *const MpuRegisters) };
 
-/// Constructor field is private to limit who can create a new MPU
-pub struct MPU(StaticRef<MpuRegisters>);
+/// Constructor fields are private to limit who can create a new MPU
+pub struct MPU {
+    registers: StaticRef<MpuRegisters>,
+    min_region_size: usize,
+}
 
 impl MPU {
+    /// Creates the MPU of an ARMv7-M core, whose regions are at least 32
+    /// bytes.
     pub const unsafe fn new() -> MPU {
-        MPU(MPU_BASE_ADDRESS)
+        MPU::with_min_region_size(32)
+    }
+
+    /// Creates an MPU whose regions are at least `min_region_size` bytes,
+    /// which must be a power of two of at least 32. ARMv6-M cores need 256.
+    pub const unsafe fn with_min_region_size(min_region_size: usize) -> MPU {
+        MPU {
+            registers: MPU_BASE_ADDRESS,
+            min_region_size: min_region_size,
+        }
     }
 }
 
@@ -285,7 +312,7 @@ impl kernel::mpu::MPU for MPU {
     type MpuConfig = CortexMConfig;
 
     fn enable_mpu(&self) {
-        let regs = &*self.0;
+        let regs = &*self.registers;
 
         // Enable the MPU, disable it during HardFault/NMI handlers, and allow
         // privileged code access to all unprotected memory.
@@ -294,12 +321,12 @@ impl kernel::mpu::MPU for MPU {
     }
 
     fn disable_mpu(&self) {
-        let regs = &*self.0;
+        let regs = &*self.registers;
         regs.ctrl.write(Control::ENABLE::CLEAR);
     }
 
     fn number_total_regions(&self) -> usize {
-        let regs = &*self.0;
+        let regs = &*self.registers;
         regs.mpu_type.read(Type::DREGION) as usize
     }
 
@@ -324,7 +351,8 @@ impl kernel::mpu::MPU for MPU {
         let mut start = unallocated_memory_start as usize;
         let mut size = min_region_size;
 
-        // Region start always has to align to 32 bytes
+        // Region start always has to align to 32 bytes, the smallest
+        // subregion
         if start % 32 != 0 {
             start += 32 - (start % 32);
         }
@@ -339,10 +367,11 @@ impl kernel::mpu::MPU for MPU {
         let mut region_size = size;
         let mut subregions = None;
 
-        // We can only create an MPU region if the size is a power of two and it divides
-        // the start address. If this is not the case, the first thing we try to do to
-        // cover the memory region is to use a larger MPU region and expose certain subregions.
-        if size.count_ones() > 1 || start % size != 0 {
+        // We can only create an MPU region if the size is a power of two of at least the
+        // minimum region size and it divides the start address. If this is not the case, the
+        // first thing we try to do to cover the memory region is to use a larger MPU region
+        // and expose certain subregions.
+        if size < self.min_region_size || size.count_ones() > 1 || start % size != 0 {
             // Which (power-of-two) subregion size would align with the start
             // address?
             //
@@ -350,17 +379,21 @@ impl kernel::mpu::MPU for MPU {
             // address with exactly one bit:
             //
             //      1 << (start.trailing_zeros())
+            //
+            // Subregions larger than an eighth of `size` rounded up to 256
+            // bytes are not needed, and would only make the region larger.
             let subregion_size = {
+                let mut ceil = math::closest_power_of_two(size as u32) as usize;
+                if ceil < 256 {
+                    ceil = 256
+                }
+
                 let tz = start.trailing_zeros();
                 if tz < 32 {
                     // Find the largest power of two that divides `start`
-                    (1 as usize) << tz
+                    cmp::min((1 as usize) << tz, ceil / 8)
                 } else {
                     // This case means `start` is 0.
-                    let mut ceil = math::closest_power_of_two(size as u32) as usize;
-                    if ceil < 256 {
-                        ceil = 256
-                    }
                     ceil / 8
                 }
             };
@@ -399,10 +432,16 @@ impl kernel::mpu::MPU for MPU {
                 subregions = Some((min_subregion, max_subregion));
             } else {
                 // In this case, we can't use subregions to solve the alignment
-                // problem. Instead, we round up `size` to a power of two and
-                // shift `start` up in memory to make it align with `size`.
-                size = math::closest_power_of_two(size as u32) as usize;
-                start += size - (start % size);
+                // problem. Instead, we round up `size` to a power of two of at
+                // least the minimum region size and shift `start` up in memory
+                // to make it align with `size`.
+                size = cmp::max(
+                    math::closest_power_of_two(size as u32) as usize,
+                    self.min_region_size,
+                );
+                if start % size != 0 {
+                    start += size - (start % size);
+                }
 
                 region_start = start;
                 region_size = size;
@@ -600,7 +639,7 @@ impl kernel::mpu::MPU for MPU {
     }
 
     fn configure_mpu(&self, config: &Self::MpuConfig) {
-        let regs = &*self.0;
+        let regs = &*self.registers;
 
         // Set MPU regions
         for region in config.regions.iter() {
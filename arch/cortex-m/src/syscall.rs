@@ -108,6 +108,20 @@ impl kernel::syscall::UserspaceKernelBoundary for SysCall {
         write_volatile(sp, return_value);
     }
 
+    unsafe fn set_syscall_return(
+        &self,
+        stack_pointer: *const usize,
+        _state: &mut Self::StoredState,
+        return_value: kernel::SyscallReturn,
+    ) {
+        // The values go where r0-r3 were passed, which the hardware restores
+        // from the stack frame when the process resumes.
+        let sp = stack_pointer as *mut usize;
+        for (i, &register) in return_value.encode().iter().enumerate() {
+            write_volatile(sp.offset(i as isize), register as usize);
+        }
+    }
+
     /// When the process calls `svc` to enter the kernel, the hardware
     /// automatically pushes a stack frame that will be unstacked when the
     /// kernel returns to the process. In the special case of process startup,
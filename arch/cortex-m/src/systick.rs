@@ -1,5 +1,6 @@
 //! ARM Cortex-M SysTick peripheral.
 
+use core::cmp;
 use kernel::common::registers::{register_bitfields, ReadOnly, ReadWrite};
 use kernel::common::StaticRef;
 
@@ -96,7 +97,14 @@ impl SysTick {
     }
 }
 
+/// The largest value of the 24-bit reload register.
+const MAX_RELOAD: u64 = 0xFF_FFFF;
+
 impl kernel::SysTick for SysTick {
+    fn max_timer_us(&self) -> u32 {
+        (MAX_RELOAD * 1_000_000 / self.hertz() as u64) as u32
+    }
+
     fn set_timer(&self, us: u32) {
         let reload = {
             // We need to convert from microseconds to native tics, which could overflow in 32-bit
@@ -106,7 +114,9 @@ impl kernel::SysTick for SysTick {
             let us = us as u64;
             let hertz = self.hertz() as u64;
 
-            hertz * us / 1_000_000
+            // Longer intervals don't fit in the reload register, and would
+            // wrap around to a much shorter one.
+            cmp::min(hertz * us / 1_000_000, MAX_RELOAD)
         };
 
         // n.b.: 4.4.5 'hints and tips' suggests setting reload before value
@@ -136,6 +146,18 @@ impl kernel::SysTick for SysTick {
         SYSTICK_BASE.syst_csr.is_set(ControlAndStatus::COUNTFLAG)
     }
 
+    fn get_value(&self) -> u32 {
+        let tics = SYSTICK_BASE.syst_cvr.read(CurrentValue::CURRENT) as u64;
+        let hertz = self.hertz() as u64;
+        if hertz == 0 {
+            return 0;
+        }
+
+        // Convert back from native tics to microseconds, again in 64-bit to
+        // avoid overflowing.
+        (tics * 1_000_000 / hertz) as u32
+    }
+
     fn reset(&self) {
         SYSTICK_BASE.syst_csr.set(0);
         SYSTICK_BASE.syst_rvr.set(0);
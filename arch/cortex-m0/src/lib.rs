@@ -1,16 +1,38 @@
-//! Shared implementations for ARM Cortex-M0 MCUs.
+//! Shared implementations for ARM Cortex-M0 and Cortex-M0+ MCUs.
+//!
+//! These cores implement ARMv6-M, which only has the Thumb-1 instruction set
+//! plus a few Thumb-2 system instructions. The handlers in this file are
+//! written with its restrictions in mind:
+//!
+//! - Most instructions, including `ldm` and `stm`, can only use the low
+//!   registers r0-r7. r8-r11 are moved through a low register to be loaded or
+//!   stored.
+//! - `ldm` and `stm` must write back the base register unless `ldm` also loads
+//!   it, so the context switch uses `ldr` and `str` with offsets instead.
+//! - There is no `movw`/`movt` and immediates are 8 bits, so constants such as
+//!   `EXC_RETURN` values are loaded from a literal pool.
+//! - Data processing instructions set the flags, and must be written as
+//!   `movs`, `adds`, `ands`, etc.
+//!
+//! The Cortex-M0+ optionally has an MPU, supported by `cortexm::mpu` with a
+//! 256 byte minimum region size, and an unprivileged thread mode. The handlers always switch processes to
+//! unprivileged mode and back, which has no effect on cores without it.
 
 #![crate_name = "cortexm0"]
 #![crate_type = "rlib"]
 #![feature(asm, const_fn, core_intrinsics, naked_functions)]
 #![no_std]
 
+pub mod syscall;
+
 // Re-export the base generic cortex-m functions here as they are
 // valid on cortex-m0.
 pub use cortexm::support;
 
+pub use cortexm::mpu;
 pub use cortexm::nvic;
-pub use cortexm::syscall;
+pub use cortexm::scb;
+pub use cortexm::systick;
 
 extern "C" {
     // _estack is not really a function, but it makes the types work
@@ -24,6 +46,33 @@ extern "C" {
     static mut _erelocate: u32;
 }
 
+#[cfg(not(target_os = "none"))]
+pub unsafe extern "C" fn systick_handler() {}
+
+#[cfg(target_os = "none")]
+#[naked]
+pub unsafe extern "C" fn systick_handler() {
+    asm!(
+        "
+    /* Mark that the systick handler was called meaning that the process */
+    /* stopped executing because it has exceeded its timeslice. */
+    ldr r0, =SYSTICK_EXPIRED
+    movs r1, #1
+    str r1, [r0, #0]
+
+    /* Set thread mode to privileged */
+    movs r0, #0
+    msr CONTROL, r0
+
+    ldr r0, SEXC_RETURN_MSP
+    bx r0
+
+.align 4
+SEXC_RETURN_MSP:
+  .word 0xFFFFFFF9"
+    : : : : "volatile" );
+}
+
 #[cfg(not(target_os = "none"))]
 pub unsafe extern "C" fn generic_isr() {}
 
@@ -43,23 +92,26 @@ pub unsafe extern "C" fn generic_isr() {
     /* lives in the second word of the hardware stacked registers on MSP */
     mov r1, sp
     ldr r1, [r1, #4]
-    str r4, [r1, #16]
-    str r5, [r1, #20]
-    str r6, [r1, #24]
-    str r7, [r1, #28]
-
-    push {r4-r7}
-    mov  r4, r8
-    mov  r5, r9
-    mov  r6, r10
-    mov  r7, r11
-    str r4, [r1, #0]
-    str r5, [r1, #4]
-    str r6, [r1, #8]
-    str r7, [r1, #12]
-    pop {r4-r7}
 
+    /* Store r4-r7, then r8-r11 through r2 and r3, which the hardware has */
+    /* already stacked. */
+    stmia r1!, {r4-r7}
+    mov r2, r8
+    mov r3, r9
+    str r2, [r1, #0]
+    str r3, [r1, #4]
+    mov r2, r10
+    mov r3, r11
+    str r2, [r1, #8]
+    str r3, [r1, #12]
+
+    /* Set thread mode to privileged */
+    movs r0, #0
+    msr CONTROL, r0
+
+    /* Return to the kernel */
     ldr r0, MEXC_RETURN_MSP
+    mov lr, r0
 _ggeneric_isr_no_stacking:
     /* Find the ISR number by looking at the low byte of the IPSR registers */
     mrs r0, IPSR
@@ -73,19 +125,19 @@ _ggeneric_isr_no_stacking:
      *    NVIC.ICER[r0 / 32] = 1 << (r0 & 31)
      * */
     /* r3 = &NVIC.ICER[r0 / 32] */
-	ldr	r2, NVICICER     /* r2 = &NVIC.ICER */
-	lsrs	r3, r0, #5   /* r3 = r0 / 32 */
-	lsls	r3, r3, #2   /* ICER is word-sized, so multiply offset by 4 */
-	adds	r3, r3, r2   /* r3 = r2 + r3 */
+    ldr r2, NVICICER     /* r2 = &NVIC.ICER */
+    lsrs r3, r0, #5      /* r3 = r0 / 32 */
+    lsls r3, r3, #2      /* ICER is word-sized, so multiply offset by 4 */
+    adds r3, r3, r2      /* r3 = r2 + r3 */
 
     /* r2 = 1 << (r0 & 31) */
-	movs	r2, #31      /* r2 = 31 */
-	ands	r0, r2       /* r0 = r0 & r2 */
-	subs	r2, r2, #30  /* r2 = r2 - 30 i.e. r2 = 1 */
-	lsls	r2, r2, r0   /* r2 = 1 << r0 */
+    movs r2, #31         /* r2 = 31 */
+    ands r0, r2          /* r0 = r0 & r2 */
+    subs r2, r2, #30     /* r2 = r2 - 30 i.e. r2 = 1 */
+    lsls r2, r2, r0      /* r2 = 1 << r0 */
 
     /* *r3 = r2 */
-	str	r2, [r3]
+    str r2, [r3]
     bx lr /* return here since we have extra words in the assembly */
 
 .align 4
@@ -98,6 +150,10 @@ MEXC_RETURN_PSP:
     : : : : "volatile");
 }
 
+#[cfg(not(target_os = "none"))]
+pub unsafe extern "C" fn svc_handler() {}
+
+#[cfg(target_os = "none")]
 #[naked]
 pub unsafe extern "C" fn svc_handler() {
     asm!(
@@ -105,6 +161,11 @@ pub unsafe extern "C" fn svc_handler() {
   ldr r0, EXC_RETURN_MSP
   cmp lr, r0
   bne to_kernel
+
+  /* Set thread mode to unprivileged */
+  movs r0, #1
+  msr CONTROL, r0
+
   ldr r1, EXC_RETURN_PSP
   bx r1
 
@@ -112,6 +173,11 @@ to_kernel:
   ldr r0, =SYSCALL_FIRED
   movs r1, #1
   str r1, [r0, #0]
+
+  /* Set thread mode to privileged */
+  movs r0, #0
+  msr CONTROL, r0
+
   ldr r1, EXC_RETURN_MSP
   bx r1
 
@@ -124,55 +190,73 @@ EXC_RETURN_PSP:
   : : : : "volatile"  );
 }
 
+#[cfg(not(target_os = "none"))]
+pub unsafe extern "C" fn switch_to_user(
+    user_stack: *const usize,
+    _process_regs: &mut [usize; 8],
+) -> *const usize {
+    user_stack
+}
+
+#[cfg(target_os = "none")]
 #[no_mangle]
+/// r0 is top of user stack, r1 is reference to `CortexMStoredState.regs`
+///
+/// The registers are stored in the same order as on the Cortex-M3 and M4,
+/// r4-r11, so the shared `cortexm::syscall` code can display them.
 pub unsafe extern "C" fn switch_to_user(
-    mut user_stack: *const u8,
+    mut user_stack: *const usize,
     process_regs: &mut [usize; 8],
-) -> *mut u8 {
+) -> *const usize {
     asm!("
-    /* Load non-hardware-stacked registers from Process stack */
-    ldmia $2!, {r4-r7}
-    mov r11, r7
-    mov r10, r6
-    mov r9,  r5
-    mov r8,  r4
-    ldmia $2!, {r4-r7}
-    subs $2, 32 /* Restore pointer to process_regs
-                /* ldmia! added a 32-byte offset */
-
     /* Load bottom of stack into Process Stack Pointer */
     msr psp, $0
 
+    /* Load non-hardware-stacked registers from the Process struct's regs */
+    /* field. Only r4-r7 can be loaded directly, so load r8-r11 first */
+    /* through them. $2 must not be written back, as it is used again */
+    /* after the switch. */
+    ldr r4, [$2, #16]
+    ldr r5, [$2, #20]
+    ldr r6, [$2, #24]
+    ldr r7, [$2, #28]
+    mov r8, r4
+    mov r9, r5
+    mov r10, r6
+    mov r11, r7
+    ldr r4, [$2, #0]
+    ldr r5, [$2, #4]
+    ldr r6, [$2, #8]
+    ldr r7, [$2, #12]
+
     /* SWITCH */
     svc 0xff /* It doesn't matter which SVC number we use here */
 
     /* Store non-hardware-stacked registers in process_regs */
-    /* $2 still points to process_regs because we are clobbering all */
-    /* non-hardware-stacked registers */
-    str r4, [$2, #16]
-    str r5, [$2, #20]
-    str r6, [$2, #24]
-    str r7, [$2, #28]
-
-    mov  r4, r8
-    mov  r5, r9
-    mov  r6, r10
-    mov  r7, r11
-
+    /* $2 still points to process_regs because the hardware restored it */
+    /* from the kernel's stack frame */
     str r4, [$2, #0]
     str r5, [$2, #4]
     str r6, [$2, #8]
     str r7, [$2, #12]
 
-    mrs $0, PSP /* PSP into user_stack */
+    mov r4, r8
+    mov r5, r9
+    mov r6, r10
+    mov r7, r11
+    str r4, [$2, #16]
+    str r5, [$2, #20]
+    str r6, [$2, #24]
+    str r7, [$2, #28]
 
-    "
+    mrs $0, PSP /* PSP into user_stack */"
     : "={r0}"(user_stack)
     : "{r0}"(user_stack), "{r1}"(process_regs)
     : "r4","r5","r6","r7","r8","r9","r10","r11" : "volatile" );
-    user_stack as *mut u8
+    user_stack
 }
 
+#[cfg(target_os = "none")]
 struct HardFaultStackedRegisters {
     r0: u32,
     r1: u32,
@@ -184,6 +268,10 @@ struct HardFaultStackedRegisters {
     xpsr: u32,
 }
 
+#[cfg(not(target_os = "none"))]
+pub unsafe extern "C" fn hard_fault_handler() {}
+
+#[cfg(target_os = "none")]
 #[inline(never)]
 unsafe fn kernel_hardfault(faulting_stack: *mut u32) {
     use core::intrinsics::offset;
@@ -215,6 +303,7 @@ unsafe fn kernel_hardfault(faulting_stack: *mut u32) {
          : "volatile");
 }
 
+#[cfg(target_os = "none")]
 #[naked]
 pub unsafe extern "C" fn hard_fault_handler() {
     let faulting_stack: *mut u32;
@@ -257,38 +346,16 @@ _hardfault_exit:
         kernel_hardfault(faulting_stack);
     } else {
         // hard fault occurred in an app, not the kernel. The app should be
-        // marked as in an error state and handled by the kernel
+        // marked as in an error state and handled by the kernel.
+        //
+        // ARMv6-M has no configurable fault status registers, so unlike the
+        // Cortex-M3 there are no SCB registers to save. MPU violations are
+        // hard faults too.
         asm!("
              ldr r0, =APP_HARD_FAULT
              movs r1, #1 /* Fault */
              str r1, [r0, #0]
 
-             /*
-              * NOTE:
-              * -----
-              *
-              * Even though ARMv6-M SCB and Control registers
-              * are different from ARMv7-M, they are still compatible
-              * with each other. So, we can keep the same code as
-              * ARMv7-M.
-              *
-              * ARMv6-M however has no _privileged_ mode.
-              */
-
-             /* Read the SCB registers. */
-             ldr r0, =SCB_REGISTERS
-             ldr r1, =0xE000ED14
-             ldr r2, [r1, #0] /* CCR */
-             str r2, [r0, #0]
-             ldr r2, [r1, #20] /* CFSR */
-             str r2, [r0, #4]
-             ldr r2, [r1, #24] /* HFSR */
-             str r2, [r0, #8]
-             ldr r2, [r1, #32] /* MMFAR */
-             str r2, [r0, #12]
-             ldr r2, [r1, #36] /* BFAR */
-             str r2, [r0, #16]
-
              /* Set thread mode to privileged */
              movs r0, #0
              msr CONTROL, r0
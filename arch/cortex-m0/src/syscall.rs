@@ -0,0 +1,93 @@
+//! Implementation of the architecture-specific portions of the kernel-userland
+//! system call interface for ARMv6-M.
+//!
+//! The stack frames and stored state are the same as on the other Cortex-M
+//! cores, so this uses `cortexm::syscall` for everything except describing
+//! faults.
+
+use core::fmt::Write;
+
+use cortexm::syscall::CortexMStoredState;
+
+/// Implementation of the `UserspaceKernelBoundary` for the Cortex-M0 and
+/// Cortex-M0+.
+pub struct SysCall(cortexm::syscall::SysCall);
+
+impl SysCall {
+    pub const unsafe fn new() -> SysCall {
+        SysCall(cortexm::syscall::SysCall::new())
+    }
+}
+
+impl kernel::syscall::UserspaceKernelBoundary for SysCall {
+    type StoredState = CortexMStoredState;
+
+    unsafe fn initialize_new_process(
+        &self,
+        stack_pointer: *const usize,
+        stack_size: usize,
+        state: &mut Self::StoredState,
+    ) -> Result<*const usize, ()> {
+        self.0
+            .initialize_new_process(stack_pointer, stack_size, state)
+    }
+
+    unsafe fn set_syscall_return_value(
+        &self,
+        stack_pointer: *const usize,
+        state: &mut Self::StoredState,
+        return_value: isize,
+    ) {
+        self.0
+            .set_syscall_return_value(stack_pointer, state, return_value)
+    }
+
+    unsafe fn set_syscall_return(
+        &self,
+        stack_pointer: *const usize,
+        state: &mut Self::StoredState,
+        return_value: kernel::SyscallReturn,
+    ) {
+        self.0
+            .set_syscall_return(stack_pointer, state, return_value)
+    }
+
+    unsafe fn set_process_function(
+        &self,
+        stack_pointer: *const usize,
+        remaining_stack_memory: usize,
+        state: &mut CortexMStoredState,
+        callback: kernel::procs::FunctionCall,
+    ) -> Result<*mut usize, *mut usize> {
+        self.0
+            .set_process_function(stack_pointer, remaining_stack_memory, state, callback)
+    }
+
+    unsafe fn switch_to_process(
+        &self,
+        stack_pointer: *const usize,
+        state: &mut CortexMStoredState,
+    ) -> (*mut usize, kernel::syscall::ContextSwitchReason) {
+        self.0.switch_to_process(stack_pointer, state)
+    }
+
+    /// ARMv6-M only has the hard fault, and no registers recording why it
+    /// happened. The faulting instruction is the PC in the process details.
+    unsafe fn fault_fmt(&self, writer: &mut dyn Write) {
+        let _ = writer.write_fmt(format_args!("\r\n---| Fault Status |---\r\n"));
+        let _ = writer.write_fmt(format_args!("Hard Fault:                         true\r\n"));
+        let _ = writer.write_fmt(format_args!(
+            "ARMv6-M does not record the cause of a fault. It may be an\r\n\
+             MPU violation, a bus error, or an invalid instruction.\r\n"
+        ));
+    }
+
+    unsafe fn process_detail_fmt(
+        &self,
+        stack_pointer: *const usize,
+        state: &CortexMStoredState,
+        writer: &mut dyn Write,
+    ) {
+        self.0.process_detail_fmt(stack_pointer, state, writer)
+    }
+}
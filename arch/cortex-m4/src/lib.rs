@@ -5,12 +5,11 @@
 #![feature(asm, const_fn, core_intrinsics, naked_functions)]
 #![no_std]
 
-pub mod mpu;
-
 // Re-export the base generic cortex-m functions here as they are
 // valid on cortex-m4.
 pub use cortexm::support;
 
+pub use cortexm::mpu;
 pub use cortexm::nvic;
 pub use cortexm::scb;
 pub use cortexm::syscall;
@@ -1 +0,0 @@
-../../cortex-m3/src/mpu.rs
\ No newline at end of file
@@ -1,3 +1,5 @@
+use core::fmt;
+
 use kernel::common::registers::{register_bitfields, LocalRegisterCopy};
 
 register_bitfields![u32,
@@ -106,3 +108,54 @@ impl Exception {
         }
     }
 }
+
+impl fmt::Display for Trap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Trap::Interrupt(interrupt) => write!(f, "{} interrupt", interrupt),
+            Trap::Exception(exception) => write!(f, "{}", exception),
+        }
+    }
+}
+
+impl fmt::Display for Interrupt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Interrupt::UserSoft => "User software",
+            Interrupt::SupervisorSoft => "Supervisor software",
+            Interrupt::MachineSoft => "Machine software",
+            Interrupt::UserTimer => "User timer",
+            Interrupt::SupervisorTimer => "Supervisor timer",
+            Interrupt::MachineTimer => "Machine timer",
+            Interrupt::UserExternal => "User external",
+            Interrupt::SupervisorExternal => "Supervisor external",
+            Interrupt::MachineExternal => "Machine external",
+            Interrupt::Unknown => "Reserved",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The names the privileged specification gives each exception.
+impl fmt::Display for Exception {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Exception::InstructionMisaligned => "Instruction address misaligned",
+            Exception::InstructionFault => "Instruction access fault",
+            Exception::IllegalInstruction => "Illegal instruction",
+            Exception::Breakpoint => "Breakpoint",
+            Exception::LoadMisaligned => "Load address misaligned",
+            Exception::LoadFault => "Load access fault",
+            Exception::StoreMisaligned => "Store/AMO address misaligned",
+            Exception::StoreFault => "Store/AMO access fault",
+            Exception::UserEnvCall => "Environment call from U-mode",
+            Exception::SupervisorEnvCall => "Environment call from S-mode",
+            Exception::MachineEnvCall => "Environment call from M-mode",
+            Exception::InstructionPageFault => "Instruction page fault",
+            Exception::LoadPageFault => "Load page fault",
+            Exception::StorePageFault => "Store/AMO page fault",
+            Exception::Unknown => "Reserved",
+        };
+        write!(f, "{}", name)
+    }
+}
@@ -1,6 +1,8 @@
 use kernel::common::registers::register_bitfields;
 
 // pmpcfg
+//
+// Each pmpcfg register holds the configuration bytes of four PMP entries.
 
 register_bitfields![u32,
 pmpcfg [
@@ -13,11 +15,11 @@ pmpcfg [
         NA4 = 2,
         NAPOT = 3
     ],
-    l0 OFFSET(5) NUMBITS(1) [],
-    r1 OFFSET(6) NUMBITS(1) [],
-    w1 OFFSET(7) NUMBITS(1) [],
-    x1 OFFSET(8) NUMBITS(1) [],
-    a1 OFFSET(9) NUMBITS(2) [
+    l0 OFFSET(7) NUMBITS(1) [],
+    r1 OFFSET(8) NUMBITS(1) [],
+    w1 OFFSET(9) NUMBITS(1) [],
+    x1 OFFSET(10) NUMBITS(1) [],
+    a1 OFFSET(11) NUMBITS(2) [
         OFF = 0,
         TOR = 1,
         NA4 = 2,
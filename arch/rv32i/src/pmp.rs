@@ -1,219 +1,421 @@
 //! Implementation of the physical memory protection unit (PMP).
+//!
+//! The PMP checks every user mode access against a list of entries, each of
+//! which gives an address range and what may be done with it. User mode
+//! accesses that no entry matches fail. Machine mode, which the kernel runs
+//! in, is only restricted by locked entries, and Tock never locks an entry, so
+//! the PMP only has to describe what the running process may access and can
+//! stay configured while the kernel runs.
+//!
+//! An entry either covers a naturally aligned power-of-two range by itself
+//! (NAPOT), or covers the range from the address of the entry before it up to
+//! its own address (TOR). Process regions are encoded as NAPOT when they can
+//! be, since that only takes one entry, and as TOR otherwise, which takes two
+//! but can cover any range of whole words. The region for app-owned RAM is
+//! always TOR, so that it can follow the app break a word at a time and never
+//! covers the grants and other kernel-owned memory at the end of the process's
+//! memory.
+//!
+//! Implemented according to the RISC-V privileged ISA 1.10:
+//! https://content.riscv.org/wp-content/uploads/2017/05/riscv-privileged-v1.10.pdf
+
+use core::cmp;
 
 use crate::csr;
+use crate::csr::pmpconfig::pmpcfg;
 use kernel;
 use kernel::mpu;
 
-/// Struct storing configuration for a RISCV PMP region.
-/// In accordance with the priviliged ISA 1.10
-/// https://content.riscv.org/wp-content/uploads/2017/05/riscv-privileged-v1.10.pdf
+/// The most entries a PMP can have in the 1.10 specification.
+const MAX_ENTRIES: usize = 16;
+
+/// The most regions a process can have. Each region takes one or two entries.
+const MAX_REGIONS: usize = 8;
+
+/// Regions are made of whole words, the smallest unit the PMP can protect.
+const WORD_SIZE: usize = 4;
+
+const APP_MEMORY_REGION_NUM: usize = 0;
 
-/// Struct storing region configuration for RISCV PMP.
+/// The PMP of a RISC-V core, which implements `kernel::mpu::MPU`.
 #[derive(Copy, Clone)]
 pub struct PMPConfig {
-    regions: usize,
+    /// How many PMP entries the core implements.
+    entries: usize,
 }
 
 impl Default for PMPConfig {
-    /// number of regions on the arty chip
+    /// number of entries on the arty chip
     fn default() -> PMPConfig {
-        PMPConfig { regions: 4 }
+        PMPConfig { entries: 4 }
     }
 }
 
 impl PMPConfig {
-    pub const fn new(num_regions: usize) -> PMPConfig {
+    pub const fn new(num_entries: usize) -> PMPConfig {
         PMPConfig {
-            regions: num_regions,
+            entries: num_entries,
         }
     }
 }
 
-impl kernel::mpu::MPU for PMPConfig {
-    fn enable_mpu(&self) {}
+/// Struct storing the PMP regions of one process.
+#[derive(Copy, Clone)]
+pub struct PMPRegions {
+    regions: [PMPRegion; MAX_REGIONS],
+}
 
-    fn disable_mpu(&self) {
-        for x in 0..self.regions {
-            // disable everything
-            match x {
-                0 => {
-                    csr::CSR.pmpcfg0.modify(csr::pmpconfig::pmpcfg::r0::CLEAR);
-                    csr::CSR.pmpcfg0.modify(csr::pmpconfig::pmpcfg::w0::CLEAR);
-                    csr::CSR.pmpcfg0.modify(csr::pmpconfig::pmpcfg::x0::CLEAR);
-                    csr::CSR.pmpcfg0.modify(csr::pmpconfig::pmpcfg::a0::OFF);
-                    csr::CSR.pmpcfg0.modify(csr::pmpconfig::pmpcfg::l0::CLEAR);
-                    csr::CSR.pmpaddr0.set(0x0);
-                }
-                1 => {
-                    csr::CSR.pmpcfg0.modify(csr::pmpconfig::pmpcfg::r1::CLEAR);
-                    csr::CSR.pmpcfg0.modify(csr::pmpconfig::pmpcfg::w1::CLEAR);
-                    csr::CSR.pmpcfg0.modify(csr::pmpconfig::pmpcfg::x1::CLEAR);
-                    csr::CSR.pmpcfg0.modify(csr::pmpconfig::pmpcfg::a1::OFF);
-                    csr::CSR.pmpcfg0.modify(csr::pmpconfig::pmpcfg::l1::CLEAR);
-                    csr::CSR.pmpaddr1.set(0x0);
-                }
-                2 => {
-                    csr::CSR.pmpcfg0.modify(csr::pmpconfig::pmpcfg::r2::CLEAR);
-                    csr::CSR.pmpcfg0.modify(csr::pmpconfig::pmpcfg::w2::CLEAR);
-                    csr::CSR.pmpcfg0.modify(csr::pmpconfig::pmpcfg::x2::CLEAR);
-                    csr::CSR.pmpcfg0.modify(csr::pmpconfig::pmpcfg::a2::OFF);
-                    csr::CSR.pmpcfg0.modify(csr::pmpconfig::pmpcfg::l2::CLEAR);
-                    csr::CSR.pmpaddr2.set(0x0);
-                }
-                3 => {
-                    csr::CSR.pmpcfg0.modify(csr::pmpconfig::pmpcfg::r3::CLEAR);
-                    csr::CSR.pmpcfg0.modify(csr::pmpconfig::pmpcfg::w3::CLEAR);
-                    csr::CSR.pmpcfg0.modify(csr::pmpconfig::pmpcfg::x3::CLEAR);
-                    csr::CSR.pmpcfg0.modify(csr::pmpconfig::pmpcfg::a3::OFF);
-                    csr::CSR.pmpcfg0.modify(csr::pmpconfig::pmpcfg::l3::CLEAR);
-                    csr::CSR.pmpaddr3.set(0x0);
-                }
-                4 => {
-                    csr::CSR.pmpcfg1.modify(csr::pmpconfig::pmpcfg::r0::CLEAR);
-                    csr::CSR.pmpcfg1.modify(csr::pmpconfig::pmpcfg::w0::CLEAR);
-                    csr::CSR.pmpcfg1.modify(csr::pmpconfig::pmpcfg::x0::CLEAR);
-                    csr::CSR.pmpcfg1.modify(csr::pmpconfig::pmpcfg::a0::OFF);
-                    csr::CSR.pmpcfg1.modify(csr::pmpconfig::pmpcfg::l0::CLEAR);
-                    csr::CSR.pmpaddr4.set(0x0);
-                }
-                5 => {
-                    csr::CSR.pmpcfg1.modify(csr::pmpconfig::pmpcfg::r1::CLEAR);
-                    csr::CSR.pmpcfg1.modify(csr::pmpconfig::pmpcfg::w1::CLEAR);
-                    csr::CSR.pmpcfg1.modify(csr::pmpconfig::pmpcfg::x1::CLEAR);
-                    csr::CSR.pmpcfg1.modify(csr::pmpconfig::pmpcfg::a1::OFF);
-                    csr::CSR.pmpcfg1.modify(csr::pmpconfig::pmpcfg::l1::CLEAR);
-                    csr::CSR.pmpaddr5.set(0x0);
-                }
-                6 => {
-                    csr::CSR.pmpcfg1.modify(csr::pmpconfig::pmpcfg::r2::CLEAR);
-                    csr::CSR.pmpcfg1.modify(csr::pmpconfig::pmpcfg::w2::CLEAR);
-                    csr::CSR.pmpcfg1.modify(csr::pmpconfig::pmpcfg::x2::CLEAR);
-                    csr::CSR.pmpcfg1.modify(csr::pmpconfig::pmpcfg::a2::OFF);
-                    csr::CSR.pmpcfg1.modify(csr::pmpconfig::pmpcfg::l2::CLEAR);
-                    csr::CSR.pmpaddr6.set(0x0);
-                }
-                7 => {
-                    csr::CSR.pmpcfg1.modify(csr::pmpconfig::pmpcfg::r3::CLEAR);
-                    csr::CSR.pmpcfg1.modify(csr::pmpconfig::pmpcfg::w3::CLEAR);
-                    csr::CSR.pmpcfg1.modify(csr::pmpconfig::pmpcfg::x3::CLEAR);
-                    csr::CSR.pmpcfg1.modify(csr::pmpconfig::pmpcfg::a3::OFF);
-                    csr::CSR.pmpcfg1.modify(csr::pmpconfig::pmpcfg::l3::CLEAR);
-                    csr::CSR.pmpaddr7.set(0x0);
-                }
-                8 => {
-                    csr::CSR.pmpcfg2.modify(csr::pmpconfig::pmpcfg::r0::CLEAR);
-                    csr::CSR.pmpcfg2.modify(csr::pmpconfig::pmpcfg::w0::CLEAR);
-                    csr::CSR.pmpcfg2.modify(csr::pmpconfig::pmpcfg::x0::CLEAR);
-                    csr::CSR.pmpcfg2.modify(csr::pmpconfig::pmpcfg::a0::OFF);
-                    csr::CSR.pmpcfg2.modify(csr::pmpconfig::pmpcfg::l0::CLEAR);
-                    csr::CSR.pmpaddr8.set(0x0);
-                }
-                9 => {
-                    csr::CSR.pmpcfg2.modify(csr::pmpconfig::pmpcfg::r1::CLEAR);
-                    csr::CSR.pmpcfg2.modify(csr::pmpconfig::pmpcfg::w1::CLEAR);
-                    csr::CSR.pmpcfg2.modify(csr::pmpconfig::pmpcfg::x1::CLEAR);
-                    csr::CSR.pmpcfg2.modify(csr::pmpconfig::pmpcfg::a1::OFF);
-                    csr::CSR.pmpcfg2.modify(csr::pmpconfig::pmpcfg::l1::CLEAR);
-                    csr::CSR.pmpaddr9.set(0x0);
-                }
-                10 => {
-                    csr::CSR.pmpcfg2.modify(csr::pmpconfig::pmpcfg::r2::CLEAR);
-                    csr::CSR.pmpcfg2.modify(csr::pmpconfig::pmpcfg::w2::CLEAR);
-                    csr::CSR.pmpcfg2.modify(csr::pmpconfig::pmpcfg::x2::CLEAR);
-                    csr::CSR.pmpcfg2.modify(csr::pmpconfig::pmpcfg::a2::OFF);
-                    csr::CSR.pmpcfg2.modify(csr::pmpconfig::pmpcfg::l2::CLEAR);
-                    csr::CSR.pmpaddr10.set(0x0);
-                }
-                11 => {
-                    csr::CSR.pmpcfg2.modify(csr::pmpconfig::pmpcfg::r3::CLEAR);
-                    csr::CSR.pmpcfg2.modify(csr::pmpconfig::pmpcfg::w3::CLEAR);
-                    csr::CSR.pmpcfg2.modify(csr::pmpconfig::pmpcfg::x3::CLEAR);
-                    csr::CSR.pmpcfg2.modify(csr::pmpconfig::pmpcfg::a3::OFF);
-                    csr::CSR.pmpcfg2.modify(csr::pmpconfig::pmpcfg::l3::CLEAR);
-                    csr::CSR.pmpaddr11.set(0x0);
-                }
-                12 => {
-                    csr::CSR.pmpcfg3.modify(csr::pmpconfig::pmpcfg::r0::CLEAR);
-                    csr::CSR.pmpcfg3.modify(csr::pmpconfig::pmpcfg::w0::CLEAR);
-                    csr::CSR.pmpcfg3.modify(csr::pmpconfig::pmpcfg::x0::CLEAR);
-                    csr::CSR.pmpcfg3.modify(csr::pmpconfig::pmpcfg::a0::OFF);
-                    csr::CSR.pmpcfg3.modify(csr::pmpconfig::pmpcfg::l0::CLEAR);
-                    csr::CSR.pmpaddr12.set(0x0);
-                }
-                13 => {
-                    csr::CSR.pmpcfg3.modify(csr::pmpconfig::pmpcfg::r1::CLEAR);
-                    csr::CSR.pmpcfg3.modify(csr::pmpconfig::pmpcfg::w1::CLEAR);
-                    csr::CSR.pmpcfg3.modify(csr::pmpconfig::pmpcfg::x1::CLEAR);
-                    csr::CSR.pmpcfg3.modify(csr::pmpconfig::pmpcfg::a1::OFF);
-                    csr::CSR.pmpcfg3.modify(csr::pmpconfig::pmpcfg::l1::CLEAR);
-                    csr::CSR.pmpaddr13.set(0x0);
-                }
-                14 => {
-                    csr::CSR.pmpcfg3.modify(csr::pmpconfig::pmpcfg::r2::CLEAR);
-                    csr::CSR.pmpcfg3.modify(csr::pmpconfig::pmpcfg::w2::CLEAR);
-                    csr::CSR.pmpcfg3.modify(csr::pmpconfig::pmpcfg::x2::CLEAR);
-                    csr::CSR.pmpcfg3.modify(csr::pmpconfig::pmpcfg::a2::OFF);
-                    csr::CSR.pmpcfg3.modify(csr::pmpconfig::pmpcfg::l2::CLEAR);
-                    csr::CSR.pmpaddr14.set(0x0);
-                }
-                15 => {
-                    csr::CSR.pmpcfg3.modify(csr::pmpconfig::pmpcfg::r3::CLEAR);
-                    csr::CSR.pmpcfg3.modify(csr::pmpconfig::pmpcfg::w3::CLEAR);
-                    csr::CSR.pmpcfg3.modify(csr::pmpconfig::pmpcfg::x3::CLEAR);
-                    csr::CSR.pmpcfg3.modify(csr::pmpconfig::pmpcfg::a3::OFF);
-                    csr::CSR.pmpcfg3.modify(csr::pmpconfig::pmpcfg::l3::CLEAR);
-                    csr::CSR.pmpaddr15.set(0x0);
+impl Default for PMPRegions {
+    fn default() -> PMPRegions {
+        PMPRegions {
+            regions: [PMPRegion::empty(); MAX_REGIONS],
+        }
+    }
+}
+
+impl PMPRegions {
+    fn unused_region_number(&self) -> Option<usize> {
+        for (number, region) in self.regions.iter().enumerate() {
+            if number == APP_MEMORY_REGION_NUM {
+                continue;
+            }
+            if let None = region.location() {
+                return Some(number);
+            }
+        }
+        None
+    }
+
+    /// How many PMP entries the regions take, with `region_num` replaced by
+    /// `region`.
+    fn entries_needed_with(&self, region_num: usize, region: &PMPRegion) -> usize {
+        self.regions
+            .iter()
+            .enumerate()
+            .map(|(number, other)| {
+                if number == region_num {
+                    region.entries_needed()
+                } else {
+                    other.entries_needed()
                 }
-                // spec 1.10 only goes to 15
-                _ => break,
+            })
+            .sum()
+    }
+
+    /// The configuration byte and address of each PMP entry. Entries after
+    /// the last region are off.
+    fn entries(&self) -> ([u8; MAX_ENTRIES], [u32; MAX_ENTRIES]) {
+        let mut cfg = [0; MAX_ENTRIES];
+        let mut addr = [0; MAX_ENTRIES];
+        let mut entry = 0;
+        for region in self.regions.iter() {
+            let (start, size) = match region.location() {
+                Some((start, size)) => (start as usize, size),
+                None => continue,
+            };
+            if region.is_napot() {
+                // The trailing ones of the address give the size of the range.
+                addr[entry] = ((start | (size / 2 - 1)) >> 2) as u32;
+                cfg[entry] = region.permissions | pmpcfg::a0::NAPOT.value as u8;
+                entry += 1;
+            } else {
+                // The first entry only holds the bottom of the range.
+                addr[entry] = (start >> 2) as u32;
+                addr[entry + 1] = ((start + size) >> 2) as u32;
+                cfg[entry + 1] = region.permissions | pmpcfg::a0::TOR.value as u8;
+                entry += 2;
             }
-            //set first PMP to have permissions to entire space
-            csr::CSR.pmpaddr0.set(0xFFFF_FFFF);
-            //enable R W X fields
-            csr::CSR.pmpcfg0.modify(csr::pmpconfig::pmpcfg::r0::SET);
-            csr::CSR.pmpcfg0.modify(csr::pmpconfig::pmpcfg::w0::SET);
-            csr::CSR.pmpcfg0.modify(csr::pmpconfig::pmpcfg::x0::SET);
-            csr::CSR.pmpcfg0.modify(csr::pmpconfig::pmpcfg::a0::OFF);
         }
+        (cfg, addr)
     }
+}
+
+/// Struct storing configuration for a RISC-V PMP region.
+#[derive(Copy, Clone)]
+pub struct PMPRegion {
+    location: Option<(*const u8, usize)>,
+    /// The R, W and X bits of the region's configuration byte.
+    permissions: u8,
+    /// Whether the region must be encoded as TOR, so that it can later grow
+    /// without taking more entries.
+    tor_only: bool,
+}
+
+impl PMPRegion {
+    fn new(
+        start: *const u8,
+        size: usize,
+        permissions: mpu::Permissions,
+        tor_only: bool,
+    ) -> PMPRegion {
+        let permissions = match permissions {
+            mpu::Permissions::ReadWriteExecute => {
+                pmpcfg::r0::SET + pmpcfg::w0::SET + pmpcfg::x0::SET
+            }
+            mpu::Permissions::ReadWriteOnly => {
+                pmpcfg::r0::SET + pmpcfg::w0::SET + pmpcfg::x0::CLEAR
+            }
+            mpu::Permissions::ReadExecuteOnly => {
+                pmpcfg::r0::SET + pmpcfg::w0::CLEAR + pmpcfg::x0::SET
+            }
+            mpu::Permissions::ReadOnly => pmpcfg::r0::SET + pmpcfg::w0::CLEAR + pmpcfg::x0::CLEAR,
+            mpu::Permissions::ExecuteOnly => {
+                pmpcfg::r0::CLEAR + pmpcfg::w0::CLEAR + pmpcfg::x0::SET
+            }
+        };
+
+        PMPRegion {
+            location: Some((start, size)),
+            permissions: permissions.value as u8,
+            tor_only: tor_only,
+        }
+    }
+
+    const fn empty() -> PMPRegion {
+        PMPRegion {
+            location: None,
+            permissions: 0,
+            tor_only: false,
+        }
+    }
+
+    fn location(&self) -> Option<(*const u8, usize)> {
+        self.location
+    }
+
+    /// Whether the region can be covered by a single NAPOT entry, which needs
+    /// a power-of-two size of at least 8 bytes and a start aligned to it.
+    fn is_napot(&self) -> bool {
+        match self.location {
+            Some((start, size)) if !self.tor_only => {
+                size.is_power_of_two() && size >= 8 && (start as usize) % size == 0
+            }
+            _ => false,
+        }
+    }
+
+    fn entries_needed(&self) -> usize {
+        match self.location {
+            Some(_) if self.is_napot() => 1,
+            Some(_) => 2,
+            None => 0,
+        }
+    }
+
+    fn overlaps(&self, other_start: *const u8, other_size: usize) -> bool {
+        let other_start = other_start as usize;
+        let other_end = other_start + other_size;
+
+        let (region_start, region_end) = match self.location {
+            Some((region_start, region_size)) => {
+                let region_start = region_start as usize;
+                let region_end = region_start + region_size;
+                (region_start, region_end)
+            }
+            None => return false,
+        };
+
+        region_start < other_end && other_start < region_end
+    }
+}
+
+/// Round `value` up to a whole number of words.
+fn align_to_word(value: usize) -> usize {
+    (value + WORD_SIZE - 1) & !(WORD_SIZE - 1)
+}
+
+/// Write the address register of PMP entry `index`.
+fn write_address(index: usize, address: u32) {
+    match index {
+        0 => csr::CSR.pmpaddr0.set(address),
+        1 => csr::CSR.pmpaddr1.set(address),
+        2 => csr::CSR.pmpaddr2.set(address),
+        3 => csr::CSR.pmpaddr3.set(address),
+        4 => csr::CSR.pmpaddr4.set(address),
+        5 => csr::CSR.pmpaddr5.set(address),
+        6 => csr::CSR.pmpaddr6.set(address),
+        7 => csr::CSR.pmpaddr7.set(address),
+        8 => csr::CSR.pmpaddr8.set(address),
+        9 => csr::CSR.pmpaddr9.set(address),
+        10 => csr::CSR.pmpaddr10.set(address),
+        11 => csr::CSR.pmpaddr11.set(address),
+        12 => csr::CSR.pmpaddr12.set(address),
+        13 => csr::CSR.pmpaddr13.set(address),
+        14 => csr::CSR.pmpaddr14.set(address),
+        15 => csr::CSR.pmpaddr15.set(address),
+        // spec 1.10 only goes to 15
+        _ => {}
+    }
+}
+
+/// Write the pmpcfg register holding the configuration of entries
+/// `4 * index` to `4 * index + 3`.
+fn write_config(index: usize, config: u32) {
+    match index {
+        0 => csr::CSR.pmpcfg0.set(config),
+        1 => csr::CSR.pmpcfg1.set(config),
+        2 => csr::CSR.pmpcfg2.set(config),
+        3 => csr::CSR.pmpcfg3.set(config),
+        _ => {}
+    }
+}
+
+impl kernel::mpu::MPU for PMPConfig {
+    type MpuConfig = PMPRegions;
+
+    // Entries that are not locked never restrict machine mode, so the PMP can
+    // stay configured for the last process while the kernel runs.
+    fn enable_mpu(&self) {}
+
+    fn disable_mpu(&self) {}
 
     fn number_total_regions(&self) -> usize {
-        self.regions
+        cmp::min(self.entries, MAX_REGIONS)
     }
 
     fn allocate_region(
         &self,
-        _unallocated_memory_start: *const u8,
-        _unallocated_memory_size: usize,
-        _min_region_size: usize,
-        _permissions: mpu::Permissions,
-        _config: &mut Self::MpuConfig,
+        unallocated_memory_start: *const u8,
+        unallocated_memory_size: usize,
+        min_region_size: usize,
+        permissions: mpu::Permissions,
+        config: &mut Self::MpuConfig,
     ) -> Option<mpu::Region> {
-        None
+        // Check that no previously allocated regions overlap the unallocated memory.
+        for region in config.regions.iter() {
+            if region.overlaps(unallocated_memory_start, unallocated_memory_size) {
+                return None;
+            }
+        }
+
+        let region_num = config.unused_region_number()?;
+
+        let start = align_to_word(unallocated_memory_start as usize);
+        let size = cmp::max(align_to_word(min_region_size), WORD_SIZE);
+
+        // Check that the region fits in memory.
+        if start + size > (unallocated_memory_start as usize) + unallocated_memory_size {
+            return None;
+        }
+
+        let region = PMPRegion::new(start as *const u8, size, permissions, false);
+
+        // Check that there are enough entries left for the region.
+        if config.entries_needed_with(region_num, &region) > self.entries {
+            return None;
+        }
+
+        config.regions[region_num] = region;
+
+        Some(mpu::Region::new(start as *const u8, size))
     }
 
     fn allocate_app_memory_region(
         &self,
-        _unallocated_memory_start: *const u8,
-        _unallocated_memory_size: usize,
-        _min_memory_size: usize,
-        _initial_app_memory_size: usize,
-        _initial_kernel_memory_size: usize,
-        _permissions: mpu::Permissions,
-        _config: &mut Self::MpuConfig,
+        unallocated_memory_start: *const u8,
+        unallocated_memory_size: usize,
+        min_memory_size: usize,
+        initial_app_memory_size: usize,
+        initial_kernel_memory_size: usize,
+        permissions: mpu::Permissions,
+        config: &mut Self::MpuConfig,
     ) -> Option<(*const u8, usize)> {
-        None
+        if config.regions[APP_MEMORY_REGION_NUM].location().is_some() {
+            return None;
+        }
+
+        // Check that no previously allocated regions overlap the unallocated memory.
+        for region in config.regions.iter() {
+            if region.overlaps(unallocated_memory_start, unallocated_memory_size) {
+                return None;
+            }
+        }
+
+        // Make sure there is enough memory for app memory and kernel memory.
+        let memory_size = cmp::max(
+            min_memory_size,
+            initial_app_memory_size + initial_kernel_memory_size,
+        );
+
+        // TOR regions can start at any word, so the process memory only needs
+        // to be word aligned.
+        let memory_start = align_to_word(unallocated_memory_start as usize);
+
+        // Make sure the process memory fits in the unallocated memory.
+        if memory_start + memory_size
+            > (unallocated_memory_start as usize) + unallocated_memory_size
+        {
+            return None;
+        }
+
+        let app_memory_size = align_to_word(initial_app_memory_size);
+        if app_memory_size + initial_kernel_memory_size > memory_size {
+            return None;
+        }
+
+        let region = PMPRegion::new(
+            memory_start as *const u8,
+            app_memory_size,
+            permissions,
+            true,
+        );
+
+        if config.entries_needed_with(APP_MEMORY_REGION_NUM, &region) > self.entries {
+            return None;
+        }
+
+        config.regions[APP_MEMORY_REGION_NUM] = region;
+
+        Some((memory_start as *const u8, memory_size))
     }
 
     fn update_app_memory_region(
         &self,
-        _app_memory_break: *const u8,
-        _kernel_memory_break: *const u8,
-        _permissions: mpu::Permissions,
-        _config: &mut Self::MpuConfig,
+        app_memory_break: *const u8,
+        kernel_memory_break: *const u8,
+        permissions: mpu::Permissions,
+        config: &mut Self::MpuConfig,
     ) -> Result<(), ()> {
-        Err(())
+        let region_start = match config.regions[APP_MEMORY_REGION_NUM].location() {
+            Some((start, _)) => start as usize,
+            None => {
+                // Error: Process tried to update app memory PMP region before it was created.
+                return Err(());
+            }
+        };
+
+        // The region ends at the first word boundary at or after the app
+        // break, which must not be inside kernel-owned memory.
+        let region_end = align_to_word(app_memory_break as usize);
+        if region_end > kernel_memory_break as usize || region_end < region_start {
+            return Err(());
+        }
+
+        config.regions[APP_MEMORY_REGION_NUM] = PMPRegion::new(
+            region_start as *const u8,
+            region_end - region_start,
+            permissions,
+            true,
+        );
+
+        Ok(())
     }
 
-    fn configure_mpu(&self, _config: &Self::MpuConfig) {}
+    fn configure_mpu(&self, config: &Self::MpuConfig) {
+        let entries = cmp::min(self.entries, MAX_ENTRIES);
+        let (cfg, addr) = config.entries();
+
+        // Only touch the registers of implemented entries, since accessing
+        // the others is an illegal instruction on some cores.
+        for (index, &address) in addr[..entries].iter().enumerate() {
+            write_address(index, address);
+        }
+        for (index, bytes) in cfg[..entries].chunks(4).enumerate() {
+            let config = bytes
+                .iter()
+                .enumerate()
+                .fold(0, |config, (i, &byte)| config | ((byte as u32) << (8 * i)));
+            write_config(index, config);
+        }
+    }
 }
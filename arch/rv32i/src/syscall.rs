@@ -1,8 +1,12 @@
 //! Kernel-userland system call interface for RISC-V architecture.
 
+use core::cell::Cell;
 use core::fmt::Write;
 
 use kernel;
+use kernel::common::registers::LocalRegisterCopy;
+
+use crate::csr::mcause::{self, McauseHelpers};
 
 /// This holds all of the state that the kernel must keep for the process when
 /// the process is not executing.
@@ -20,14 +24,45 @@ pub struct RiscvimacStoredState {
     /// We need to store the mcause CSR between when the trap occurs and after
     /// we exit the trap handler and resume the context switching code.
     mcause: usize,
+
+    /// The mtval CSR from the same trap, which holds the faulting address or
+    /// instruction for some exceptions.
+    mtval: usize,
+}
+
+/// The ABI names of the registers in `RiscvimacStoredState::regs`, x1 to x31.
+const REGISTER_NAMES: [&str; 31] = [
+    "ra", "sp", "gp", "tp", "t0", "t1", "t2", "s0", "s1", "a0", "a1", "a2", "a3", "a4", "a5", "a6",
+    "a7", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11", "t3", "t4", "t5", "t6",
+];
+
+/// The trap CSRs of the last process fault.
+#[derive(Copy, Clone)]
+struct FaultState {
+    mcause: usize,
+    mtval: usize,
+    mepc: usize,
+}
+
+/// Decode a saved mcause value.
+fn trap_cause(mcause: usize) -> mcause::Trap {
+    // Like the context switch code, ignore the bits above the cause, which
+    // the E21 core uses for other flags, except for the interrupt bit.
+    let mcause = (mcause as u32) & (1 << 31 | 0x1ff);
+    LocalRegisterCopy::<u32, mcause::mcause::Register>::new(mcause).cause()
 }
 
 /// Implementation of the `UserspaceKernelBoundary` for the RISC-V architecture.
-pub struct SysCall(());
+pub struct SysCall {
+    /// Recorded when a process faults so that `fault_fmt` can report it.
+    last_fault: Cell<Option<FaultState>>,
+}
 
 impl SysCall {
     pub const unsafe fn new() -> SysCall {
-        SysCall(())
+        SysCall {
+            last_fault: Cell::new(None),
+        }
     }
 }
 
@@ -60,6 +95,20 @@ impl kernel::syscall::UserspaceKernelBoundary for SysCall {
         state.regs[9] = return_value as usize; // a0 = regs[9] = return value
     }
 
+    unsafe fn set_syscall_return(
+        &self,
+        _stack_pointer: *const usize,
+        state: &mut Self::StoredState,
+        return_value: kernel::SyscallReturn,
+    ) {
+        // The values go in a0-a3, the registers the arguments were passed in.
+        let registers = return_value.encode();
+        state.regs[9] = registers[0] as usize; // a0 = x10 = regs[9]
+        state.regs[10] = registers[1] as usize; // a1 = x11 = regs[10]
+        state.regs[11] = registers[2] as usize; // a2 = x12 = regs[11]
+        state.regs[12] = registers[3] as usize; // a3 = x13 = regs[12]
+    }
+
     unsafe fn set_process_function(
         &self,
         stack_pointer: *const usize,
@@ -95,7 +144,6 @@ impl kernel::syscall::UserspaceKernelBoundary for SysCall {
     ) -> (*mut usize, kernel::syscall::ContextSwitchReason) {
         let switch_reason: u32;
         let mut syscall_args: [u32; 5] = [0; 5];
-        let new_stack_pointer: u32;
 
         asm! ("
           // Before switching to the app we need to save the kernel registers to
@@ -177,9 +225,9 @@ impl kernel::syscall::UserspaceKernelBoundary for SysCall {
           sw   x29, 30*4(sp)
           sw   x30, 31*4(sp)
           sw   x31, 32*4(sp)
-          sw   $3,  33*4(sp) // save syscall_args, so we can access it later
+          sw   $2,  33*4(sp) // save syscall_args, so we can access it later
 
-          sw   $2, 1*4(sp)    // Store process state pointer on stack as well.
+          sw   $1, 1*4(sp)    // Store process state pointer on stack as well.
                               // We need to have the available for after the app
                               // returns to the kernel so we can store its
                               // registers.
@@ -208,7 +256,7 @@ impl kernel::syscall::UserspaceKernelBoundary for SysCall {
           // executing at. This has been saved in RiscvimacStoredState for us
           // (either when the app returned back to the kernel or in the
           // `set_process_function()` function).
-          lw   t0, 31*4($2)   // Retrieve the PC from RiscvimacStoredState
+          lw   t0, 31*4($1)   // Retrieve the PC from RiscvimacStoredState
           csrw 0x341, t0      // Set mepc CSR. This is the PC we want to go to.
 
           // Restore all of the app registers from what we saved. If this is the
@@ -216,7 +264,7 @@ impl kernel::syscall::UserspaceKernelBoundary for SysCall {
           // irrelevant, However we do need to set the four arguments to the
           // `_start_ function in the app. If the app has been executing then this
           // allows the app to correctly resume.
-          mv   t0,  $2       // Save the state pointer to a specific register.
+          mv   t0,  $1       // Save the state pointer to a specific register.
           lw   x1,  0*4(t0)  // ra
           lw   x2,  1*4(t0)  // sp
           lw   x3,  2*4(t0)  // gp
@@ -350,12 +398,11 @@ impl kernel::syscall::UserspaceKernelBoundary for SysCall {
           sw   t0, 3*4(t2)
           lw   t0, 13*4(t6)   // Fetch a4
           sw   t0, 4*4(t2)
-          lw   $1, 1*4(t6)    // Fetch sp
 
         _done:
           nop
         "
-          : "=r"(switch_reason), "=r"(new_stack_pointer)
+          : "=r"(switch_reason)
           : "r"(state), "r"(&mut syscall_args)
           : "a0", "a1", "a2", "a3"
           : "volatile");
@@ -381,23 +428,101 @@ impl kernel::syscall::UserspaceKernelBoundary for SysCall {
             1 => kernel::syscall::ContextSwitchReason::Interrupted,
 
             // Some exception occurred in the app.
-            2 => kernel::syscall::ContextSwitchReason::Fault,
+            2 => {
+                self.last_fault.set(Some(FaultState {
+                    mcause: state.mcause,
+                    mtval: state.mtval,
+                    mepc: state.pc,
+                }));
+                kernel::syscall::ContextSwitchReason::Fault
+            }
 
             // This case should never happen but if something goes wrong with
             // the switch back to the kernel mark the app as faulted.
             _ => kernel::syscall::ContextSwitchReason::Fault,
         };
 
-        (new_stack_pointer as *mut usize, ret)
+        // The trap handler saves the app's stack pointer on every trap, not
+        // just on syscalls, so it is also current after a fault.
+        (state.regs[1] as *mut usize, ret)
     }
 
-    unsafe fn fault_fmt(&self, _writer: &mut dyn Write) {}
+    unsafe fn fault_fmt(&self, writer: &mut dyn Write) {
+        let _ = writer.write_fmt(format_args!("\r\n---| Fault Status |---\r\n"));
+
+        let fault = match self.last_fault.get() {
+            Some(fault) => fault,
+            None => {
+                let _ = writer.write_fmt(format_args!("No faults detected.\r\n"));
+                return;
+            }
+        };
+
+        let cause = trap_cause(fault.mcause);
+        let _ = writer.write_fmt(format_args!(
+            "{}\r\n\
+             Faulting Instruction Address:       {:#010X}\r\n",
+            cause, fault.mepc,
+        ));
+        match cause {
+            mcause::Trap::Exception(mcause::Exception::InstructionMisaligned)
+            | mcause::Trap::Exception(mcause::Exception::InstructionFault)
+            | mcause::Trap::Exception(mcause::Exception::LoadMisaligned)
+            | mcause::Trap::Exception(mcause::Exception::LoadFault)
+            | mcause::Trap::Exception(mcause::Exception::StoreMisaligned)
+            | mcause::Trap::Exception(mcause::Exception::StoreFault) => {
+                let _ = writer.write_fmt(format_args!(
+                    "Faulting Memory Address:            {:#010X}\r\n",
+                    fault.mtval
+                ));
+            }
+            // Cores may report the instruction's bits, or zero if they do not.
+            mcause::Trap::Exception(mcause::Exception::IllegalInstruction) if fault.mtval != 0 => {
+                let _ = writer.write_fmt(format_args!(
+                    "Faulting Instruction:               {:#010X}\r\n",
+                    fault.mtval
+                ));
+            }
+            _ => {}
+        }
+        let _ = writer.write_fmt(format_args!(
+            "Machine Cause Register (mcause):    {:#010X}\r\n\
+             Machine Trap Value (mtval):         {:#010X}\r\n",
+            fault.mcause, fault.mtval,
+        ));
+    }
 
     unsafe fn process_detail_fmt(
         &self,
         _stack_pointer: *const usize,
-        _state: &RiscvimacStoredState,
-        _writer: &mut dyn Write,
+        state: &RiscvimacStoredState,
+        writer: &mut dyn Write,
     ) {
+        // Two columns, x1 to x16 on the left and x17 to x31 on the right.
+        for row in 0..16 {
+            let _ = writer.write_fmt(format_args!(
+                "\r\n  {:<3}: {:#010X}",
+                REGISTER_NAMES[row], state.regs[row]
+            ));
+            if let Some(name) = REGISTER_NAMES.get(row + 16) {
+                let _ = writer.write_fmt(format_args!(
+                    "    {:<3}: {:#010X}",
+                    name,
+                    state.regs[row + 16]
+                ));
+            }
+        }
+        let _ = writer.write_fmt(format_args!(
+            "\
+             \r\n  pc : {:#010X}\
+             \r\n\
+             \r\n mcause: {:#010X} ({})\
+             \r\n mtval:  {:#010X}\
+             \r\n",
+            state.pc,
+            state.mcause,
+            trap_cause(state.mcause),
+            state.mtval,
+        ));
     }
 }
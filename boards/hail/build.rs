@@ -1,5 +1,50 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+
+/// Hex encoded Ed25519 public keys, separated by commas or whitespace, whose
+/// signatures on apps the kernel trusts. If any are given, the kernel only
+/// starts apps signed with one of them.
+const APP_SIGNING_KEYS: &str = "APP_SIGNING_KEYS";
+
+fn parse_key(hex: &str) -> [u8; 32] {
+    if hex.len() != 64 {
+        panic!(
+            "{}: `{}` is not 32 hex encoded bytes",
+            APP_SIGNING_KEYS, hex
+        );
+    }
+    let mut key = [0; 32];
+    for (i, byte) in key.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).unwrap_or_else(|_| {
+            panic!(
+                "{}: `{}` is not 32 hex encoded bytes",
+                APP_SIGNING_KEYS, hex
+            )
+        });
+    }
+    key
+}
+
 fn main() {
     println!("cargo:rerun-if-changed=layout.ld");
     println!("cargo:rerun-if-changed=chip_layout.ld");
     println!("cargo:rerun-if-changed=../kernel_layout.ld");
+    println!("cargo:rerun-if-env-changed={}", APP_SIGNING_KEYS);
+
+    let keys: Vec<[u8; 32]> = env::var(APP_SIGNING_KEYS)
+        .unwrap_or_default()
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|hex| !hex.is_empty())
+        .map(parse_key)
+        .collect();
+
+    let mut out = format!(
+        "static TRUSTED_APP_KEYS: [[u8; 32]; {}] = {:?};\n",
+        keys.len(),
+        keys
+    );
+    out += &format!("const REQUIRE_SIGNED_APPS: bool = {};\n", !keys.is_empty());
+    let path = Path::new(&env::var("OUT_DIR").unwrap()).join("app_signing_keys.rs");
+    fs::write(path, out).unwrap();
 }
@@ -40,6 +40,12 @@ const NUM_PROCS: usize = 20;
 // How should the kernel respond when a process faults.
 const FAULT_RESPONSE: kernel::procs::FaultResponse = kernel::procs::FaultResponse::Panic;
 
+// Ed25519 public keys whose signatures on apps are trusted, given with
+// `APP_SIGNING_KEYS` when building the kernel, and whether to require them.
+// `build.rs` sets `REQUIRE_SIGNED_APPS` if there are any keys, so that apps
+// without a valid signature are kept loaded but never started.
+include!(concat!(env!("OUT_DIR"), "/app_signing_keys.rs"));
+
 // RAM to be shared by all application processes.
 #[link_section = ".app_memory"]
 static mut APP_MEMORY: [u8; 49152] = [0; 49152];
@@ -73,6 +79,7 @@ struct Hail {
     button: &'static capsules::button::Button<'static>,
     rng: &'static capsules::rng::RngDriver<'static>,
     ipc: kernel::ipc::IPC,
+    mailbox: kernel::ipc::Mailbox,
     crc: &'static capsules::crc::Crc<'static, sam4l::crccu::Crccu<'static>>,
     dac: &'static capsules::dac::Dac<'static>,
 }
@@ -105,6 +112,7 @@ impl Platform for Hail {
             capsules::dac::DRIVER_NUM => f(Some(self.dac)),
 
             kernel::ipc::DRIVER_NUM => f(Some(&self.ipc)),
+            kernel::ipc::MAILBOX_DRIVER_NUM => f(Some(&self.mailbox)),
             _ => f(None),
         }
     }
@@ -192,7 +200,7 @@ pub unsafe fn reset_handler() {
 
     set_pin_primary_functions();
 
-    let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(&PROCESSES));
+    let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(&mut PROCESSES));
 
     // Create capabilities that the board needs to call certain protected kernel
     // functions.
@@ -513,6 +521,7 @@ pub unsafe fn reset_handler() {
         button: button,
         rng: rng,
         ipc: kernel::ipc::IPC::new(board_kernel, &memory_allocation_capability),
+        mailbox: kernel::ipc::Mailbox::new(board_kernel, &memory_allocation_capability),
         crc: crc,
         dac: dac,
     };
@@ -534,6 +543,13 @@ pub unsafe fn reset_handler() {
         kernel::debug::DebugWriterWrapper,
         kernel::debug::DebugWriterWrapper::new(debugger)
     );
+    // Keep the most recent debug output, including trace messages, in RAM
+    // so it is printed if the kernel panics.
+    let ram_log = static_init!(
+        kernel::debug::RamLog,
+        kernel::debug::RamLog::new(&mut kernel::debug::RAM_LOG_BUF)
+    );
+    debug_wrapper.add_sink(ram_log, kernel::debug::LogLevel::Trace);
     kernel::debug::set_debug_writer_wrapper(debug_wrapper);
 
     // Reset the nRF and setup the UART bus.
@@ -554,14 +570,32 @@ pub unsafe fn reset_handler() {
         static _sapps: u8;
     }
 
-    kernel::procs::load_processes(
+    let app_checker = static_init!(
+        capsules::app_checker_ed25519::AppCheckerEd25519,
+        capsules::app_checker_ed25519::AppCheckerEd25519::new(
+            &TRUSTED_APP_KEYS,
+            REQUIRE_SIGNED_APPS,
+            kernel::procs::CredentialsFailure::KeepUnstarted
+        )
+    );
+    kernel::procs::load_and_check_processes(
         board_kernel,
         chip,
         &_sapps as *const u8,
         &mut APP_MEMORY,
-        &mut PROCESSES,
         FAULT_RESPONSE,
+        app_checker,
         &process_management_capability,
     );
-    board_kernel.kernel_loop(&hail, chip, Some(&hail.ipc), &main_loop_capability);
+    let scheduler = static_init!(
+        kernel::schedulers::RoundRobinSched,
+        kernel::schedulers::RoundRobinSched::new()
+    );
+    board_kernel.kernel_loop(
+        &hail,
+        chip,
+        Some(&hail.ipc),
+        scheduler,
+        &main_loop_capability,
+    );
 }
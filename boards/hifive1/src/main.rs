@@ -88,7 +88,7 @@ pub unsafe fn reset_handler() {
 
     let main_loop_cap = create_capability!(capabilities::MainLoopCapability);
 
-    let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(&PROCESSES));
+    let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(&mut PROCESSES));
 
     // Configure kernel debug gpios as early as possible
     kernel::debug::assign_gpios(
@@ -215,10 +215,13 @@ pub unsafe fn reset_handler() {
         chip,
         &_sapps as *const u8,
         &mut APP_MEMORY,
-        &mut PROCESSES,
         FAULT_RESPONSE,
         &process_mgmt_cap,
     );
 
-    board_kernel.kernel_loop(&hifive1, chip, None, &main_loop_cap);
+    let scheduler = static_init!(
+        kernel::schedulers::RoundRobinSched,
+        kernel::schedulers::RoundRobinSched::new()
+    );
+    board_kernel.kernel_loop(&hifive1, chip, None, scheduler, &main_loop_cap);
 }
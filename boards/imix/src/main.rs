@@ -99,7 +99,8 @@ const DEFAULT_CTX_PREFIX_LEN: u8 = 8; //Length of context for 6LoWPAN compressio
 const DEFAULT_CTX_PREFIX: [u8; 16] = [0x0 as u8; 16]; //Context for 6LoWPAN Compression
 const PAN_ID: u16 = 0xABCD;
 
-// how should the kernel respond when a process faults
+// how should the kernel respond when a process faults. The restart supervisor
+// set up in `reset_handler` decides this instead while it is the fault policy.
 const FAULT_RESPONSE: kernel::procs::FaultResponse = kernel::procs::FaultResponse::Panic;
 
 #[link_section = ".app_memory"]
@@ -108,6 +109,23 @@ static mut APP_MEMORY: [u8; 32768] = [0; 32768];
 static mut PROCESSES: [Option<&'static dyn kernel::procs::ProcessType>; NUM_PROCS] =
     [None; NUM_PROCS];
 
+/// Record of the last process fault, kept across resets.
+#[link_section = ".retained_memory"]
+static mut FAULT_LOG_BUF: [u8; 1024] = [0; 1024];
+
+/// Restart history of each process slot, for the restart supervisor.
+static mut RESTART_NODES: [capsules::restart_supervisor::RestartNode; NUM_PROCS] = [
+    capsules::restart_supervisor::RestartNode::new(),
+    capsules::restart_supervisor::RestartNode::new(),
+    capsules::restart_supervisor::RestartNode::new(),
+    capsules::restart_supervisor::RestartNode::new(),
+];
+
+/// Lets the fault log and the restart supervisor look up and restart
+/// processes.
+struct ProcessMgmtCapability;
+unsafe impl capabilities::ProcessManagementCapability for ProcessMgmtCapability {}
+
 /// Dummy buffer that causes the linker to reserve enough space for the stack.
 #[no_mangle]
 #[link_section = ".stack_buffer"]
@@ -134,6 +152,12 @@ struct Imix {
     >,
     spi: &'static capsules::spi::Spi<'static, VirtualSpiMasterDevice<'static, sam4l::spi::SpiHw>>,
     ipc: kernel::ipc::IPC,
+    mailbox: kernel::ipc::Mailbox,
+    fault_log: &'static capsules::fault_log::FaultLog<ProcessMgmtCapability>,
+    heartbeat: &'static kernel::heartbeat::Heartbeat<
+        'static,
+        VirtualMuxAlarm<'static, sam4l::ast::Ast<'static>>,
+    >,
     ninedof: &'static capsules::ninedof::NineDof<'static>,
     radio_driver: &'static capsules::ieee802154::RadioDriver<'static>,
     udp_driver: &'static capsules::net::udp::UDPDriver<'static>,
@@ -185,9 +209,16 @@ impl kernel::Platform for Imix {
             capsules::nonvolatile_storage_driver::DRIVER_NUM => f(Some(self.nonvolatile_storage)),
             capsules::rng::DRIVER_NUM => f(Some(self.rng)),
             kernel::ipc::DRIVER_NUM => f(Some(&self.ipc)),
+            kernel::ipc::MAILBOX_DRIVER_NUM => f(Some(&self.mailbox)),
+            kernel::heartbeat::DRIVER_NUM => f(Some(self.heartbeat)),
+            capsules::fault_log::DRIVER_NUM => f(Some(self.fault_log)),
             _ => f(None),
         }
     }
+
+    fn watchdog(&self) -> Option<&dyn hil::watchdog::Watchdog> {
+        Some(unsafe { &sam4l::wdt::WDT })
+    }
 }
 
 unsafe fn set_pin_primary_functions() {
@@ -294,7 +325,7 @@ pub unsafe fn reset_handler() {
         trng: true,
     });
 
-    let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(&PROCESSES));
+    let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(&mut PROCESSES));
 
     // # CONSOLE
     // Create a shared UART channel for the consoles and for kernel debug.
@@ -328,9 +359,62 @@ pub unsafe fn reset_handler() {
         MuxAlarm::new(&sam4l::ast::AST)
     );
     ast.configure(mux_alarm);
+    board_kernel.set_sleep_clock(ast, &main_cap);
     let alarm = AlarmDriverComponent::new(board_kernel, mux_alarm)
         .finalize(components::alarm_component_helper!(sam4l::ast::Ast));
 
+    // Restart apps that ask to be watched and then stop making progress.
+    let heartbeat_alarm = static_init!(
+        VirtualMuxAlarm<'static, sam4l::ast::Ast>,
+        VirtualMuxAlarm::new(mux_alarm)
+    );
+    let heartbeat = static_init!(
+        kernel::heartbeat::Heartbeat<'static, VirtualMuxAlarm<'static, sam4l::ast::Ast>>,
+        kernel::heartbeat::Heartbeat::new(board_kernel, heartbeat_alarm, &grant_cap)
+    );
+    hil::time::Alarm::set_client(heartbeat_alarm, heartbeat);
+
+    // Restart a crashing app at most 5 times a minute, waiting longer before
+    // each restart, rather than panicking.
+    let supervisor_alarm = static_init!(
+        VirtualMuxAlarm<'static, sam4l::ast::Ast>,
+        VirtualMuxAlarm::new(mux_alarm)
+    );
+    let supervisor = static_init!(
+        capsules::restart_supervisor::RestartSupervisor<
+            'static,
+            VirtualMuxAlarm<'static, sam4l::ast::Ast>,
+            ProcessMgmtCapability,
+        >,
+        capsules::restart_supervisor::RestartSupervisor::new(
+            supervisor_alarm,
+            board_kernel,
+            &RESTART_NODES,
+            capsules::restart_supervisor::RestartPolicy {
+                max_restarts: 5,
+                window_ms: 60_000,
+                initial_backoff_ms: 100,
+                max_backoff_ms: 10_000,
+            },
+            ProcessMgmtCapability,
+        )
+    );
+    hil::time::Alarm::set_client(supervisor_alarm, supervisor);
+    board_kernel.set_fault_policy(supervisor, &process_mgmt_cap);
+
+    // Keep the last process fault so it can be read later, also after the
+    // board reboots.
+    let fault_log = static_init!(
+        capsules::fault_log::FaultLog<ProcessMgmtCapability>,
+        capsules::fault_log::FaultLog::new(
+            &mut FAULT_LOG_BUF,
+            board_kernel.create_grant(&grant_cap),
+            board_kernel,
+            ProcessMgmtCapability,
+        )
+    );
+    board_kernel.set_fault_log(fault_log, &process_mgmt_cap);
+
     // # I2C and I2C Sensors
     let mux_i2c = static_init!(MuxI2C<'static>, MuxI2C::new(&sam4l::i2c::I2C2));
     sam4l::i2c::I2C2.set_master_client(mux_i2c);
@@ -432,6 +516,9 @@ pub unsafe fn reset_handler() {
         crc,
         spi: spi_syscalls,
         ipc: kernel::ipc::IPC::new(board_kernel, &grant_cap),
+        mailbox: kernel::ipc::Mailbox::new(board_kernel, &grant_cap),
+        fault_log,
+        heartbeat,
         ninedof,
         radio_driver,
         udp_driver,
@@ -471,15 +558,23 @@ pub unsafe fn reset_handler() {
         /// Beginning of the ROM region containing app images.
         static _sapps: u8;
     }
+    // Keep the last system calls of each app for the process console's
+    // `trace` command.
+    board_kernel.set_syscall_trace_length(16, &process_mgmt_cap);
     kernel::procs::load_processes(
         board_kernel,
         chip,
         &_sapps as *const u8,
         &mut APP_MEMORY,
-        &mut PROCESSES,
         FAULT_RESPONSE,
         &process_mgmt_cap,
     );
 
-    board_kernel.kernel_loop(&imix, chip, Some(&imix.ipc), &main_cap);
+    let scheduler = static_init!(
+        kernel::schedulers::RoundRobinSched,
+        kernel::schedulers::RoundRobinSched::new()
+    );
+    // Reset the board if the kernel stops servicing the watchdog.
+    hil::watchdog::Watchdog::start(&sam4l::wdt::WDT, 1000);
+    board_kernel.kernel_loop(&imix, chip, Some(&imix.ipc), scheduler, &main_cap);
 }
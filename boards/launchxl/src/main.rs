@@ -160,7 +160,7 @@ pub unsafe fn reset_handler() {
 
     while !prcm::Power::is_enabled(prcm::PowerDomain::Serial) {}
 
-    let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(&PROCESSES));
+    let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(&mut PROCESSES));
 
     // Enable the GPIO clocks
     prcm::Clock::enable_gpio();
@@ -407,10 +407,19 @@ pub unsafe fn reset_handler() {
         chip,
         &_sapps as *const u8,
         &mut APP_MEMORY,
-        &mut PROCESSES,
         FAULT_RESPONSE,
         &process_management_capability,
     );
 
-    board_kernel.kernel_loop(&launchxl, chip, Some(&launchxl.ipc), &main_loop_capability);
+    let scheduler = static_init!(
+        kernel::schedulers::RoundRobinSched,
+        kernel::schedulers::RoundRobinSched::new()
+    );
+    board_kernel.kernel_loop(
+        &launchxl,
+        chip,
+        Some(&launchxl.ipc),
+        scheduler,
+        &main_loop_capability,
+    );
 }
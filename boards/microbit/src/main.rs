@@ -0,0 +1,193 @@
+//! Board file for the BBC micro:bit, which QEMU also emulates.
+//!
+//! - <https://tech.microbit.org/hardware/1-5-revision/>
+//!
+//! The micro:bit has an nRF51822, a Cortex-M0 without an MPU, so processes
+//! are not isolated from each other or from the kernel. See the README for
+//! how to run it in QEMU.
+
+#![no_std]
+#![no_main]
+#![feature(asm, core_intrinsics)]
+#![deny(missing_docs)]
+
+use capsules::virtual_alarm::MuxAlarm;
+use capsules::virtual_alarm::VirtualMuxAlarm;
+use capsules::virtual_uart::{MuxUart, UartDevice};
+use components::process_console::ProcessConsoleComponent;
+use kernel::capabilities;
+use kernel::component::Component;
+use kernel::hil;
+use kernel::Platform;
+use kernel::{create_capability, debug, static_init};
+use nrf5x::pinmux::Pinmux;
+use nrf5x::rtc::Rtc;
+
+/// Support routines for debugging I/O.
+pub mod io;
+
+// The UART pins that go to the interface chip, and from there to USB.
+const UART_TXD: u32 = 24;
+const UART_RXD: u32 = 25;
+
+// Number of concurrent processes this platform supports.
+const NUM_PROCS: usize = 2;
+
+// Actual memory for holding the active process structures.
+static mut PROCESSES: [Option<&'static dyn kernel::procs::ProcessType>; NUM_PROCS] = [None, None];
+
+// How should the kernel respond when a process faults.
+const FAULT_RESPONSE: kernel::procs::FaultResponse = kernel::procs::FaultResponse::Panic;
+
+// RAM to be shared by all application processes. The nRF51822 has only 16KB.
+#[link_section = ".app_memory"]
+static mut APP_MEMORY: [u8; 6144] = [0; 6144];
+
+/// Dummy buffer that causes the linker to reserve enough space for the stack.
+#[no_mangle]
+#[link_section = ".stack_buffer"]
+pub static mut STACK_MEMORY: [u8; 0x1000] = [0; 0x1000];
+
+/// A structure representing this platform that holds references to all
+/// capsules for this platform.
+struct MicroBit {
+    pconsole: &'static capsules::process_console::ProcessConsole<
+        'static,
+        components::process_console::Capability,
+    >,
+    console: &'static capsules::console::Console<'static>,
+    ipc: kernel::ipc::IPC,
+    mailbox: kernel::ipc::Mailbox,
+    alarm: &'static capsules::alarm::AlarmDriver<'static, VirtualMuxAlarm<'static, Rtc<'static>>>,
+}
+
+/// Mapping of integer syscalls to objects that implement syscalls.
+impl Platform for MicroBit {
+    fn with_driver<F, R>(&self, driver_num: usize, f: F) -> R
+    where
+        F: FnOnce(Option<&dyn kernel::Driver>) -> R,
+    {
+        match driver_num {
+            capsules::console::DRIVER_NUM => f(Some(self.console)),
+            capsules::alarm::DRIVER_NUM => f(Some(self.alarm)),
+            kernel::ipc::DRIVER_NUM => f(Some(&self.ipc)),
+            kernel::ipc::MAILBOX_DRIVER_NUM => f(Some(&self.mailbox)),
+            _ => f(None),
+        }
+    }
+}
+
+/// Reset Handler.
+///
+/// This symbol is loaded into vector table by the nRF51 chip crate. The chip
+/// starts executing here after reset, as does QEMU after loading the kernel.
+#[no_mangle]
+pub unsafe fn reset_handler() {
+    nrf51::init();
+
+    let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(&mut PROCESSES));
+
+    let chip = static_init!(nrf51::chip::NRF51, nrf51::chip::NRF51::new());
+
+    // The RTC runs from the low frequency clock.
+    nrf51::clock::CLOCK.low_set_source(nrf51::clock::LowClockSource::RC);
+    nrf51::clock::CLOCK.low_start();
+    while !nrf51::clock::CLOCK.low_started() {}
+
+    // UART
+
+    nrf51::uart::UART0.initialize(Pinmux::new(UART_TXD), Pinmux::new(UART_RXD));
+
+    // Create a shared UART channel for the consoles and for kernel debug.
+    let mux_uart = static_init!(
+        MuxUart<'static>,
+        MuxUart::new(
+            &nrf51::uart::UART0,
+            &mut capsules::virtual_uart::RX_BUF,
+            115200
+        )
+    );
+    mux_uart.initialize();
+    hil::uart::Transmit::set_transmit_client(&nrf51::uart::UART0, mux_uart);
+    hil::uart::Receive::set_receive_client(&nrf51::uart::UART0, mux_uart);
+    cortexm0::nvic::Nvic::new(nrf5x::peripheral_interrupts::UART0).enable();
+
+    // The process console, which also carries kernel debug output. The
+    // QEMU tests in `tools/qemu-test` use it to inspect processes.
+    let pconsole = ProcessConsoleComponent::new(board_kernel, mux_uart).finalize(());
+
+    // Create capabilities that the board needs to call certain protected kernel
+    // functions.
+    let memory_allocation_capability = create_capability!(capabilities::MemoryAllocationCapability);
+    let main_loop_capability = create_capability!(capabilities::MainLoopCapability);
+    let process_management_capability =
+        create_capability!(capabilities::ProcessManagementCapability);
+
+    // Create a UartDevice for console
+    let console_uart = static_init!(UartDevice, UartDevice::new(mux_uart, true));
+    console_uart.setup();
+    let console = static_init!(
+        capsules::console::Console,
+        capsules::console::Console::new(
+            console_uart,
+            &mut capsules::console::WRITE_BUF,
+            &mut capsules::console::READ_BUF,
+            board_kernel.create_grant(&memory_allocation_capability)
+        )
+    );
+    hil::uart::Transmit::set_transmit_client(console_uart, console);
+    hil::uart::Receive::set_receive_client(console_uart, console);
+
+    // ALARM
+
+    let rtc = &nrf5x::rtc::RTC;
+    rtc.start();
+    cortexm0::nvic::Nvic::new(nrf5x::peripheral_interrupts::RTC1).enable();
+
+    let mux_alarm = static_init!(MuxAlarm<'static, Rtc>, MuxAlarm::new(&nrf5x::rtc::RTC));
+    hil::time::Alarm::set_client(rtc, mux_alarm);
+    board_kernel.set_sleep_clock(rtc, &main_loop_capability);
+
+    let alarm = components::alarm::AlarmDriverComponent::new(board_kernel, mux_alarm)
+        .finalize(components::alarm_component_helper!(Rtc));
+
+    let microbit = MicroBit {
+        pconsole: pconsole,
+        console: console,
+        ipc: kernel::ipc::IPC::new(board_kernel, &memory_allocation_capability),
+        mailbox: kernel::ipc::Mailbox::new(board_kernel, &memory_allocation_capability),
+        alarm: alarm,
+    };
+
+    microbit.pconsole.start();
+
+    debug!("Initialization complete. Entering main loop");
+
+    extern "C" {
+        /// Beginning of the ROM region containing app images.
+        ///
+        /// This symbol is defined in the linker script.
+        static _sapps: u8;
+    }
+
+    kernel::procs::load_processes(
+        board_kernel,
+        chip,
+        &_sapps as *const u8,
+        &mut APP_MEMORY,
+        FAULT_RESPONSE,
+        &process_management_capability,
+    );
+
+    let scheduler = static_init!(
+        kernel::schedulers::RoundRobinSched,
+        kernel::schedulers::RoundRobinSched::new()
+    );
+    board_kernel.kernel_loop(
+        &microbit,
+        chip,
+        Some(&microbit.ipc),
+        scheduler,
+        &main_loop_capability,
+    );
+}
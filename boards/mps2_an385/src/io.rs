@@ -0,0 +1,61 @@
+use core::fmt::Write;
+use core::panic::PanicInfo;
+
+use cortexm3;
+
+use kernel::debug;
+use kernel::hil::uart;
+use kernel::hil::uart::Configure;
+
+use mps2;
+
+use crate::PROCESSES;
+
+/// Writer is used by kernel::debug to panic message to the serial port.
+pub struct Writer {
+    initialized: bool,
+}
+
+/// Global static for debug writer
+pub static mut WRITER: Writer = Writer { initialized: false };
+
+impl Write for Writer {
+    fn write_str(&mut self, s: &str) -> ::core::fmt::Result {
+        let uart = unsafe { &mut mps2::uart::UART0 };
+
+        if !self.initialized {
+            self.initialized = true;
+
+            uart.configure(uart::Parameters {
+                baud_rate: 115200,
+                stop_bits: uart::StopBits::One,
+                parity: uart::Parity::None,
+                hw_flow_control: false,
+                width: uart::Width::Eight,
+            });
+        }
+
+        for c in s.bytes() {
+            uart.send_byte(c);
+        }
+
+        Ok(())
+    }
+}
+
+/// Panic handler.
+///
+/// The board has no LEDs that QEMU shows, so this prints the panic and then
+/// spins.
+#[no_mangle]
+#[panic_handler]
+pub unsafe extern "C" fn panic_fmt(info: &PanicInfo) -> ! {
+    let writer = &mut WRITER;
+
+    debug::panic_begin(&cortexm3::support::nop);
+    debug::panic_banner(writer, info);
+    debug::flush(writer);
+    debug::panic_process_info(&PROCESSES, writer);
+
+    loop {}
+}
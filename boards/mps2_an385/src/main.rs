@@ -0,0 +1,195 @@
+//! Board file for the ARM MPS2 with the AN385 FPGA image, as emulated by QEMU.
+//!
+//! - <https://developer.arm.com/tools-and-software/development-boards/fpga-prototyping-boards/mps2>
+//!
+//! This board exists to run the kernel and applications without hardware,
+//! for example in continuous integration. See the README for how to start it.
+
+#![no_std]
+#![no_main]
+#![feature(asm, core_intrinsics)]
+#![deny(missing_docs)]
+
+use capsules::virtual_alarm::{MuxAlarm, VirtualMuxAlarm};
+use capsules::virtual_uart::{MuxUart, UartDevice};
+use components::process_console::ProcessConsoleComponent;
+use kernel::capabilities;
+use kernel::component::Component;
+use kernel::hil;
+use kernel::hil::time::Alarm;
+use kernel::Platform;
+use kernel::{create_capability, debug, static_init};
+
+/// Support routines for debugging I/O.
+pub mod io;
+
+// Number of concurrent processes this platform supports.
+const NUM_PROCS: usize = 4;
+
+// Actual memory for holding the active process structures.
+static mut PROCESSES: [Option<&'static dyn kernel::procs::ProcessType>; NUM_PROCS] =
+    [None, None, None, None];
+
+// How should the kernel respond when a process faults.
+const FAULT_RESPONSE: kernel::procs::FaultResponse = kernel::procs::FaultResponse::Panic;
+
+// RAM to be shared by all application processes.
+#[link_section = ".app_memory"]
+static mut APP_MEMORY: [u8; 65536] = [0; 65536];
+
+/// Dummy buffer that causes the linker to reserve enough space for the stack.
+#[no_mangle]
+#[link_section = ".stack_buffer"]
+pub static mut STACK_MEMORY: [u8; 0x1000] = [0; 0x1000];
+
+/// A structure representing this platform that holds references to all
+/// capsules for this platform.
+struct Mps2An385 {
+    pconsole: &'static capsules::process_console::ProcessConsole<
+        'static,
+        components::process_console::Capability,
+    >,
+    console: &'static capsules::console::Console<'static>,
+    ipc: kernel::ipc::IPC,
+    mailbox: kernel::ipc::Mailbox,
+    alarm: &'static capsules::alarm::AlarmDriver<
+        'static,
+        VirtualMuxAlarm<'static, mps2::timer::TimerAlarm<'static>>,
+    >,
+}
+
+/// Mapping of integer syscalls to objects that implement syscalls.
+impl Platform for Mps2An385 {
+    fn with_driver<F, R>(&self, driver_num: usize, f: F) -> R
+    where
+        F: FnOnce(Option<&dyn kernel::Driver>) -> R,
+    {
+        match driver_num {
+            capsules::console::DRIVER_NUM => f(Some(self.console)),
+            capsules::alarm::DRIVER_NUM => f(Some(self.alarm)),
+            kernel::ipc::DRIVER_NUM => f(Some(&self.ipc)),
+            kernel::ipc::MAILBOX_DRIVER_NUM => f(Some(&self.mailbox)),
+            _ => f(None),
+        }
+    }
+}
+
+/// Reset Handler.
+///
+/// This symbol is loaded into vector table by the MPS2 chip crate. QEMU
+/// starts executing here after loading the kernel image.
+#[no_mangle]
+pub unsafe fn reset_handler() {
+    mps2::init();
+
+    let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(&mut PROCESSES));
+
+    let chip = static_init!(mps2::chip::Mps2, mps2::chip::Mps2::new());
+
+    // UART
+
+    // Create a shared UART channel for the consoles and for kernel debug.
+    let mux_uart = static_init!(
+        MuxUart<'static>,
+        MuxUart::new(
+            &mps2::uart::UART0,
+            &mut capsules::virtual_uart::RX_BUF,
+            115200
+        )
+    );
+    mux_uart.initialize();
+    hil::uart::Transmit::set_transmit_client(&mps2::uart::UART0, mux_uart);
+    hil::uart::Receive::set_receive_client(&mps2::uart::UART0, mux_uart);
+    cortexm3::nvic::Nvic::new(mps2::nvic::UART0_RX).enable();
+    cortexm3::nvic::Nvic::new(mps2::nvic::UART0_TX).enable();
+
+    // The process console, which also carries kernel debug output. The
+    // QEMU tests in `tools/qemu-test` use it to inspect processes.
+    let pconsole = ProcessConsoleComponent::new(board_kernel, mux_uart).finalize(());
+
+    // Create capabilities that the board needs to call certain protected kernel
+    // functions.
+    let memory_allocation_capability = create_capability!(capabilities::MemoryAllocationCapability);
+    let main_loop_capability = create_capability!(capabilities::MainLoopCapability);
+    let process_management_capability =
+        create_capability!(capabilities::ProcessManagementCapability);
+
+    // Create a UartDevice for console
+    let console_uart = static_init!(UartDevice, UartDevice::new(mux_uart, true));
+    console_uart.setup();
+    let console = static_init!(
+        capsules::console::Console,
+        capsules::console::Console::new(
+            console_uart,
+            &mut capsules::console::WRITE_BUF,
+            &mut capsules::console::READ_BUF,
+            board_kernel.create_grant(&memory_allocation_capability)
+        )
+    );
+    hil::uart::Transmit::set_transmit_client(console_uart, console);
+    hil::uart::Receive::set_receive_client(console_uart, console);
+
+    // ALARM
+
+    mps2::timer::ALARM.start();
+    cortexm3::nvic::Nvic::new(mps2::nvic::TIMER1).enable();
+
+    let mux_alarm = static_init!(
+        MuxAlarm<'static, mps2::timer::TimerAlarm>,
+        MuxAlarm::new(&mps2::timer::ALARM)
+    );
+    mps2::timer::ALARM.set_client(mux_alarm);
+
+    let virtual_alarm = static_init!(
+        VirtualMuxAlarm<'static, mps2::timer::TimerAlarm>,
+        VirtualMuxAlarm::new(mux_alarm)
+    );
+    let alarm = static_init!(
+        capsules::alarm::AlarmDriver<'static, VirtualMuxAlarm<'static, mps2::timer::TimerAlarm>>,
+        capsules::alarm::AlarmDriver::new(
+            virtual_alarm,
+            board_kernel.create_grant(&memory_allocation_capability)
+        )
+    );
+    virtual_alarm.set_client(alarm);
+
+    let mps2_an385 = Mps2An385 {
+        pconsole: pconsole,
+        console: console,
+        ipc: kernel::ipc::IPC::new(board_kernel, &memory_allocation_capability),
+        mailbox: kernel::ipc::Mailbox::new(board_kernel, &memory_allocation_capability),
+        alarm: alarm,
+    };
+
+    mps2_an385.pconsole.start();
+
+    debug!("Initialization complete. Entering main loop");
+
+    extern "C" {
+        /// Beginning of the ROM region containing app images.
+        ///
+        /// This symbol is defined in the linker script.
+        static _sapps: u8;
+    }
+
+    kernel::procs::load_processes(
+        board_kernel,
+        chip,
+        &_sapps as *const u8,
+        &mut APP_MEMORY,
+        FAULT_RESPONSE,
+        &process_management_capability,
+    );
+
+    let scheduler = static_init!(
+        kernel::schedulers::RoundRobinSched,
+        kernel::schedulers::RoundRobinSched::new()
+    );
+    board_kernel.kernel_loop(
+        &mps2_an385,
+        chip,
+        Some(&mps2_an385.ipc),
+        scheduler,
+        &main_loop_capability,
+    );
+}
@@ -209,7 +209,7 @@ pub unsafe fn reset_handler() {
         btn.set_floating_state(kernel::hil::gpio::FloatingState::PullUp);
     }
 
-    let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(&PROCESSES));
+    let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(&mut PROCESSES));
 
     nrf52dk_base::setup_board(
         board_kernel,
@@ -229,7 +229,6 @@ pub unsafe fn reset_handler() {
         button_pins,
         true,
         &mut APP_MEMORY,
-        &mut PROCESSES,
         FAULT_RESPONSE,
     );
 }
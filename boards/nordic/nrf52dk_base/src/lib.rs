@@ -19,6 +19,10 @@ pub mod nrf52_components;
 use nrf52_components::ble::BLEComponent;
 use nrf52_components::ieee802154::Ieee802154Component;
 
+/// Buffers for the kernel debug output sent over Segger RTT.
+static mut RTT_DEBUG_OUTPUT_BUF: [u8; 64] = [0; 64];
+static mut RTT_DEBUG_INTERNAL_BUF: [u8; 1024] = [0; 1024];
+
 // Constants related to the configuration of the 15.4 network stack
 const SRC_MAC: u16 = 0xf00f;
 const PAN_ID: u16 = 0xABCD;
@@ -142,8 +146,7 @@ pub unsafe fn setup_board(
         capsules::button::GpioMode,
     )],
     ieee802154: bool,
-    app_memory: &mut [u8],
-    process_pointers: &'static mut [Option<&'static dyn kernel::procs::ProcessType>],
+    app_memory: &'static mut [u8],
     app_fault_response: kernel::procs::FaultResponse,
 ) {
     // Make non-volatile memory writable and activate the reset button
@@ -207,6 +210,7 @@ pub unsafe fn setup_board(
         capsules::virtual_alarm::MuxAlarm::new(&nrf5x::rtc::RTC)
     );
     hil::time::Alarm::set_client(rtc, mux_alarm);
+    board_kernel.set_sleep_clock(rtc, &main_loop_capability);
 
     let alarm = components::alarm::AlarmDriverComponent::new(board_kernel, mux_alarm)
         .finalize(components::alarm_component_helper!(nrf5x::rtc::Rtc));
@@ -263,6 +267,50 @@ pub unsafe fn setup_board(
         kernel::debug::DebugWriterWrapper,
         kernel::debug::DebugWriterWrapper::new(debugger)
     );
+
+    // Also send debug output over Segger RTT, which can be read through the
+    // on-board J-Link without using the UART.
+    let virtual_alarm_rtt = static_init!(
+        VirtualMuxAlarm<'static, nrf5x::rtc::Rtc>,
+        VirtualMuxAlarm::new(mux_alarm)
+    );
+    let rtt_memory = static_init!(
+        capsules::segger_rtt::SeggerRttMemory,
+        capsules::segger_rtt::SeggerRttMemory::new(
+            b"Terminal\0",
+            &mut capsules::segger_rtt::UP_BUFFER,
+            b"Terminal\0",
+            &mut capsules::segger_rtt::DOWN_BUFFER
+        )
+    );
+    let rtt = static_init!(
+        capsules::segger_rtt::SeggerRtt<VirtualMuxAlarm<'static, nrf5x::rtc::Rtc>>,
+        capsules::segger_rtt::SeggerRtt::new(
+            virtual_alarm_rtt,
+            rtt_memory,
+            &mut capsules::segger_rtt::UP_BUFFER,
+            &mut capsules::segger_rtt::DOWN_BUFFER
+        )
+    );
+    hil::time::Alarm::set_client(virtual_alarm_rtt, rtt);
+    let rtt_debugger = static_init!(
+        kernel::debug::DebugWriter,
+        kernel::debug::DebugWriter::new(
+            rtt,
+            &mut RTT_DEBUG_OUTPUT_BUF,
+            &mut RTT_DEBUG_INTERNAL_BUF,
+        )
+    );
+    hil::uart::Transmit::set_transmit_client(rtt, rtt_debugger);
+    debug_wrapper.add_sink(rtt_debugger, kernel::debug::LogLevel::Debug);
+
+    // Keep the most recent debug output, including trace messages, in RAM
+    // so it is printed if the kernel panics.
+    let ram_log = static_init!(
+        kernel::debug::RamLog,
+        kernel::debug::RamLog::new(&mut kernel::debug::RAM_LOG_BUF)
+    );
+    debug_wrapper.add_sink(ram_log, kernel::debug::LogLevel::Trace);
     kernel::debug::set_debug_writer_wrapper(debug_wrapper);
 
     let ble_radio =
@@ -426,10 +474,19 @@ pub unsafe fn setup_board(
         chip,
         &_sapps as *const u8,
         app_memory,
-        process_pointers,
         app_fault_response,
         &process_management_capability,
     );
 
-    board_kernel.kernel_loop(&platform, chip, Some(&platform.ipc), &main_loop_capability);
+    let scheduler = static_init!(
+        kernel::schedulers::RoundRobinSched,
+        kernel::schedulers::RoundRobinSched::new()
+    );
+    board_kernel.kernel_loop(
+        &platform,
+        chip,
+        Some(&platform.ipc),
+        scheduler,
+        &main_loop_capability,
+    );
 }
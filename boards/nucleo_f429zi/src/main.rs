@@ -180,7 +180,7 @@ pub unsafe fn reset_handler() {
 
     setup_peripherals();
 
-    let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(&PROCESSES));
+    let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(&mut PROCESSES));
 
     let chip = static_init!(
         stm32f4xx::chip::Stm32f4xx,
@@ -363,15 +363,19 @@ pub unsafe fn reset_handler() {
         chip,
         &_sapps as *const u8,
         &mut APP_MEMORY,
-        &mut PROCESSES,
         FAULT_RESPONSE,
         &process_management_capability,
     );
 
+    let scheduler = static_init!(
+        kernel::schedulers::RoundRobinSched,
+        kernel::schedulers::RoundRobinSched::new()
+    );
     board_kernel.kernel_loop(
         &nucleo_f429zi,
         chip,
         Some(&nucleo_f429zi.ipc),
+        scheduler,
         &main_loop_capability,
     );
 }
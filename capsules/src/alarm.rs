@@ -2,7 +2,7 @@
 
 use core::cell::Cell;
 use kernel::hil::time::{self, Alarm, Frequency};
-use kernel::{AppId, Callback, Driver, Grant, ReturnCode};
+use kernel::{AppId, Callback, Driver, Grant, ReturnCode, SyscallReturn};
 
 /// Syscall driver number.
 use crate::driver;
@@ -34,6 +34,10 @@ pub struct AlarmDriver<'a, A: Alarm<'a>> {
     num_armed: Cell<usize>,
     app_alarm: Grant<AlarmData>,
     prev: Cell<u32>,
+    /// The last clock value seen, and how many times the clock has wrapped
+    /// around before it.
+    last_now: Cell<u32>,
+    wraps: Cell<u32>,
 }
 
 impl<A: Alarm<'a>> AlarmDriver<'a, A> {
@@ -43,9 +47,23 @@ impl<A: Alarm<'a>> AlarmDriver<'a, A> {
             num_armed: Cell::new(0),
             app_alarm: grant,
             prev: Cell::new(0),
+            last_now: Cell::new(0),
+            wraps: Cell::new(0),
         }
     }
 
+    /// The current clock value extended to 64 bits. The driver reads the
+    /// clock at least once per alarm and command, so this only misses a wrap
+    /// if none of those happen for a whole period of the clock.
+    fn now_u64(&self) -> u64 {
+        let now = self.alarm.now();
+        if now < self.last_now.get() {
+            self.wraps.set(self.wraps.get().wrapping_add(1));
+        }
+        self.last_now.set(now);
+        (self.wraps.get() as u64) << 32 | now as u64
+    }
+
     fn reset_active_alarm(&self, now: u32) -> Option<u32> {
         self.prev.set(now);
         let mut next_alarm = u32::max_value();
@@ -108,7 +126,7 @@ impl<A: Alarm<'a>> Driver for AlarmDriver<'a, A> {
         // (i.e. no change to the alarms).
         self.app_alarm
             .enter(caller_id, |td, _alloc| {
-                let now = self.alarm.now();
+                let now = self.now_u64() as u32;
                 let (return_code, reset) = match cmd_type {
                     0 /* check if present */ => (ReturnCode::SuccessWithValue { value: 1 }, false),
                     1 /* Get clock frequency */ => {
@@ -156,6 +174,41 @@ impl<A: Alarm<'a>> Driver for AlarmDriver<'a, A> {
             })
             .unwrap_or_else(|err| err.into())
     }
+
+    /// Setup and read the alarm, returning more than one value.
+    ///
+    /// ### `command_num`
+    ///
+    /// - `2`: Read the current clock value extended to 64 bits.
+    /// - `3`: Stop the alarm if it is outstanding. If `data` does not match
+    ///        the outstanding alarm, the error comes with its clock value.
+    ///
+    /// Other commands return the same as `command`.
+    fn typed_command(
+        &self,
+        cmd_type: usize,
+        data: usize,
+        r3: usize,
+        caller_id: AppId,
+    ) -> SyscallReturn {
+        match cmd_type {
+            2 => SyscallReturn::SuccessU64(self.now_u64()),
+            3 => {
+                let outstanding = self
+                    .app_alarm
+                    .enter(caller_id, |td, _alloc| match td.expiration {
+                        Expiration::Abs(exp) if exp != data as u32 => Some(exp),
+                        _ => None,
+                    })
+                    .unwrap_or(None);
+                match outstanding {
+                    Some(exp) => SyscallReturn::FailureU32(ReturnCode::EINVAL, exp),
+                    None => self.command(cmd_type, data, r3, caller_id).into(),
+                }
+            }
+            _ => self.command(cmd_type, data, r3, caller_id).into(),
+        }
+    }
 }
 
 fn has_expired(alarm: u32, now: u32, prev: u32) -> bool {
@@ -164,7 +217,7 @@ fn has_expired(alarm: u32, now: u32, prev: u32) -> bool {
 
 impl<A: Alarm<'a>> time::AlarmClient for AlarmDriver<'a, A> {
     fn fired(&self) {
-        let now = self.alarm.now();
+        let now = self.now_u64() as u32;
         self.app_alarm.each(|alarm| {
             if let Expiration::Abs(exp) = alarm.expiration {
                 let expired = has_expired(exp, now, self.prev.get());
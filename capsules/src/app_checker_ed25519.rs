@@ -0,0 +1,104 @@
+//! Process credentials checker that verifies Ed25519 signatures in software.
+//!
+//! This checker accepts an app if one of its credentials footers is an Ed25519
+//! signature over the app's integrity region made with one of the board's
+//! trusted public keys. A signature that does not verify against any trusted
+//! key is passed on rather than rejected, so an app can carry signatures from
+//! several signers. Credentials in other formats are passed on as well. If the
+//! board requires signatures, apps without an accepted one are refused or kept
+//! unstarted, as the board's `CredentialsFailure` asks.
+//!
+//! Signatures are verified with the `tock-ed25519` library. This is slow,
+//! taking on the order of a second per app on a Cortex-M4, but needs no
+//! hardware support and only happens at boot.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! static TRUSTED_KEYS: [[u8; 32]; 1] = [[
+//!     0xd7, 0x5a, 0x98, 0x01, 0x82, 0xb1, 0x0a, 0xb7, 0xd5, 0x4b, 0xfe, 0xd3, 0xc9, 0x64,
+//!     0x07, 0x3a, 0x0e, 0xe1, 0x72, 0xf3, 0xda, 0xa6, 0x23, 0x25, 0xaf, 0x02, 0x1a, 0x68,
+//!     0xf7, 0x07, 0x51, 0x1a,
+//! ]];
+//!
+//! let checker = static_init!(
+//!     capsules::app_checker_ed25519::AppCheckerEd25519,
+//!     capsules::app_checker_ed25519::AppCheckerEd25519::new(
+//!         &TRUSTED_KEYS,
+//!         true,
+//!         kernel::procs::CredentialsFailure::KeepUnstarted
+//!     )
+//! );
+//!
+//! kernel::procs::load_and_check_processes(
+//!     board_kernel,
+//!     chip,
+//!     &_sapps as *const u8,
+//!     &mut APP_MEMORY,
+//!     FAULT_RESPONSE,
+//!     checker,
+//!     &process_management_capability,
+//! );
+//! ```
+
+use kernel::procs::{
+    AppCredentialsChecker, CheckResult, CredentialsFailure, TbfFooterV2Credentials,
+    TbfFooterV2CredentialsType,
+};
+
+pub struct AppCheckerEd25519 {
+    trusted_keys: &'static [[u8; 32]],
+    require_signature: bool,
+    failure_response: CredentialsFailure,
+}
+
+impl AppCheckerEd25519 {
+    /// Create a checker for apps signed with one of `trusted_keys`. If
+    /// `require_signature` is true, apps without such a signature are handled
+    /// according to `failure_response`; otherwise they are loaded as well.
+    pub const fn new(
+        trusted_keys: &'static [[u8; 32]],
+        require_signature: bool,
+        failure_response: CredentialsFailure,
+    ) -> AppCheckerEd25519 {
+        AppCheckerEd25519 {
+            trusted_keys: trusted_keys,
+            require_signature: require_signature,
+            failure_response: failure_response,
+        }
+    }
+}
+
+impl AppCredentialsChecker for AppCheckerEd25519 {
+    fn require_credentials(&self) -> bool {
+        self.require_signature
+    }
+
+    fn check_credentials(
+        &self,
+        credentials: TbfFooterV2Credentials,
+        integrity_region: &[u8],
+    ) -> CheckResult {
+        match credentials.format() {
+            TbfFooterV2CredentialsType::Ed25519 => {
+                let mut signature = [0u8; 64];
+                signature.copy_from_slice(credentials.data());
+                if self
+                    .trusted_keys
+                    .iter()
+                    .any(|key| tock_ed25519::verify(key, integrity_region, &signature))
+                {
+                    CheckResult::Accept
+                } else {
+                    CheckResult::Pass
+                }
+            }
+            _ => CheckResult::Pass,
+        }
+    }
+
+    fn failure_response(&self) -> CredentialsFailure {
+        self.failure_response
+    }
+}
@@ -0,0 +1,204 @@
+//! Process credentials checker that verifies SHA-256 hashes in software.
+//!
+//! This checker accepts an app if one of its credentials footers is a SHA-256
+//! hash that matches the app's integrity region, and refuses it if a SHA-256
+//! footer does not match. Credentials in other formats are passed on. A hash
+//! alone only protects against corrupted images, not malicious ones; boards
+//! that need to authenticate apps should use the signature checker in
+//! `app_checker_ed25519` instead.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! let checker = static_init!(
+//!     capsules::app_checker_sha256::AppCheckerSha256,
+//!     capsules::app_checker_sha256::AppCheckerSha256::new(true)
+//! );
+//!
+//! kernel::procs::load_and_check_processes(
+//!     board_kernel,
+//!     chip,
+//!     &_sapps as *const u8,
+//!     &mut APP_MEMORY,
+//!     FAULT_RESPONSE,
+//!     checker,
+//!     &process_management_capability,
+//! );
+//! ```
+
+use kernel::procs::{
+    AppCredentialsChecker, CheckResult, TbfFooterV2Credentials, TbfFooterV2CredentialsType,
+};
+
+pub struct AppCheckerSha256 {
+    require_credentials: bool,
+}
+
+impl AppCheckerSha256 {
+    /// Create a checker. If `require_credentials` is true, apps without a
+    /// matching SHA-256 credential are refused.
+    pub const fn new(require_credentials: bool) -> AppCheckerSha256 {
+        AppCheckerSha256 {
+            require_credentials: require_credentials,
+        }
+    }
+}
+
+impl AppCredentialsChecker for AppCheckerSha256 {
+    fn require_credentials(&self) -> bool {
+        self.require_credentials
+    }
+
+    fn check_credentials(
+        &self,
+        credentials: TbfFooterV2Credentials,
+        integrity_region: &[u8],
+    ) -> CheckResult {
+        match credentials.format() {
+            TbfFooterV2CredentialsType::Sha256 => {
+                if sha256(integrity_region)[..] == credentials.data()[..] {
+                    CheckResult::Accept
+                } else {
+                    CheckResult::Reject
+                }
+            }
+            _ => CheckResult::Pass,
+        }
+    }
+}
+
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+const H0: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+fn compress(state: &mut [u32; 8], block: &[u8]) {
+    let mut w = [0u32; 64];
+    for i in 0..16 {
+        w[i] = (block[4 * i] as u32) << 24
+            | (block[4 * i + 1] as u32) << 16
+            | (block[4 * i + 2] as u32) << 8
+            | (block[4 * i + 3] as u32);
+    }
+    for i in 16..64 {
+        let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+        let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+        w[i] = w[i - 16]
+            .wrapping_add(s0)
+            .wrapping_add(w[i - 7])
+            .wrapping_add(s1);
+    }
+
+    let mut v = *state;
+    for i in 0..64 {
+        let s1 = v[4].rotate_right(6) ^ v[4].rotate_right(11) ^ v[4].rotate_right(25);
+        let ch = (v[4] & v[5]) ^ (!v[4] & v[6]);
+        let t1 = v[7]
+            .wrapping_add(s1)
+            .wrapping_add(ch)
+            .wrapping_add(K[i])
+            .wrapping_add(w[i]);
+        let s0 = v[0].rotate_right(2) ^ v[0].rotate_right(13) ^ v[0].rotate_right(22);
+        let maj = (v[0] & v[1]) ^ (v[0] & v[2]) ^ (v[1] & v[2]);
+        let t2 = s0.wrapping_add(maj);
+        v = [
+            t1.wrapping_add(t2),
+            v[0],
+            v[1],
+            v[2],
+            v[3].wrapping_add(t1),
+            v[4],
+            v[5],
+            v[6],
+        ];
+    }
+
+    for (s, v) in state.iter_mut().zip(v.iter()) {
+        *s = s.wrapping_add(*v);
+    }
+}
+
+/// Compute the SHA-256 hash of `data`.
+fn sha256(data: &[u8]) -> [u8; 32] {
+    let mut state = H0;
+
+    let mut blocks = data.chunks_exact(64);
+    for block in &mut blocks {
+        compress(&mut state, block);
+    }
+
+    // Pad the remainder with a 1 bit, zeros, and the message length in bits.
+    // This takes one or two more blocks.
+    let remainder = blocks.remainder();
+    let mut last = [0u8; 128];
+    last[..remainder.len()].copy_from_slice(remainder);
+    last[remainder.len()] = 0x80;
+    let last_len = if remainder.len() < 56 { 64 } else { 128 };
+    let bit_len = (data.len() as u64) * 8;
+    for i in 0..8 {
+        last[last_len - 1 - i] = (bit_len >> (8 * i)) as u8;
+    }
+    for block in last[..last_len].chunks(64) {
+        compress(&mut state, block);
+    }
+
+    let mut hash = [0u8; 32];
+    for (i, word) in state.iter().enumerate() {
+        hash[4 * i] = (word >> 24) as u8;
+        hash[4 * i + 1] = (word >> 16) as u8;
+        hash[4 * i + 2] = (word >> 8) as u8;
+        hash[4 * i + 3] = *word as u8;
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::sha256;
+
+    fn from_hex(s: &str) -> [u8; 32] {
+        let mut out = [0u8; 32];
+        for (i, byte) in out.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&s[2 * i..2 * i + 2], 16).unwrap();
+        }
+        out
+    }
+
+    // Test vectors from the NIST SHA-256 examples.
+
+    #[test]
+    fn empty() {
+        assert_eq!(
+            sha256(b""),
+            from_hex("e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855")
+        );
+    }
+
+    #[test]
+    fn abc() {
+        assert_eq!(
+            sha256(b"abc"),
+            from_hex("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad")
+        );
+    }
+
+    #[test]
+    fn two_blocks() {
+        // The padding of this message does not fit in its last block.
+        assert_eq!(
+            sha256(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"),
+            from_hex("248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1")
+        );
+    }
+}
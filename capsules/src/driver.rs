@@ -19,6 +19,9 @@ pub enum NUM {
 
     // Kernel
     Ipc                   = 0x10000,
+    FaultLog              = 0x10001,
+    Mailbox               = 0x10002,
+    Heartbeat             = 0x10003,
 
     // HW Buses
     Spi                   = 0x20001,
@@ -0,0 +1,309 @@
+//! Keeps a record of the last process fault in memory that survives a reboot.
+//!
+//! The record holds the name and restart count of the process, the fault
+//! status registers, and the process's registers, as text. It is written when
+//! the process faults and kept in a buffer the board places in retained RAM,
+//! i.e. RAM that is not zeroed when the kernel boots. After the next boot the
+//! record can be printed with the process console's `faultlog` command or read
+//! by an app with this capsule's system calls.
+//!
+//! Only the most recent fault is kept. A record survives resets that keep the
+//! RAM powered, such as a panic followed by a watchdog or debugger reset, but
+//! not a power cycle.
+//!
+//! The register dump can contain an app's secrets, so an app can only read or
+//! erase a record of its own faults. A record belongs to the app whose image
+//! starts at the same flash address as the image of the process that faulted;
+//! the app's name is not used, as any app can claim any name in its TBF
+//! header. To other apps, the log looks empty. If a different app is flashed
+//! at the same address, e.g. while replacing the apps, it can read a record
+//! that survived the reboot.
+//!
+//! Usage
+//! -----
+//!
+//! The buffer must be placed in the `.retained_memory` section so that it is
+//! not zeroed on boot:
+//!
+//! ```rust
+//! #[link_section = ".retained_memory"]
+//! static mut FAULT_LOG_BUF: [u8; 1024] = [0; 1024];
+//!
+//! struct FaultLogCapability;
+//! unsafe impl capabilities::ProcessManagementCapability for FaultLogCapability {}
+//!
+//! let fault_log = static_init!(
+//!     capsules::fault_log::FaultLog<FaultLogCapability>,
+//!     capsules::fault_log::FaultLog::new(
+//!         &mut FAULT_LOG_BUF,
+//!         board_kernel.create_grant(&memory_allocation_capability),
+//!         board_kernel,
+//!         FaultLogCapability,
+//!     )
+//! );
+//! board_kernel.set_fault_log(fault_log, &process_management_capability);
+//! ```
+
+use core::cell::Cell;
+use core::cmp;
+use core::fmt;
+use kernel::capabilities::ProcessManagementCapability;
+use kernel::common::cells::TakeCell;
+use kernel::procs::{ProcessFault, ProcessFaultLog};
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, Kernel, ReturnCode, Shared};
+
+/// Syscall driver number.
+use crate::driver;
+pub const DRIVER_NUM: usize = driver::NUM::FaultLog as usize;
+
+/// Marks a buffer that holds a valid record.
+const MAGIC: u32 = 0x7E1F_A018;
+
+/// Bytes before the record text: magic, flash address of the image of the
+/// process that faulted, text length and checksum.
+const HEADER_LEN: usize = 16;
+
+#[derive(Default)]
+pub struct App {
+    buffer: Option<AppSlice<Shared, u8>>,
+}
+
+pub struct FaultLog<C: ProcessManagementCapability> {
+    buffer: TakeCell<'static, [u8]>,
+    apps: Grant<App>,
+    kernel: &'static Kernel,
+    capability: C,
+}
+
+impl<C: ProcessManagementCapability> FaultLog<C> {
+    /// Create the fault log. If `buffer` holds a record from before the
+    /// board rebooted, that record is kept.
+    pub fn new(
+        buffer: &'static mut [u8],
+        apps: Grant<App>,
+        kernel: &'static Kernel,
+        capability: C,
+    ) -> FaultLog<C> {
+        if record_bounds(buffer).is_none() {
+            clear(buffer);
+        }
+        FaultLog {
+            buffer: TakeCell::new(buffer),
+            apps: apps,
+            kernel: kernel,
+            capability: capability,
+        }
+    }
+
+    /// Flash address of the image of the process `appid` refers to, if it
+    /// still exists.
+    fn flash_start(&self, appid: AppId) -> Option<u32> {
+        let flash_start = Cell::new(None);
+        self.kernel
+            .process_each_capability(&self.capability, |_, process| {
+                if process.appid() == appid {
+                    flash_start.set(Some(process.flash_start() as u32));
+                }
+            });
+        flash_start.get()
+    }
+
+    /// Call `f` with the buffer and the length of the record text if the
+    /// record is of a fault of the process `appid`. Returns `None` otherwise.
+    fn map_own_fault<F, R>(&self, appid: AppId, f: F) -> Option<R>
+    where
+        F: FnOnce(&mut [u8], usize) -> R,
+    {
+        let flash_start = self.flash_start(appid)?;
+        self.buffer
+            .map(|buffer| match record_bounds(buffer) {
+                Some(len) if read_u32(buffer, 4) == flash_start => Some(f(buffer, len)),
+                _ => None,
+            })
+            .unwrap_or(None)
+    }
+}
+
+fn read_u32(buffer: &[u8], offset: usize) -> u32 {
+    (buffer[offset] as u32)
+        | (buffer[offset + 1] as u32) << 8
+        | (buffer[offset + 2] as u32) << 16
+        | (buffer[offset + 3] as u32) << 24
+}
+
+fn write_u32(buffer: &mut [u8], offset: usize, value: u32) {
+    buffer[offset] = value as u8;
+    buffer[offset + 1] = (value >> 8) as u8;
+    buffer[offset + 2] = (value >> 16) as u8;
+    buffer[offset + 3] = (value >> 24) as u8;
+}
+
+/// Checksum over the flash address of the faulting process and the text.
+fn checksum(flash_start: u32, text: &[u8]) -> u32 {
+    text.iter().fold(MAGIC ^ flash_start, |sum, &byte| {
+        sum.rotate_left(5) ^ byte as u32
+    })
+}
+
+/// Returns the length of the record text if `buffer` holds a valid record.
+/// Uninitialized RAM after a power cycle will almost never pass this check.
+fn record_bounds(buffer: &[u8]) -> Option<usize> {
+    if buffer.len() < HEADER_LEN || read_u32(buffer, 0) != MAGIC {
+        return None;
+    }
+    let len = read_u32(buffer, 8) as usize;
+    if len > buffer.len() - HEADER_LEN {
+        return None;
+    }
+    if checksum(read_u32(buffer, 4), &buffer[HEADER_LEN..HEADER_LEN + len]) != read_u32(buffer, 12)
+    {
+        return None;
+    }
+    Some(len)
+}
+
+/// The record text of a buffer that `record_bounds()` accepted.
+fn record_text(buffer: &mut [u8], len: usize) -> &mut [u8] {
+    &mut buffer[HEADER_LEN..HEADER_LEN + len]
+}
+
+fn clear(buffer: &mut [u8]) {
+    for byte in buffer.iter_mut().take(HEADER_LEN) {
+        *byte = 0;
+    }
+}
+
+/// Writes formatted text into a buffer, dropping whatever does not fit.
+struct RecordWriter<'a> {
+    buffer: &'a mut [u8],
+    len: usize,
+}
+
+impl fmt::Write for RecordWriter<'a> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let count = cmp::min(s.len(), self.buffer.len() - self.len);
+        self.buffer[self.len..self.len + count].copy_from_slice(&s.as_bytes()[..count]);
+        self.len += count;
+        Ok(())
+    }
+}
+
+impl<C: ProcessManagementCapability> ProcessFaultLog for FaultLog<C> {
+    fn log_fault(&self, fault: &ProcessFault) {
+        self.buffer.map(|buffer| {
+            if buffer.len() < HEADER_LEN {
+                return;
+            }
+            // Invalidate the old record first, so a reset while writing does
+            // not leave a record that looks valid.
+            clear(buffer);
+
+            let (header, text) = buffer.split_at_mut(HEADER_LEN);
+            let flash_start = fault.flash_start() as u32;
+            let mut writer = RecordWriter {
+                buffer: text,
+                len: 0,
+            };
+            fault.fmt(&mut writer);
+            let len = writer.len;
+            let sum = checksum(flash_start, &text[..len]);
+
+            write_u32(header, 4, flash_start);
+            write_u32(header, 8, len as u32);
+            write_u32(header, 12, sum);
+            write_u32(header, 0, MAGIC);
+        });
+    }
+
+    fn map_last_fault(&self, f: &mut dyn FnMut(&[u8])) {
+        self.buffer.map(|buffer| {
+            if let Some(len) = record_bounds(buffer) {
+                f(record_text(buffer, len));
+            }
+        });
+    }
+}
+
+impl<C: ProcessManagementCapability> Driver for FaultLog<C> {
+    /// Setup shared buffers.
+    ///
+    /// ### `allow_num`
+    ///
+    /// - `0`: Buffer the fault record is copied into.
+    fn allow(
+        &self,
+        appid: AppId,
+        allow_num: usize,
+        slice: Option<AppSlice<Shared, u8>>,
+    ) -> ReturnCode {
+        match allow_num {
+            0 => self
+                .apps
+                .enter(appid, |app, _| {
+                    app.buffer = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn subscribe(
+        &self,
+        _subscribe_num: usize,
+        _callback: Option<Callback>,
+        _app_id: AppId,
+    ) -> ReturnCode {
+        ReturnCode::ENOSUPPORT
+    }
+
+    /// Read or clear the record of a fault of the calling process, i.e. of a
+    /// process whose image started at the same flash address. Records of
+    /// other processes' faults are treated as if there were no record.
+    ///
+    /// ### `command_num`
+    ///
+    /// - `0`: Driver check.
+    /// - `1`: Return the length of the fault record in bytes, 0 if there is
+    ///        none.
+    /// - `2`: Copy the fault record into the allowed buffer. Returns the
+    ///        number of bytes copied, which is less than the record length if
+    ///        the buffer is too small. Returns `EINVAL` if no buffer was
+    ///        allowed.
+    /// - `3`: Erase the fault record.
+    fn command(&self, command_num: usize, _data: usize, _: usize, appid: AppId) -> ReturnCode {
+        match command_num {
+            0 => ReturnCode::SUCCESS,
+
+            1 => {
+                let len = self.map_own_fault(appid, |_, len| len).unwrap_or(0);
+                ReturnCode::SuccessWithValue { value: len }
+            }
+
+            2 => self
+                .apps
+                .enter(appid, |app, _| match app.buffer {
+                    Some(ref mut slice) => {
+                        let copied = self
+                            .map_own_fault(appid, |buffer, len| {
+                                let text = record_text(buffer, len);
+                                let copied = cmp::min(text.len(), slice.len());
+                                slice.as_mut()[..copied].copy_from_slice(&text[..copied]);
+                                copied
+                            })
+                            .unwrap_or(0);
+                        ReturnCode::SuccessWithValue { value: copied }
+                    }
+                    None => ReturnCode::EINVAL,
+                })
+                .unwrap_or_else(|err| err.into()),
+
+            3 => {
+                self.map_own_fault(appid, |buffer, _| clear(buffer));
+                ReturnCode::SUCCESS
+            }
+
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
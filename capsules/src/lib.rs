@@ -13,6 +13,8 @@ pub mod alarm;
 pub mod ambient_light;
 pub mod analog_comparator;
 pub mod analog_sensor;
+pub mod app_checker_ed25519;
+pub mod app_checker_sha256;
 pub mod app_flash_driver;
 pub mod ble_advertising_driver;
 pub mod button;
@@ -22,6 +24,7 @@ pub mod crc;
 pub mod dac;
 pub mod debug_process_restart;
 pub mod driver;
+pub mod fault_log;
 pub mod fm25cl;
 pub mod fxos8700cq;
 pub mod gpio;
@@ -43,6 +46,7 @@ pub mod nonvolatile_to_pages;
 pub mod nrf51822_serialization;
 pub mod pca9544a;
 pub mod process_console;
+pub mod restart_supervisor;
 pub mod rf233;
 pub mod rf233_const;
 pub mod rng;
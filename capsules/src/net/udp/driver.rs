@@ -14,7 +14,9 @@ use crate::net::udp::udp_recv::{UDPReceiver, UDPRecvClient};
 use crate::net::udp::udp_send::{UDPSendClient, UDPSender};
 use core::cell::Cell;
 use core::{cmp, mem};
-use kernel::{debug, AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+use kernel::{
+    debug, AppId, AppSlice, Callback, Driver, Grant, ReadOnlyAppSlice, ReturnCode, Shared,
+};
 
 /// Syscall number
 use crate::driver;
@@ -61,6 +63,9 @@ pub struct App {
     tx_callback: Option<Callback>,
     app_read: Option<AppSlice<Shared, u8>>,
     app_write: Option<AppSlice<Shared, u8>>,
+    /// Write buffer shared with read-only allow, used instead of `app_write`
+    /// if it was shared more recently.
+    app_write_read_only: Option<ReadOnlyAppSlice<u8>>,
     app_cfg: Option<AppSlice<Shared, u8>>,
     app_rx_cfg: Option<AppSlice<Shared, u8>>,
     pending_tx: Option<[UDPEndpoint; 2]>,
@@ -243,13 +248,13 @@ impl<'a> UDPDriver<'a> {
             let src_port = addr_ports[0].port;
 
             // Send UDP payload. Payload will be copied into IP6Packet in kernel mem.
-            let result = app
-                .app_write
-                .as_ref()
-                .map_or(ReturnCode::ENOMEM, |payload| {
-                    self.sender
-                        .send_to(dst_addr, dst_port, src_port, payload.as_ref())
-                });
+            let payload = match app.app_write_read_only {
+                Some(ref payload) => Some(payload.as_ref()),
+                None => app.app_write.as_ref().map(|payload| payload.as_ref()),
+            };
+            let result = payload.map_or(ReturnCode::ENOMEM, |payload| {
+                self.sender.send_to(dst_addr, dst_port, src_port, payload)
+            });
             if result == ReturnCode::SUCCESS {
                 self.current_app.set(Some(appid));
             }
@@ -333,7 +338,10 @@ impl<'a> Driver for UDPDriver<'a> {
             0 | 1 | 2 | 3 => self.do_with_app(appid, |app| {
                 match allow_num {
                     0 => app.app_read = slice,
-                    1 => app.app_write = slice,
+                    1 => {
+                        app.app_write = slice;
+                        app.app_write_read_only = None;
+                    }
                     2 => app.app_cfg = slice,
                     3 => app.app_rx_cfg = slice,
                     _ => {}
@@ -344,6 +352,28 @@ impl<'a> Driver for UDPDriver<'a> {
         }
     }
 
+    /// Setup read-only buffers to write from.
+    ///
+    /// ### `allow_num`
+    ///
+    /// - `1`: Write buffer. Contains the UDP payload to be transmitted, and
+    ///        can be in flash.
+    fn allow_read_only(
+        &self,
+        appid: AppId,
+        allow_num: usize,
+        slice: Option<ReadOnlyAppSlice<u8>>,
+    ) -> ReturnCode {
+        match allow_num {
+            1 => self.do_with_app(appid, |app| {
+                app.app_write_read_only = slice;
+                app.app_write = None;
+                ReturnCode::SUCCESS
+            }),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
     /// Setup callbacks.
     ///
     /// ### `subscribe_num`
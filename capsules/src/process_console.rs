@@ -8,10 +8,19 @@
 //! which processes are running. The console has five commands:
 //!  - 'help' prints the available commands and arguments
 //!  - 'status' prints the current system status
-//!  - 'list' lists the current processes with their IDs and running state
+//!  - 'list' lists the current processes with their IDs, CPU usage and
+//!    running state
 //!  - 'stop n' stops the process with name n
 //!  - 'start n' starts the stopped process with name n
 //!  - 'fault n' forces the process with name n into a fault state
+//!  - 'faultlog' prints the last process fault recorded in the board's fault
+//!    log, which may be from before the last reboot
+//!  - 'grants n' prints how much grant memory the process with name n uses,
+//!    in total and for each grant
+//!  - 'trace n' prints the most recent system calls of the process with name
+//!    n, if the board enabled syscall tracing
+//!  - 'power' prints how often and how long the chip has slept in each of its
+//!    sleep states
 //!
 //! Setup
 //! -----
@@ -69,11 +78,18 @@
 //! Initialization complete. Entering main loop
 //! Hello World!
 //! list
-//! PID    Name    Quanta  Syscalls  Dropped Callbacks    State
-//! 00     blink        0       113                  0  Yielded
-//! 01     c_hello      0         8                  0  Yielded
+//!  PID    Name                Quanta  Syscalls  Dropped Callbacks    CPU  State
+//!   00    blink                    0       113                  0   12.5%  Yielded
+//!   01    c_hello                  0         8                  0    0.3%  Yielded
+//!   Kernel                                                          2.1%
 //! ```
 //!
+//! The CPU column shows how much of the time the CPU has been awake each
+//! process has used since boot, including time spent in the kernel handling
+//! its system calls. The last line shows the time the kernel spent servicing
+//! interrupts. On chips without a system tick timer, CPU time is not measured
+//! and the column shows `n/a`.
+//!
 //! To get a general view of the system, use the status command:
 //!
 //! ```text
@@ -83,6 +99,19 @@
 //! Timeslice expirations: 0
 //! ```
 //!
+//! If the board enabled syscall tracing with
+//! `Kernel::set_syscall_trace_length()`, the trace command shows the most
+//! recent system calls of a process, like `strace`:
+//!
+//! ```text
+//! trace blink
+//! Syscall trace of blink (CPU time, call = return value):
+//!       4817us command(0x2, 1, 0x0, 0x0) = 0
+//!       4823us subscribe(0x0, 0, 0x30871, 0x0) = 0
+//!       4830us command(0x0, 4, 0x3e8, 0x0) = 0
+//!       4836us yield()
+//! ```
+//!
 //! and you can control processes with the `start` and `stop` commands:
 //!
 //! ```text
@@ -92,6 +121,7 @@
 
 use core::cell::Cell;
 use core::cmp;
+use core::fmt;
 use core::str;
 use kernel::capabilities::ProcessManagementCapability;
 use kernel::common::cells::TakeCell;
@@ -111,6 +141,20 @@ pub static mut READ_BUF: [u8; 4] = [0; 4];
 // characters, limiting arguments to 25 bytes or so seems fine for now.
 pub static mut COMMAND_BUF: [u8; 32] = [0; 32];
 
+/// A share of the CPU time, in tenths of a percent, printed 8 characters wide
+/// as in the `list` command's CPU column, or `n/a` if CPU time is not
+/// measured.
+struct CpuShare(Option<u64>);
+
+impl fmt::Display for CpuShare {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Some(share) => write!(f, "{:5}.{}%", share / 10, share % 10),
+            None => write!(f, "{:>8}", "n/a"),
+        }
+    }
+}
+
 pub struct ProcessConsole<'a, C: ProcessManagementCapability> {
     uart: &'a dyn uart::UartData<'a>,
     tx_in_progress: Cell<bool>,
@@ -181,7 +225,7 @@ impl<'a, C: ProcessManagementCapability> ProcessConsole<'a, C> {
                         let clean_str = s.trim();
                         if clean_str.starts_with("help") {
                             debug!("Welcome to the process console.");
-                            debug!("Valid commands are: help status list stop start");
+                            debug!("Valid commands are: help status list stop start fault faultlog grants trace power");
                         } else if clean_str.starts_with("start") {
                             let argument = clean_str.split_whitespace().nth(1);
                             argument.map(|name| {
@@ -210,6 +254,22 @@ impl<'a, C: ProcessManagementCapability> ProcessConsole<'a, C> {
                                     },
                                 );
                             });
+                        } else if clean_str.starts_with("faultlog") {
+                            let info: KernelInfo = KernelInfo::new(self.kernel);
+                            let mut found = false;
+                            info.map_last_process_fault(
+                                &mut |record| {
+                                    found = true;
+                                    // Records are text, print them line by line.
+                                    for line in str::from_utf8(record).unwrap_or("").lines() {
+                                        debug!("{}", line.trim_end());
+                                    }
+                                },
+                                &self.capability,
+                            );
+                            if !found {
+                                debug!("No fault recorded");
+                            }
                         } else if clean_str.starts_with("fault") {
                             let argument = clean_str.split_whitespace().nth(1);
                             argument.map(|name| {
@@ -224,21 +284,121 @@ impl<'a, C: ProcessManagementCapability> ProcessConsole<'a, C> {
                                     },
                                 );
                             });
+                        } else if clean_str.starts_with("grants") {
+                            let argument = clean_str.split_whitespace().nth(1);
+                            argument.map(|name| {
+                                let info: KernelInfo = KernelInfo::new(self.kernel);
+                                self.kernel.process_each_capability(
+                                    &self.capability,
+                                    |_i, proc| {
+                                        if proc.get_process_name() != name {
+                                            return;
+                                        }
+                                        let appid = proc.appid();
+                                        let total =
+                                            info.number_app_grant_bytes(appid, &self.capability);
+                                        match info.app_grant_quota(appid, &self.capability) {
+                                            Some(quota) => debug!(
+                                                "Grant memory of {}: {} of {} bytes",
+                                                name,
+                                                total,
+                                                quota
+                                            ),
+                                            None => {
+                                                debug!("Grant memory of {}: {} bytes", name, total)
+                                            }
+                                        }
+                                        for grant_num in 0..info.number_grants(&self.capability) {
+                                            let bytes = info.number_app_grant_bytes_in_grant(
+                                                appid,
+                                                grant_num,
+                                                &self.capability,
+                                            );
+                                            if bytes > 0 {
+                                                debug!(
+                                                    "  Grant {:2}: {:6} bytes",
+                                                    grant_num,
+                                                    bytes
+                                                );
+                                            }
+                                        }
+                                    },
+                                );
+                            });
+                        } else if clean_str.starts_with("trace") {
+                            let argument = clean_str.split_whitespace().nth(1);
+                            argument.map(|name| {
+                                self.kernel.process_each_capability(
+                                    &self.capability,
+                                    |_i, proc| {
+                                        if proc.get_process_name() != name {
+                                            return;
+                                        }
+                                        let mut traced = 0;
+                                        proc.debug_syscall_trace(&mut |record| {
+                                            if traced == 0 {
+                                                debug!("Syscall trace of {} (CPU time, call = return value):", name);
+                                            }
+                                            debug!("{}", record);
+                                            traced += 1;
+                                        });
+                                        if traced == 0 {
+                                            debug!("No syscalls traced for {}", name);
+                                        }
+                                    },
+                                );
+                            });
+                        } else if clean_str.starts_with("power") {
+                            let info: KernelInfo = KernelInfo::new(self.kernel);
+                            let states = info.number_sleep_states(&self.capability);
+                            if states == 0 {
+                                debug!("No sleep states recorded");
+                            } else {
+                                debug!(" State                 Entries        Time (ms)");
+                            }
+                            for state in 0..states {
+                                info.sleep_state_residency(state, &self.capability)
+                                    .map(|(name, residency)| {
+                                        debug!(
+                                            "  {:<20}{:8}{:17}",
+                                            name,
+                                            residency.entries,
+                                            residency.time_us / 1000
+                                        );
+                                    });
+                            }
                         } else if clean_str.starts_with("list") {
-                            debug!(" PID    Name                Quanta  Syscalls  Dropped Callbacks    State");
+                            let info: KernelInfo = KernelInfo::new(self.kernel);
+                            // Show CPU usage as a share of the time the CPU
+                            // has been awake, in tenths of a percent.
+                            let total_us = info
+                                .total_cpu_time_us(&self.capability)
+                                .map(|total_us| cmp::max(total_us, 1));
+                            let share = |time_us: Option<u64>| {
+                                CpuShare(total_us.and_then(|total_us| {
+                                    time_us.map(|time_us| time_us * 1000 / total_us)
+                                }))
+                            };
+                            debug!(" PID    Name                Quanta  Syscalls  Dropped Callbacks     CPU  State");
                             self.kernel
                                 .process_each_capability(&self.capability, |i, proc| {
                                     let pname = proc.get_process_name();
                                     debug!(
-                                        "  {:02}\t{:<20}{:6}{:10}{:19}  {:?}",
+                                        "  {:02}\t{:<20}{:6}{:10}{:19}{}  {:?}",
                                         i,
                                         pname,
                                         proc.debug_timeslice_expiration_count(),
                                         proc.debug_syscall_count(),
                                         proc.debug_dropped_callback_count(),
+                                        share(info.app_cpu_time_us(proc.appid(), &self.capability)),
                                         proc.get_state()
                                     );
                                 });
+                            debug!(
+                                "  Kernel{:59}{}",
+                                "",
+                                share(info.kernel_cpu_time_us(&self.capability))
+                            );
                         } else if clean_str.starts_with("status") {
                             let info: KernelInfo = KernelInfo::new(self.kernel);
                             debug!(
@@ -254,7 +414,7 @@ impl<'a, C: ProcessManagementCapability> ProcessConsole<'a, C> {
                                 info.timeslice_expirations(&self.capability)
                             );
                         } else {
-                            debug!("Valid commands are: help status list stop start fault");
+                            debug!("Valid commands are: help status list stop start fault faultlog grants trace power");
                         }
                     }
                     Err(_e) => debug!("Invalid command: {:?}", command),
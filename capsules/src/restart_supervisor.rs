@@ -0,0 +1,226 @@
+//! Fault policy that limits how often crashing processes are restarted.
+//!
+//! `RestartSupervisor` implements `ProcessFaultPolicy`, so once the board sets
+//! it as the kernel's fault policy it decides what happens each time a process
+//! faults:
+//!
+//! - A process may be restarted at most `max_restarts` times within any
+//!   `window_ms` long window. After that it is left stopped, and the client,
+//!   if any, is told that the process was given up on.
+//! - Restarts are delayed with exponential backoff: the first restart in a
+//!   window waits `initial_backoff_ms`, and each further restart waits twice
+//!   as long as the previous one, up to `max_backoff_ms`. While it waits, the
+//!   process is stopped and uses no CPU. An `initial_backoff_ms` of 0 restarts
+//!   processes immediately.
+//!
+//! Usage
+//! -----
+//!
+//! ```rust
+//! static mut RESTART_NODES: [capsules::restart_supervisor::RestartNode; 2] = [
+//!     capsules::restart_supervisor::RestartNode::new(),
+//!     capsules::restart_supervisor::RestartNode::new(),
+//! ];
+//!
+//! let supervisor_alarm = static_init!(
+//!     VirtualMuxAlarm<'static, sam4l::ast::Ast>,
+//!     VirtualMuxAlarm::new(mux_alarm)
+//! );
+//! let supervisor = static_init!(
+//!     capsules::restart_supervisor::RestartSupervisor<
+//!         'static,
+//!         VirtualMuxAlarm<'static, sam4l::ast::Ast>,
+//!         Capability,
+//!     >,
+//!     capsules::restart_supervisor::RestartSupervisor::new(
+//!         supervisor_alarm,
+//!         board_kernel,
+//!         &RESTART_NODES,
+//!         capsules::restart_supervisor::RestartPolicy {
+//!             max_restarts: 5,
+//!             window_ms: 60_000,
+//!             initial_backoff_ms: 100,
+//!             max_backoff_ms: 10_000,
+//!         },
+//!         Capability,
+//!     )
+//! );
+//! supervisor_alarm.set_client(supervisor);
+//! board_kernel.set_fault_policy(supervisor, &process_management_capability);
+//! ```
+
+use core::cell::Cell;
+use core::cmp;
+use core::ptr;
+use kernel::capabilities::ProcessManagementCapability;
+use kernel::common::cells::OptionalCell;
+use kernel::hil::time::{self, Alarm, Frequency};
+use kernel::procs::{FaultResponse, ProcessFaultPolicy, ProcessType};
+use kernel::{AppId, Kernel};
+
+/// Limits on restarting processes.
+#[derive(Copy, Clone, Debug)]
+pub struct RestartPolicy {
+    /// How many times a process may be restarted within `window_ms`.
+    pub max_restarts: usize,
+    /// Length of the window restarts are counted in.
+    pub window_ms: u32,
+    /// Delay before the first restart in a window. 0 disables backoff.
+    pub initial_backoff_ms: u32,
+    /// Longest delay before a restart.
+    pub max_backoff_ms: u32,
+}
+
+/// Restart history of one process slot.
+pub struct RestartNode {
+    /// Flash address of the process the history belongs to. A different
+    /// process loaded into the slot starts with an empty history.
+    flash_start: Cell<*const u8>,
+    window_start: Cell<u32>,
+    restarts: Cell<usize>,
+    /// When to restart the process, if it is waiting for its backoff.
+    restart_at: Cell<Option<(AppId, u32)>>,
+}
+
+impl RestartNode {
+    pub const fn new() -> RestartNode {
+        RestartNode {
+            flash_start: Cell::new(ptr::null()),
+            window_start: Cell::new(0),
+            restarts: Cell::new(0),
+            restart_at: Cell::new(None),
+        }
+    }
+}
+
+/// Notified about processes the supervisor stopped restarting.
+pub trait RestartSupervisorClient {
+    fn restarts_exhausted(&self, appid: AppId);
+}
+
+pub struct RestartSupervisor<'a, A: Alarm<'a>, C: ProcessManagementCapability> {
+    alarm: &'a A,
+    kernel: &'static Kernel,
+    nodes: &'a [RestartNode],
+    policy: RestartPolicy,
+    client: OptionalCell<&'a dyn RestartSupervisorClient>,
+    capability: C,
+}
+
+impl<A: Alarm<'a>, C: ProcessManagementCapability> RestartSupervisor<'a, A, C> {
+    /// `nodes` needs one node per process slot of the kernel.
+    pub fn new(
+        alarm: &'a A,
+        kernel: &'static Kernel,
+        nodes: &'a [RestartNode],
+        policy: RestartPolicy,
+        capability: C,
+    ) -> RestartSupervisor<'a, A, C> {
+        RestartSupervisor {
+            alarm: alarm,
+            kernel: kernel,
+            nodes: nodes,
+            policy: policy,
+            client: OptionalCell::empty(),
+            capability: capability,
+        }
+    }
+
+    pub fn set_client(&self, client: &'a dyn RestartSupervisorClient) {
+        self.client.set(client);
+    }
+
+    fn ms_to_tics(ms: u32) -> u32 {
+        (ms as u64 * <A::Frequency>::frequency() as u64 / 1000) as u32
+    }
+
+    /// Delay before the `restarts`th restart in a window, counting from 1.
+    fn backoff_ms(&self, restarts: usize) -> u32 {
+        let shift = cmp::min(restarts.saturating_sub(1), 31) as u32;
+        let backoff = (self.policy.initial_backoff_ms as u64) << shift;
+        cmp::min(backoff, self.policy.max_backoff_ms as u64) as u32
+    }
+
+    /// Set the alarm for the earliest pending restart, if any.
+    fn set_next_alarm(&self, now: u32) {
+        let next = self
+            .nodes
+            .iter()
+            .filter_map(|node| node.restart_at.get())
+            .map(|(_, at)| at)
+            .min_by_key(|at| at.wrapping_sub(now));
+        match next {
+            Some(at) => self.alarm.set_alarm(at),
+            None => self.alarm.disable(),
+        }
+    }
+}
+
+impl<A: Alarm<'a>, C: ProcessManagementCapability> ProcessFaultPolicy
+    for RestartSupervisor<'a, A, C>
+{
+    fn action(&self, process: &dyn ProcessType) -> FaultResponse {
+        let appid = process.appid();
+        let node = match self.nodes.get(appid.idx()) {
+            Some(node) => node,
+            None => return FaultResponse::Restart,
+        };
+
+        // The slot is indexed by `AppId::idx()`, so it may have held a
+        // different process since the last fault. Restarts of the same
+        // process keep its image in flash, and so its history.
+        if node.flash_start.get() != process.flash_start() {
+            node.flash_start.set(process.flash_start());
+            node.restarts.set(0);
+            node.restart_at.set(None);
+        }
+
+        // Start a new window with this fault if the process has not been
+        // restarted yet or the previous window is over.
+        let now = self.alarm.now();
+        if node.restarts.get() == 0
+            || now.wrapping_sub(node.window_start.get()) >= Self::ms_to_tics(self.policy.window_ms)
+        {
+            node.window_start.set(now);
+            node.restarts.set(0);
+        }
+
+        if node.restarts.get() >= self.policy.max_restarts {
+            node.restart_at.set(None);
+            self.client.map(|client| client.restarts_exhausted(appid));
+            return FaultResponse::Stop;
+        }
+        node.restarts.set(node.restarts.get() + 1);
+
+        let backoff_ms = self.backoff_ms(node.restarts.get());
+        if backoff_ms == 0 {
+            FaultResponse::Restart
+        } else {
+            // Leave the process stopped until the backoff has passed.
+            node.restart_at.set(Some((
+                appid,
+                now.wrapping_add(Self::ms_to_tics(backoff_ms)),
+            )));
+            self.set_next_alarm(now);
+            FaultResponse::Stop
+        }
+    }
+}
+
+impl<A: Alarm<'a>, C: ProcessManagementCapability> time::AlarmClient
+    for RestartSupervisor<'a, A, C>
+{
+    fn fired(&self) {
+        let now = self.alarm.now();
+        for node in self.nodes.iter() {
+            if let Some((appid, at)) = node.restart_at.get() {
+                // Restart every process whose backoff has passed.
+                if (now.wrapping_sub(at) as i32) >= 0 {
+                    node.restart_at.set(None);
+                    self.kernel.restart_process(appid, &self.capability);
+                }
+            }
+        }
+        self.set_next_alarm(now);
+    }
+}
@@ -14,6 +14,7 @@ extern "C" {
 pub struct ArtyExx {
     userspace_kernel_boundary: rv32i::syscall::SysCall,
     clic: rv32i::clic::Clic,
+    pmp: rv32i::pmp::PMPConfig,
 }
 
 impl ArtyExx {
@@ -26,6 +27,8 @@ impl ArtyExx {
         ArtyExx {
             userspace_kernel_boundary: rv32i::syscall::SysCall::new(),
             clic: rv32i::clic::Clic::new(in_use_interrupts),
+            // The E21 core implements 4 PMP entries.
+            pmp: rv32i::pmp::PMPConfig::new(4),
         }
     }
 
@@ -33,38 +36,6 @@ impl ArtyExx {
         self.clic.enable_all();
     }
 
-    /// Configure the PMP to allow all accesses in both machine mode (the
-    /// default) and in user mode.
-    ///
-    /// This needs to be replaced with a real PMP driver. See
-    /// https://github.com/tock/tock/issues/1135
-    pub unsafe fn disable_pmp(&self) {
-        asm!("
-            // PMP PMP PMP
-            // PMP PMP PMP
-            // PMP PMP PMP
-            // PMP PMP PMP
-            // TODO: Add a real PMP driver!!
-            // Take some time to disable the PMP.
-
-            // Set the first region address to 0xFFFFFFFF. When using top-of-range mode
-            // this will include the entire address space.
-            lui  t0, %hi(0xFFFFFFFF)
-            addi t0, t0, %lo(0xFFFFFFFF)
-            csrw 0x3b0, t0    // CSR=pmpaddr0
-
-            // Set the first region to use top-of-range and allow everything.
-            // This is equivalent to:
-            // R=1, W=1, X=1, A=01, L=0
-            li   t0, 0x0F
-            csrw 0x3a0, t0    // CSR=pmpcfg0
-        "
-        :
-        :
-        :
-        : "volatile");
-    }
-
     /// By default the machine timer is enabled and will trigger interrupts. To
     /// prevent that we can make the compare register very large to effectively
     /// stop the interrupt from triggering, and then the machine timer can be
@@ -114,26 +85,18 @@ impl ArtyExx {
     /// operations. Different boards can call the functions that `initialize()`
     /// calls directly if it needs to use a custom setup operation.
     pub unsafe fn initialize(&self) {
-        self.disable_pmp();
         self.disable_machine_timer();
         self.configure_trap_handler();
     }
 }
 
 impl kernel::Chip for ArtyExx {
-    // While there is initial support for a PMP driver (as of 2019-10-04), it is not
-    // complete, and while it should disable the PMP, it seems to cause some negative
-    // side effects (context switching does not work correctly) on the Arty-E21 platform.
-    //
-    // TODO: implement the PMP driver and add it here `type MPU = rv32i::pmp::PMPConfig;`.
-    //
-    // See https://github.com/tock/tock/pull/1382 for (a little) more information.
-    type MPU = ();
+    type MPU = rv32i::pmp::PMPConfig;
     type UserspaceKernelBoundary = rv32i::syscall::SysCall;
     type SysTick = ();
 
     fn mpu(&self) -> &Self::MPU {
-        &()
+        &self.pmp
     }
 
     fn systick(&self) -> &Self::SysTick {
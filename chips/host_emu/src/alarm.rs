@@ -0,0 +1,96 @@
+//! Alarm running on the virtual clock, with one tic per microsecond.
+
+use std::cell::Cell;
+
+use kernel::common::cells::OptionalCell;
+use kernel::hil::time::{self, Alarm, Frequency, Time};
+
+use crate::chip::Clock;
+
+#[derive(Debug)]
+pub struct Freq1MHz;
+impl Frequency for Freq1MHz {
+    fn frequency() -> u32 {
+        1_000_000
+    }
+}
+
+pub struct EmuAlarm<'a> {
+    clock: &'a Clock,
+    /// Tic count the alarm was set for.
+    alarm: Cell<u32>,
+    /// Virtual time the alarm fires at, if it is enabled.
+    fires_at: Cell<Option<u64>>,
+    client: OptionalCell<&'a dyn time::AlarmClient>,
+}
+
+impl EmuAlarm<'a> {
+    crate fn new(clock: &'a Clock) -> EmuAlarm<'a> {
+        EmuAlarm {
+            clock: clock,
+            alarm: Cell::new(0),
+            fires_at: Cell::new(None),
+            client: OptionalCell::empty(),
+        }
+    }
+
+    /// When the alarm will fire, if it is enabled.
+    pub fn fires_at(&self) -> Option<u64> {
+        self.fires_at.get()
+    }
+
+    crate fn is_pending(&self) -> bool {
+        self.fires_at
+            .get()
+            .map_or(false, |at| self.clock.now_us() >= at)
+    }
+
+    crate fn handle_interrupt(&self) {
+        self.fires_at.set(None);
+        self.client.map(|client| client.fired());
+    }
+}
+
+impl Time for EmuAlarm<'a> {
+    type Frequency = Freq1MHz;
+
+    fn now(&self) -> u32 {
+        self.clock.now_us() as u32
+    }
+
+    fn max_tics(&self) -> u32 {
+        u32::max_value()
+    }
+}
+
+impl Alarm<'a> for EmuAlarm<'a> {
+    fn set_alarm(&self, tics: u32) {
+        // An alarm more than half the counter range away is taken to be in
+        // the past, and fires right away.
+        let now = self.now();
+        let delta = tics.wrapping_sub(now);
+        let delta = if delta > u32::max_value() / 2 {
+            0
+        } else {
+            delta as u64
+        };
+        self.alarm.set(tics);
+        self.fires_at.set(Some(self.clock.now_us() + delta));
+    }
+
+    fn get_alarm(&self) -> u32 {
+        self.alarm.get()
+    }
+
+    fn set_client(&'a self, client: &'a dyn time::AlarmClient) {
+        self.client.set(client);
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.fires_at.get().is_some()
+    }
+
+    fn disable(&self) {
+        self.fires_at.set(None);
+    }
+}
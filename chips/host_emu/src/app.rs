@@ -0,0 +1,365 @@
+//! Writing processes for the emulated chip.
+//!
+//! A process is a `fn(&App)`. It makes system calls with the methods of
+//! `App`, which block its thread until the kernel has handled the call, and
+//! receives callbacks while it is in `yieldk()`. Like an app linked against
+//! libtock, a process that returns from its main function keeps yielding.
+//!
+//! Processes are put into TBF images with `AppImage`, and the images into a
+//! flash region with `flash()`, which the kernel loads processes from. The
+//! entry point of each image holds the address of its main function; the
+//! process thread reads it from there when the kernel starts the process.
+
+use std::cell::Cell;
+use std::mem;
+use std::slice;
+use std::sync::mpsc::{Receiver, Sender};
+
+use crate::boundary::{Resume, Trap};
+
+/// Main function of a process.
+pub type Main = fn(&App);
+
+/// Function a process subscribes as a callback. It is called with the three
+/// arguments from the capsule and the `appdata` given to `subscribe()`.
+pub type Callback = fn(usize, usize, usize, usize);
+
+/// Unwinds a process thread the kernel has restarted or dropped.
+crate struct Exit;
+
+/// A running process, as seen from its own thread.
+pub struct App {
+    to_kernel: Sender<Trap>,
+    from_kernel: Receiver<Resume>,
+    flash_start: Cell<usize>,
+    mem_start: Cell<usize>,
+    mem_len: Cell<usize>,
+    /// Start of the process memory that `alloc()` has not handed out yet.
+    heap_next: Cell<usize>,
+    app_break: Cell<usize>,
+    /// Virtual time spent computing since the last system call.
+    computed_us: Cell<u64>,
+}
+
+impl App {
+    crate fn new(to_kernel: Sender<Trap>, from_kernel: Receiver<Resume>) -> App {
+        App {
+            to_kernel: to_kernel,
+            from_kernel: from_kernel,
+            flash_start: Cell::new(0),
+            mem_start: Cell::new(0),
+            mem_len: Cell::new(0),
+            heap_next: Cell::new(0),
+            app_break: Cell::new(0),
+            computed_us: Cell::new(0),
+        }
+    }
+
+    /// Wait for the kernel to start the process, then run its main function.
+    crate fn wait_for_start(&self) {
+        if let Resume::Call {
+            pc,
+            args,
+            start: true,
+        } = self.resume()
+        {
+            self.flash_start.set(args[0]);
+            self.mem_start.set(args[1]);
+            self.mem_len.set(args[2]);
+            self.heap_next.set(args[1]);
+            self.app_break.set(args[3]);
+
+            let main: Main = unsafe { mem::transmute(*(pc as *const usize)) };
+            main(self);
+        }
+        loop {
+            self.yieldk();
+        }
+    }
+
+    /// Block until the kernel resumes the process.
+    fn resume(&self) -> Resume {
+        match self.from_kernel.recv() {
+            Ok(resume) => resume,
+            // The kernel dropped this thread, e.g. because the process was
+            // restarted. Exit without running any more process code.
+            Err(_) => std::panic::resume_unwind(Box::new(Exit)),
+        }
+    }
+
+    fn syscall(&self, number: u8, args: [usize; 4]) -> isize {
+        self.syscall_registers(number, args)[0] as isize
+    }
+
+    /// Make a system call and return all four registers the kernel may have
+    /// set.
+    fn syscall_registers(&self, number: u8, args: [usize; 4]) -> [usize; 4] {
+        if self
+            .to_kernel
+            .send(Trap::Syscall {
+                number: number,
+                args: args,
+                run_us: self.computed_us.replace(0),
+            })
+            .is_err()
+        {
+            std::panic::resume_unwind(Box::new(Exit));
+        }
+        loop {
+            match self.resume() {
+                Resume::Return(registers) => return registers,
+                Resume::Call { pc, args, .. } => Self::call(pc, args),
+            }
+        }
+    }
+
+    fn call(pc: usize, args: [usize; 4]) {
+        let callback: Callback = unsafe { mem::transmute(pc) };
+        callback(args[0], args[1], args[2], args[3]);
+    }
+
+    /// Wait until the kernel delivers a callback, and run it.
+    pub fn yieldk(&self) {
+        if self
+            .to_kernel
+            .send(Trap::Syscall {
+                number: 0,
+                args: [0; 4],
+                run_us: self.computed_us.replace(0),
+            })
+            .is_err()
+        {
+            std::panic::resume_unwind(Box::new(Exit));
+        }
+        if let Resume::Call { pc, args, .. } = self.resume() {
+            Self::call(pc, args);
+        }
+    }
+
+    /// Yield until `condition` returns true.
+    pub fn yieldk_for<F: Fn() -> bool>(&self, condition: F) {
+        while !condition() {
+            self.yieldk();
+        }
+    }
+
+    pub fn subscribe(
+        &self,
+        driver: usize,
+        subscribe_num: usize,
+        callback: Option<Callback>,
+        appdata: usize,
+    ) -> isize {
+        let callback_ptr = callback.map_or(0, |callback| callback as usize);
+        self.syscall(1, [driver, subscribe_num, callback_ptr, appdata])
+    }
+
+    pub fn command(&self, driver: usize, command_num: usize, arg0: usize, arg1: usize) -> isize {
+        self.syscall(2, [driver, command_num, arg0, arg1])
+    }
+
+    /// Returns the four registers of the command's `SyscallReturn`: its tag
+    /// followed by its values.
+    pub fn typed_command(
+        &self,
+        driver: usize,
+        command_num: usize,
+        arg0: usize,
+        arg1: usize,
+    ) -> [usize; 4] {
+        self.syscall_registers(6, [driver, command_num, arg0, arg1])
+    }
+
+    /// Share `buffer` with a capsule. The buffer must have been allocated with
+    /// `alloc()`, as the kernel only accepts buffers in process memory.
+    pub fn allow(&self, driver: usize, allow_num: usize, buffer: Option<&mut [u8]>) -> isize {
+        let (address, len) = buffer.map_or((0, 0), |buffer| {
+            (buffer.as_mut_ptr() as usize, buffer.len())
+        });
+        self.syscall(3, [driver, allow_num, address, len])
+    }
+
+    /// Share `buffer` with a capsule for reading only. The buffer can be in
+    /// process memory or in the process's flash.
+    pub fn allow_read_only(&self, driver: usize, allow_num: usize, buffer: Option<&[u8]>) -> isize {
+        let (address, len) =
+            buffer.map_or((0, 0), |buffer| (buffer.as_ptr() as usize, buffer.len()));
+        self.syscall(5, [driver, allow_num, address, len])
+    }
+
+    pub fn memop(&self, operand: usize, arg0: usize) -> isize {
+        self.syscall(4, [operand, arg0, 0, 0])
+    }
+
+    /// Allocate a zeroed buffer in process memory, moving the app break with
+    /// `memop` if needed. Returns `None` if the kernel refuses to move it.
+    pub fn alloc(&self, len: usize) -> Option<&'static mut [u8]> {
+        let start = (self.heap_next.get() + 7) & !7;
+        let end = start + len;
+        if end > self.app_break.get() {
+            let increment = end - self.app_break.get();
+            if self.memop(1, increment) < 0 {
+                return None;
+            }
+            self.app_break.set(end);
+        }
+        self.heap_next.set(end);
+        let buffer = unsafe { slice::from_raw_parts_mut(start as *mut u8, len) };
+        for byte in buffer.iter_mut() {
+            *byte = 0;
+        }
+        Some(buffer)
+    }
+
+    /// Start of the process's flash, after its TBF header.
+    pub fn flash_start(&self) -> usize {
+        self.flash_start.get()
+    }
+
+    /// Start and length of the process's memory.
+    pub fn memory(&self) -> (usize, usize) {
+        (self.mem_start.get(), self.mem_len.get())
+    }
+
+    /// Spend `us` of virtual time computing. The time passes when the process
+    /// next makes a system call, on top of `PROCESS_RUN_US`.
+    pub fn compute(&self, us: u64) {
+        self.computed_us.set(self.computed_us.get() + us);
+    }
+
+    /// Crash the process, as if it had accessed memory it does not own.
+    pub fn fault(&self) -> ! {
+        panic!("process faulted");
+    }
+}
+
+/// Process memory an image asks for unless told otherwise. Like on a board,
+/// memory beyond what the kernel needs to set up the process is what its
+/// heap and the grants of capsules it uses are allocated from.
+const DEFAULT_MINIMUM_RAM_SIZE: u32 = 8 * 1024;
+
+/// A process to be placed in flash.
+pub struct AppImage {
+    name: &'static str,
+    main: Main,
+    minimum_ram_size: u32,
+    enabled: bool,
+    task_queue: Option<(u32, bool)>,
+}
+
+impl AppImage {
+    pub fn new(name: &'static str, main: Main) -> AppImage {
+        AppImage {
+            name: name,
+            main: main,
+            minimum_ram_size: DEFAULT_MINIMUM_RAM_SIZE,
+            enabled: true,
+            task_queue: None,
+        }
+    }
+
+    /// Ask for at least `bytes` of process memory.
+    pub fn minimum_ram_size(mut self, bytes: u32) -> AppImage {
+        self.minimum_ram_size = bytes;
+        self
+    }
+
+    /// Mark the image as disabled, so the kernel does not load it.
+    pub fn disabled(mut self) -> AppImage {
+        self.enabled = false;
+        self
+    }
+
+    /// Ask for a task queue that holds `depth` callbacks, and whether a new
+    /// callback should replace a pending one for the same subscription when
+    /// it is full.
+    pub fn task_queue(mut self, depth: u32, coalesce: bool) -> AppImage {
+        self.task_queue = Some((depth, coalesce));
+        self
+    }
+
+    /// The TBF image: a header with the main and package name elements, and
+    /// the task queue element if asked for, followed by the address of the
+    /// main function as the entry point.
+    fn tbf(&self) -> Vec<u8> {
+        let name = self.name.as_bytes();
+        let name_len_padded = (name.len() + 3) & !3;
+        let task_queue_size = if self.task_queue.is_some() { 12 } else { 0 };
+        let header_size = 16 + 16 + 4 + name_len_padded + task_queue_size;
+        let header_size = (header_size + 7) & !7;
+        let total_size = header_size + mem::size_of::<usize>();
+
+        let mut tbf = Vec::with_capacity(total_size);
+        push_u16(&mut tbf, 2);
+        push_u16(&mut tbf, header_size as u16);
+        push_u32(&mut tbf, total_size as u32);
+        push_u32(&mut tbf, if self.enabled { 1 } else { 0 });
+        push_u32(&mut tbf, 0);
+
+        // Main element: entry point right after the header, nothing
+        // protected.
+        push_u16(&mut tbf, 1);
+        push_u16(&mut tbf, 12);
+        push_u32(&mut tbf, 0);
+        push_u32(&mut tbf, 0);
+        push_u32(&mut tbf, self.minimum_ram_size);
+
+        // Package name element.
+        push_u16(&mut tbf, 3);
+        push_u16(&mut tbf, name.len() as u16);
+        tbf.extend_from_slice(name);
+        tbf.resize(16 + 16 + 4 + name_len_padded, 0);
+
+        // Task queue element.
+        if let Some((depth, coalesce)) = self.task_queue {
+            push_u16(&mut tbf, 8);
+            push_u16(&mut tbf, 8);
+            push_u32(&mut tbf, depth);
+            push_u32(&mut tbf, if coalesce { 1 } else { 0 });
+        }
+        tbf.resize(header_size, 0);
+
+        // The checksum is the XOR of all header words but itself.
+        let checksum = tbf
+            .chunks(4)
+            .enumerate()
+            .filter(|&(i, _)| i != 3)
+            .fold(0, |sum, (_, word)| {
+                sum ^ u32::from_le_bytes([word[0], word[1], word[2], word[3]])
+            });
+        tbf[12..16].copy_from_slice(&checksum.to_le_bytes());
+
+        tbf.extend_from_slice(&(self.main as usize).to_ne_bytes());
+        tbf
+    }
+}
+
+fn push_u16(buffer: &mut Vec<u8>, value: u16) {
+    buffer.extend_from_slice(&value.to_le_bytes());
+}
+
+fn push_u32(buffer: &mut Vec<u8>, value: u32) {
+    buffer.extend_from_slice(&value.to_le_bytes());
+}
+
+/// Place `apps` one after the other in a flash region that lives for the
+/// rest of the test program, and return it.
+pub fn flash(apps: &[AppImage]) -> &'static [u8] {
+    let mut image: Vec<u8> = apps.iter().flat_map(|app| app.tbf()).collect();
+    // An empty header ends the list of apps.
+    image.extend_from_slice(&[0; 16]);
+
+    // Copy into 8-byte aligned memory, as the kernel reads the headers as
+    // words and the entry points as addresses.
+    let words = vec![0u64; (image.len() + 7) / 8].into_boxed_slice();
+    let words: &'static mut [u64] = Box::leak(words);
+    let flash =
+        unsafe { slice::from_raw_parts_mut(words.as_mut_ptr() as *mut u8, words.len() * 8) };
+    flash[..image.len()].copy_from_slice(&image);
+    flash
+}
+
+/// Allocate memory for processes that lives for the rest of the test program.
+pub fn app_memory(len: usize) -> &'static mut [u8] {
+    Box::leak(vec![0; len].into_boxed_slice())
+}
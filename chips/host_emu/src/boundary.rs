@@ -0,0 +1,270 @@
+//! Userspace/kernel boundary that runs each process as a host thread.
+//!
+//! Switching to a process hands control to its thread and blocks the kernel
+//! until the process makes a system call, which hands control back. A process
+//! thread therefore only runs while the kernel waits for it, just like a
+//! process on a single-core chip.
+//!
+//! The first function call the kernel sets up for a process, the call to its
+//! entry point, starts a new thread. The entry point in the app's flash holds
+//! the address of the Rust function to run, see `app::AppImage`. Later
+//! function calls are callbacks, which the thread runs when it is resumed in
+//! `yield`. If the kernel restarts the process, the old thread is told to
+//! exit and a new one is started.
+
+use std::cell::RefCell;
+use std::fmt::Write;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+use kernel::procs::{FunctionCall, FunctionCallSource};
+use kernel::syscall::{self, ContextSwitchReason, SyscallReturn, UserspaceKernelBoundary};
+
+use crate::app::{self, App};
+use crate::chip::{Clock, PROCESS_RUN_US};
+
+/// What a process thread is told to do when it is resumed.
+crate enum Resume {
+    /// Call a function. `start` is set for the entry point of the process.
+    Call {
+        pc: usize,
+        args: [usize; 4],
+        start: bool,
+    },
+    /// Return from the pending system call with these values in the four
+    /// argument registers. Plain system calls only set the first.
+    Return([usize; 4]),
+}
+
+/// Why a process thread handed control back to the kernel.
+crate enum Trap {
+    /// A system call, made after the process spent `run_us` computing on top
+    /// of `PROCESS_RUN_US`.
+    Syscall {
+        number: u8,
+        args: [usize; 4],
+        run_us: u64,
+    },
+    Fault,
+}
+
+/// Kernel side of a process thread.
+struct EmuProcess {
+    /// What to do when the process is next resumed.
+    pending: Option<Resume>,
+    to_app: Option<Sender<Resume>>,
+    from_app: Option<Receiver<Trap>>,
+    syscall_count: usize,
+    last_trap_was_fault: bool,
+}
+
+#[derive(Copy, Clone, Default)]
+pub struct EmuStoredState {
+    /// Index of the process's thread state in the boundary.
+    id: Option<usize>,
+}
+
+pub struct EmuUserspaceKernelBoundary<'a> {
+    clock: &'a Clock,
+    processes: RefCell<Vec<EmuProcess>>,
+}
+
+impl EmuUserspaceKernelBoundary<'a> {
+    crate fn new(clock: &'a Clock) -> EmuUserspaceKernelBoundary<'a> {
+        EmuUserspaceKernelBoundary {
+            clock: clock,
+            processes: RefCell::new(Vec::new()),
+        }
+    }
+}
+
+/// Start a thread for a process at its entry point. Any thread the process
+/// had before exits once its channels are dropped.
+fn start_thread(process: &mut EmuProcess) {
+    let (to_app, from_kernel) = mpsc::channel();
+    let (to_kernel, from_app) = mpsc::channel();
+    process.to_app = Some(to_app);
+    process.from_app = Some(from_app);
+
+    thread::spawn(move || {
+        let fault_sender = to_kernel.clone();
+        let result = panic::catch_unwind(AssertUnwindSafe(move || {
+            let app = App::new(to_kernel, from_kernel);
+            app.wait_for_start();
+        }));
+        if let Err(payload) = result {
+            // A process that is told to exit is not faulting.
+            if !payload.is::<app::Exit>() {
+                let _ = fault_sender.send(Trap::Fault);
+            }
+        }
+    });
+}
+
+impl UserspaceKernelBoundary for EmuUserspaceKernelBoundary<'a> {
+    type StoredState = EmuStoredState;
+
+    unsafe fn initialize_new_process(
+        &self,
+        stack_pointer: *const usize,
+        _stack_size: usize,
+        state: &mut EmuStoredState,
+    ) -> Result<*const usize, ()> {
+        let mut processes = self.processes.borrow_mut();
+        state.id = Some(processes.len());
+        processes.push(EmuProcess {
+            pending: None,
+            to_app: None,
+            from_app: None,
+            syscall_count: 0,
+            last_trap_was_fault: false,
+        });
+        Ok(stack_pointer)
+    }
+
+    unsafe fn set_syscall_return_value(
+        &self,
+        _stack_pointer: *const usize,
+        state: &mut EmuStoredState,
+        return_value: isize,
+    ) {
+        if let Some(id) = state.id {
+            self.processes.borrow_mut()[id].pending =
+                Some(Resume::Return([return_value as usize, 0, 0, 0]));
+        }
+    }
+
+    unsafe fn set_syscall_return(
+        &self,
+        _stack_pointer: *const usize,
+        state: &mut EmuStoredState,
+        return_value: SyscallReturn,
+    ) {
+        if let Some(id) = state.id {
+            let mut registers = [0; 4];
+            for (register, &value) in registers.iter_mut().zip(return_value.encode().iter()) {
+                *register = value as usize;
+            }
+            self.processes.borrow_mut()[id].pending = Some(Resume::Return(registers));
+        }
+    }
+
+    unsafe fn set_process_function(
+        &self,
+        stack_pointer: *const usize,
+        _remaining_stack_memory: usize,
+        state: &mut EmuStoredState,
+        callback: FunctionCall,
+    ) -> Result<*mut usize, *mut usize> {
+        let id = match state.id {
+            Some(id) => id,
+            None => return Err(stack_pointer as *mut usize),
+        };
+        let start = match callback.source {
+            FunctionCallSource::Kernel => true,
+            FunctionCallSource::Driver(_) => false,
+        };
+        self.processes.borrow_mut()[id].pending = Some(Resume::Call {
+            pc: callback.pc,
+            args: [
+                callback.argument0,
+                callback.argument1,
+                callback.argument2,
+                callback.argument3,
+            ],
+            start: start,
+        });
+        Ok(stack_pointer as *mut usize)
+    }
+
+    unsafe fn switch_to_process(
+        &self,
+        stack_pointer: *const usize,
+        state: &mut EmuStoredState,
+    ) -> (*mut usize, ContextSwitchReason) {
+        let stack_pointer = stack_pointer as *mut usize;
+        let id = match state.id {
+            Some(id) => id,
+            None => return (stack_pointer, ContextSwitchReason::Fault),
+        };
+        let mut processes = self.processes.borrow_mut();
+        let process = &mut processes[id];
+
+        match process.pending.take() {
+            Some(resume) => {
+                if let Resume::Call { start: true, .. } = resume {
+                    start_thread(process);
+                }
+                let sent = process
+                    .to_app
+                    .as_ref()
+                    .map_or(false, |to_app| to_app.send(resume).is_ok());
+                if !sent {
+                    process.last_trap_was_fault = true;
+                    return (stack_pointer, ContextSwitchReason::Fault);
+                }
+            }
+            // The process is continuing where it stopped, e.g. in a loop
+            // around `yield`, so there is nothing new to tell it.
+            None => {}
+        }
+
+        // Block until the process traps back into the kernel.
+        let trap = process
+            .from_app
+            .as_ref()
+            .and_then(|from_app| from_app.recv().ok())
+            .unwrap_or(Trap::Fault);
+
+        let reason = match trap {
+            Trap::Syscall {
+                number,
+                args,
+                run_us,
+            } => {
+                self.clock.advance(PROCESS_RUN_US + run_us);
+                process.syscall_count += 1;
+                syscall::arguments_to_syscall(number, args[0], args[1], args[2], args[3])
+                    .map_or(ContextSwitchReason::Fault, |syscall| {
+                        ContextSwitchReason::SyscallFired { syscall: syscall }
+                    })
+            }
+            Trap::Fault => {
+                self.clock.advance(PROCESS_RUN_US);
+                ContextSwitchReason::Fault
+            }
+        };
+        process.last_trap_was_fault = reason == ContextSwitchReason::Fault;
+        (stack_pointer, reason)
+    }
+
+    unsafe fn fault_fmt(&self, writer: &mut dyn Write) {
+        let _ = writer.write_fmt(format_args!(
+            "\r\n---| Emulated process fault |---\r\n\
+             The process thread panicked or made an invalid system call.\r\n"
+        ));
+    }
+
+    unsafe fn process_detail_fmt(
+        &self,
+        _stack_pointer: *const usize,
+        state: &EmuStoredState,
+        writer: &mut dyn Write,
+    ) {
+        if let Some(id) = state.id {
+            let processes = self.processes.borrow();
+            let process = &processes[id];
+            let _ = writer.write_fmt(format_args!(
+                "\r\n Emulated process thread {}: {} system calls{}\r\n",
+                id,
+                process.syscall_count,
+                if process.last_trap_was_fault {
+                    ", faulted"
+                } else {
+                    ""
+                }
+            ));
+        }
+    }
+}
@@ -0,0 +1,151 @@
+//! The emulated chip and its virtual clock.
+
+use std::cell::Cell;
+
+use kernel::power::{Constraint, SleepState};
+
+use crate::alarm::EmuAlarm;
+use crate::boundary::EmuUserspaceKernelBoundary;
+use crate::mpu::EmuMpu;
+use crate::systick::EmuSysTick;
+use crate::uart::EmuUart;
+use crate::watchdog::EmuWatchdog;
+
+/// Virtual time a process uses each time it runs until its next system call,
+/// in microseconds.
+pub const PROCESS_RUN_US: u64 = 100;
+
+/// Virtual clock, counting microseconds since the chip was created.
+pub struct Clock {
+    now_us: Cell<u64>,
+}
+
+impl Clock {
+    pub fn new() -> Clock {
+        Clock {
+            now_us: Cell::new(0),
+        }
+    }
+
+    pub fn now_us(&self) -> u64 {
+        self.now_us.get()
+    }
+
+    /// Let `us` microseconds pass.
+    pub fn advance(&self, us: u64) {
+        self.now_us.set(self.now_us.get() + us);
+    }
+
+    /// Let time pass until `us`, unless that is in the past.
+    pub fn advance_to(&self, us: u64) {
+        if us > self.now_us.get() {
+            self.now_us.set(us);
+        }
+    }
+}
+
+/// Two sleep states, so tests can check which one the kernel chooses. Both
+/// sleep the same way.
+static SLEEP_STATES: [SleepState; 2] = [
+    SleepState {
+        name: "idle",
+        keeps: &[
+            Constraint::HighSpeedClock,
+            Constraint::PeripheralClocks,
+            Constraint::UartActive,
+            Constraint::RadioActive,
+        ],
+    },
+    SleepState {
+        name: "deep",
+        keeps: &[],
+    },
+];
+
+pub struct EmuChip {
+    clock: &'static Clock,
+    mpu: EmuMpu,
+    systick: EmuSysTick<'static>,
+    userspace_kernel_boundary: EmuUserspaceKernelBoundary<'static>,
+    pub alarm: EmuAlarm<'static>,
+    pub uart: EmuUart<'static>,
+    pub watchdog: EmuWatchdog<'static>,
+}
+
+impl EmuChip {
+    /// Create a chip. It lives for the rest of the test program, like a chip
+    /// struct in a board's `static_init!`.
+    pub fn new() -> &'static EmuChip {
+        let clock: &'static Clock = Box::leak(Box::new(Clock::new()));
+        Box::leak(Box::new(EmuChip {
+            clock: clock,
+            mpu: EmuMpu::new(),
+            systick: EmuSysTick::new(clock),
+            userspace_kernel_boundary: EmuUserspaceKernelBoundary::new(clock),
+            alarm: EmuAlarm::new(clock),
+            uart: EmuUart::new(),
+            watchdog: EmuWatchdog::new(clock),
+        }))
+    }
+
+    pub fn clock(&self) -> &'static Clock {
+        self.clock
+    }
+}
+
+impl kernel::Chip for EmuChip {
+    type MPU = EmuMpu;
+    type UserspaceKernelBoundary = EmuUserspaceKernelBoundary<'static>;
+    type SysTick = EmuSysTick<'static>;
+
+    fn service_pending_interrupts(&self) {
+        while self.has_pending_interrupts() {
+            if self.alarm.is_pending() {
+                self.alarm.handle_interrupt();
+            }
+            if self.uart.is_pending() {
+                self.uart.handle_interrupt();
+            }
+        }
+    }
+
+    fn has_pending_interrupts(&self) -> bool {
+        self.alarm.is_pending() || self.uart.is_pending()
+    }
+
+    fn mpu(&self) -> &EmuMpu {
+        &self.mpu
+    }
+
+    fn systick(&self) -> &EmuSysTick<'static> {
+        &self.systick
+    }
+
+    fn userspace_kernel_boundary(&self) -> &EmuUserspaceKernelBoundary<'static> {
+        &self.userspace_kernel_boundary
+    }
+
+    /// Nothing can wake the chip but the alarm, so sleeping skips ahead to
+    /// when it fires.
+    fn sleep(&self) {
+        if let Some(at) = self.alarm.fires_at() {
+            self.clock.advance_to(at);
+        }
+    }
+
+    fn sleep_states(&self) -> &'static [SleepState] {
+        &SLEEP_STATES
+    }
+
+    fn sleep_in(&self, state: usize) -> usize {
+        self.sleep();
+        state
+    }
+
+    unsafe fn atomic<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        f()
+    }
+}
@@ -0,0 +1,55 @@
+//! Emulated chip that runs the Tock kernel on a development machine.
+//!
+//! This crate lets the kernel's scheduler, process management, grants and
+//! capsules be exercised with `cargo test`, without a board. `EmuChip`
+//! implements `kernel::Chip` with:
+//!
+//! - a fake MPU that hands out memory but protects nothing,
+//! - a system tick timer and an alarm driven by a virtual clock, so tests are
+//!   deterministic and never wait for real time to pass,
+//! - a UART whose output is collected in memory and whose input is injected
+//!   by the test,
+//! - a watchdog that records whether it would have reset the chip,
+//! - a userspace/kernel boundary that runs each process as a host thread.
+//!
+//! Processes are ordinary Rust functions taking an `app::App`, which provides
+//! the system calls. They are packaged into TBF images with `app::flash()`, so
+//! they are loaded by `kernel::procs::load_processes()` just like apps on a
+//! board. Only one thread, the kernel's or one process's, runs at a time.
+//!
+//! The kernel is driven with `Kernel::kernel_loop_operation()`, which returns
+//! after each iteration so the test can check for the outcome it expects:
+//!
+//! ```ignore
+//! let chip = host_emu::EmuChip::new();
+//! let flash = host_emu::app::flash(&[host_emu::app::AppImage::new("hello", hello_main)]);
+//! kernel::procs::load_processes(
+//!     board_kernel,
+//!     chip,
+//!     flash.as_ptr(),
+//!     host_emu::app::app_memory(64 * 1024),
+//!     kernel::procs::FaultResponse::Stop,
+//!     &capability,
+//! );
+//! while !done() {
+//!     board_kernel.kernel_loop_operation(&platform, chip, None, &scheduler, &capability);
+//! }
+//! ```
+//!
+//! Time only passes when something makes it pass: every time a process runs
+//! until its next system call costs `PROCESS_RUN_US` of virtual time, plus
+//! whatever it spent in `App::compute()`, and when the kernel puts the chip to
+//! sleep the clock jumps to the next alarm.
+
+#![feature(in_band_lifetimes, crate_visibility_modifier)]
+
+pub mod alarm;
+pub mod app;
+pub mod boundary;
+pub mod chip;
+pub mod mpu;
+pub mod systick;
+pub mod uart;
+pub mod watchdog;
+
+pub use crate::chip::{Clock, EmuChip, PROCESS_RUN_US};
@@ -0,0 +1,85 @@
+//! MPU that lays out process memory like a real one but enforces nothing.
+//!
+//! Processes run as host threads, which cannot be confined to their memory.
+//! The MPU still keeps process memory aligned, as the kernel places its own
+//! structures at the end of it, and refuses to let the app break and the
+//! kernel break cross, so the kernel's memory checks behave as on hardware.
+
+use std::cmp;
+
+use kernel::mpu::{self, Permissions, Region};
+
+/// Alignment of process memory, enough for any kernel structure on the host.
+const ALIGNMENT: usize = 16;
+
+fn align_up(value: usize) -> usize {
+    (value + ALIGNMENT - 1) & !(ALIGNMENT - 1)
+}
+
+pub struct EmuMpu(());
+
+impl EmuMpu {
+    crate fn new() -> EmuMpu {
+        EmuMpu(())
+    }
+}
+
+impl mpu::MPU for EmuMpu {
+    type MpuConfig = ();
+
+    fn number_total_regions(&self) -> usize {
+        8
+    }
+
+    fn allocate_region(
+        &self,
+        unallocated_memory_start: *const u8,
+        unallocated_memory_size: usize,
+        min_region_size: usize,
+        _permissions: Permissions,
+        _config: &mut (),
+    ) -> Option<Region> {
+        if min_region_size > unallocated_memory_size {
+            None
+        } else {
+            Some(Region::new(unallocated_memory_start, min_region_size))
+        }
+    }
+
+    fn allocate_app_memory_region(
+        &self,
+        unallocated_memory_start: *const u8,
+        unallocated_memory_size: usize,
+        min_memory_size: usize,
+        initial_app_memory_size: usize,
+        initial_kernel_memory_size: usize,
+        _permissions: Permissions,
+        _config: &mut (),
+    ) -> Option<(*const u8, usize)> {
+        let start = align_up(unallocated_memory_start as usize);
+        let padding = start - unallocated_memory_start as usize;
+        let memory_size = align_up(cmp::max(
+            min_memory_size,
+            initial_app_memory_size + initial_kernel_memory_size,
+        ));
+        if padding + memory_size > unallocated_memory_size {
+            None
+        } else {
+            Some((start as *const u8, memory_size))
+        }
+    }
+
+    fn update_app_memory_region(
+        &self,
+        app_memory_break: *const u8,
+        kernel_memory_break: *const u8,
+        _permissions: Permissions,
+        _config: &mut (),
+    ) -> Result<(), ()> {
+        if app_memory_break > kernel_memory_break {
+            Err(())
+        } else {
+            Ok(())
+        }
+    }
+}
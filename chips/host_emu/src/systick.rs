@@ -0,0 +1,85 @@
+//! System tick timer counting down on the virtual clock.
+//!
+//! Like the Cortex-M SysTick, the timer reloads and keeps counting when it
+//! expires, and reading whether it expired clears the flag that records it.
+
+use std::cell::Cell;
+
+use crate::chip::Clock;
+
+pub struct EmuSysTick<'a> {
+    clock: &'a Clock,
+    interval_us: Cell<u64>,
+    /// When the timer was last started, if it is running.
+    started_at: Cell<Option<u64>>,
+    /// How many times the timer had expired when `overflowed()` was last
+    /// called.
+    expirations_read: Cell<u64>,
+}
+
+impl EmuSysTick<'a> {
+    crate fn new(clock: &'a Clock) -> EmuSysTick<'a> {
+        EmuSysTick {
+            clock: clock,
+            interval_us: Cell::new(0),
+            started_at: Cell::new(None),
+            expirations_read: Cell::new(0),
+        }
+    }
+
+    fn elapsed_us(&self) -> u64 {
+        self.started_at
+            .get()
+            .map_or(0, |start| self.clock.now_us() - start)
+    }
+
+    fn expirations(&self) -> u64 {
+        match self.interval_us.get() {
+            0 => 0,
+            interval => self.elapsed_us() / interval,
+        }
+    }
+}
+
+impl kernel::SysTick for EmuSysTick<'a> {
+    fn set_timer(&self, us: u32) {
+        self.interval_us.set(us as u64);
+        // Like writing the current value register, this restarts the count.
+        if self.started_at.get().is_some() {
+            self.started_at.set(Some(self.clock.now_us()));
+        }
+        self.expirations_read.set(0);
+    }
+
+    fn greater_than(&self, us: u32) -> bool {
+        self.get_value() > us
+    }
+
+    fn overflowed(&self) -> bool {
+        let expirations = self.expirations();
+        let expired = expirations > self.expirations_read.get();
+        self.expirations_read.set(expirations);
+        expired
+    }
+
+    fn get_value(&self) -> u32 {
+        match self.interval_us.get() {
+            0 => 0,
+            interval => (interval - self.elapsed_us() % interval) as u32,
+        }
+    }
+
+    fn reset(&self) {
+        self.interval_us.set(0);
+        self.started_at.set(None);
+        self.expirations_read.set(0);
+    }
+
+    /// The timer interrupt is not emulated: processes only return to the
+    /// kernel at system calls, and the kernel checks the timer then.
+    fn enable(&self, _with_interrupt: bool) {
+        if self.started_at.get().is_none() {
+            self.started_at.set(Some(self.clock.now_us()));
+        }
+    }
+}
@@ -0,0 +1,168 @@
+//! UART whose output is kept in memory and whose input comes from the test.
+//!
+//! Transmissions complete at the next interrupt servicing, without using
+//! virtual time. A receive completes once the test has injected enough bytes
+//! with `push_input()`.
+
+use std::cell::{Cell, RefCell};
+use std::cmp;
+use std::collections::VecDeque;
+
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::hil::uart;
+use kernel::ReturnCode;
+
+pub struct EmuUart<'a> {
+    tx_client: OptionalCell<&'a dyn uart::TransmitClient>,
+    rx_client: OptionalCell<&'a dyn uart::ReceiveClient>,
+    tx_buffer: TakeCell<'static, [u8]>,
+    tx_len: Cell<usize>,
+    rx_buffer: TakeCell<'static, [u8]>,
+    rx_len: Cell<usize>,
+    rx_aborted: Cell<bool>,
+    output: RefCell<Vec<u8>>,
+    input: RefCell<VecDeque<u8>>,
+}
+
+impl EmuUart<'a> {
+    crate fn new() -> EmuUart<'a> {
+        EmuUart {
+            tx_client: OptionalCell::empty(),
+            rx_client: OptionalCell::empty(),
+            tx_buffer: TakeCell::empty(),
+            tx_len: Cell::new(0),
+            rx_buffer: TakeCell::empty(),
+            rx_len: Cell::new(0),
+            rx_aborted: Cell::new(false),
+            output: RefCell::new(Vec::new()),
+            input: RefCell::new(VecDeque::new()),
+        }
+    }
+
+    /// Everything transmitted so far.
+    pub fn output(&self) -> Vec<u8> {
+        self.output.borrow().clone()
+    }
+
+    /// Forget what was transmitted so far.
+    pub fn clear_output(&self) {
+        self.output.borrow_mut().clear();
+    }
+
+    /// Make `bytes` arrive on the receive line.
+    pub fn push_input(&self, bytes: &[u8]) {
+        self.input.borrow_mut().extend(bytes.iter());
+    }
+
+    crate fn is_pending(&self) -> bool {
+        self.tx_buffer.is_some()
+            || (self.rx_buffer.is_some()
+                && (self.rx_aborted.get() || self.input.borrow().len() >= self.rx_len.get()))
+    }
+
+    crate fn handle_interrupt(&self) {
+        if let Some(buffer) = self.tx_buffer.take() {
+            let len = self.tx_len.get();
+            self.output.borrow_mut().extend_from_slice(&buffer[..len]);
+            self.tx_client
+                .map(move |client| client.transmitted_buffer(buffer, len, ReturnCode::SUCCESS));
+        }
+
+        let rx_done = self.rx_aborted.get() || self.input.borrow().len() >= self.rx_len.get();
+        if rx_done {
+            if let Some(buffer) = self.rx_buffer.take() {
+                let mut input = self.input.borrow_mut();
+                let len = cmp::min(self.rx_len.get(), input.len());
+                for (byte, input) in buffer.iter_mut().zip(input.drain(..len)) {
+                    *byte = input;
+                }
+                drop(input);
+
+                let (rval, error) = if self.rx_aborted.get() {
+                    (ReturnCode::ECANCEL, uart::Error::Aborted)
+                } else {
+                    (ReturnCode::SUCCESS, uart::Error::None)
+                };
+                self.rx_aborted.set(false);
+                self.rx_client
+                    .map(move |client| client.received_buffer(buffer, len, rval, error));
+            }
+        }
+    }
+}
+
+impl uart::Configure for EmuUart<'a> {
+    fn configure(&self, _params: uart::Parameters) -> ReturnCode {
+        ReturnCode::SUCCESS
+    }
+}
+
+impl uart::Transmit<'a> for EmuUart<'a> {
+    fn set_transmit_client(&self, client: &'a dyn uart::TransmitClient) {
+        self.tx_client.set(client);
+    }
+
+    fn transmit_buffer(
+        &self,
+        tx_buffer: &'static mut [u8],
+        tx_len: usize,
+    ) -> (ReturnCode, Option<&'static mut [u8]>) {
+        if self.tx_buffer.is_some() {
+            (ReturnCode::EBUSY, Some(tx_buffer))
+        } else if tx_len == 0 || tx_len > tx_buffer.len() {
+            (ReturnCode::ESIZE, Some(tx_buffer))
+        } else {
+            self.tx_len.set(tx_len);
+            self.tx_buffer.replace(tx_buffer);
+            (ReturnCode::SUCCESS, None)
+        }
+    }
+
+    fn transmit_word(&self, _word: u32) -> ReturnCode {
+        ReturnCode::FAIL
+    }
+
+    /// Transmissions finish at the next interrupt, so there is nothing to
+    /// abort.
+    fn transmit_abort(&self) -> ReturnCode {
+        ReturnCode::FAIL
+    }
+}
+
+impl uart::Receive<'a> for EmuUart<'a> {
+    fn set_receive_client(&self, client: &'a dyn uart::ReceiveClient) {
+        self.rx_client.set(client);
+    }
+
+    fn receive_buffer(
+        &self,
+        rx_buffer: &'static mut [u8],
+        rx_len: usize,
+    ) -> (ReturnCode, Option<&'static mut [u8]>) {
+        if self.rx_buffer.is_some() {
+            (ReturnCode::EBUSY, Some(rx_buffer))
+        } else if rx_len > rx_buffer.len() {
+            (ReturnCode::ESIZE, Some(rx_buffer))
+        } else {
+            self.rx_len.set(rx_len);
+            self.rx_buffer.replace(rx_buffer);
+            (ReturnCode::SUCCESS, None)
+        }
+    }
+
+    fn receive_word(&self) -> ReturnCode {
+        ReturnCode::FAIL
+    }
+
+    fn receive_abort(&self) -> ReturnCode {
+        if self.rx_buffer.is_some() {
+            self.rx_aborted.set(true);
+            ReturnCode::EBUSY
+        } else {
+            ReturnCode::SUCCESS
+        }
+    }
+}
+
+impl uart::UartData<'a> for EmuUart<'a> {}
+impl uart::Uart<'a> for EmuUart<'a> {}
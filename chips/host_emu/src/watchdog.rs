@@ -0,0 +1,93 @@
+//! Watchdog timer counting on the virtual clock.
+//!
+//! Instead of resetting the chip, an expired watchdog is remembered so the
+//! test can check that it never expired.
+
+use std::cell::Cell;
+
+use kernel::hil;
+
+use crate::chip::Clock;
+
+pub struct EmuWatchdog<'a> {
+    clock: &'a Clock,
+    period_us: Cell<u64>,
+    /// When the watchdog was last started or tickled, if it is running.
+    serviced_at: Cell<Option<u64>>,
+    suspended: Cell<bool>,
+    tickles: Cell<usize>,
+    expired: Cell<bool>,
+}
+
+impl EmuWatchdog<'a> {
+    crate fn new(clock: &'a Clock) -> EmuWatchdog<'a> {
+        EmuWatchdog {
+            clock: clock,
+            period_us: Cell::new(0),
+            serviced_at: Cell::new(None),
+            suspended: Cell::new(false),
+            tickles: Cell::new(0),
+            expired: Cell::new(false),
+        }
+    }
+
+    /// How many times the watchdog was tickled.
+    pub fn tickles(&self) -> usize {
+        self.tickles.get()
+    }
+
+    /// Whether the watchdog went longer than its period without being
+    /// tickled while it was running, which would have reset a real chip.
+    pub fn expired(&self) -> bool {
+        self.check_expired();
+        self.expired.get()
+    }
+
+    pub fn is_suspended(&self) -> bool {
+        self.suspended.get()
+    }
+
+    fn check_expired(&self) {
+        if let Some(serviced_at) = self.serviced_at.get() {
+            if self.clock.now_us() - serviced_at > self.period_us.get() {
+                self.expired.set(true);
+            }
+        }
+    }
+}
+
+impl hil::watchdog::Watchdog for EmuWatchdog<'a> {
+    fn start(&self, period: usize) {
+        self.period_us.set(period as u64 * 1000);
+        self.serviced_at.set(Some(self.clock.now_us()));
+        self.suspended.set(false);
+    }
+
+    fn stop(&self) {
+        self.check_expired();
+        self.serviced_at.set(None);
+    }
+
+    fn tickle(&self) {
+        self.tickles.set(self.tickles.get() + 1);
+        self.check_expired();
+        if self.serviced_at.get().is_some() {
+            self.serviced_at.set(Some(self.clock.now_us()));
+        }
+    }
+
+    fn suspend(&self) {
+        self.check_expired();
+        if self.serviced_at.get().is_some() {
+            self.serviced_at.set(None);
+            self.suspended.set(true);
+        }
+    }
+
+    fn resume(&self) {
+        if self.suspended.get() {
+            self.serviced_at.set(Some(self.clock.now_us()));
+            self.suspended.set(false);
+        }
+    }
+}
@@ -0,0 +1,144 @@
+//! Sends kernel debug output to the emulated UART and a RAM log.
+//!
+//! The debug writer is global, so this is its own test program.
+
+use host_emu::EmuChip;
+use kernel::debug::{DebugSink, DebugWriter, DebugWriterWrapper, LogLevel, RamLog};
+use kernel::hil::uart::Transmit;
+use kernel::{debug, debug_deferred, log_trace, Chip, ReturnCode};
+
+mod noisy {
+    use kernel::{debug, log_warn};
+
+    pub fn chatter() {
+        debug!("chatter");
+        log_warn!("noisy warning");
+    }
+}
+
+fn send_all(chip: &EmuChip) {
+    while chip.has_pending_interrupts() {
+        chip.service_pending_interrupts();
+    }
+}
+
+/// Undo the COBS encoding of a deferred record sent between two zeros.
+fn cobs_decode(frame: &[u8]) -> Vec<u8> {
+    assert_eq!(frame[0], 0);
+    assert_eq!(frame[frame.len() - 1], 0);
+    let encoded = &frame[1..frame.len() - 1];
+    let mut record = Vec::new();
+    let mut i = 0;
+    while i < encoded.len() {
+        let code = encoded[i] as usize;
+        record.extend_from_slice(&encoded[i + 1..i + code]);
+        i += code;
+        if i < encoded.len() {
+            record.push(0);
+        }
+    }
+    record
+}
+
+fn read_varint(bytes: &mut &[u8]) -> u64 {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = bytes[0];
+        *bytes = &bytes[1..];
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return value;
+        }
+        shift += 7;
+    }
+}
+
+fn uart_output(chip: &EmuChip) -> String {
+    String::from_utf8(chip.uart.output()).unwrap()
+}
+
+#[test]
+fn output_is_filtered_per_sink_and_module_and_drops_are_counted() {
+    let chip = EmuChip::new();
+    let writer: &'static DebugWriter = Box::leak(Box::new(DebugWriter::new(
+        &chip.uart,
+        Box::leak(Box::new([0; 16])),
+        Box::leak(Box::new([0; 64])),
+    )));
+    chip.uart.set_transmit_client(writer);
+    let ram_log: &'static RamLog = Box::leak(Box::new(RamLog::new(Box::leak(Box::new([0; 256])))));
+    let wrapper = Box::leak(Box::new(DebugWriterWrapper::new(writer)));
+    assert_eq!(
+        wrapper.add_sink(ram_log, LogLevel::Trace),
+        ReturnCode::SUCCESS
+    );
+    assert_eq!(
+        wrapper.set_module_level("debug_log::noisy", LogLevel::Warn),
+        ReturnCode::SUCCESS
+    );
+    unsafe { kernel::debug::set_debug_writer_wrapper(wrapper) };
+
+    debug!("hello");
+    log_trace!("traced");
+    noisy::chatter();
+    send_all(chip);
+    assert_eq!(
+        uart_output(chip),
+        "hello\r\nWARN debug_log::noisy: noisy warning\r\n"
+    );
+
+    // Messages that do not fit are dropped, and the UART is told how much
+    // it missed once there is room again.
+    chip.uart.clear_output();
+    for i in 0..10 {
+        debug!("message {}", i);
+    }
+    send_all(chip);
+    debug!("after");
+    send_all(chip);
+    let output = uart_output(chip);
+    assert!(output.starts_with("message 0\r\n"));
+    assert!(output.ends_with(" bytes dropped]\r\nafter\r\n"));
+    let dropped = unsafe { kernel::debug::get_debug_writer().dropped_bytes() };
+    assert!(dropped > 0);
+    assert!(output.contains(&format!("[{} bytes dropped]", dropped)));
+
+    // Deferred messages only send where their format string is and the
+    // arguments.
+    chip.uart.clear_output();
+    debug_deferred!("{} of {} in {}", 300u32, -3i32, "emu");
+    send_all(chip);
+    let record = cobs_decode(&chip.uart.output());
+    let mut bytes = &record[..];
+    let format = read_varint(&mut bytes) as *const u8;
+    let format = unsafe { std::ffi::CStr::from_ptr(format as *const _) };
+    assert_eq!(format.to_str().unwrap(), "{} of {} in {}");
+    assert_eq!(bytes, &[0, 0xac, 0x02, 1, 5, 2, 3, b'e', b'm', b'u'][..]);
+
+    // The RAM log kept everything, including the trace message.
+    let mut crash_dump = String::new();
+    unsafe { kernel::debug::flush(&mut crash_dump) };
+    assert!(crash_dump.contains("TRACE debug_log: traced\r\n"));
+    assert!(crash_dump.contains("message 9\r\nafter\r\n"));
+    assert!(!crash_dump.contains("chatter"));
+
+    // It holds text, so it got the deferred message as a line of hex rather
+    // than as a binary frame.
+    let hex: String = record.iter().map(|byte| format!("{:02x}", byte)).collect();
+    assert!(crash_dump.contains(&format!("[deferred {}]\r\n", hex)));
+    assert!(!crash_dump.contains('\u{fffd}'));
+}
+
+#[test]
+fn ram_log_flushes_utf8_text_after_wrapping() {
+    let ram_log = RamLog::new(Box::leak(Box::new([0; 8])));
+    // Ten bytes in an eight byte log: the first character is cut in half, and
+    // the last one is split where the log wraps around.
+    ram_log.write("a\u{20ac}\u{20ac}".as_bytes());
+    ram_log.write("\u{20ac}".as_bytes());
+
+    let mut crash_dump = String::new();
+    ram_log.flush(&mut crash_dump);
+    assert_eq!(crash_dump, "\r\n---| Kernel Log |---\r\n\u{20ac}\u{20ac}");
+}
@@ -0,0 +1,1247 @@
+//! Runs the kernel loop with processes on the emulated chip.
+
+use std::cell::Cell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use capsules::alarm::AlarmDriver;
+use capsules::restart_supervisor::{
+    RestartNode, RestartPolicy, RestartSupervisor, RestartSupervisorClient,
+};
+use capsules::virtual_alarm::{MuxAlarm, VirtualMuxAlarm};
+use host_emu::alarm::EmuAlarm;
+use host_emu::app::{self, App, AppImage};
+use host_emu::watchdog::EmuWatchdog;
+use host_emu::EmuChip;
+use kernel::capabilities;
+use kernel::heartbeat::Heartbeat;
+use kernel::hil::time::Alarm;
+use kernel::hil::watchdog::Watchdog;
+use kernel::introspection::KernelInfo;
+use kernel::ipc::{Mailbox, IPC, MAILBOX_DRIVER_NUM, MAX_MESSAGE_LEN};
+use kernel::power::{Constraint, PowerConstraint};
+use kernel::procs::{
+    AppCredentialsChecker, CheckResult, CredentialsFailure, DynamicProcessLoader, FaultResponse,
+    ProcessType, State, TbfFooterV2Credentials,
+};
+use kernel::schedulers::{CooperativeSched, MLFQProcessNode, MLFQSched, RoundRobinSched};
+use kernel::{
+    AppId, Callback, Driver, Grant, Kernel, Platform, ReadOnlyAppSlice, ReturnCode, Scheduler,
+    SchedulingDecision, StoppedExecutingReason, SyscallReturn,
+};
+
+struct Capability;
+unsafe impl capabilities::MainLoopCapability for Capability {}
+unsafe impl capabilities::MemoryAllocationCapability for Capability {}
+unsafe impl capabilities::ProcessManagementCapability for Capability {}
+unsafe impl capabilities::ProcessLoadingCapability for Capability {}
+
+const COUNTER_DRIVER_NUM: usize = 0x9000;
+const EVENTS_DRIVER_NUM: usize = 0x9001;
+
+/// Counts commands per process in a grant.
+struct Counter {
+    apps: Grant<usize>,
+}
+
+impl Driver for Counter {
+    fn subscribe(&self, _: usize, _: Option<Callback>, _: AppId) -> ReturnCode {
+        ReturnCode::ENOSUPPORT
+    }
+
+    fn command(&self, command_num: usize, _: usize, _: usize, appid: AppId) -> ReturnCode {
+        match command_num {
+            0 => ReturnCode::SUCCESS,
+            1 => self
+                .apps
+                .enter(appid, |count, _| {
+                    **count += 1;
+                    ReturnCode::SuccessWithValue { value: **count }
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    /// Command 2 returns a 64-bit value and command 3 a status together with
+    /// a length. Others are answered by `command`.
+    fn typed_command(&self, command_num: usize, _: usize, _: usize, appid: AppId) -> SyscallReturn {
+        match command_num {
+            2 => SyscallReturn::SuccessU64(0x1_2345_6789),
+            3 => SyscallReturn::FailureU32(ReturnCode::ESIZE, 42),
+            _ => self.command(command_num, 0, 0, appid).into(),
+        }
+    }
+
+    /// Sums the bytes of a read-only buffer.
+    fn allow_read_only(
+        &self,
+        _: AppId,
+        _: usize,
+        slice: Option<ReadOnlyAppSlice<u8>>,
+    ) -> ReturnCode {
+        slice.map_or(ReturnCode::SUCCESS, |slice| ReturnCode::SuccessWithValue {
+            value: slice.iter().map(|&byte| byte as usize).sum(),
+        })
+    }
+}
+
+/// Schedules a burst of callbacks, like a button pressed faster than the app
+/// handles it.
+struct Events {
+    apps: Grant<Option<Callback>>,
+}
+
+impl Driver for Events {
+    fn subscribe(&self, _: usize, callback: Option<Callback>, appid: AppId) -> ReturnCode {
+        self.apps
+            .enter(appid, |app_callback, _| {
+                **app_callback = callback;
+                ReturnCode::SUCCESS
+            })
+            .unwrap_or_else(|err| err.into())
+    }
+
+    /// Command 1 schedules `count` callbacks, numbered from 0.
+    fn command(&self, command_num: usize, count: usize, _: usize, appid: AppId) -> ReturnCode {
+        match command_num {
+            0 => ReturnCode::SUCCESS,
+            1 => self
+                .apps
+                .enter(appid, |app_callback, _| {
+                    app_callback.map(|mut callback| {
+                        for event in 0..count {
+                            callback.schedule(event, 0, 0);
+                        }
+                    });
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
+
+type TestAlarm = VirtualMuxAlarm<'static, EmuAlarm<'static>>;
+
+struct TestPlatform {
+    counter: &'static Counter,
+    events: &'static Events,
+    alarm: &'static AlarmDriver<'static, TestAlarm>,
+    ipc: &'static IPC,
+    mailbox: &'static Mailbox,
+    heartbeat: &'static Heartbeat<'static, TestAlarm>,
+    watchdog: &'static EmuWatchdog<'static>,
+}
+
+impl Platform for TestPlatform {
+    fn with_driver<F, R>(&self, driver_num: usize, f: F) -> R
+    where
+        F: FnOnce(Option<&dyn Driver>) -> R,
+    {
+        match driver_num {
+            COUNTER_DRIVER_NUM => f(Some(self.counter)),
+            EVENTS_DRIVER_NUM => f(Some(self.events)),
+            capsules::alarm::DRIVER_NUM => f(Some(self.alarm)),
+            kernel::ipc::DRIVER_NUM => f(Some(self.ipc)),
+            MAILBOX_DRIVER_NUM => f(Some(self.mailbox)),
+            kernel::heartbeat::DRIVER_NUM => f(Some(self.heartbeat)),
+            _ => f(None),
+        }
+    }
+
+    fn watchdog(&self) -> Option<&dyn Watchdog> {
+        Some(self.watchdog)
+    }
+}
+
+struct Board {
+    kernel: &'static Kernel,
+    chip: &'static EmuChip,
+    platform: TestPlatform,
+    mux_alarm: &'static MuxAlarm<'static, EmuAlarm<'static>>,
+    loader: &'static DynamicProcessLoader<EmuChip>,
+    scheduler: RoundRobinSched,
+}
+
+impl Board {
+    fn new(apps: &[AppImage]) -> Board {
+        Board::build(apps, 0, None)
+    }
+
+    fn with_syscall_trace(apps: &[AppImage], trace_length: usize) -> Board {
+        Board::build(apps, trace_length, None)
+    }
+
+    fn with_checker(apps: &[AppImage], checker: &'static dyn AppCredentialsChecker) -> Board {
+        Board::build(apps, 0, Some(checker))
+    }
+
+    fn build(
+        apps: &[AppImage],
+        trace_length: usize,
+        checker: Option<&'static dyn AppCredentialsChecker>,
+    ) -> Board {
+        let processes: &'static mut [Option<&'static dyn ProcessType>] =
+            Box::leak(Box::new([None; 4]));
+        let kernel: &'static Kernel = Box::leak(Box::new(Kernel::new(processes)));
+        let chip = EmuChip::new();
+
+        let counter: &'static Counter = Box::leak(Box::new(Counter {
+            apps: kernel.create_grant(&Capability),
+        }));
+        let events: &'static Events = Box::leak(Box::new(Events {
+            apps: kernel.create_grant(&Capability),
+        }));
+        let mux_alarm: &'static MuxAlarm<'static, EmuAlarm<'static>> =
+            Box::leak(Box::new(MuxAlarm::new(&chip.alarm)));
+        chip.alarm.set_client(mux_alarm);
+        let alarm_driver_alarm: &'static TestAlarm =
+            Box::leak(Box::new(VirtualMuxAlarm::new(mux_alarm)));
+        let alarm: &'static AlarmDriver<'static, TestAlarm> = Box::leak(Box::new(
+            AlarmDriver::new(alarm_driver_alarm, kernel.create_grant(&Capability)),
+        ));
+        alarm_driver_alarm.set_client(alarm);
+        let heartbeat_alarm: &'static TestAlarm =
+            Box::leak(Box::new(VirtualMuxAlarm::new(mux_alarm)));
+        let heartbeat: &'static Heartbeat<'static, TestAlarm> = Box::leak(Box::new(
+            Heartbeat::new(kernel, heartbeat_alarm, &Capability),
+        ));
+        heartbeat_alarm.set_client(heartbeat);
+        let ipc: &'static IPC = Box::leak(Box::new(IPC::new(kernel, &Capability)));
+        let mailbox: &'static Mailbox = Box::leak(Box::new(Mailbox::new(kernel, &Capability)));
+
+        kernel.set_sleep_clock(&chip.alarm, &Capability);
+        kernel.set_syscall_trace_length(trace_length, &Capability);
+        match checker {
+            Some(checker) => kernel::procs::load_and_check_processes(
+                kernel,
+                chip,
+                app::flash(apps).as_ptr(),
+                app::app_memory(64 * 1024),
+                FaultResponse::Stop,
+                checker,
+                &Capability,
+            ),
+            None => kernel::procs::load_processes(
+                kernel,
+                chip,
+                app::flash(apps).as_ptr(),
+                app::app_memory(64 * 1024),
+                FaultResponse::Stop,
+                &Capability,
+            ),
+        }
+
+        Board {
+            kernel: kernel,
+            chip: chip,
+            platform: TestPlatform {
+                counter: counter,
+                events: events,
+                alarm: alarm,
+                ipc: ipc,
+                mailbox: mailbox,
+                heartbeat: heartbeat,
+                watchdog: &chip.watchdog,
+            },
+            mux_alarm: mux_alarm,
+            loader: Box::leak(Box::new(DynamicProcessLoader::new(
+                kernel,
+                chip,
+                FaultResponse::Stop,
+                None,
+            ))),
+            scheduler: RoundRobinSched::new(),
+        }
+    }
+
+    /// Run the kernel loop until `done` returns true, at most `iterations`
+    /// times.
+    fn run_until<F: Fn() -> bool>(&self, iterations: usize, done: F) -> bool {
+        for _ in 0..iterations {
+            if done() {
+                return true;
+            }
+            self.kernel.kernel_loop_operation(
+                &self.platform,
+                self.chip,
+                None,
+                &self.scheduler,
+                &Capability,
+            );
+        }
+        done()
+    }
+
+    /// Set a `RestartSupervisor` with `policy` as the kernel's fault policy.
+    fn supervise(
+        &self,
+        policy: RestartPolicy,
+    ) -> &'static RestartSupervisor<'static, TestAlarm, Capability> {
+        let nodes: &'static [RestartNode] = Box::leak(Box::new([
+            RestartNode::new(),
+            RestartNode::new(),
+            RestartNode::new(),
+            RestartNode::new(),
+        ]));
+        let alarm: &'static TestAlarm = Box::leak(Box::new(VirtualMuxAlarm::new(self.mux_alarm)));
+        let supervisor = Box::leak(Box::new(RestartSupervisor::new(
+            alarm,
+            self.kernel,
+            nodes,
+            policy,
+            Capability,
+        )));
+        alarm.set_client(supervisor);
+        self.kernel.set_fault_policy(supervisor, &Capability);
+        supervisor
+    }
+
+    fn try_process(&self, index: usize) -> Option<&'static dyn ProcessType> {
+        let found = Cell::new(None);
+        self.kernel
+            .process_each_capability(&Capability, |i, process| {
+                if i == index {
+                    found.set(Some(process));
+                }
+            });
+        found.get()
+    }
+
+    fn process(&self, index: usize) -> &'static dyn ProcessType {
+        self.try_process(index).expect("process not loaded")
+    }
+}
+
+static COUNTED: AtomicUsize = AtomicUsize::new(0);
+
+fn counting_main(app: &App) {
+    for _ in 0..5 {
+        let count = app.command(COUNTER_DRIVER_NUM, 1, 0, 0);
+        COUNTED.store(count as usize, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn process_syscalls_reach_capsule_grant() {
+    let board = Board::new(&[AppImage::new("counting", counting_main)]);
+    assert!(board.run_until(100, || COUNTED.load(Ordering::SeqCst) == 5));
+
+    let process = board.process(0);
+    assert_eq!(process.get_process_name(), "counting");
+    assert!(process.grant_bytes_allocated() >= std::mem::size_of::<usize>());
+}
+
+static ALARM_FIRED_AT: AtomicUsize = AtomicUsize::new(0);
+
+fn alarm_fired(now: usize, _: usize, _: usize, _: usize) {
+    ALARM_FIRED_AT.store(now, Ordering::SeqCst);
+}
+
+fn sleeping_main(app: &App) {
+    let alarm = capsules::alarm::DRIVER_NUM;
+    app.subscribe(alarm, 0, Some(alarm_fired), 0);
+    let now = app.command(alarm, 2, 0, 0) as usize;
+    app.command(alarm, 4, now + 50_000, 0);
+    app.yieldk_for(|| ALARM_FIRED_AT.load(Ordering::SeqCst) != 0);
+}
+
+#[test]
+fn sleeping_advances_virtual_time_to_alarm() {
+    let board = Board::new(&[AppImage::new("sleeping", sleeping_main)]);
+    assert!(board.run_until(100, || ALARM_FIRED_AT.load(Ordering::SeqCst) != 0));
+    assert!(ALARM_FIRED_AT.load(Ordering::SeqCst) >= 50_000);
+    assert!(board.chip.clock().now_us() >= 50_000);
+}
+
+// Three sleeps of this many microseconds take the 32-bit, 1 MHz alarm clock
+// past its first wrap.
+const LONG_SLEEP_US: usize = 0x6000_0000;
+static LONG_SLEEPS: AtomicUsize = AtomicUsize::new(0);
+static mut ALARM_TYPED_RESULTS: [[usize; 4]; 2] = [[0; 4]; 2];
+static ALARM_TYPED_DONE: AtomicUsize = AtomicUsize::new(0);
+
+fn long_sleep_woke(_: usize, _: usize, _: usize, _: usize) {
+    LONG_SLEEPS.fetch_add(1, Ordering::SeqCst);
+}
+
+fn long_sleeper_main(app: &App) {
+    let alarm = capsules::alarm::DRIVER_NUM;
+    app.subscribe(alarm, 0, Some(long_sleep_woke), 0);
+    for sleep in 1..=3 {
+        let now = app.command(alarm, 2, 0, 0) as usize;
+        app.command(alarm, 4, now.wrapping_add(LONG_SLEEP_US) & 0xffff_ffff, 0);
+        app.yieldk_for(|| LONG_SLEEPS.load(Ordering::SeqCst) == sleep);
+    }
+    let now = app.command(alarm, 2, 0, 0) as usize;
+    let expiration = now.wrapping_add(LONG_SLEEP_US) & 0xffff_ffff;
+    app.command(alarm, 4, expiration, 0);
+    let results = [
+        app.typed_command(alarm, 2, 0, 0),
+        app.typed_command(alarm, 3, expiration + 1, 0),
+    ];
+    unsafe {
+        ALARM_TYPED_RESULTS = results;
+    }
+    ALARM_TYPED_DONE.store(expiration, Ordering::SeqCst);
+}
+
+#[test]
+fn alarm_typed_commands_return_64_bit_time_and_outstanding_alarm() {
+    let board = Board::new(&[AppImage::new("long-sleeper", long_sleeper_main)]);
+    assert!(board.run_until(100, || ALARM_TYPED_DONE.load(Ordering::SeqCst) != 0));
+
+    let results = unsafe { ALARM_TYPED_RESULTS };
+    assert_eq!(results[0][0], 131);
+    let now = (results[0][2] as u64) << 32 | results[0][1] as u64;
+    assert!(now >= 3 * LONG_SLEEP_US as u64);
+    assert!(now <= board.chip.clock().now_us());
+    let expiration = ALARM_TYPED_DONE.load(Ordering::SeqCst);
+    assert_eq!(results[1], [1, -6isize as u32 as usize, expiration, 0]);
+}
+
+fn faulting_main(app: &App) {
+    app.command(COUNTER_DRIVER_NUM, 1, 0, 0);
+    app.fault();
+}
+
+static SURVIVOR_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+fn survivor_main(app: &App) {
+    loop {
+        let count = app.command(COUNTER_DRIVER_NUM, 1, 0, 0);
+        SURVIVOR_COUNT.store(count as usize, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn faulting_process_stops_while_others_run() {
+    let board = Board::new(&[
+        AppImage::new("faulting", faulting_main),
+        AppImage::new("survivor", survivor_main),
+    ]);
+    assert!(board.run_until(1000, || { SURVIVOR_COUNT.load(Ordering::SeqCst) >= 200 }));
+    assert_eq!(board.process(0).get_state(), State::StoppedFaulted);
+    assert!(board.process(1).debug_timeslice_expiration_count() > 0);
+}
+
+#[test]
+fn stale_appid_does_not_reach_restarted_process() {
+    let board = Board::new(&[AppImage::new("faulting", faulting_main)]);
+    let process = board.process(0);
+    assert!(board.run_until(100, || { process.get_state() == State::StoppedFaulted }));
+
+    let old = process.appid();
+    assert_eq!(
+        board.kernel.restart_process(old, &Capability),
+        ReturnCode::SUCCESS
+    );
+    assert!(process.appid() != old);
+    assert_eq!(process.appid().idx(), old.idx());
+
+    let entered = board.platform.counter.apps.enter(old, |count, _| **count);
+    assert!(entered.is_err());
+    assert_eq!(
+        board.kernel.restart_process(old, &Capability),
+        ReturnCode::EINVAL
+    );
+}
+
+static IPC_NOTIFY_RESULTS: [AtomicUsize; 2] = [AtomicUsize::new(0), AtomicUsize::new(0)];
+static IPC_SERVICE_RESTARTED: AtomicUsize = AtomicUsize::new(0);
+
+fn ipc_service_main(_app: &App) {}
+
+fn ipc_client_main(app: &App) {
+    let name = app.alloc(11).unwrap();
+    name.copy_from_slice(b"ipc_service");
+    let service = app.allow(kernel::ipc::DRIVER_NUM, 0, Some(&mut name[..])) as usize;
+    let notify = || app.command(kernel::ipc::DRIVER_NUM, service, 0, 0) as usize;
+
+    IPC_NOTIFY_RESULTS[0].store(notify().wrapping_add(1), Ordering::SeqCst);
+    while IPC_SERVICE_RESTARTED.load(Ordering::SeqCst) == 0 {
+        app.command(COUNTER_DRIVER_NUM, 0, 0, 0);
+    }
+    IPC_NOTIFY_RESULTS[1].store(notify().wrapping_add(1), Ordering::SeqCst);
+}
+
+#[test]
+fn ipc_rejects_identifier_of_restarted_service() {
+    let board = Board::new(&[
+        AppImage::new("ipc_service", ipc_service_main),
+        AppImage::new("ipc_client", ipc_client_main),
+    ]);
+    assert!(board.run_until(100, || IPC_NOTIFY_RESULTS[0].load(Ordering::SeqCst) != 0));
+    assert_eq!(IPC_NOTIFY_RESULTS[0].load(Ordering::SeqCst) - 1, 0);
+
+    // The service restarts in the same slot, so the identifier the client
+    // looked up now names a process that no longer exists.
+    let service = board.process(0);
+    let old = service.appid();
+    service.set_fault_state();
+    assert_eq!(
+        board.kernel.restart_process(old, &Capability),
+        ReturnCode::SUCCESS
+    );
+    assert_eq!(service.appid().idx(), old.idx());
+    IPC_SERVICE_RESTARTED.store(1, Ordering::SeqCst);
+
+    assert!(board.run_until(100, || IPC_NOTIFY_RESULTS[1].load(Ordering::SeqCst) != 0));
+    assert_eq!(
+        IPC_NOTIFY_RESULTS[1].load(Ordering::SeqCst).wrapping_sub(1) as isize,
+        isize::from(ReturnCode::EINVAL)
+    );
+}
+
+static CRASH_LOOP_RUNS: AtomicUsize = AtomicUsize::new(0);
+static CRASH_LOOP_GIVEN_UP: AtomicUsize = AtomicUsize::new(0);
+
+fn crash_loop_main(app: &App) {
+    CRASH_LOOP_RUNS.fetch_add(1, Ordering::SeqCst);
+    app.fault();
+}
+
+struct CrashLoopGivenUp;
+
+impl RestartSupervisorClient for CrashLoopGivenUp {
+    fn restarts_exhausted(&self, _: AppId) {
+        CRASH_LOOP_GIVEN_UP.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn supervisor_stops_process_after_max_restarts() {
+    let board = Board::new(&[AppImage::new("crash_loop", crash_loop_main)]);
+    let supervisor = board.supervise(RestartPolicy {
+        max_restarts: 3,
+        window_ms: 60_000,
+        initial_backoff_ms: 0,
+        max_backoff_ms: 0,
+    });
+    supervisor.set_client(&CrashLoopGivenUp);
+
+    let process = board.process(0);
+    assert!(board.run_until(1000, || process.get_state() == State::StoppedFaulted));
+    board.run_until(10, || false);
+
+    // Started once and restarted three times, then left stopped.
+    assert_eq!(CRASH_LOOP_RUNS.load(Ordering::SeqCst), 4);
+    assert_eq!(process.debug_restart_count(), 3);
+    assert_eq!(process.get_state(), State::StoppedFaulted);
+    assert_eq!(CRASH_LOOP_GIVEN_UP.load(Ordering::SeqCst), 1);
+}
+
+static BACKOFF_RUNS: AtomicUsize = AtomicUsize::new(0);
+static BACKOFF_STARTED_AT: [AtomicUsize; 4] = [
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+];
+
+fn backoff_main(app: &App) {
+    let now = app.command(capsules::alarm::DRIVER_NUM, 2, 0, 0) as usize;
+    let run = BACKOFF_RUNS.fetch_add(1, Ordering::SeqCst);
+    BACKOFF_STARTED_AT[run].store(now, Ordering::SeqCst);
+    app.fault();
+}
+
+#[test]
+fn supervisor_delays_restarts_with_exponential_backoff() {
+    let board = Board::new(&[AppImage::new("backoff", backoff_main)]);
+    board.supervise(RestartPolicy {
+        max_restarts: 3,
+        window_ms: 60_000,
+        initial_backoff_ms: 10,
+        max_backoff_ms: 15,
+    });
+
+    let process = board.process(0);
+    assert!(
+        board.run_until(1000, || BACKOFF_RUNS.load(Ordering::SeqCst) == 4
+            && process.get_state() == State::StoppedFaulted)
+    );
+    board.run_until(10, || false);
+    assert_eq!(BACKOFF_RUNS.load(Ordering::SeqCst), 4);
+    assert_eq!(process.debug_restart_count(), 3);
+
+    // The backoff doubles from 10 ms and is capped at 15 ms. Nothing else
+    // runs, so the chip sleeps through each backoff and wakes right after.
+    let started_at: Vec<usize> = BACKOFF_STARTED_AT
+        .iter()
+        .map(|at| at.load(Ordering::SeqCst))
+        .collect();
+    for (gap, backoff_us) in started_at.windows(2).zip(&[10_000, 15_000, 15_000]) {
+        let delay = gap[1] - gap[0];
+        assert!(delay >= *backoff_us && delay < backoff_us + 1_000);
+    }
+}
+
+static HOG_DONE: AtomicUsize = AtomicUsize::new(0);
+
+fn hog_main(app: &App) {
+    // Each run between system calls is long enough that the timer can expire
+    // and reload without coming close to running out first.
+    for _ in 0..20 {
+        app.compute(3_000);
+        app.command(COUNTER_DRIVER_NUM, 1, 0, 0);
+    }
+    HOG_DONE.store(1, Ordering::SeqCst);
+}
+
+#[test]
+fn process_is_preempted_when_timer_expires_between_syscalls() {
+    let board = Board::new(&[
+        AppImage::new("hog", hog_main),
+        AppImage::new("survivor", survivor_main),
+    ]);
+    assert!(board.run_until(1000, || HOG_DONE.load(Ordering::SeqCst) != 0));
+
+    // 60 ms of computing in 10 ms timeslices.
+    let hog = board.process(0);
+    assert!(hog.debug_timeslice_expiration_count() >= 5);
+}
+
+static LOADED_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+fn loaded_main(app: &App) {
+    loop {
+        let count = app.command(COUNTER_DRIVER_NUM, 1, 0, 0);
+        LOADED_COUNT.store(count as usize, Ordering::SeqCst);
+    }
+}
+
+static REPLACEMENT_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+fn replacement_main(app: &App) {
+    loop {
+        let count = app.command(COUNTER_DRIVER_NUM, 1, 0, 0);
+        REPLACEMENT_COUNT.store(count as usize, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn loader_loads_and_unloads_processes_at_runtime() {
+    let board = Board::new(&[AppImage::new("resident", survivor_main)]);
+    let flash = app::flash(&[AppImage::new("loaded", loaded_main)]);
+
+    let appid = board
+        .loader
+        .load(flash.as_ptr(), &Capability)
+        .expect("load failed");
+    assert_eq!(appid.idx(), 1);
+    assert_eq!(board.process(1).get_process_name(), "loaded");
+    assert!(board.run_until(1000, || LOADED_COUNT.load(Ordering::SeqCst) >= 10));
+
+    // The same image can't run twice.
+    assert_eq!(
+        board.loader.load(flash.as_ptr(), &Capability),
+        Err(ReturnCode::EBUSY)
+    );
+
+    let mem_start = board.process(1).mem_start();
+    assert_eq!(board.loader.unload(appid, &Capability), ReturnCode::SUCCESS);
+    assert!(board.try_process(1).is_none());
+    assert!(board.platform.counter.apps.enter(appid, |_, _| ()).is_err());
+    assert_eq!(board.loader.unload(appid, &Capability), ReturnCode::EINVAL);
+
+    // Loading it again reuses the slot and the memory it was given before.
+    let reloaded = board
+        .loader
+        .load(flash.as_ptr(), &Capability)
+        .expect("reload failed");
+    assert_eq!(reloaded.idx(), 1);
+    assert!(reloaded != appid);
+    assert_eq!(board.process(1).mem_start(), mem_start);
+}
+
+#[test]
+fn loader_replaces_process_and_restores_it_on_failure() {
+    let board = Board::new(&[AppImage::new("original", survivor_main)]);
+    let original = board.process(0).appid();
+    let replacement = app::flash(&[AppImage::new("replacement", replacement_main)]);
+    let too_big =
+        app::flash(&[AppImage::new("too_big", replacement_main).minimum_ram_size(1 << 20)]);
+
+    // The new image is checked before the old process is touched.
+    assert_eq!(
+        board
+            .loader
+            .replace(original, &[0u8; 16] as *const u8, &Capability),
+        Err(ReturnCode::EINVAL)
+    );
+    assert_eq!(board.process(0).appid(), original);
+
+    // An image that doesn't fit is only found out after the old process is
+    // gone, which is then loaded again from its flash.
+    assert_eq!(
+        board
+            .loader
+            .replace(original, too_big.as_ptr(), &Capability),
+        Err(ReturnCode::ENOMEM)
+    );
+    let restored = board.process(0);
+    assert_eq!(restored.get_process_name(), "original");
+    assert!(restored.appid() != original);
+
+    let new = board
+        .loader
+        .replace(restored.appid(), replacement.as_ptr(), &Capability)
+        .expect("replace failed");
+    assert_eq!(board.process(new.idx()).get_process_name(), "replacement");
+    assert!(board.run_until(1000, || REPLACEMENT_COUNT.load(Ordering::SeqCst) >= 10));
+}
+
+/// Requires credentials, which the emulated app images do not have, and keeps
+/// the apps it refuses.
+struct RequireCredentials;
+
+impl AppCredentialsChecker for RequireCredentials {
+    fn require_credentials(&self) -> bool {
+        true
+    }
+
+    fn check_credentials(&self, _: TbfFooterV2Credentials, _: &[u8]) -> CheckResult {
+        CheckResult::Pass
+    }
+
+    fn failure_response(&self) -> CredentialsFailure {
+        CredentialsFailure::KeepUnstarted
+    }
+}
+
+static UNSIGNED_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+fn unsigned_main(app: &App) {
+    loop {
+        let count = app.command(COUNTER_DRIVER_NUM, 1, 0, 0);
+        UNSIGNED_COUNT.store(count as usize, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn app_failing_credentials_is_kept_but_never_started() {
+    let board = Board::with_checker(
+        &[AppImage::new("unsigned", unsigned_main)],
+        &RequireCredentials,
+    );
+    let process = board.process(0);
+    assert_eq!(process.get_process_name(), "unsigned");
+    assert_eq!(process.get_state(), State::CredentialsFailed);
+
+    board.run_until(100, || false);
+    assert_eq!(UNSIGNED_COUNT.load(Ordering::SeqCst), 0);
+    assert_eq!(process.get_state(), State::CredentialsFailed);
+    assert_eq!(
+        board.kernel.restart_process(process.appid(), &Capability),
+        ReturnCode::EALREADY
+    );
+}
+
+static FAULTED_UNSIGNED_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+fn faulted_unsigned_main(app: &App) {
+    loop {
+        let count = app.command(COUNTER_DRIVER_NUM, 1, 0, 0);
+        FAULTED_UNSIGNED_COUNT.store(count as usize, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn faulting_app_failing_credentials_does_not_restart_it() {
+    let board = Board::with_checker(
+        &[AppImage::new("unsigned", faulted_unsigned_main)],
+        &RequireCredentials,
+    );
+    board.supervise(RestartPolicy {
+        max_restarts: 3,
+        window_ms: 60_000,
+        initial_backoff_ms: 0,
+        max_backoff_ms: 0,
+    });
+    let process = board.process(0);
+
+    board.kernel.hardfault_all_apps(&Capability);
+    board.run_until(100, || false);
+    assert_eq!(FAULTED_UNSIGNED_COUNT.load(Ordering::SeqCst), 0);
+    assert_eq!(process.get_state(), State::CredentialsFailed);
+    assert_eq!(process.debug_restart_count(), 0);
+}
+
+const MESSAGES_SENT: usize = 8;
+static MESSAGES_RECEIVED: AtomicUsize = AtomicUsize::new(0);
+static MESSAGES_OUT_OF_ORDER: AtomicUsize = AtomicUsize::new(0);
+static SENDER_BLOCKED: AtomicUsize = AtomicUsize::new(0);
+static SENDER_READY: AtomicUsize = AtomicUsize::new(0);
+
+fn message_received(_sender: usize, _len: usize, _: usize, _: usize) {
+    MESSAGES_RECEIVED.fetch_add(1, Ordering::SeqCst);
+}
+
+fn receiver_main(app: &App) {
+    let rx = app.alloc(MAX_MESSAGE_LEN).unwrap();
+    app.allow(MAILBOX_DRIVER_NUM, 0, Some(&mut rx[..]));
+    app.subscribe(MAILBOX_DRIVER_NUM, 0, Some(message_received), 0);
+    loop {
+        let seen = MESSAGES_RECEIVED.load(Ordering::SeqCst);
+        app.yieldk_for(|| MESSAGES_RECEIVED.load(Ordering::SeqCst) > seen);
+        if rx[0] as usize != seen {
+            MESSAGES_OUT_OF_ORDER.fetch_add(1, Ordering::SeqCst);
+        }
+        app.command(MAILBOX_DRIVER_NUM, 3, 0, 0);
+    }
+}
+
+fn sender_ready(_receiver: usize, _: usize, _: usize, _: usize) {
+    SENDER_READY.store(1, Ordering::SeqCst);
+}
+
+fn sender_main(app: &App) {
+    let name = app.alloc(8).unwrap();
+    name.copy_from_slice(b"receiver");
+    let receiver = app.allow(MAILBOX_DRIVER_NUM, 2, Some(&mut name[..]));
+    assert!(receiver >= 0);
+
+    let tx = app.alloc(MAX_MESSAGE_LEN).unwrap();
+    app.allow(MAILBOX_DRIVER_NUM, 1, Some(&mut tx[..]));
+    app.subscribe(MAILBOX_DRIVER_NUM, 1, Some(sender_ready), 0);
+    for i in 0..MESSAGES_SENT {
+        tx[0] = i as u8;
+        loop {
+            SENDER_READY.store(0, Ordering::SeqCst);
+            match app.command(MAILBOX_DRIVER_NUM, 2, receiver as usize, 1) {
+                0 => break,
+                // EBUSY: the mailbox is full, wait for room.
+                -2 => {
+                    SENDER_BLOCKED.fetch_add(1, Ordering::SeqCst);
+                    app.yieldk_for(|| SENDER_READY.load(Ordering::SeqCst) != 0);
+                }
+                // EOFF: the receiver has not set up its mailbox yet, try
+                // again once the sender's timeslice has let it run.
+                -4 => {}
+                err => panic!("send failed: {}", err),
+            }
+        }
+    }
+}
+
+#[test]
+fn mailbox_delivers_messages_in_order_with_backpressure() {
+    let board = Board::new(&[
+        AppImage::new("receiver", receiver_main),
+        AppImage::new("sender", sender_main),
+    ]);
+    assert!(board.run_until(1000, || {
+        MESSAGES_RECEIVED.load(Ordering::SeqCst) == MESSAGES_SENT
+    }));
+    assert_eq!(MESSAGES_OUT_OF_ORDER.load(Ordering::SeqCst), 0);
+    assert!(SENDER_BLOCKED.load(Ordering::SeqCst) > 0);
+}
+
+static HELD_EVENTS: AtomicUsize = AtomicUsize::new(0);
+static HELD_RECEIVED: AtomicUsize = AtomicUsize::new(0);
+static HELD_MESSAGES: [AtomicUsize; 2] = [AtomicUsize::new(0), AtomicUsize::new(0)];
+static HELD_EVENTS_BEFORE_MESSAGE: AtomicUsize = AtomicUsize::new(0);
+
+fn held_event(_: usize, _: usize, _: usize, _: usize) {
+    HELD_EVENTS.fetch_add(1, Ordering::SeqCst);
+}
+
+fn held_message_received(_sender: usize, _len: usize, _: usize, _: usize) {
+    HELD_RECEIVED.fetch_add(1, Ordering::SeqCst);
+}
+
+fn full_queue_receiver_main(app: &App) {
+    let rx = app.alloc(MAX_MESSAGE_LEN).unwrap();
+    app.allow(MAILBOX_DRIVER_NUM, 0, Some(&mut rx[..]));
+    app.subscribe(MAILBOX_DRIVER_NUM, 0, Some(held_message_received), 0);
+    let name = app.alloc(10).unwrap();
+    name.copy_from_slice(b"full_queue");
+    let own_id = app.allow(MAILBOX_DRIVER_NUM, 2, Some(&mut name[..])) as usize;
+    let tx = app.alloc(MAX_MESSAGE_LEN).unwrap();
+    app.allow(MAILBOX_DRIVER_NUM, 1, Some(&mut tx[..]));
+
+    // Fill the task queue, so the first message can't be delivered yet. It
+    // is delivered once the events have made room, without using the
+    // mailbox again.
+    app.subscribe(EVENTS_DRIVER_NUM, 0, Some(held_event), 0);
+    app.command(EVENTS_DRIVER_NUM, 1, 2, 0);
+    tx[0] = 1;
+    app.command(MAILBOX_DRIVER_NUM, 2, own_id, 1);
+    app.yieldk_for(|| HELD_RECEIVED.load(Ordering::SeqCst) == 1);
+    HELD_MESSAGES[0].store(rx[0] as usize, Ordering::SeqCst);
+    HELD_EVENTS_BEFORE_MESSAGE.store(HELD_EVENTS.load(Ordering::SeqCst), Ordering::SeqCst);
+    app.command(MAILBOX_DRIVER_NUM, 3, 0, 0);
+
+    tx[0] = 2;
+    app.command(MAILBOX_DRIVER_NUM, 2, own_id, 1);
+    app.yieldk_for(|| HELD_RECEIVED.load(Ordering::SeqCst) == 2);
+    HELD_MESSAGES[1].store(rx[0] as usize, Ordering::SeqCst);
+    app.command(MAILBOX_DRIVER_NUM, 3, 0, 0);
+}
+
+#[test]
+fn mailbox_delivers_held_message_when_task_queue_drains() {
+    let board =
+        Board::new(&[AppImage::new("full_queue", full_queue_receiver_main).task_queue(2, false)]);
+    assert!(board.run_until(1000, || HELD_MESSAGES[1].load(Ordering::SeqCst) != 0));
+    assert_eq!(HELD_MESSAGES[0].load(Ordering::SeqCst), 1);
+    assert_eq!(HELD_MESSAGES[1].load(Ordering::SeqCst), 2);
+    // The message waited behind the callbacks that filled the queue.
+    assert_eq!(HELD_EVENTS_BEFORE_MESSAGE.load(Ordering::SeqCst), 2);
+}
+
+static FLASH_SUM: AtomicUsize = AtomicUsize::new(0);
+static OUTSIDE_FLASH_RESULT: AtomicUsize = AtomicUsize::new(0);
+
+fn read_only_main(app: &App) {
+    let flash = unsafe { std::slice::from_raw_parts(app.flash_start() as *const u8, 8) };
+    let sum = app.allow_read_only(COUNTER_DRIVER_NUM, 0, Some(flash));
+    FLASH_SUM.store(sum as usize, Ordering::SeqCst);
+
+    // The kernel refuses buffers that are neither in the process's memory nor
+    // in its flash.
+    let outside = [1u8; 8];
+    let rval = app.allow_read_only(COUNTER_DRIVER_NUM, 0, Some(&outside));
+    OUTSIDE_FLASH_RESULT.store(rval as usize, Ordering::SeqCst);
+}
+
+#[test]
+fn read_only_allow_accepts_process_flash() {
+    let board = Board::new(&[AppImage::new("read_only", read_only_main)]);
+    assert!(board.run_until(100, || OUTSIDE_FLASH_RESULT.load(Ordering::SeqCst) != 0));
+
+    let flash_start = board.process(0).flash_non_protected_start();
+    let flash = unsafe { std::slice::from_raw_parts(flash_start, 8) };
+    let expected: usize = flash.iter().map(|&byte| byte as usize).sum();
+    assert_eq!(FLASH_SUM.load(Ordering::SeqCst), expected);
+    // EINVAL
+    assert_eq!(OUTSIDE_FLASH_RESULT.load(Ordering::SeqCst) as isize, -6);
+}
+
+static TRACED_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+fn traced_main(app: &App) {
+    for _ in 0..5 {
+        let count = app.command(COUNTER_DRIVER_NUM, 1, 0, 0);
+        TRACED_COUNT.store(count as usize, Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn syscall_trace_keeps_most_recent_calls() {
+    let board = Board::with_syscall_trace(&[AppImage::new("traced", traced_main)], 3);
+    let process = board.process(0);
+    assert!(board.run_until(100, || { process.get_state() == State::Yielded }));
+
+    // Once main returns the process yields forever, so the trace ends with
+    // the last two commands and a yield.
+    let mut records = Vec::new();
+    process.debug_syscall_trace(&mut |record| records.push(*record));
+    assert_eq!(records.len(), 3);
+    for (record, count) in records[..2].iter().zip(4..) {
+        assert_eq!(record.syscall.driver_number(), Some(COUNTER_DRIVER_NUM));
+        assert_eq!(record.return_value, Some(count));
+    }
+    assert_eq!(records[2].syscall, kernel::syscall::Syscall::YIELD);
+    assert!(records[0].cpu_time_us <= records[2].cpu_time_us);
+    assert!(process.debug_syscall_count() >= 5);
+}
+
+static mut TYPED_RESULTS: [[usize; 4]; 5] = [[0; 4]; 5];
+static TYPED_DONE: AtomicUsize = AtomicUsize::new(0);
+
+fn typed_main(app: &App) {
+    let results = [
+        app.typed_command(COUNTER_DRIVER_NUM, 1, 0, 0),
+        app.typed_command(COUNTER_DRIVER_NUM, 2, 0, 0),
+        app.typed_command(COUNTER_DRIVER_NUM, 3, 0, 0),
+        app.typed_command(COUNTER_DRIVER_NUM, 9, 0, 0),
+        app.typed_command(0xdead, 0, 0, 0),
+    ];
+    unsafe {
+        TYPED_RESULTS = results;
+    }
+    TYPED_DONE.store(1, Ordering::SeqCst);
+}
+
+#[test]
+fn typed_command_returns_several_values() {
+    let board = Board::with_syscall_trace(&[AppImage::new("typed", typed_main)], 8);
+    assert!(board.run_until(100, || TYPED_DONE.load(Ordering::SeqCst) != 0));
+
+    let results = unsafe { TYPED_RESULTS };
+    // A plain command's result converted by default.
+    assert_eq!(results[0], [129, 1, 0, 0]);
+    // 64-bit values are split low word first.
+    assert_eq!(results[1], [131, 0x2345_6789, 1, 0]);
+    // Failures carry the same negative code a plain command returns.
+    assert_eq!(results[2], [1, -7isize as u32 as usize, 42, 0]);
+    assert_eq!(results[3], [0, -10isize as u32 as usize, 0, 0]);
+    assert_eq!(results[4], [0, -11isize as u32 as usize, 0, 0]);
+
+    let mut records = Vec::new();
+    board
+        .process(0)
+        .debug_syscall_trace(&mut |record| records.push(*record));
+    assert_eq!(records[0].return_value, Some(1));
+    assert_eq!(records[2].return_value, Some(-7));
+}
+
+static DROPPING_EVENTS: AtomicUsize = AtomicUsize::new(0);
+static DROPPING_LAST_EVENT: AtomicUsize = AtomicUsize::new(0);
+static COALESCING_EVENTS: AtomicUsize = AtomicUsize::new(0);
+static COALESCING_LAST_EVENT: AtomicUsize = AtomicUsize::new(0);
+const EVENTS_SCHEDULED: usize = 10;
+
+fn dropping_event(event: usize, _: usize, _: usize, _: usize) {
+    DROPPING_EVENTS.fetch_add(1, Ordering::SeqCst);
+    DROPPING_LAST_EVENT.store(event, Ordering::SeqCst);
+}
+
+fn dropping_main(app: &App) {
+    app.subscribe(EVENTS_DRIVER_NUM, 0, Some(dropping_event), 0);
+    app.command(EVENTS_DRIVER_NUM, 1, EVENTS_SCHEDULED, 0);
+}
+
+fn coalescing_event(event: usize, _: usize, _: usize, _: usize) {
+    COALESCING_EVENTS.fetch_add(1, Ordering::SeqCst);
+    COALESCING_LAST_EVENT.store(event, Ordering::SeqCst);
+}
+
+fn coalescing_main(app: &App) {
+    app.subscribe(EVENTS_DRIVER_NUM, 0, Some(coalescing_event), 0);
+    app.command(EVENTS_DRIVER_NUM, 1, EVENTS_SCHEDULED, 0);
+}
+
+#[test]
+fn full_task_queue_drops_or_coalesces_callbacks() {
+    let board = Board::new(&[
+        AppImage::new("dropping", dropping_main).task_queue(4, false),
+        AppImage::new("coalescing", coalescing_main).task_queue(4, true),
+    ]);
+    assert!(board.run_until(100, || {
+        DROPPING_EVENTS.load(Ordering::SeqCst) == 4 && COALESCING_EVENTS.load(Ordering::SeqCst) == 4
+    }));
+    board.run_until(10, || false);
+
+    // Without coalescing the queue keeps the first events and drops the
+    // rest; with it, the most recent events replace the oldest.
+    assert_eq!(DROPPING_EVENTS.load(Ordering::SeqCst), 4);
+    assert_eq!(DROPPING_LAST_EVENT.load(Ordering::SeqCst), 3);
+    assert_eq!(
+        board.process(0).debug_dropped_callback_count(),
+        EVENTS_SCHEDULED - 4
+    );
+    assert_eq!(COALESCING_EVENTS.load(Ordering::SeqCst), 4);
+    assert_eq!(
+        COALESCING_LAST_EVENT.load(Ordering::SeqCst),
+        EVENTS_SCHEDULED - 1
+    );
+    assert_eq!(board.process(1).debug_dropped_callback_count(), 0);
+}
+
+static DEEP_QUEUE_EVENTS: AtomicUsize = AtomicUsize::new(0);
+const DEEP_QUEUE_EVENTS_SCHEDULED: usize = 200;
+
+fn deep_queue_event(_: usize, _: usize, _: usize, _: usize) {
+    DEEP_QUEUE_EVENTS.fetch_add(1, Ordering::SeqCst);
+}
+
+fn deep_queue_main(app: &App) {
+    app.subscribe(EVENTS_DRIVER_NUM, 0, Some(deep_queue_event), 0);
+    app.command(EVENTS_DRIVER_NUM, 1, DEEP_QUEUE_EVENTS_SCHEDULED, 0);
+}
+
+#[test]
+fn oversized_task_queue_request_is_capped() {
+    let board = Board::new(&[
+        AppImage::new("deep_queue", deep_queue_main).task_queue(u32::max_value(), false)
+    ]);
+    let process = board.process(0);
+    assert!(
+        board.run_until(1000, || process.get_state() == State::Yielded
+            && DEEP_QUEUE_EVENTS.load(Ordering::SeqCst) > 0)
+    );
+    board.run_until(10, || false);
+
+    // The queue was only as deep as the kernel allows.
+    let delivered = DEEP_QUEUE_EVENTS.load(Ordering::SeqCst);
+    assert!(delivered < DEEP_QUEUE_EVENTS_SCHEDULED);
+    assert_eq!(
+        process.debug_dropped_callback_count(),
+        DEEP_QUEUE_EVENTS_SCHEDULED - delivered
+    );
+}
+
+const HEARTBEAT_PERIOD_MS: usize = 20;
+const HEALTHY_BEATS: usize = 5;
+static HEALTHY_BEATS_SENT: AtomicUsize = AtomicUsize::new(0);
+static HEALTHY_WOKE: AtomicUsize = AtomicUsize::new(0);
+
+fn healthy_woke(_: usize, _: usize, _: usize, _: usize) {
+    HEALTHY_WOKE.fetch_add(1, Ordering::SeqCst);
+}
+
+fn healthy_main(app: &App) {
+    let alarm = capsules::alarm::DRIVER_NUM;
+    let heartbeat = kernel::heartbeat::DRIVER_NUM;
+    app.subscribe(alarm, 0, Some(healthy_woke), 0);
+    app.command(heartbeat, 1, HEARTBEAT_PERIOD_MS, 0);
+    for beat in 1..=HEALTHY_BEATS {
+        // Sleep for half the heartbeat period between heartbeats.
+        let now = app.command(alarm, 2, 0, 0) as usize;
+        app.command(alarm, 4, now + HEARTBEAT_PERIOD_MS * 1000 / 2, 0);
+        app.yieldk_for(|| HEALTHY_WOKE.load(Ordering::SeqCst) == beat);
+        app.command(heartbeat, 2, 0, 0);
+        HEALTHY_BEATS_SENT.store(beat, Ordering::SeqCst);
+    }
+    app.command(heartbeat, 3, 0, 0);
+}
+
+fn hung_main(app: &App) {
+    let heartbeat = kernel::heartbeat::DRIVER_NUM;
+    app.command(heartbeat, 1, HEARTBEAT_PERIOD_MS, 0);
+    app.command(heartbeat, 2, 0, 0);
+    // Waits for a callback that never comes.
+    app.yieldk_for(|| false);
+}
+
+#[test]
+fn heartbeat_faults_hung_process_and_watchdog_survives_sleep() {
+    let board = Board::new(&[
+        AppImage::new("healthy", healthy_main),
+        AppImage::new("hung", hung_main),
+    ]);
+    // The watchdog period is shorter than the processes sleep for, so it only
+    // survives if the kernel suspends it while the chip sleeps.
+    board.chip.watchdog.start(2);
+
+    let hung = board.process(1);
+    assert!(board.run_until(1000, || { hung.get_state() == State::StoppedFaulted }));
+    assert!(board.chip.clock().now_us() >= HEARTBEAT_PERIOD_MS as u64 * 1000);
+    assert!(board.run_until(1000, || {
+        HEALTHY_BEATS_SENT.load(Ordering::SeqCst) == HEALTHY_BEATS
+    }));
+    board.run_until(10, || false);
+
+    // The healthy process slept past its heartbeat period in total, but sent
+    // a heartbeat in time every time.
+    assert!(board.chip.clock().now_us() > (HEALTHY_BEATS * HEARTBEAT_PERIOD_MS) as u64 * 1000 / 2);
+    assert_ne!(board.process(0).get_state(), State::StoppedFaulted);
+    assert!(board.chip.watchdog.tickles() > 0);
+    assert!(!board.chip.watchdog.is_suspended());
+    assert!(!board.chip.watchdog.expired());
+}
+
+fn busy_main(app: &App) {
+    loop {
+        app.compute(3_000);
+        app.command(COUNTER_DRIVER_NUM, 1, 0, 0);
+    }
+}
+
+#[test]
+fn heartbeat_faults_every_process_that_misses_the_same_deadline() {
+    // The busy process keeps the kernel from handling the heartbeat alarm
+    // until both hung processes have missed their deadlines.
+    let board = Board::new(&[
+        AppImage::new("busy", busy_main),
+        AppImage::new("hung-a", hung_main),
+        AppImage::new("hung-b", hung_main),
+    ]);
+
+    let (first, second) = (board.process(1), board.process(2));
+    assert!(board.run_until(1000, || {
+        first.get_state() == State::StoppedFaulted && second.get_state() == State::StoppedFaulted
+    }));
+}
+
+static UART_ACTIVE: PowerConstraint = PowerConstraint::new(Constraint::UartActive);
+static NAPS_TAKEN: AtomicUsize = AtomicUsize::new(0);
+
+fn napper_woke(_: usize, _: usize, _: usize, _: usize) {
+    NAPS_TAKEN.fetch_add(1, Ordering::SeqCst);
+}
+
+fn napper_main(app: &App) {
+    let alarm = capsules::alarm::DRIVER_NUM;
+    app.subscribe(alarm, 0, Some(napper_woke), 0);
+    for (nap, nap_us) in [10_000, 20_000].iter().enumerate() {
+        let now = app.command(alarm, 2, 0, 0) as usize;
+        app.command(alarm, 4, now + nap_us, 0);
+        app.yieldk_for(|| NAPS_TAKEN.load(Ordering::SeqCst) > nap);
+    }
+}
+
+#[test]
+fn sleep_state_follows_power_constraints() {
+    let board = Board::new(&[AppImage::new("napper", napper_main)]);
+    let info = KernelInfo::new(board.kernel);
+
+    // While a driver needs the UART the chip only sleeps in the light state.
+    UART_ACTIVE.require();
+    assert!(board.run_until(100, || NAPS_TAKEN.load(Ordering::SeqCst) == 1));
+    UART_ACTIVE.release();
+    assert!(board.run_until(100, || NAPS_TAKEN.load(Ordering::SeqCst) == 2));
+
+    assert_eq!(info.number_sleep_states(&Capability), 2);
+    let (name, idle) = info.sleep_state_residency(0, &Capability).unwrap();
+    assert_eq!(name, "idle");
+    assert!(idle.entries > 0);
+    assert!(idle.time_us >= 9_000 && idle.time_us <= 10_000);
+    let (name, deep) = info.sleep_state_residency(1, &Capability).unwrap();
+    assert_eq!(name, "deep");
+    assert!(deep.entries > 0);
+    assert!(deep.time_us >= 19_000 && deep.time_us <= 20_000);
+    assert!(info.sleep_state_residency(2, &Capability).is_none());
+}
+
+#[test]
+fn schedulers_sleep_without_process_slots() {
+    let processes: &'static mut [Option<&'static dyn ProcessType>] = Box::leak(Box::new([]));
+    let kernel: &'static Kernel = Box::leak(Box::new(Kernel::new(processes)));
+    let nodes: &'static [MLFQProcessNode] = Box::leak(Box::new([]));
+
+    assert_eq!(
+        RoundRobinSched::new().next(kernel),
+        SchedulingDecision::TrySleep
+    );
+    assert_eq!(
+        CooperativeSched::new().next(kernel),
+        SchedulingDecision::TrySleep
+    );
+    assert_eq!(
+        MLFQSched::new(nodes).next(kernel),
+        SchedulingDecision::TrySleep
+    );
+}
+
+#[test]
+fn mlfq_puts_restarted_process_in_top_queue() {
+    let board = Board::new(&[AppImage::new("busy", busy_main)]);
+    let nodes: &'static [MLFQProcessNode] = Box::leak(Box::new([MLFQProcessNode::new()]));
+    let scheduler = MLFQSched::new(nodes);
+    let process = board.process(0);
+    let timeslice = || match scheduler.next(board.kernel) {
+        SchedulingDecision::RunProcess((appid, timeslice)) => {
+            assert_eq!(appid, process.appid());
+            timeslice
+        }
+        SchedulingDecision::TrySleep => panic!("process not scheduled"),
+    };
+
+    // Using up its timeslice moves the process down a queue.
+    let top = timeslice();
+    scheduler.result(StoppedExecutingReason::TimesliceExpired, top);
+    assert!(timeslice() > top);
+
+    // After a restart, the process in the slot starts over in the top queue.
+    let old = process.appid();
+    process.set_fault_state();
+    assert_eq!(
+        board.kernel.restart_process(old, &Capability),
+        ReturnCode::SUCCESS
+    );
+    assert_eq!(timeslice(), top);
+}
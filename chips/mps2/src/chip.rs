@@ -0,0 +1,85 @@
+//! Chip trait setup.
+
+use cortexm3;
+use kernel::Chip;
+
+use crate::nvic;
+use crate::timer;
+use crate::uart;
+
+pub struct Mps2 {
+    mpu: cortexm3::mpu::MPU,
+    userspace_kernel_boundary: cortexm3::syscall::SysCall,
+    systick: cortexm3::systick::SysTick,
+}
+
+impl Mps2 {
+    pub unsafe fn new() -> Mps2 {
+        Mps2 {
+            mpu: cortexm3::mpu::MPU::new(),
+            userspace_kernel_boundary: cortexm3::syscall::SysCall::new(),
+            // The SysTick runs from the system clock. If the calibration
+            // register is set, as it is in some versions of QEMU, the
+            // SysTick driver uses that instead.
+            systick: cortexm3::systick::SysTick::new_with_calibration(crate::SYSCLK_HZ),
+        }
+    }
+}
+
+impl Chip for Mps2 {
+    type MPU = cortexm3::mpu::MPU;
+    type UserspaceKernelBoundary = cortexm3::syscall::SysCall;
+    type SysTick = cortexm3::systick::SysTick;
+
+    fn service_pending_interrupts(&self) {
+        unsafe {
+            while let Some(interrupt) = cortexm3::nvic::next_pending() {
+                match interrupt {
+                    nvic::UART0_RX | nvic::UART0_TX => uart::UART0.handle_interrupt(),
+                    nvic::UART1_RX | nvic::UART1_TX => uart::UART1.handle_interrupt(),
+                    nvic::UART2_RX | nvic::UART2_TX => uart::UART2.handle_interrupt(),
+
+                    nvic::TIMER1 => timer::ALARM.handle_interrupt(),
+
+                    _ => {
+                        panic!("unhandled interrupt {}", interrupt);
+                    }
+                }
+
+                let n = cortexm3::nvic::Nvic::new(interrupt);
+                n.clear_pending();
+                n.enable();
+            }
+        }
+    }
+
+    fn has_pending_interrupts(&self) -> bool {
+        unsafe { cortexm3::nvic::has_pending() }
+    }
+
+    fn mpu(&self) -> &cortexm3::mpu::MPU {
+        &self.mpu
+    }
+
+    fn systick(&self) -> &cortexm3::systick::SysTick {
+        &self.systick
+    }
+
+    fn userspace_kernel_boundary(&self) -> &cortexm3::syscall::SysCall {
+        &self.userspace_kernel_boundary
+    }
+
+    fn sleep(&self) {
+        unsafe {
+            cortexm3::scb::unset_sleepdeep();
+            cortexm3::support::wfi();
+        }
+    }
+
+    unsafe fn atomic<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        cortexm3::support::atomic(f)
+    }
+}
@@ -0,0 +1,123 @@
+//! Peripheral implementations for the ARM MPS2 with the AN385 FPGA image.
+//!
+//! This is the `mps2-an385` machine in QEMU, a Cortex-M3 with peripherals
+//! from the Cortex-M System Design Kit.
+//!
+//! AN385: <http://infocenter.arm.com/help/topic/com.arm.doc.dai0385d/index.html>
+
+#![crate_name = "mps2"]
+#![crate_type = "rlib"]
+#![feature(asm, const_fn, in_band_lifetimes)]
+#![no_std]
+
+pub mod chip;
+pub mod nvic;
+pub mod timer;
+pub mod uart;
+
+use cortexm3::{generic_isr, hard_fault_handler, svc_handler, systick_handler};
+
+/// Frequency of the system clock, which drives the core and the peripherals.
+pub const SYSCLK_HZ: u32 = 25_000_000;
+
+unsafe extern "C" fn unhandled_interrupt() {
+    let mut interrupt_number: u32;
+
+    // IPSR[8:0] holds the currently active interrupt
+    asm!(
+    "mrs    r0, ipsr                    "
+    : "={r0}"(interrupt_number)
+    :
+    : "r0"
+    :
+    );
+
+    interrupt_number = interrupt_number & 0x1ff;
+
+    panic!("Unhandled Interrupt. ISR {} is active.", interrupt_number);
+}
+
+extern "C" {
+    // _estack is not really a function, but it makes the types work
+    // You should never actually invoke it!!
+    fn _estack();
+
+    // Defined by platform
+    fn reset_handler();
+}
+
+#[link_section = ".vectors"]
+// used Ensures that the symbol is kept until the final binary
+#[used]
+pub static BASE_VECTORS: [unsafe extern "C" fn(); 16] = [
+    _estack,
+    reset_handler,
+    unhandled_interrupt, // NMI
+    hard_fault_handler,  // Hard Fault
+    unhandled_interrupt, // MemManage
+    unhandled_interrupt, // BusFault
+    unhandled_interrupt, // UsageFault
+    unhandled_interrupt,
+    unhandled_interrupt,
+    unhandled_interrupt,
+    unhandled_interrupt,
+    svc_handler,         // SVC
+    unhandled_interrupt, // DebugMon
+    unhandled_interrupt,
+    unhandled_interrupt, // PendSV
+    systick_handler,     // SysTick
+];
+
+// The AN385 image has 32 interrupts, see table 4-2 of the application note.
+#[link_section = ".irqs"]
+#[used] // Ensures that the symbol is kept until the final binary
+pub static IRQS: [unsafe extern "C" fn(); 32] = [
+    generic_isr, // UART0 RX (0)
+    generic_isr, // UART0 TX (1)
+    generic_isr, // UART1 RX (2)
+    generic_isr, // UART1 TX (3)
+    generic_isr, // UART2 RX (4)
+    generic_isr, // UART2 TX (5)
+    generic_isr, // GPIO0 combined (6)
+    generic_isr, // GPIO1 combined (7)
+    generic_isr, // TIMER0 (8)
+    generic_isr, // TIMER1 (9)
+    generic_isr, // DUALTIMER (10)
+    generic_isr, // SPI0 (11)
+    generic_isr, // UART0, 1 and 2 overflow (12)
+    generic_isr, // ETHERNET (13)
+    generic_isr, // I2S (14)
+    generic_isr, // TOUCHSCREEN (15)
+    generic_isr, // GPIO2 (16)
+    generic_isr, // GPIO3 (17)
+    generic_isr, // UART3 RX (18)
+    generic_isr, // UART3 TX (19)
+    generic_isr, // UART4 RX (20)
+    generic_isr, // UART4 TX (21)
+    generic_isr, // SPI2 (22)
+    generic_isr, // SPI3 and SPI4 (23)
+    generic_isr, // GPIO0 pin 0 (24)
+    generic_isr, // GPIO0 pin 1 (25)
+    generic_isr, // GPIO0 pin 2 (26)
+    generic_isr, // GPIO0 pin 3 (27)
+    generic_isr, // GPIO0 pin 4 (28)
+    generic_isr, // GPIO0 pin 5 (29)
+    generic_isr, // GPIO0 pin 6 (30)
+    generic_isr, // GPIO0 pin 7 (31)
+];
+
+extern "C" {
+    static mut _szero: u32;
+    static mut _ezero: u32;
+    static mut _etext: u32;
+    static mut _srelocate: u32;
+    static mut _erelocate: u32;
+}
+
+pub unsafe fn init() {
+    tock_rt0::init_data(&mut _etext, &mut _srelocate, &mut _erelocate);
+    tock_rt0::zero_bss(&mut _szero, &mut _ezero);
+
+    cortexm3::nvic::disable_all();
+    cortexm3::nvic::clear_all_pending();
+}
@@ -0,0 +1,24 @@
+//! Named constants for NVIC ids
+
+pub const UART0_RX: u32 = 0;
+pub const UART0_TX: u32 = 1;
+pub const UART1_RX: u32 = 2;
+pub const UART1_TX: u32 = 3;
+pub const UART2_RX: u32 = 4;
+pub const UART2_TX: u32 = 5;
+pub const GPIO0: u32 = 6;
+pub const GPIO1: u32 = 7;
+pub const TIMER0: u32 = 8;
+pub const TIMER1: u32 = 9;
+pub const DUALTIMER: u32 = 10;
+pub const SPI0: u32 = 11;
+pub const UART_OVERFLOW: u32 = 12;
+pub const ETHERNET: u32 = 13;
+pub const I2S: u32 = 14;
+pub const TOUCHSCREEN: u32 = 15;
+pub const GPIO2: u32 = 16;
+pub const GPIO3: u32 = 17;
+pub const UART3_RX: u32 = 18;
+pub const UART3_TX: u32 = 19;
+pub const UART4_RX: u32 = 20;
+pub const UART4_TX: u32 = 21;
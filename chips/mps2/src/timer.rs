@@ -0,0 +1,134 @@
+//! Alarm built from two CMSDK APB timers.
+//!
+//! The CMSDK timers are 32-bit down counters that interrupt when they reach
+//! zero, and have no compare register. TIMER0 free-runs to provide the time,
+//! and TIMER1 is loaded with the number of tics until the alarm.
+//!
+//! - ARM Cortex-M System Design Kit Technical Reference Manual, section 4.4
+
+use core::cell::Cell;
+use kernel::common::cells::OptionalCell;
+use kernel::common::registers::{register_bitfields, ReadWrite};
+use kernel::common::StaticRef;
+use kernel::hil::time::{self, Alarm, Frequency, Time};
+
+#[repr(C)]
+struct TimerRegisters {
+    ctrl: ReadWrite<u32, Control::Register>,
+    value: ReadWrite<u32>,
+    reload: ReadWrite<u32>,
+    /// Reads the interrupt status, writing a one clears it.
+    intstatus: ReadWrite<u32, Interrupt::Register>,
+}
+
+register_bitfields![
+    u32,
+    Control [
+        ENABLE OFFSET(0) NUMBITS(1) [],
+        EXTERNAL_ENABLE OFFSET(1) NUMBITS(1) [],
+        EXTERNAL_CLOCK OFFSET(2) NUMBITS(1) [],
+        INTERRUPT_ENABLE OFFSET(3) NUMBITS(1) []
+    ],
+    Interrupt [
+        INTERRUPT OFFSET(0) NUMBITS(1) []
+    ]
+];
+
+const TIMER0_BASE: StaticRef<TimerRegisters> =
+    unsafe { StaticRef::new(0x4000_0000 as *const TimerRegisters) };
+const TIMER1_BASE: StaticRef<TimerRegisters> =
+    unsafe { StaticRef::new(0x4000_1000 as *const TimerRegisters) };
+
+pub static mut ALARM: TimerAlarm<'static> = TimerAlarm::new(TIMER0_BASE, TIMER1_BASE);
+
+/// 25MHz `Frequency`, the system clock.
+#[derive(Debug)]
+pub struct Freq25MHz;
+impl Frequency for Freq25MHz {
+    fn frequency() -> u32 {
+        crate::SYSCLK_HZ
+    }
+}
+
+pub struct TimerAlarm<'a> {
+    counter: StaticRef<TimerRegisters>,
+    alarm: StaticRef<TimerRegisters>,
+    client: OptionalCell<&'a dyn time::AlarmClient>,
+    tics: Cell<u32>,
+}
+
+impl TimerAlarm<'a> {
+    const fn new(
+        counter: StaticRef<TimerRegisters>,
+        alarm: StaticRef<TimerRegisters>,
+    ) -> TimerAlarm<'a> {
+        TimerAlarm {
+            counter,
+            alarm,
+            client: OptionalCell::empty(),
+            tics: Cell::new(0),
+        }
+    }
+
+    /// Start the counter. This must be called before the alarm is used.
+    pub fn start(&self) {
+        self.counter.ctrl.set(0);
+        self.counter.reload.set(core::u32::MAX);
+        self.counter.value.set(core::u32::MAX);
+        self.counter.ctrl.write(Control::ENABLE::SET);
+    }
+
+    pub fn handle_interrupt(&self) {
+        self.disable();
+        self.client.map(|client| client.fired());
+    }
+}
+
+impl Time for TimerAlarm<'a> {
+    type Frequency = Freq25MHz;
+
+    fn now(&self) -> u32 {
+        // The counter counts down, so invert it.
+        !self.counter.value.get()
+    }
+
+    fn max_tics(&self) -> u32 {
+        core::u32::MAX
+    }
+}
+
+impl Alarm<'a> for TimerAlarm<'a> {
+    fn set_client(&self, client: &'a dyn time::AlarmClient) {
+        self.client.set(client);
+    }
+
+    fn set_alarm(&self, tics: u32) {
+        self.tics.set(tics);
+
+        // Like a compare register, an alarm for a time that has just passed
+        // fires when the counter next wraps around to it. The timer only
+        // interrupts on its way to zero, so it needs at least one tic.
+        let remaining = core::cmp::max(tics.wrapping_sub(self.now()), 1);
+
+        self.alarm.ctrl.set(0);
+        self.alarm.intstatus.write(Interrupt::INTERRUPT::SET);
+        self.alarm.reload.set(core::u32::MAX);
+        self.alarm.value.set(remaining);
+        self.alarm
+            .ctrl
+            .write(Control::ENABLE::SET + Control::INTERRUPT_ENABLE::SET);
+    }
+
+    fn get_alarm(&self) -> u32 {
+        self.tics.get()
+    }
+
+    fn disable(&self) {
+        self.alarm.ctrl.set(0);
+        self.alarm.intstatus.write(Interrupt::INTERRUPT::SET);
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.alarm.ctrl.is_set(Control::ENABLE)
+    }
+}
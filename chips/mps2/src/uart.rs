@@ -0,0 +1,306 @@
+//! CMSDK APB UART.
+//!
+//! The UART has one byte buffers for transmit and receive, and separate
+//! interrupt lines for each direction. Both are routed to `handle_interrupt`.
+//!
+//! - ARM Cortex-M System Design Kit Technical Reference Manual, section 4.3
+
+use core::cell::Cell;
+use kernel::common::cells::{MapCell, OptionalCell};
+use kernel::common::registers::{register_bitfields, ReadWrite};
+use kernel::common::StaticRef;
+use kernel::hil::uart;
+use kernel::ReturnCode;
+
+#[repr(C)]
+struct UartRegisters {
+    data: ReadWrite<u32>,
+    state: ReadWrite<u32, State::Register>,
+    ctrl: ReadWrite<u32, Control::Register>,
+    /// Reads the interrupt status, writing a one clears that interrupt.
+    intstatus: ReadWrite<u32, Interrupts::Register>,
+    bauddiv: ReadWrite<u32>,
+}
+
+register_bitfields![
+    u32,
+    State [
+        TX_FULL OFFSET(0) NUMBITS(1) [],
+        RX_FULL OFFSET(1) NUMBITS(1) [],
+        TX_OVERRUN OFFSET(2) NUMBITS(1) [],
+        RX_OVERRUN OFFSET(3) NUMBITS(1) []
+    ],
+    Control [
+        TX_ENABLE OFFSET(0) NUMBITS(1) [],
+        RX_ENABLE OFFSET(1) NUMBITS(1) [],
+        TX_INT_ENABLE OFFSET(2) NUMBITS(1) [],
+        RX_INT_ENABLE OFFSET(3) NUMBITS(1) [],
+        TX_OVERRUN_INT_ENABLE OFFSET(4) NUMBITS(1) [],
+        RX_OVERRUN_INT_ENABLE OFFSET(5) NUMBITS(1) [],
+        HIGH_SPEED_TEST OFFSET(6) NUMBITS(1) []
+    ],
+    Interrupts [
+        TX OFFSET(0) NUMBITS(1) [],
+        RX OFFSET(1) NUMBITS(1) [],
+        TX_OVERRUN OFFSET(2) NUMBITS(1) [],
+        RX_OVERRUN OFFSET(3) NUMBITS(1) []
+    ]
+];
+
+const UART0_BASE: StaticRef<UartRegisters> =
+    unsafe { StaticRef::new(0x4000_4000 as *const UartRegisters) };
+const UART1_BASE: StaticRef<UartRegisters> =
+    unsafe { StaticRef::new(0x4000_5000 as *const UartRegisters) };
+const UART2_BASE: StaticRef<UartRegisters> =
+    unsafe { StaticRef::new(0x4000_6000 as *const UartRegisters) };
+
+pub static mut UART0: Uart = Uart::new(UART0_BASE);
+pub static mut UART1: Uart = Uart::new(UART1_BASE);
+pub static mut UART2: Uart = Uart::new(UART2_BASE);
+
+/// Stores an ongoing transaction
+struct Transaction {
+    /// The buffer containing the bytes to transmit or receive, as it should be
+    /// returned to the client
+    buffer: &'static mut [u8],
+    /// The total amount to transfer
+    length: usize,
+    /// The index of the next byte to transfer
+    index: usize,
+}
+
+pub struct Uart<'a> {
+    registers: StaticRef<UartRegisters>,
+    tx_client: OptionalCell<&'a dyn uart::TransmitClient>,
+    rx_client: OptionalCell<&'a dyn uart::ReceiveClient>,
+    tx: MapCell<Transaction>,
+    rx: MapCell<Transaction>,
+    transmitting_word: Cell<bool>,
+    receiving_word: Cell<bool>,
+}
+
+impl Uart<'a> {
+    const fn new(registers: StaticRef<UartRegisters>) -> Uart<'a> {
+        Uart {
+            registers,
+            tx_client: OptionalCell::empty(),
+            rx_client: OptionalCell::empty(),
+            tx: MapCell::empty(),
+            rx: MapCell::empty(),
+            transmitting_word: Cell::new(false),
+            receiving_word: Cell::new(false),
+        }
+    }
+
+    pub fn handle_interrupt(&self) {
+        let status = self.registers.intstatus.extract();
+        self.registers.intstatus.set(status.get());
+
+        if status.is_set(Interrupts::RX) || self.registers.state.is_set(State::RX_FULL) {
+            self.handle_receive();
+        }
+        if status.is_set(Interrupts::TX) {
+            self.handle_transmit();
+        }
+    }
+
+    fn handle_receive(&self) {
+        // Reading the data register empties the receive buffer, so always
+        // read it even if no one wants the byte.
+        let byte = self.registers.data.get();
+
+        if self.receiving_word.get() {
+            self.receiving_word.set(false);
+            self.rx_client.map(|client| {
+                client.received_word(byte, ReturnCode::SUCCESS, uart::Error::None);
+            });
+        } else {
+            self.rx.take().map(|mut rx| {
+                rx.buffer[rx.index] = byte as u8;
+                rx.index += 1;
+
+                if rx.index == rx.length {
+                    self.rx_client.map(move |client| {
+                        client.received_buffer(
+                            rx.buffer,
+                            rx.index,
+                            ReturnCode::SUCCESS,
+                            uart::Error::None,
+                        );
+                    });
+                } else {
+                    self.rx.put(rx);
+                }
+            });
+        }
+    }
+
+    fn handle_transmit(&self) {
+        if self.transmitting_word.get() {
+            self.transmitting_word.set(false);
+            self.tx_client.map(|client| {
+                client.transmitted_word(ReturnCode::SUCCESS);
+            });
+        } else {
+            self.tx.take().map(|mut tx| {
+                if tx.index == tx.length {
+                    self.tx_client.map(move |client| {
+                        client.transmitted_buffer(tx.buffer, tx.length, ReturnCode::SUCCESS);
+                    });
+                } else {
+                    // The interrupt fires when the byte has been sent, which
+                    // brings us back here for the next one.
+                    self.write(tx.buffer[tx.index]);
+                    tx.index += 1;
+                    self.tx.put(tx);
+                }
+            });
+        }
+    }
+
+    fn write(&self, byte: u8) {
+        self.registers.data.set(byte as u32);
+    }
+
+    /// Busy-wait until the transmit buffer is free, then send `byte`. This is
+    /// for panics, where interrupts are not serviced.
+    pub fn send_byte(&self, byte: u8) {
+        while self.registers.state.is_set(State::TX_FULL) {}
+        self.write(byte);
+    }
+}
+
+impl uart::Uart<'a> for Uart<'a> {}
+impl uart::UartData<'a> for Uart<'a> {}
+
+impl uart::Configure for Uart<'a> {
+    fn configure(&self, params: uart::Parameters) -> ReturnCode {
+        // The CMSDK UART only supports 8N1.
+        if params.stop_bits != uart::StopBits::One
+            || params.parity != uart::Parity::None
+            || params.hw_flow_control
+            || params.width != uart::Width::Eight
+        {
+            return ReturnCode::ENOSUPPORT;
+        }
+
+        // The divider must be at least 16.
+        let divider = crate::SYSCLK_HZ / params.baud_rate;
+        if divider < 16 {
+            return ReturnCode::EINVAL;
+        }
+
+        self.registers.ctrl.set(0);
+        self.registers.bauddiv.set(divider);
+        self.registers.ctrl.write(
+            Control::TX_ENABLE::SET
+                + Control::RX_ENABLE::SET
+                + Control::TX_INT_ENABLE::SET
+                + Control::RX_INT_ENABLE::SET,
+        );
+
+        ReturnCode::SUCCESS
+    }
+}
+
+impl uart::Transmit<'a> for Uart<'a> {
+    fn set_transmit_client(&self, client: &'a dyn uart::TransmitClient) {
+        self.tx_client.set(client);
+    }
+
+    fn transmit_buffer(
+        &self,
+        buffer: &'static mut [u8],
+        len: usize,
+    ) -> (ReturnCode, Option<&'static mut [u8]>) {
+        if len == 0 || len > buffer.len() {
+            (ReturnCode::ESIZE, Some(buffer))
+        } else if self.tx.is_some() || self.transmitting_word.get() {
+            (ReturnCode::EBUSY, Some(buffer))
+        } else {
+            self.write(buffer[0]);
+            self.tx.put(Transaction {
+                buffer: buffer,
+                length: len,
+                index: 1,
+            });
+            (ReturnCode::SUCCESS, None)
+        }
+    }
+
+    fn transmit_word(&self, word: u32) -> ReturnCode {
+        if self.tx.is_some() || self.transmitting_word.get() {
+            ReturnCode::EBUSY
+        } else {
+            self.transmitting_word.set(true);
+            self.write(word as u8);
+            ReturnCode::SUCCESS
+        }
+    }
+
+    fn transmit_abort(&self) -> ReturnCode {
+        if self.tx.is_some() || self.transmitting_word.get() {
+            // The byte in flight will still raise an interrupt, which makes
+            // the callback.
+            ReturnCode::FAIL
+        } else {
+            ReturnCode::SUCCESS
+        }
+    }
+}
+
+impl uart::Receive<'a> for Uart<'a> {
+    fn set_receive_client(&self, client: &'a dyn uart::ReceiveClient) {
+        self.rx_client.set(client);
+    }
+
+    fn receive_buffer(
+        &self,
+        buffer: &'static mut [u8],
+        len: usize,
+    ) -> (ReturnCode, Option<&'static mut [u8]>) {
+        if len == 0 || len > buffer.len() {
+            (ReturnCode::ESIZE, Some(buffer))
+        } else if self.rx.is_some() || self.receiving_word.get() {
+            (ReturnCode::EBUSY, Some(buffer))
+        } else {
+            self.rx.put(Transaction {
+                buffer: buffer,
+                length: len,
+                index: 0,
+            });
+            (ReturnCode::SUCCESS, None)
+        }
+    }
+
+    fn receive_word(&self) -> ReturnCode {
+        if self.rx.is_some() || self.receiving_word.get() {
+            ReturnCode::EBUSY
+        } else {
+            self.receiving_word.set(true);
+            ReturnCode::SUCCESS
+        }
+    }
+
+    fn receive_abort(&self) -> ReturnCode {
+        if self.receiving_word.get() {
+            self.receiving_word.set(false);
+            self.rx_client.map(|client| {
+                client.received_word(0, ReturnCode::ECANCEL, uart::Error::Aborted);
+            });
+            ReturnCode::EBUSY
+        } else if let Some(rx) = self.rx.take() {
+            self.rx_client.map(move |client| {
+                client.received_buffer(
+                    rx.buffer,
+                    rx.index,
+                    ReturnCode::ECANCEL,
+                    uart::Error::Aborted,
+                );
+            });
+            ReturnCode::EBUSY
+        } else {
+            ReturnCode::SUCCESS
+        }
+    }
+}
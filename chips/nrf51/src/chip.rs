@@ -0,0 +1,73 @@
+//! Chip trait setup.
+
+use cortexm0::{self, nvic};
+use kernel::debug;
+use nrf5x::peripheral_interrupts;
+
+use crate::uart;
+
+pub struct NRF51 {
+    userspace_kernel_boundary: cortexm0::syscall::SysCall,
+}
+
+impl NRF51 {
+    pub unsafe fn new() -> NRF51 {
+        NRF51 {
+            userspace_kernel_boundary: cortexm0::syscall::SysCall::new(),
+        }
+    }
+}
+
+impl kernel::Chip for NRF51 {
+    // The nRF51's Cortex-M0 has neither an MPU nor a SysTick.
+    type MPU = ();
+    type UserspaceKernelBoundary = cortexm0::syscall::SysCall;
+    type SysTick = ();
+
+    fn mpu(&self) -> &Self::MPU {
+        &()
+    }
+
+    fn systick(&self) -> &Self::SysTick {
+        &()
+    }
+
+    fn userspace_kernel_boundary(&self) -> &Self::UserspaceKernelBoundary {
+        &self.userspace_kernel_boundary
+    }
+
+    fn service_pending_interrupts(&self) {
+        unsafe {
+            while let Some(interrupt) = nvic::next_pending() {
+                match interrupt {
+                    peripheral_interrupts::GPIOTE => nrf5x::gpio::PORT.handle_interrupt(),
+                    peripheral_interrupts::RNG => nrf5x::trng::TRNG.handle_interrupt(),
+                    peripheral_interrupts::RTC1 => nrf5x::rtc::RTC.handle_interrupt(),
+                    peripheral_interrupts::TEMP => nrf5x::temperature::TEMP.handle_interrupt(),
+                    peripheral_interrupts::UART0 => uart::UART0.handle_interrupt(),
+                    _ => debug!("NvicIdx not supported by Tock"),
+                }
+                let n = nvic::Nvic::new(interrupt);
+                n.clear_pending();
+                n.enable();
+            }
+        }
+    }
+
+    fn has_pending_interrupts(&self) -> bool {
+        unsafe { nvic::has_pending() }
+    }
+
+    fn sleep(&self) {
+        unsafe {
+            cortexm0::support::wfi();
+        }
+    }
+
+    unsafe fn atomic<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        cortexm0::support::atomic(f)
+    }
+}
@@ -0,0 +1,94 @@
+//! Clock peripheral driver, nRF51
+//!
+//! Only the low frequency clock, which drives the RTC, is controlled here.
+//! The high frequency clock runs from the internal 16 MHz RC oscillator, the
+//! reset default, and is started by the chip whenever it is needed.
+//!
+//! LFCLK - Low Frequency Clock Source:
+//!
+//!     * 32.768 kHz RC oscillator (LFRC)
+//!     * 32.768 kHz crystal oscillator (LFXO)
+//!     * 32.768 kHz synthesized from HFCLK (LFSYNT)
+
+use kernel::common::registers::{register_bitfields, ReadOnly, ReadWrite, WriteOnly};
+use kernel::common::StaticRef;
+
+#[repr(C)]
+struct ClockRegisters {
+    /// TASKS_HFCLKSTART and TASKS_HFCLKSTOP
+    _reserved0: [u32; 2],
+    tasks_lfclkstart: WriteOnly<u32, Control::Register>,
+    tasks_lfclkstop: WriteOnly<u32, Control::Register>,
+    _reserved1: [u32; 60],
+    /// EVENTS_HFCLKSTARTED
+    _reserved2: u32,
+    events_lfclkstarted: ReadOnly<u32, Status::Register>,
+    _reserved3: [u32; 260],
+    lfclksrc: ReadWrite<u32, LfClkSrc::Register>,
+}
+
+register_bitfields! [u32,
+    Control [
+        ENABLE OFFSET(0) NUMBITS(1)
+    ],
+    Status [
+        READY OFFSET(0) NUMBITS(1)
+    ],
+    LfClkSrc [
+        SRC OFFSET(0) NUMBITS(2) [
+            RC = 0,
+            XTAL = 1,
+            SYNTH = 2
+        ]
+    ]
+];
+
+const CLOCK_BASE: StaticRef<ClockRegisters> =
+    unsafe { StaticRef::new(0x40000000 as *const ClockRegisters) };
+
+/// Low frequency clock source
+pub enum LowClockSource {
+    RC = 0,
+    XTAL = 1,
+    SYNTH = 2,
+}
+
+/// Clock struct
+pub struct Clock {
+    registers: StaticRef<ClockRegisters>,
+}
+
+pub static mut CLOCK: Clock = Clock::new();
+
+impl Clock {
+    /// Constructor
+    pub const fn new() -> Clock {
+        Clock {
+            registers: CLOCK_BASE,
+        }
+    }
+
+    /// Start the low frequency clock
+    pub fn low_start(&self) {
+        let regs = &*self.registers;
+        regs.tasks_lfclkstart.write(Control::ENABLE::SET);
+    }
+
+    /// Stop the low frequency clock
+    pub fn low_stop(&self) {
+        let regs = &*self.registers;
+        regs.tasks_lfclkstop.write(Control::ENABLE::SET);
+    }
+
+    /// Check if the low frequency clock has started
+    pub fn low_started(&self) -> bool {
+        let regs = &*self.registers;
+        regs.events_lfclkstarted.matches_all(Status::READY::SET)
+    }
+
+    /// Set low frequency clock source. The clock must be stopped.
+    pub fn low_set_source(&self, clock_source: LowClockSource) {
+        let regs = &*self.registers;
+        regs.lfclksrc.write(LfClkSrc::SRC.val(clock_source as u32));
+    }
+}
@@ -0,0 +1,120 @@
+//! Peripheral implementations for the Nordic nRF51 series.
+//!
+//! The nRF51 has a Cortex-M0 without an MPU or a SysTick, so processes are
+//! neither isolated nor preempted. Most peripherals are shared with the nRF52
+//! and live in the `nrf5x` crate.
+//!
+//! - nRF51 Series Reference Manual, version 3.0
+
+#![crate_name = "nrf51"]
+#![crate_type = "rlib"]
+#![feature(asm, const_fn, in_band_lifetimes)]
+#![no_std]
+
+pub mod chip;
+pub mod clock;
+pub mod uart;
+
+use cortexm0::{generic_isr, hard_fault_handler, svc_handler, systick_handler};
+
+unsafe extern "C" fn unhandled_interrupt() {
+    let mut interrupt_number: u32;
+
+    // IPSR[5:0] holds the currently active interrupt
+    asm!(
+    "mrs    r0, ipsr                    "
+    : "={r0}"(interrupt_number)
+    :
+    : "r0"
+    :
+    );
+
+    interrupt_number = interrupt_number & 0x3f;
+
+    panic!("Unhandled Interrupt. ISR {} is active.", interrupt_number);
+}
+
+extern "C" {
+    // _estack is not really a function, but it makes the types work
+    // You should never actually invoke it!!
+    fn _estack();
+
+    // Defined by platform
+    fn reset_handler();
+}
+
+#[link_section = ".vectors"]
+// used Ensures that the symbol is kept until the final binary
+#[used]
+pub static BASE_VECTORS: [unsafe extern "C" fn(); 16] = [
+    _estack,
+    reset_handler,
+    unhandled_interrupt, // NMI
+    hard_fault_handler,  // Hard Fault
+    unhandled_interrupt,
+    unhandled_interrupt,
+    unhandled_interrupt,
+    unhandled_interrupt,
+    unhandled_interrupt,
+    unhandled_interrupt,
+    unhandled_interrupt,
+    svc_handler, // SVC
+    unhandled_interrupt,
+    unhandled_interrupt,
+    unhandled_interrupt, // PendSV
+    systick_handler,     // SysTick, not implemented on the nRF51
+];
+
+// See section 7.3 of the nRF51 reference manual for the interrupt numbers.
+#[link_section = ".irqs"]
+#[used] // Ensures that the symbol is kept until the final binary
+pub static IRQS: [unsafe extern "C" fn(); 32] = [
+    generic_isr,         // POWER_CLOCK (0)
+    generic_isr,         // RADIO (1)
+    generic_isr,         // UART0 (2)
+    generic_isr,         // SPI0_TWI0 (3)
+    generic_isr,         // SPI1_TWI1 (4)
+    unhandled_interrupt, // (5)
+    generic_isr,         // GPIOTE (6)
+    generic_isr,         // ADC (7)
+    generic_isr,         // TIMER0 (8)
+    generic_isr,         // TIMER1 (9)
+    generic_isr,         // TIMER2 (10)
+    generic_isr,         // RTC0 (11)
+    generic_isr,         // TEMP (12)
+    generic_isr,         // RNG (13)
+    generic_isr,         // ECB (14)
+    generic_isr,         // CCM_AAR (15)
+    generic_isr,         // WDT (16)
+    generic_isr,         // RTC1 (17)
+    generic_isr,         // QDEC (18)
+    generic_isr,         // LPCOMP (19)
+    generic_isr,         // SWI0 (20)
+    generic_isr,         // SWI1 (21)
+    generic_isr,         // SWI2 (22)
+    generic_isr,         // SWI3 (23)
+    generic_isr,         // SWI4 (24)
+    generic_isr,         // SWI5 (25)
+    unhandled_interrupt, // (26)
+    unhandled_interrupt, // (27)
+    unhandled_interrupt, // (28)
+    unhandled_interrupt, // (29)
+    unhandled_interrupt, // (30)
+    unhandled_interrupt, // (31)
+];
+
+extern "C" {
+    static mut _szero: u32;
+    static mut _ezero: u32;
+    static mut _etext: u32;
+    static mut _srelocate: u32;
+    static mut _erelocate: u32;
+}
+
+pub unsafe fn init() {
+    tock_rt0::init_data(&mut _etext, &mut _srelocate, &mut _erelocate);
+    tock_rt0::zero_bss(&mut _szero, &mut _ezero);
+
+    cortexm0::nvic::disable_all();
+    cortexm0::nvic::clear_all_pending();
+}
@@ -0,0 +1,401 @@
+//! Universal asynchronous receiver/transmitter (UART), nRF51
+//!
+//! Unlike the nRF52's UARTE, the nRF51's UART has no EasyDMA. It transmits
+//! and receives one byte at a time through the TXD and RXD registers, with an
+//! event, and an interrupt, after each byte. Both are routed to
+//! `handle_interrupt`.
+//!
+//! - nRF51 Series Reference Manual, section 29
+
+use core::cell::Cell;
+use kernel::common::cells::{MapCell, OptionalCell};
+use kernel::common::registers::{register_bitfields, ReadOnly, ReadWrite, WriteOnly};
+use kernel::common::StaticRef;
+use kernel::hil::uart;
+use kernel::ReturnCode;
+use nrf5x::pinmux;
+
+const UART0_BASE: StaticRef<UartRegisters> =
+    unsafe { StaticRef::new(0x40002000 as *const UartRegisters) };
+
+#[repr(C)]
+struct UartRegisters {
+    task_startrx: WriteOnly<u32, Task::Register>,
+    task_stoprx: WriteOnly<u32, Task::Register>,
+    task_starttx: WriteOnly<u32, Task::Register>,
+    task_stoptx: WriteOnly<u32, Task::Register>,
+    _reserved1: [u32; 60],
+    event_cts: ReadWrite<u32, Event::Register>,
+    event_ncts: ReadWrite<u32, Event::Register>,
+    event_rxdrdy: ReadWrite<u32, Event::Register>,
+    _reserved2: [u32; 4],
+    event_txdrdy: ReadWrite<u32, Event::Register>,
+    _reserved3: [u32; 1],
+    event_error: ReadWrite<u32, Event::Register>,
+    _reserved4: [u32; 7],
+    event_rxto: ReadWrite<u32, Event::Register>,
+    _reserved5: [u32; 111],
+    intenset: ReadWrite<u32, Interrupt::Register>,
+    intenclr: ReadWrite<u32, Interrupt::Register>,
+    _reserved6: [u32; 93],
+    errorsrc: ReadWrite<u32, ErrorSrc::Register>,
+    _reserved7: [u32; 31],
+    enable: ReadWrite<u32, Enable::Register>,
+    _reserved8: [u32; 1],
+    pselrts: ReadWrite<u32, Psel::Register>,
+    pseltxd: ReadWrite<u32, Psel::Register>,
+    pselcts: ReadWrite<u32, Psel::Register>,
+    pselrxd: ReadWrite<u32, Psel::Register>,
+    rxd: ReadOnly<u32>,
+    txd: WriteOnly<u32>,
+    _reserved9: [u32; 1],
+    baudrate: ReadWrite<u32, Baudrate::Register>,
+    _reserved10: [u32; 17],
+    config: ReadWrite<u32, Config::Register>,
+}
+
+register_bitfields! [u32,
+    /// Start task
+    Task [
+        ENABLE OFFSET(0) NUMBITS(1)
+    ],
+
+    /// Read event
+    Event [
+        READY OFFSET(0) NUMBITS(1)
+    ],
+
+    /// UART Interrupts
+    Interrupt [
+        CTS OFFSET(0) NUMBITS(1),
+        NCTS OFFSET(1) NUMBITS(1),
+        RXDRDY OFFSET(2) NUMBITS(1),
+        TXDRDY OFFSET(7) NUMBITS(1),
+        ERROR OFFSET(9) NUMBITS(1),
+        RXTO OFFSET(17) NUMBITS(1)
+    ],
+
+    /// UART Errors
+    ErrorSrc [
+        OVERRUN OFFSET(0) NUMBITS(1),
+        PARITY OFFSET(1) NUMBITS(1),
+        FRAMING OFFSET(2) NUMBITS(1),
+        BREAK OFFSET(3) NUMBITS(1)
+    ],
+
+    /// Enable UART
+    Enable [
+        ENABLE OFFSET(0) NUMBITS(3) [
+            ON = 4,
+            OFF = 0
+        ]
+    ],
+
+    /// Pin select
+    Psel [
+        // Pin number, or 0xFFFFFFFF for disconnected
+        PIN OFFSET(0) NUMBITS(32)
+    ],
+
+    /// Baudrate
+    Baudrate [
+        BAUDRAUTE OFFSET(0) NUMBITS(32)
+    ],
+
+    /// Configuration of parity and flow control
+    Config [
+        HWFC OFFSET(0) NUMBITS(1),
+        PARITY OFFSET(1) NUMBITS(3)
+    ]
+];
+
+/// UART0 handle
+// This should only be accessed by the reset_handler on startup
+pub static mut UART0: Uart = Uart::new(UART0_BASE);
+
+/// Stores an ongoing transaction
+struct Transaction {
+    /// The buffer containing the bytes to transmit or receive, as it should be
+    /// returned to the client
+    buffer: &'static mut [u8],
+    /// The total amount to transfer
+    length: usize,
+    /// The index of the next byte to transfer
+    index: usize,
+}
+
+pub struct Uart<'a> {
+    registers: StaticRef<UartRegisters>,
+    tx_client: OptionalCell<&'a dyn uart::TransmitClient>,
+    rx_client: OptionalCell<&'a dyn uart::ReceiveClient>,
+    tx: MapCell<Transaction>,
+    rx: MapCell<Transaction>,
+    transmitting_word: Cell<bool>,
+    receiving_word: Cell<bool>,
+    /// Whether a byte has been written to TXD and not sent yet.
+    tx_busy: Cell<bool>,
+}
+
+impl Uart<'a> {
+    const fn new(registers: StaticRef<UartRegisters>) -> Uart<'a> {
+        Uart {
+            registers,
+            tx_client: OptionalCell::empty(),
+            rx_client: OptionalCell::empty(),
+            tx: MapCell::empty(),
+            rx: MapCell::empty(),
+            transmitting_word: Cell::new(false),
+            receiving_word: Cell::new(false),
+            tx_busy: Cell::new(false),
+        }
+    }
+
+    /// Configure which pins the UART should use for txd and rxd
+    pub fn initialize(&self, txd: pinmux::Pinmux, rxd: pinmux::Pinmux) {
+        let regs = &*self.registers;
+        regs.pseltxd.write(Psel::PIN.val(txd.into()));
+        regs.pselrxd.write(Psel::PIN.val(rxd.into()));
+    }
+
+    fn set_baud_rate(&self, baud_rate: u32) {
+        let regs = &*self.registers;
+        match baud_rate {
+            1200 => regs.baudrate.set(0x0004F000),
+            2400 => regs.baudrate.set(0x0009D000),
+            4800 => regs.baudrate.set(0x0013B000),
+            9600 => regs.baudrate.set(0x00275000),
+            14400 => regs.baudrate.set(0x003B0000),
+            19200 => regs.baudrate.set(0x004EA000),
+            28800 => regs.baudrate.set(0x0075F000),
+            38400 => regs.baudrate.set(0x009D5000),
+            57600 => regs.baudrate.set(0x00EBF000),
+            76800 => regs.baudrate.set(0x013A9000),
+            115200 => regs.baudrate.set(0x01D7E000),
+            230400 => regs.baudrate.set(0x03AFB000),
+            250000 => regs.baudrate.set(0x04000000),
+            460800 => regs.baudrate.set(0x075F7000),
+            921600 => regs.baudrate.set(0x0EBEDFA4),
+            1000000 => regs.baudrate.set(0x10000000),
+            _ => regs.baudrate.set(0x01D7E000), //setting default to 115200
+        }
+    }
+
+    /// UART interrupt handler that listens for both txdrdy and rxdrdy events
+    pub fn handle_interrupt(&self) {
+        let regs = &*self.registers;
+
+        if regs.event_rxdrdy.is_set(Event::READY) {
+            regs.event_rxdrdy.write(Event::READY::CLEAR);
+            self.handle_receive();
+        }
+        if regs.event_txdrdy.is_set(Event::READY) {
+            regs.event_txdrdy.write(Event::READY::CLEAR);
+            self.tx_busy.set(false);
+            self.handle_transmit();
+        }
+    }
+
+    fn handle_receive(&self) {
+        // Reading RXD makes room for the next byte, so always read it even
+        // if no one wants the byte.
+        let byte = self.registers.rxd.get();
+
+        if self.receiving_word.get() {
+            self.receiving_word.set(false);
+            self.rx_client.map(|client| {
+                client.received_word(byte, ReturnCode::SUCCESS, uart::Error::None);
+            });
+        } else {
+            self.rx.take().map(|mut rx| {
+                rx.buffer[rx.index] = byte as u8;
+                rx.index += 1;
+
+                if rx.index == rx.length {
+                    self.rx_client.map(move |client| {
+                        client.received_buffer(
+                            rx.buffer,
+                            rx.index,
+                            ReturnCode::SUCCESS,
+                            uart::Error::None,
+                        );
+                    });
+                } else {
+                    self.rx.put(rx);
+                }
+            });
+        }
+    }
+
+    fn handle_transmit(&self) {
+        if self.transmitting_word.get() {
+            self.transmitting_word.set(false);
+            self.tx_client.map(|client| {
+                client.transmitted_word(ReturnCode::SUCCESS);
+            });
+        } else {
+            self.tx.take().map(|mut tx| {
+                if tx.index == tx.length {
+                    self.tx_client.map(move |client| {
+                        client.transmitted_buffer(tx.buffer, tx.length, ReturnCode::SUCCESS);
+                    });
+                } else {
+                    // TXDRDY fires when the byte has been sent, which brings
+                    // us back here for the next one.
+                    self.write(tx.buffer[tx.index]);
+                    tx.index += 1;
+                    self.tx.put(tx);
+                }
+            });
+        }
+    }
+
+    fn write(&self, byte: u8) {
+        self.tx_busy.set(true);
+        self.registers.txd.set(byte as u32);
+    }
+
+    /// Busy-wait until `byte` has been sent. This is for panics, where
+    /// interrupts are not serviced.
+    pub fn send_byte(&self, byte: u8) {
+        let regs = &*self.registers;
+
+        // Let a byte of an interrupted transmission leave TXD first.
+        if self.tx_busy.get() {
+            while !regs.event_txdrdy.is_set(Event::READY) {}
+        }
+        regs.event_txdrdy.write(Event::READY::CLEAR);
+        self.write(byte);
+        while !regs.event_txdrdy.is_set(Event::READY) {}
+        regs.event_txdrdy.write(Event::READY::CLEAR);
+        self.tx_busy.set(false);
+    }
+}
+
+impl uart::Uart<'a> for Uart<'a> {}
+impl uart::UartData<'a> for Uart<'a> {}
+
+impl uart::Configure for Uart<'a> {
+    fn configure(&self, params: uart::Parameters) -> ReturnCode {
+        // These could probably be implemented, but are currently ignored, so
+        // throw an error.
+        if params.stop_bits != uart::StopBits::One
+            || params.parity != uart::Parity::None
+            || params.hw_flow_control
+            || params.width != uart::Width::Eight
+        {
+            return ReturnCode::ENOSUPPORT;
+        }
+
+        let regs = &*self.registers;
+        self.set_baud_rate(params.baud_rate);
+        regs.enable.write(Enable::ENABLE::ON);
+        regs.intenset
+            .write(Interrupt::RXDRDY::SET + Interrupt::TXDRDY::SET);
+        regs.task_starttx.write(Task::ENABLE::SET);
+        regs.task_startrx.write(Task::ENABLE::SET);
+
+        ReturnCode::SUCCESS
+    }
+}
+
+impl uart::Transmit<'a> for Uart<'a> {
+    fn set_transmit_client(&self, client: &'a dyn uart::TransmitClient) {
+        self.tx_client.set(client);
+    }
+
+    fn transmit_buffer(
+        &self,
+        buffer: &'static mut [u8],
+        len: usize,
+    ) -> (ReturnCode, Option<&'static mut [u8]>) {
+        if len == 0 || len > buffer.len() {
+            (ReturnCode::ESIZE, Some(buffer))
+        } else if self.tx.is_some() || self.transmitting_word.get() {
+            (ReturnCode::EBUSY, Some(buffer))
+        } else {
+            self.write(buffer[0]);
+            self.tx.put(Transaction {
+                buffer: buffer,
+                length: len,
+                index: 1,
+            });
+            (ReturnCode::SUCCESS, None)
+        }
+    }
+
+    fn transmit_word(&self, word: u32) -> ReturnCode {
+        if self.tx.is_some() || self.transmitting_word.get() {
+            ReturnCode::EBUSY
+        } else {
+            self.transmitting_word.set(true);
+            self.write(word as u8);
+            ReturnCode::SUCCESS
+        }
+    }
+
+    fn transmit_abort(&self) -> ReturnCode {
+        if self.tx.is_some() || self.transmitting_word.get() {
+            // The byte in flight will still raise an interrupt, which makes
+            // the callback.
+            ReturnCode::FAIL
+        } else {
+            ReturnCode::SUCCESS
+        }
+    }
+}
+
+impl uart::Receive<'a> for Uart<'a> {
+    fn set_receive_client(&self, client: &'a dyn uart::ReceiveClient) {
+        self.rx_client.set(client);
+    }
+
+    fn receive_buffer(
+        &self,
+        buffer: &'static mut [u8],
+        len: usize,
+    ) -> (ReturnCode, Option<&'static mut [u8]>) {
+        if len == 0 || len > buffer.len() {
+            (ReturnCode::ESIZE, Some(buffer))
+        } else if self.rx.is_some() || self.receiving_word.get() {
+            (ReturnCode::EBUSY, Some(buffer))
+        } else {
+            self.rx.put(Transaction {
+                buffer: buffer,
+                length: len,
+                index: 0,
+            });
+            (ReturnCode::SUCCESS, None)
+        }
+    }
+
+    fn receive_word(&self) -> ReturnCode {
+        if self.rx.is_some() || self.receiving_word.get() {
+            ReturnCode::EBUSY
+        } else {
+            self.receiving_word.set(true);
+            ReturnCode::SUCCESS
+        }
+    }
+
+    fn receive_abort(&self) -> ReturnCode {
+        if self.receiving_word.get() {
+            self.receiving_word.set(false);
+            self.rx_client.map(|client| {
+                client.received_word(0, ReturnCode::ECANCEL, uart::Error::Aborted);
+            });
+            ReturnCode::EBUSY
+        } else if let Some(rx) = self.rx.take() {
+            self.rx_client.map(move |client| {
+                client.received_buffer(
+                    rx.buffer,
+                    rx.index,
+                    ReturnCode::ECANCEL,
+                    uart::Error::Aborted,
+                );
+            });
+            ReturnCode::EBUSY
+        } else {
+            ReturnCode::SUCCESS
+        }
+    }
+}
@@ -40,6 +40,7 @@ use kernel::common::registers::{register_bitfields, ReadOnly, ReadWrite, WriteOn
 use kernel::common::StaticRef;
 use kernel::hil::ble_advertising;
 use kernel::hil::ble_advertising::RadioChannel;
+use kernel::power::{Constraint, PowerConstraint};
 use kernel::ReturnCode;
 use nrf5x::constants::TxPower;
 
@@ -533,6 +534,10 @@ pub struct Radio {
     tx_power: Cell<TxPower>,
     rx_client: OptionalCell<&'static dyn ble_advertising::RxClient>,
     tx_client: OptionalCell<&'static dyn ble_advertising::TxClient>,
+    /// Held while the radio is powered, which needs the HFXO running and
+    /// constant latency wakeups.
+    active: PowerConstraint,
+    hfxo: PowerConstraint,
 }
 
 pub static mut RADIO: Radio = Radio::new();
@@ -544,6 +549,8 @@ impl Radio {
             tx_power: Cell::new(TxPower::ZerodBm),
             rx_client: OptionalCell::empty(),
             tx_client: OptionalCell::empty(),
+            active: PowerConstraint::new(Constraint::RadioActive),
+            hfxo: PowerConstraint::new(Constraint::HighSpeedClock),
         }
     }
 
@@ -574,6 +581,8 @@ impl Radio {
     }
 
     fn radio_on(&self) {
+        self.active.require();
+        self.hfxo.require();
         let regs = &*self.registers;
         // reset and enable power
         regs.power.write(Task::ENABLE::CLEAR);
@@ -583,6 +592,8 @@ impl Radio {
     fn radio_off(&self) {
         let regs = &*self.registers;
         regs.power.write(Task::ENABLE::CLEAR);
+        self.active.release();
+        self.hfxo.release();
     }
 
     fn set_tx_power(&self) {
@@ -1,5 +1,6 @@
 use crate::adc;
 use crate::ble_radio;
+use crate::clock;
 use crate::deferred_call_tasks::DeferredCallTask;
 use crate::i2c;
 use crate::ieee802154_radio;
@@ -9,8 +10,38 @@ use crate::uart;
 use cortexm4::{self, nvic};
 use kernel::common::deferred_call;
 use kernel::debug;
+use kernel::power::{Constraint, SleepState};
 use nrf5x::peripheral_interrupts;
 
+const CONSTANT_LATENCY: usize = 0;
+const HFXO_OFF: usize = 2;
+
+static SLEEP_STATES: [SleepState; 3] = [
+    SleepState {
+        name: "ConstLat",
+        keeps: &[
+            Constraint::HighSpeedClock,
+            Constraint::PeripheralClocks,
+            Constraint::UartActive,
+            Constraint::RadioActive,
+        ],
+    },
+    SleepState {
+        name: "LowPower",
+        keeps: &[
+            Constraint::HighSpeedClock,
+            Constraint::PeripheralClocks,
+            Constraint::UartActive,
+        ],
+    },
+    // Peripherals that need a high frequency clock, such as the UARTE, start
+    // the internal oscillator on their own while the HFXO is stopped.
+    SleepState {
+        name: "LowPower, HFXO off",
+        keeps: &[Constraint::PeripheralClocks, Constraint::UartActive],
+    },
+];
+
 pub struct NRF52 {
     mpu: cortexm4::mpu::MPU,
     userspace_kernel_boundary: cortexm4::syscall::SysCall,
@@ -129,6 +160,38 @@ impl kernel::Chip for NRF52 {
         }
     }
 
+    fn sleep_states(&self) -> &'static [SleepState] {
+        &SLEEP_STATES
+    }
+
+    fn sleep_in(&self, state: usize) -> usize {
+        unsafe {
+            let clock = &clock::CLOCK;
+            if state == CONSTANT_LATENCY {
+                clock.constant_latency();
+            } else {
+                clock.low_power();
+            }
+
+            // Stop the crystal oscillator while sleeping if it is running,
+            // and start it again right after waking up.
+            let stop_hfxo = state == HFXO_OFF
+                && clock.high_running()
+                && match clock.high_source() {
+                    clock::HighClockSource::XTAL => true,
+                    clock::HighClockSource::RC => false,
+                };
+            if stop_hfxo {
+                clock.high_stop();
+            }
+            cortexm4::support::wfi();
+            if stop_hfxo {
+                clock.high_start();
+            }
+        }
+        state
+    }
+
     unsafe fn atomic<F, R>(&self, f: F) -> R
     where
         F: FnOnce() -> R,
@@ -29,7 +29,11 @@ struct ClockRegisters {
     tasks_cal: WriteOnly<u32, Control::Register>,
     tasks_ctstart: WriteOnly<u32, Control::Register>,
     tasks_ctstop: WriteOnly<u32, Control::Register>,
-    _reserved1: [u32; 57],
+    _reserved1: [u32; 23],
+    // The POWER peripheral shares this register block.
+    tasks_constlat: WriteOnly<u32, Control::Register>,
+    tasks_lowpwr: WriteOnly<u32, Control::Register>,
+    _reserved1b: [u32; 32],
     events_hfclkstarted: ReadOnly<u32, Status::Register>,
     events_lfclkstarted: ReadOnly<u32, Status::Register>,
     _reserverd2: u32,
@@ -229,6 +233,21 @@ impl Clock {
         regs.hfclkstat.matches_all(HfClkStat::STATE::RUNNING)
     }
 
+    /// Keep the regulators and clock sources ready while the CPU sleeps, so
+    /// that waking up always takes the same time (POWER constant latency
+    /// sub-mode).
+    pub fn constant_latency(&self) {
+        let regs = &*self.registers;
+        regs.tasks_constlat.write(Control::ENABLE::SET);
+    }
+
+    /// Let the chip power down what it can while the CPU sleeps (POWER low
+    /// power sub-mode, the default).
+    pub fn low_power(&self) {
+        let regs = &*self.registers;
+        regs.tasks_lowpwr.write(Control::ENABLE::SET);
+    }
+
     /// Start the low frequency clock
     pub fn low_start(&self) {
         let regs = &*self.registers;
@@ -8,6 +8,7 @@ use kernel::common::registers::{register_bitfields, ReadOnly, ReadWrite, WriteOn
 use kernel::common::StaticRef;
 use kernel::hil::radio::{self, PowerClient};
 use kernel::hil::time::Alarm;
+use kernel::power::{Constraint, PowerConstraint};
 use kernel::ReturnCode;
 
 use crate::ppi;
@@ -665,6 +666,10 @@ pub struct Radio {
     random_nonce: Cell<u32>,
     channel: Cell<RadioChannel>,
     transmitting: Cell<bool>,
+    /// Held while the radio is powered, which needs the HFXO running and
+    /// constant latency wakeups.
+    active: PowerConstraint,
+    hfxo: PowerConstraint,
 }
 
 pub static mut RADIO: Radio = Radio::new();
@@ -686,6 +691,8 @@ impl Radio {
             random_nonce: Cell::new(0xDEADBEEF),
             channel: Cell::new(RadioChannel::DataChannel11),
             transmitting: Cell::new(false),
+            active: PowerConstraint::new(Constraint::RadioActive),
+            hfxo: PowerConstraint::new(Constraint::HighSpeedClock),
         }
     }
 
@@ -730,6 +737,8 @@ impl Radio {
     }
 
     fn radio_on(&self) {
+        self.active.require();
+        self.hfxo.require();
         let regs = &*self.registers;
         // reset and enable power
         regs.power.write(Task::ENABLE::CLEAR);
@@ -739,6 +748,8 @@ impl Radio {
     fn radio_off(&self) {
         let regs = &*self.registers;
         regs.power.write(Task::ENABLE::CLEAR);
+        self.active.release();
+        self.hfxo.release();
     }
 
     fn set_tx_power(&self) {
@@ -159,6 +159,19 @@ pub enum PowerScaling {
     PS2,
 }
 
+/// Which clocks stop when the CPU sleeps without SLEEPDEEP set, as described
+/// in the datasheet's section on the SLEEP modes.
+pub enum SleepMode {
+    /// SLEEP0: only the CPU clock stops.
+    Sleep0 = 0,
+    /// SLEEP1: the AHB clocks stop too.
+    Sleep1 = 1,
+    /// SLEEP2: the peripheral bus and generic clocks stop too.
+    Sleep2 = 2,
+    /// SLEEP3: the clock sources, other than the 32kHz ones, stop too.
+    Sleep3 = 3,
+}
+
 pub enum CK32Source {
     OSC32K = 0,
     RC32K = 1,
@@ -172,6 +185,13 @@ pub unsafe fn set_ck32source(source: CK32Source) {
         .modify_no_read(control, PowerModeControl::CK32S.val(source as u32));
 }
 
+pub unsafe fn set_sleep_mode(mode: SleepMode) {
+    let control = BPM.pmcon.extract();
+    unlock_register(0x1c); // Control
+    BPM.pmcon
+        .modify_no_read(control, PowerModeControl::SLEEP.val(mode as u32));
+}
+
 unsafe fn unlock_register(register_offset: u32) {
     BPM.unlock
         .write(Unlock::KEY.val(BPM_UNLOCK_KEY) + Unlock::ADDR.val(register_offset));
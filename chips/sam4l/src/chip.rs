@@ -4,6 +4,7 @@ use crate::acifc;
 use crate::adc;
 use crate::aes;
 use crate::ast;
+use crate::bpm;
 use crate::crccu;
 use crate::dac;
 use crate::deferred_call_tasks::Task;
@@ -21,8 +22,33 @@ use crate::usbc;
 
 use cortexm4;
 use kernel::common::deferred_call;
+use kernel::power::{Constraint, SleepState};
 use kernel::Chip;
 
+const SLEEP0: usize = 0;
+const SLEEP2: usize = 1;
+const WAIT: usize = 2;
+
+static SLEEP_STATES: [SleepState; 3] = [
+    SleepState {
+        name: "SLEEP0",
+        keeps: &[
+            Constraint::HighSpeedClock,
+            Constraint::PeripheralClocks,
+            Constraint::UartActive,
+            Constraint::RadioActive,
+        ],
+    },
+    SleepState {
+        name: "SLEEP2",
+        keeps: &[Constraint::HighSpeedClock],
+    },
+    SleepState {
+        name: "WAIT",
+        keeps: &[],
+    },
+];
+
 pub struct Sam4l {
     mpu: cortexm4::mpu::MPU,
     userspace_kernel_boundary: cortexm4::syscall::SysCall,
@@ -183,19 +209,37 @@ impl Chip for Sam4l {
     }
 
     fn sleep(&self) {
-        if pm::deep_sleep_ready() {
-            unsafe {
-                cortexm4::scb::set_sleepdeep();
-            }
+        self.sleep_in(WAIT);
+    }
+
+    fn sleep_states(&self) -> &'static [SleepState] {
+        &SLEEP_STATES
+    }
+
+    fn sleep_in(&self, state: usize) -> usize {
+        // Both SLEEP2 and WAIT stop the peripheral bus clocks, which is only
+        // safe once every peripheral has masked its clock.
+        let state = if pm::deep_sleep_ready() {
+            state
         } else {
-            unsafe {
-                cortexm4::scb::unset_sleepdeep();
-            }
-        }
+            SLEEP0
+        };
 
         unsafe {
+            match state {
+                WAIT => cortexm4::scb::set_sleepdeep(),
+                SLEEP2 => {
+                    cortexm4::scb::unset_sleepdeep();
+                    bpm::set_sleep_mode(bpm::SleepMode::Sleep2);
+                }
+                _ => {
+                    cortexm4::scb::unset_sleepdeep();
+                    bpm::set_sleep_mode(bpm::SleepMode::Sleep0);
+                }
+            }
             cortexm4::support::wfi();
         }
+        state
     }
 
     unsafe fn atomic<F, R>(&self, f: F) -> R
@@ -11,6 +11,7 @@ use kernel::common::StaticRef;
 use kernel::hil;
 use kernel::hil::spi;
 use kernel::hil::uart;
+use kernel::power::{Constraint, PowerConstraint};
 use kernel::ReturnCode;
 
 use crate::dma;
@@ -285,6 +286,7 @@ const USART_BASE_ADDRS: [StaticRef<UsartRegisters>; 4] = unsafe {
 pub struct USARTRegManager<'a> {
     registers: &'a UsartRegisters,
     clock: pm::Clock,
+    active: &'a PowerConstraint,
     rx_dma: Option<&'a dma::DMAChannel>,
     tx_dma: Option<&'a dma::DMAChannel>,
 }
@@ -300,6 +302,7 @@ impl USARTRegManager<'a> {
         USARTRegManager {
             registers: regs,
             clock: usart.clock,
+            active: &usart.active,
             rx_dma: usart.rx_dma.get(),
             tx_dma: usart.tx_dma.get(),
         }
@@ -339,6 +342,9 @@ impl Drop for USARTRegManager<'a> {
         let is_panic = IS_PANICING.load(Ordering::Relaxed);
         if !(rx_active || tx_active || ints_active || is_panic) {
             pm::disable_clock(self.clock);
+            self.active.release();
+        } else {
+            self.active.require();
         }
     }
 }
@@ -377,6 +383,9 @@ enum UsartClient<'a> {
 pub struct USART<'a> {
     registers: StaticRef<UsartRegisters>,
     clock: pm::Clock,
+    /// Keeps the chip out of sleep states that stop the USART's clock while
+    /// a transfer is in progress, in any mode.
+    active: PowerConstraint,
 
     usart_mode: Cell<UsartMode>,
 
@@ -431,6 +440,7 @@ impl USART<'a> {
         USART {
             registers: base_addr,
             clock: pm::Clock::PBA(clock),
+            active: PowerConstraint::new(Constraint::UartActive),
 
             usart_mode: Cell::new(UsartMode::Unused),
 
@@ -102,6 +102,7 @@ const WDT_REGS: StaticRef<WdtRegisters> =
 
 pub struct Wdt {
     enabled: Cell<bool>,
+    period: Cell<usize>,
 }
 
 pub static mut WDT: Wdt = Wdt::new();
@@ -125,6 +126,7 @@ impl Wdt {
     const fn new() -> Wdt {
         Wdt {
             enabled: Cell::new(false),
+            period: Cell::new(0),
         }
     }
 
@@ -172,6 +174,7 @@ impl Wdt {
 
     fn start(&self, period: usize) {
         self.enabled.set(true);
+        self.period.set(period);
 
         pm::enable_clock(Clock::PBD(PBDClock::WDT));
 
@@ -232,4 +235,12 @@ impl hil::watchdog::Watchdog for Wdt {
     fn tickle(&self) {
         self.tickle();
     }
+
+    fn period(&self) -> Option<usize> {
+        if self.period.get() == 0 {
+            None
+        } else {
+            Some(self.period.get())
+        }
+    }
 }
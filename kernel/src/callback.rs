@@ -7,15 +7,21 @@ use crate::process;
 use crate::sched::Kernel;
 
 /// Userspace app identifier.
+///
+/// An `AppId` names one run of one process. Every time a process is loaded or
+/// restarted it gets a new identifier, which is never reused, so an `AppId`
+/// held from a previous run of a process, or from a process that was in the
+/// same slot before, no longer refers to any process.
 #[derive(Clone, Copy)]
 pub struct AppId {
     crate kernel: &'static Kernel,
-    idx: usize,
+    identifier: usize,
+    index: usize,
 }
 
 impl PartialEq for AppId {
     fn eq(&self, other: &AppId) -> bool {
-        self.idx == other.idx
+        self.identifier == other.identifier
     }
 }
 
@@ -23,20 +29,29 @@ impl Eq for AppId {}
 
 impl fmt::Debug for AppId {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.idx)
+        write!(f, "{}", self.identifier)
     }
 }
 
 impl AppId {
-    crate fn new(kernel: &'static Kernel, idx: usize) -> AppId {
+    crate fn new(kernel: &'static Kernel, identifier: usize, index: usize) -> AppId {
         AppId {
             kernel: kernel,
-            idx: idx,
+            identifier: identifier,
+            index: index,
         }
     }
 
+    /// Index of the slot in the process table the process is in. Capsules can
+    /// use this to keep per-process state in an array, but must not assume
+    /// that the process in the slot is still the one this `AppId` names.
     pub fn idx(&self) -> usize {
-        self.idx
+        self.index
+    }
+
+    /// The unique identifier of this run of the process.
+    pub fn id(&self) -> usize {
+        self.identifier
     }
 
     /// Returns the full address of the start and end of the flash region that
@@ -44,7 +59,7 @@ impl AppId {
     /// any padding at the end of the app. It does not include the TBF header,
     /// or any space that the kernel is using for any potential bookkeeping.
     pub fn get_editable_flash_range(&self) -> (usize, usize) {
-        self.kernel.process_map_or((0, 0), self.idx, |process| {
+        self.kernel.process_map_or((0, 0), *self, |process| {
             let start = process.flash_non_protected_start() as usize;
             let end = process.flash_end() as usize;
             (start, end)
@@ -96,17 +111,33 @@ impl Callback {
     /// The arguments (`r0-r2`) are the values passed back to the process and
     /// are specific to the individual `Driver` interfaces.
     pub fn schedule(&mut self, r0: usize, r1: usize, r2: usize) -> bool {
+        let task = self.task(r0, r1, r2);
+        self.app_id
+            .kernel
+            .process_map_or(false, self.app_id, |process| process.enqueue_task(task))
+    }
+
+    /// Trigger the callback even if the process's task queue is full, in
+    /// which case it is queued as soon as the process takes a task off the
+    /// queue. Each process can only have one callback waiting like this, so
+    /// this returns `false` if another one already is.
+    crate fn schedule_when_room(&mut self, r0: usize, r1: usize, r2: usize) -> bool {
+        let task = self.task(r0, r1, r2);
         self.app_id
             .kernel
-            .process_map_or(false, self.app_id.idx(), |process| {
-                process.enqueue_task(process::Task::FunctionCall(process::FunctionCall {
-                    source: process::FunctionCallSource::Driver(self.callback_id),
-                    argument0: r0,
-                    argument1: r1,
-                    argument2: r2,
-                    argument3: self.appdata,
-                    pc: self.fn_ptr.as_ptr() as usize,
-                }))
+            .process_map_or(false, self.app_id, |process| {
+                process.enqueue_task_when_room(task)
             })
     }
+
+    fn task(&self, r0: usize, r1: usize, r2: usize) -> process::Task {
+        process::Task::FunctionCall(process::FunctionCall {
+            source: process::FunctionCallSource::Driver(self.callback_id),
+            argument0: r0,
+            argument1: r1,
+            argument2: r2,
+            argument3: self.appdata,
+            pc: self.fn_ptr.as_ptr() as usize,
+        })
+    }
 }
@@ -50,6 +50,10 @@
 /// otherwise managing processes.
 pub unsafe trait ProcessManagementCapability {}
 
+/// The `ProcessLoadingCapability` allows the holder to load new processes
+/// and to unload or replace running ones while the kernel is running.
+pub unsafe trait ProcessLoadingCapability {}
+
 /// The `MainLoopCapability` capability allows the holder to start executing
 /// the main scheduler loop in Tock.
 pub unsafe trait MainLoopCapability {}
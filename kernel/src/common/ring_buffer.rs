@@ -16,6 +16,29 @@ impl<T: Copy> RingBuffer<'a, T> {
             ring: ring,
         }
     }
+
+    /// Iterate over the elements in the buffer, from the oldest to the most
+    /// recently enqueued.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        let (first, second): (&[T], &[T]) = if self.head <= self.tail {
+            (&self.ring[self.head..self.tail], &[])
+        } else {
+            (&self.ring[self.head..], &self.ring[..self.tail])
+        };
+        first.iter().chain(second.iter())
+    }
+
+    /// Iterate mutably over the elements in the buffer, from the oldest to
+    /// the most recently enqueued.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        let (first, second): (&mut [T], &mut [T]) = if self.head <= self.tail {
+            (&mut self.ring[self.head..self.tail], &mut [])
+        } else {
+            let (front, back) = self.ring.split_at_mut(self.head);
+            (back, &mut front[..self.tail])
+        };
+        first.iter_mut().chain(second.iter_mut())
+    }
 }
 
 impl<T: Copy> queue::Queue<T> for RingBuffer<'a, T> {
@@ -184,6 +207,24 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_iter() {
+        const LEN: usize = 10;
+        let mut ring = [0; LEN];
+        let mut buf = RingBuffer::new(&mut ring);
+
+        // Wrap the buffer around the end of the ring.
+        move_head(&mut buf, LEN - 2);
+        enqueue_iota(&mut buf, LEN);
+        assert!(buf.iter().cloned().eq(1..LEN));
+
+        for x in buf.iter_mut() {
+            *x *= 2;
+        }
+        assert!(buf.iter().cloned().eq((1..LEN).map(|x| x * 2)));
+        assert_eq!(buf.iter_mut().last().map(|x| *x), Some(2 * (LEN - 1)));
+    }
+
     #[test]
     fn test_retain() {
         const LEN: usize = 10;
@@ -4,6 +4,10 @@
 //! If you are writing and the buffer fills up, you can make the size of
 //! `output_buffer` larger.
 //!
+//! Messages have a level. `debug!()` and `debug_verbose!()` log at the `Debug`
+//! level, and the `log_error!()`, `log_warn!()`, `log_info!()`, `log_debug!()`
+//! and `log_trace!()` macros at theirs.
+//!
 //! Before debug interfaces can be used, the board file must assign them hardware:
 //!
 //! ```ignore
@@ -19,15 +23,39 @@
 //! kernel::debug::assign_console_driver(Some(hail.console), kc);
 //! ```
 //!
+//! Output can also go to more places than the `DebugWriter`, each with its
+//! own level: for example a second `DebugWriter` over Segger RTT, and a
+//! `RamLog` that keeps the most recent output to print if the kernel panics.
+//! The level of individual modules can be lowered so that one chatty capsule
+//! does not drown out everything else:
+//!
+//! ```ignore
+//! let ram_log = static_init!(
+//!     kernel::debug::RamLog,
+//!     kernel::debug::RamLog::new(&mut kernel::debug::RAM_LOG_BUF));
+//! debug_wrapper.add_sink(ram_log, kernel::debug::LogLevel::Trace);
+//! debug_wrapper.set_module_level("capsules::usb", kernel::debug::LogLevel::Warn);
+//! ```
+//!
+//! When a sink falls behind, messages that do not fit are dropped, and the
+//! sink is told how many bytes it missed once it has room again.
+//!
+//! `debug_deferred!()` takes the same arguments as `debug!()`, but leaves the
+//! formatting to the host: only an index of the format string and the raw
+//! arguments are sent, and the format strings are not stored on the chip.
+//! `tools/decode_deferred_log.py` turns the output back into text using the
+//! kernel's ELF file.
+//!
 //! Example
 //! -------
 //!
 //! ```no_run
-//! # use kernel::{debug, debug_gpio, debug_verbose};
+//! # use kernel::{debug, debug_gpio, debug_verbose, log_warn};
 //! # fn main() {
 //! # let i = 42;
 //! debug!("Yes the code gets here with value {}", i);
 //! debug_verbose!("got here"); // includes message count, file, and line
+//! log_warn!("value {} is getting large", i); // includes level and module
 //! debug_gpio!(0, toggle); // Toggles the first debug GPIO
 //! # }
 //! ```
@@ -35,18 +63,20 @@
 //! ```text
 //! Yes the code gets here with value 42
 //! TOCK_DEBUG(0): /tock/capsules/src/sensys.rs:24: got here
+//! WARN capsules::sensys: value 42 is getting large
+//! [17 bytes dropped]
 //! ```
 
 use core::cell::Cell;
+use core::char;
 use core::cmp::{self, min};
 use core::fmt::{write, Arguments, Result, Write};
 use core::panic::PanicInfo;
 use core::ptr;
-use core::slice;
 use core::str;
 
 use crate::common::cells::NumericCellExt;
-use crate::common::cells::{MapCell, TakeCell};
+use crate::common::cells::{OptionalCell, TakeCell};
 use crate::hil;
 use crate::process::ProcessType;
 use crate::ReturnCode;
@@ -194,12 +224,136 @@ macro_rules! debug_gpio {
 }
 
 ///////////////////////////////////////////////////////////////////
-// debug! and debug_verbose! support
+// debug!, debug_verbose! and log_*! support
+
+/// How important a log message is. Less important levels compare greater, so
+/// a filter set to `Info` lets through `Error`, `Warn` and `Info` messages.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    fn tag(&self) -> &'static str {
+        match *self {
+            LogLevel::Error => "ERROR",
+            LogLevel::Warn => "WARN",
+            LogLevel::Info => "INFO",
+            LogLevel::Debug => "DEBUG",
+            LogLevel::Trace => "TRACE",
+        }
+    }
+}
+
+/// A destination for `debug!()` and log output.
+///
+/// The kernel writes each message to every sink whose level lets it through,
+/// so output can go to a UART, a Segger RTT channel and a RAM buffer at once.
+pub trait DebugSink {
+    /// Add `bytes` to the output. Returns `false`, without adding any of
+    /// them, if there is not enough room.
+    fn write(&self, bytes: &[u8]) -> bool;
+
+    /// Start sending out what was written, if the sink is not already.
+    fn publish(&self) {}
+
+    /// Synchronously write out what the sink still holds. Called when the
+    /// kernel panics.
+    fn flush(&self, _writer: &mut dyn Write) {}
+
+    /// Whether the sink holds text rather than raw bytes. Text sinks get
+    /// `debug_deferred!()` messages hex encoded instead of as binary frames.
+    fn is_text(&self) -> bool {
+        false
+    }
+}
+
+/// Most sinks debug output can go to at once.
+const MAX_SINKS: usize = 4;
+
+/// Most modules that can have their own log level.
+const MAX_MODULE_LEVELS: usize = 8;
+
+/// A sink and how much of the output it gets.
+struct SinkSlot {
+    sink: OptionalCell<&'static dyn DebugSink>,
+    level: Cell<LogLevel>,
+    // Bytes dropped because the sink was full.
+    dropped: Cell<usize>,
+    // Bytes dropped that the sink has not been told about yet.
+    unreported: Cell<usize>,
+}
+
+impl SinkSlot {
+    fn empty() -> SinkSlot {
+        SinkSlot {
+            sink: OptionalCell::empty(),
+            level: Cell::new(LogLevel::Debug),
+            dropped: Cell::new(0),
+            unreported: Cell::new(0),
+        }
+    }
+
+    /// Write `bytes` to the sink, counting them if they do not fit.
+    fn write(&self, bytes: &[u8]) {
+        self.sink.map(|sink| {
+            if !sink.write(bytes) {
+                self.dropped.add(bytes.len());
+                self.unreported.add(bytes.len());
+            }
+        });
+    }
+
+    /// Tell the sink how many bytes it dropped since the last time, if it
+    /// has room for that now.
+    fn report_dropped(&self) {
+        let unreported = self.unreported.get();
+        if unreported == 0 {
+            return;
+        }
+        let mut notice = NoticeBuffer {
+            buffer: [0; 32],
+            len: 0,
+        };
+        let _ = notice.write_fmt(format_args!("[{} bytes dropped]\r\n", unreported));
+        self.sink.map(|sink| {
+            if sink.write(&notice.buffer[..notice.len]) {
+                self.unreported.set(0);
+            }
+        });
+    }
+}
+
+/// Small buffer to format the dropped bytes notice into.
+struct NoticeBuffer {
+    buffer: [u8; 32],
+    len: usize,
+}
+
+impl Write for NoticeBuffer {
+    fn write_str(&mut self, s: &str) -> Result {
+        let end = min(self.len + s.len(), self.buffer.len());
+        self.buffer[self.len..end].copy_from_slice(&s.as_bytes()[..end - self.len]);
+        self.len = end;
+        Ok(())
+    }
+}
 
 /// Wrapper type that we need a mutable reference to for the core::fmt::Write
-/// interface.
+/// interface. It decides which messages are output and sends them to each
+/// sink.
 pub struct DebugWriterWrapper {
-    dw: MapCell<&'static DebugWriter>,
+    sinks: [SinkSlot; MAX_SINKS],
+    // Levels of modules that do not use the default, by module path prefix.
+    module_levels: [Cell<Option<(&'static str, LogLevel)>>; MAX_MODULE_LEVELS],
+    // Level of the message being written.
+    message_level: Cell<LogLevel>,
+    // Number of debug_verbose!() calls.
+    count: Cell<usize>,
 }
 
 /// Main type that we need an immutable reference to so we can share it with
@@ -215,8 +369,6 @@ pub struct DebugWriter {
     tail: Cell<usize>,
     // How many bytes are being written on the current publish_str call.
     active_len: Cell<usize>,
-    // Number of debug!() calls.
-    count: Cell<usize>,
 }
 
 /// Static variable that holds the kernel's reference to the debug tool. This is
@@ -225,6 +377,7 @@ static mut DEBUG_WRITER: Option<&'static mut DebugWriterWrapper> = None;
 
 pub static mut OUTPUT_BUF: [u8; 64] = [0; 64];
 pub static mut INTERNAL_BUF: [u8; 1024] = [0; 1024];
+pub static mut RAM_LOG_BUF: [u8; 1024] = [0; 1024];
 
 pub unsafe fn get_debug_writer() -> &'static mut DebugWriterWrapper {
     match ptr::read(&DEBUG_WRITER) {
@@ -239,13 +392,156 @@ pub unsafe fn set_debug_writer_wrapper(debug_writer: &'static mut DebugWriterWra
 }
 
 impl DebugWriterWrapper {
+    /// Create the wrapper with `dw` as its first sink. It gets messages up
+    /// to the `Debug` level, which includes all `debug!()` output.
     pub fn new(dw: &'static DebugWriter) -> DebugWriterWrapper {
-        DebugWriterWrapper {
-            dw: MapCell::new(dw),
+        let wrapper = DebugWriterWrapper {
+            sinks: [
+                SinkSlot::empty(),
+                SinkSlot::empty(),
+                SinkSlot::empty(),
+                SinkSlot::empty(),
+            ],
+            module_levels: Default::default(),
+            message_level: Cell::new(LogLevel::Debug),
+            count: Cell::new(0),
+        };
+        wrapper.sinks[0].sink.set(dw);
+        wrapper
+    }
+
+    /// Also send messages up to `level` to `sink`.
+    ///
+    /// Returns `ENOMEM` if the wrapper already has as many sinks as it can
+    /// hold.
+    pub fn add_sink(&self, sink: &'static dyn DebugSink, level: LogLevel) -> ReturnCode {
+        match self.sinks.iter().find(|slot| slot.sink.is_none()) {
+            Some(slot) => {
+                slot.level.set(level);
+                slot.sink.set(sink);
+                ReturnCode::SUCCESS
+            }
+            None => ReturnCode::ENOMEM,
+        }
+    }
+
+    /// Set the most verbose level of messages sent to the `DebugWriter` the
+    /// wrapper was created with.
+    pub fn set_writer_level(&self, level: LogLevel) {
+        self.sinks[0].level.set(level);
+    }
+
+    /// Only output messages up to `level` from `module` and the modules in
+    /// it, e.g. `"capsules::usb"`. The most specific module that matches a
+    /// message applies.
+    ///
+    /// Returns `ENOMEM` if the wrapper already has as many module levels as
+    /// it can hold.
+    pub fn set_module_level(&self, module: &'static str, level: LogLevel) -> ReturnCode {
+        let existing = self
+            .module_levels
+            .iter()
+            .find(|entry| entry.get().map_or(false, |(name, _)| name == module));
+        match existing.or_else(|| {
+            self.module_levels
+                .iter()
+                .find(|entry| entry.get().is_none())
+        }) {
+            Some(entry) => {
+                entry.set(Some((module, level)));
+                ReturnCode::SUCCESS
+            }
+            None => ReturnCode::ENOMEM,
+        }
+    }
+
+    /// Total number of bytes of output dropped because a sink was full.
+    pub fn dropped_bytes(&self) -> usize {
+        self.sinks.iter().map(|slot| slot.dropped.get()).sum()
+    }
+
+    fn increment_count(&self) {
+        self.count.increment();
+    }
+
+    fn get_count(&self) -> usize {
+        self.count.get()
+    }
+
+    /// Start a message at `level` from `module`. Returns `false` if no sink
+    /// should get it, in which case it does not need to be formatted.
+    fn begin_message(&self, level: LogLevel, module: &str) -> bool {
+        let module_level = self
+            .module_levels
+            .iter()
+            .filter_map(|entry| entry.get())
+            .filter(|&(name, _)| {
+                module.starts_with(name)
+                    && (module.len() == name.len() || module[name.len()..].starts_with("::"))
+            })
+            .max_by_key(|&(name, _)| name.len())
+            .map(|(_, level)| level);
+        if module_level.map_or(false, |module_level| level > module_level) {
+            return false;
+        }
+
+        self.message_level.set(level);
+        let mut any = false;
+        for slot in self.sinks.iter().filter(|slot| level <= slot.level.get()) {
+            slot.report_dropped();
+            any |= slot.sink.is_some();
+        }
+        any
+    }
+
+    /// Write part of the current message to each sink that gets it.
+    fn write_bytes(&self, bytes: &[u8]) {
+        let level = self.message_level.get();
+        for slot in self.sinks.iter().filter(|slot| level <= slot.level.get()) {
+            slot.write(bytes);
+        }
+    }
+
+    /// Write a deferred message to each sink that gets it: `frame` to sinks
+    /// that take bytes and `text` to those that hold text.
+    fn write_deferred(&self, frame: &[u8], text: &[u8]) {
+        let level = self.message_level.get();
+        for slot in self.sinks.iter().filter(|slot| level <= slot.level.get()) {
+            if slot.sink.map_or(false, |sink| sink.is_text()) {
+                slot.write(text);
+            } else {
+                slot.write(frame);
+            }
+        }
+    }
+
+    fn publish_str(&self) {
+        for slot in self.sinks.iter() {
+            slot.sink.map(|sink| sink.publish());
+        }
+    }
+
+    fn flush(&self, writer: &mut dyn Write) {
+        for slot in self.sinks.iter() {
+            slot.sink.map(|sink| sink.flush(writer));
+        }
+        let dropped = self.dropped_bytes();
+        if dropped > 0 {
+            let _ = writer.write_fmt(format_args!(
+                "\r\n---| {} bytes of debug output were dropped\r\n",
+                dropped
+            ));
         }
     }
 }
 
+impl Write for DebugWriterWrapper {
+    fn write_str(&mut self, s: &str) -> Result {
+        self.write_bytes(s.as_bytes());
+        Ok(())
+    }
+}
+
 impl DebugWriter {
     pub fn new(
         uart: &'static dyn hil::uart::Transmit,
@@ -259,39 +555,22 @@ impl DebugWriter {
             head: Cell::new(0),       // first valid index in output_buffer
             tail: Cell::new(0),       // one past last valid index (wraps to 0)
             active_len: Cell::new(0), // how big is the current transaction?
-            count: Cell::new(0),      // how many debug! calls
         }
     }
 
-    fn increment_count(&self) {
-        self.count.increment();
-    }
-
-    fn get_count(&self) -> usize {
-        self.count.get()
-    }
-
-    /// Convenience method that writes (end-start) bytes from bytes into the
-    /// internal debug buffer.
-    fn write_buffer(&self, start: usize, end: usize, bytes: &[u8]) {
-        if end < start {
-            panic!("wb bounds: start {} end {} bytes {:?}", start, end, bytes);
-        }
-        self.internal_buffer.map(|in_buffer| {
-            for (dst, src) in in_buffer[start..end].iter_mut().zip(bytes.iter()) {
-                *dst = *src;
-            }
-        });
-    }
-
     /// Write as many of the bytes from the internal_buffer to the output
     /// mechanism as possible.
     fn publish_str(&self) {
+        let head = self.head.get();
+        let tail = self.tail.get();
+        if head == tail {
+            // Nothing to send.
+            return;
+        }
+
         // Can only publish if we have the output_buffer. If we don't that is
         // fine, we will do it when the transmit done callback happens.
         self.output_buffer.take().map(|out_buffer| {
-            let head = self.head.get();
-            let tail = self.tail.get();
             let len = self
                 .internal_buffer
                 .map_or(0, |internal_buffer| internal_buffer.len());
@@ -303,15 +582,9 @@ impl DebugWriter {
                 // write from tail to end of buffer. The completion
                 // callback will see that the buffer's not empty and
                 // call again to write the rest (tail will be 0)
-                let start = tail;
-                let end = len;
-                (start, end)
-            } else if tail < head {
-                let start = tail;
-                let end = head;
-                (start, end)
+                (tail, len)
             } else {
-                panic!("Consistency error: publish empty buffer?")
+                (tail, head)
             };
 
             // Check that we aren't writing a segment larger than the output
@@ -341,11 +614,60 @@ impl DebugWriter {
             }
         });
     }
+}
+
+impl DebugSink for DebugWriter {
+    fn write(&self, bytes: &[u8]) -> bool {
+        // Circular buffer.
+        //
+        // Note, we don't use the kernel's RingBuffer here because we want
+        // slightly different semantics. Specifically, we need to be able
+        // to take *contiguous* slices of the buffer and pass them around.
+        //
+        //  - head points to the index of the first valid place to write
+        //  - tail points to the first byte not yet sent
+        //  -> head == tail implies buffer is empty
+        //  -> there's no "full/empty" bit, so the effective buffer size is -1
+        self.internal_buffer.map_or(false, |buffer| {
+            let len = buffer.len();
+            let head = self.head.get();
+            let available = (self.tail.get() + len - head - 1) % len;
+            if bytes.len() > available {
+                return false;
+            }
+            for (i, byte) in bytes.iter().enumerate() {
+                buffer[(head + i) % len] = *byte;
+            }
+            self.head.set((head + bytes.len()) % len);
+            true
+        })
+    }
 
-    fn extract(&self) -> Option<(usize, usize, &mut [u8])> {
-        self.internal_buffer
-            .take()
-            .map(|buf| (self.head.get(), self.tail.get(), buf))
+    fn publish(&self) {
+        self.publish_str();
+    }
+
+    fn flush(&self, writer: &mut dyn Write) {
+        // Take the buffer so nothing else is written to it.
+        if let Some(buffer) = self.internal_buffer.take() {
+            let head = self.head.get();
+            let tail = self.tail.get();
+            if head != tail {
+                let _ = writer.write_str(
+                    "\r\n---| Debug buffer not empty. Flushing. May repeat some of last message(s):\r\n",
+                );
+
+                let (back, front) = if tail > head {
+                    (&buffer[tail..], &buffer[..head])
+                } else {
+                    (&buffer[tail..head], &buffer[..0])
+                };
+                unsafe {
+                    let _ = writer.write_str(str::from_utf8_unchecked(back));
+                    let _ = writer.write_str(str::from_utf8_unchecked(front));
+                }
+            }
+        }
     }
 }
 
@@ -366,12 +688,10 @@ impl hil::uart::TransmitClient for DebugWriter {
         let head = self.head.get();
         let mut tail = self.tail.get();
 
-        //panic!("Tail: {}, head: {}, tx_len: {}, rcode: {:?}", tail, head, tx_len, _rcode);
-
         // Increment the tail with how many bytes were written to the output
         // mechanism, and wrap if needed.
         tail += tx_len;
-        if tail > len {
+        if tail >= len {
             tail = tail - len;
         }
 
@@ -389,136 +709,143 @@ impl hil::uart::TransmitClient for DebugWriter {
     fn transmitted_word(&self, _rcode: ReturnCode) {}
 }
 
-/// Pass through functions.
-impl DebugWriterWrapper {
-    fn increment_count(&self) {
-        self.dw.map(|dw| {
-            dw.increment_count();
-        });
-    }
+/// Keeps the most recent debug output in RAM, overwriting the oldest.
+///
+/// Unlike a UART, it never falls behind, so it holds messages that were
+/// dropped elsewhere and those written just before a crash. The kernel prints
+/// it when it panics, and a debugger can read it from the buffer it was given,
+/// normally `RAM_LOG_BUF`.
+pub struct RamLog {
+    buffer: TakeCell<'static, [u8]>,
+    // Where the next byte is written.
+    head: Cell<usize>,
+    // Whether the buffer has been filled, so all of it holds output.
+    wrapped: Cell<bool>,
+}
 
-    fn get_count(&self) -> usize {
-        self.dw.map_or(0, |dw| dw.get_count())
+impl RamLog {
+    pub fn new(buffer: &'static mut [u8]) -> RamLog {
+        RamLog {
+            buffer: TakeCell::new(buffer),
+            head: Cell::new(0),
+            wrapped: Cell::new(false),
+        }
     }
+}
 
-    fn publish_str(&self) {
-        self.dw.map(|dw| {
-            dw.publish_str();
+impl DebugSink for RamLog {
+    fn write(&self, bytes: &[u8]) -> bool {
+        self.buffer.map(|buffer| {
+            let len = buffer.len();
+            let mut head = self.head.get();
+            for byte in bytes.iter() {
+                buffer[head] = *byte;
+                head += 1;
+                if head == len {
+                    head = 0;
+                    self.wrapped.set(true);
+                }
+            }
+            self.head.set(head);
         });
+        true
     }
 
-    fn extract(&self) -> Option<(usize, usize, &mut [u8])> {
-        self.dw.map_or(None, |dw| dw.extract())
+    fn is_text(&self) -> bool {
+        true
     }
-}
 
-impl Write for DebugWriterWrapper {
-    fn write_str(&mut self, s: &str) -> Result {
-        // Circular buffer.
-        //
-        // Note, we don't use the kernel's RingBuffer here because we want
-        // slightly different semantics. Specifically, we need to be able
-        // to take *contiguous* slices of the buffer and pass them around,
-        // we're also okay with fragmenting if we're inserting a slice over
-        // the internal wraparound, but we need to handle that case manually
-        //
-        //  - head points to the index of the first valid place to write
-        //  - tail points one past the index of the last open place to write
-        //  -> head == tail implies buffer is empty
-        //  -> there's no "full/empty" bit, so the effective buffer size is -1
-
-        self.dw.map(|dw| {
-            let mut head = dw.head.get();
-            let tail = dw.tail.get();
-            let len = dw.internal_buffer.map_or(0, |buffer| buffer.len());
-
-            let remaining_bytes = if head >= tail {
-                let bytes = s.as_bytes();
-
-                // First write from current head to end of buffer in memory
-                let mut backside_len = len - head;
-                if tail == 0 {
-                    // Handle special case where tail has just wrapped to 0,
-                    // so we can't let the head point to 0 as well
-                    backside_len -= 1;
-                }
-
-                let written = if backside_len != 0 {
-                    let start = head;
-                    let end = head + backside_len;
-                    dw.write_buffer(start, end, bytes);
-                    min(end - start, bytes.len())
-                } else {
-                    0
-                };
-
-                // Advance and possibly wrap the head
-                head += written;
-                if head == len {
-                    head = 0;
-                }
-                &bytes[written..]
+    fn flush(&self, writer: &mut dyn Write) {
+        self.buffer.map(|buffer| {
+            let head = self.head.get();
+            let _ = writer.write_str("\r\n---| Kernel Log |---\r\n");
+            let oldest = if self.wrapped.get() {
+                // The oldest byte may be in the middle of a character whose
+                // start was overwritten.
+                let oldest = &buffer[head..];
+                let start = oldest
+                    .iter()
+                    .position(|&byte| !is_utf8_continuation(byte))
+                    .unwrap_or(oldest.len());
+                &oldest[start..]
             } else {
-                s.as_bytes()
+                &[]
             };
-
-            // At this point, either
-            //  o head < tail
-            //  o head = len-1, tail = 0 (buffer full edge case)
-            //  o there are no more bytes to write
-
-            if remaining_bytes.len() != 0 {
-                // Now write from the head up to tail
-                let start = head;
-                let end = tail;
-                if (tail == 0) && (head == len - 1) {
-                    let active = dw.active_len.get();
-                    panic!(
-                        "Debug buffer full. Head {} tail {} len {} active {} remaining {}",
-                        head,
-                        tail,
-                        len,
-                        active,
-                        remaining_bytes.len()
-                    );
+            let mut newest = &buffer[..head];
+
+            // A character may also be split where the buffer wraps around.
+            let split = write_utf8(writer, oldest);
+            if !split.is_empty() {
+                let mut character = [0; 4];
+                let mut len = split.len();
+                character[..len].copy_from_slice(split);
+                while len < character.len() && !newest.is_empty() && is_utf8_continuation(newest[0])
+                {
+                    character[len] = newest[0];
+                    len += 1;
+                    newest = &newest[1..];
                 }
-                if remaining_bytes.len() > end - start {
-                    let active = dw.active_len.get();
-                    panic!(
-                        "Debug buffer out of room. Head {} tail {} len {} active {} remaining {}",
-                        head,
-                        tail,
-                        len,
-                        active,
-                        remaining_bytes.len()
-                    );
+                if !write_utf8(writer, &character[..len]).is_empty() {
+                    let _ = writer.write_char(char::REPLACEMENT_CHARACTER);
                 }
-                dw.write_buffer(start, end, remaining_bytes);
-                let written = min(end - start, remaining_bytes.len());
-
-                // head cannot wrap here
-                head += written;
             }
-
-            dw.head.set(head);
+            if !write_utf8(writer, newest).is_empty() {
+                let _ = writer.write_char(char::REPLACEMENT_CHARACTER);
+            }
         });
+    }
+}
 
-        Ok(())
+fn is_utf8_continuation(byte: u8) -> bool {
+    byte & 0xc0 == 0x80
+}
+
+/// Write `bytes` as text, replacing invalid UTF-8 with U+FFFD. Returns the
+/// bytes at the end that start a character without completing it.
+fn write_utf8<'a>(writer: &mut dyn Write, mut bytes: &'a [u8]) -> &'a [u8] {
+    loop {
+        match str::from_utf8(bytes) {
+            Ok(text) => {
+                let _ = writer.write_str(text);
+                return &[];
+            }
+            Err(error) => {
+                let (valid, rest) = bytes.split_at(error.valid_up_to());
+                let _ = writer.write_str(unsafe { str::from_utf8_unchecked(valid) });
+                match error.error_len() {
+                    Some(len) => {
+                        let _ = writer.write_char(char::REPLACEMENT_CHARACTER);
+                        bytes = &rest[len..];
+                    }
+                    None => return rest,
+                }
+            }
+        }
     }
 }
 
-pub fn begin_debug_fmt(args: Arguments) {
+pub fn begin_debug_fmt(module: &'static str, args: Arguments) {
     unsafe {
         let writer = get_debug_writer();
+        if !writer.begin_message(LogLevel::Debug, module) {
+            return;
+        }
         let _ = write(writer, args);
         let _ = writer.write_str("\r\n");
         writer.publish_str();
     }
 }
 
-pub fn begin_debug_verbose_fmt(args: Arguments, file_line: &(&'static str, u32)) {
+pub fn begin_debug_verbose_fmt(
+    module: &'static str,
+    args: Arguments,
+    file_line: &(&'static str, u32),
+) {
     unsafe {
         let writer = get_debug_writer();
+        if !writer.begin_message(LogLevel::Debug, module) {
+            return;
+        }
 
         writer.increment_count();
         let count = writer.get_count();
@@ -531,6 +858,19 @@ pub fn begin_debug_verbose_fmt(args: Arguments, file_line: &(&'static str, u32))
     }
 }
 
+pub fn begin_log_fmt(level: LogLevel, module: &'static str, args: Arguments) {
+    unsafe {
+        let writer = get_debug_writer();
+        if !writer.begin_message(level, module) {
+            return;
+        }
+        let _ = writer.write_fmt(format_args!("{} {}: ", level.tag(), module));
+        let _ = write(writer, args);
+        let _ = writer.write_str("\r\n");
+        writer.publish_str();
+    }
+}
+
 /// In-kernel `println()` debugging.
 #[macro_export]
 macro_rules! debug {
@@ -539,10 +879,10 @@ macro_rules! debug {
         debug!("")
     });
     ($msg:expr) => ({
-        $crate::debug::begin_debug_fmt(format_args!($msg))
+        $crate::debug::begin_debug_fmt(module_path!(), format_args!($msg))
     });
     ($fmt:expr, $($arg:tt)+) => ({
-        $crate::debug::begin_debug_fmt(format_args!($fmt, $($arg)+))
+        $crate::debug::begin_debug_fmt(module_path!(), format_args!($fmt, $($arg)+))
     });
 }
 
@@ -554,7 +894,7 @@ macro_rules! debug_verbose {
         debug_verbose!("")
     });
     ($msg:expr) => ({
-        $crate::debug::begin_debug_verbose_fmt(format_args!($msg), {
+        $crate::debug::begin_debug_verbose_fmt(module_path!(), format_args!($msg), {
             // TODO: Maybe make opposite choice of panic!, no `static`, more
             // runtime code for less static data
             static _FILE_LINE: (&'static str, u32) = (file!(), line!());
@@ -562,13 +902,289 @@ macro_rules! debug_verbose {
         })
     });
     ($fmt:expr, $($arg:tt)+) => ({
-        $crate::debug::begin_debug_verbose_fmt(format_args!($fmt, $($arg)+), {
+        $crate::debug::begin_debug_verbose_fmt(module_path!(), format_args!($fmt, $($arg)+), {
             static _FILE_LINE: (&'static str, u32) = (file!(), line!());
             &_FILE_LINE
         })
     });
 }
 
+/// Log an error, prefixed with the level and module.
+#[macro_export]
+macro_rules! log_error {
+    ($($arg:tt)+) => ({
+        $crate::debug::begin_log_fmt($crate::debug::LogLevel::Error, module_path!(), format_args!($($arg)+))
+    });
+}
+
+/// Log a warning, prefixed with the level and module.
+#[macro_export]
+macro_rules! log_warn {
+    ($($arg:tt)+) => ({
+        $crate::debug::begin_log_fmt($crate::debug::LogLevel::Warn, module_path!(), format_args!($($arg)+))
+    });
+}
+
+/// Log an informational message, prefixed with the level and module.
+#[macro_export]
+macro_rules! log_info {
+    ($($arg:tt)+) => ({
+        $crate::debug::begin_log_fmt($crate::debug::LogLevel::Info, module_path!(), format_args!($($arg)+))
+    });
+}
+
+/// Log a debugging message, prefixed with the level and module.
+#[macro_export]
+macro_rules! log_debug {
+    ($($arg:tt)+) => ({
+        $crate::debug::begin_log_fmt($crate::debug::LogLevel::Debug, module_path!(), format_args!($($arg)+))
+    });
+}
+
+/// Log a tracing message, prefixed with the level and module. Sinks do not
+/// get these unless their level is set to `Trace`.
+#[macro_export]
+macro_rules! log_trace {
+    ($($arg:tt)+) => ({
+        $crate::debug::begin_log_fmt($crate::debug::LogLevel::Trace, module_path!(), format_args!($($arg)+))
+    });
+}
+
+///////////////////////////////////////////////////////////////////
+// debug_deferred! support
+//
+// Deferred messages are not formatted on the chip. The format string is
+// placed in the `.tock_log_strings` section, which is not loaded onto the
+// chip, and only its address and the raw arguments are sent. The host
+// decoder, `tools/decode_deferred_log.py`, finds the format string in the
+// kernel's ELF file and formats the message.
+//
+// Each message is a record made of the address of its format string and the
+// arguments, all as LEB128 varints:
+//
+//   record   = varint(address) argument*
+//   argument = 0x00 varint(unsigned)
+//            | 0x01 varint(zigzag(signed))
+//            | 0x02 varint(length) bytes      (a UTF-8 string)
+//
+// A record is COBS encoded so it does not contain `0x00`, and sent between
+// two `0x00` bytes. Text output never contains `0x00`, so the decoder can
+// tell records and text apart in the same stream.
+//
+// Sinks that hold text, such as `RamLog`, would mangle a binary frame when
+// they print it as UTF-8, so they get the record as a line of hex instead:
+//
+//   [deferred 0a1b2c...]\r\n
+
+/// Longest record, before COBS encoding. Arguments that do not fit are left
+/// out, and the decoder shows them as `?`.
+const MAX_DEFERRED_RECORD: usize = 64;
+
+const DEFERRED_TAG_UNSIGNED: u8 = 0;
+const DEFERRED_TAG_SIGNED: u8 = 1;
+const DEFERRED_TAG_STR: u8 = 2;
+
+/// Start of the line a record is hex encoded in for text sinks.
+const DEFERRED_TEXT_START: &[u8] = b"[deferred ";
+
+/// End of the line a record is hex encoded in for text sinks.
+const DEFERRED_TEXT_END: &[u8] = b"]\r\n";
+
+/// Longest hex encoded record, including the start and end of its line.
+const MAX_DEFERRED_TEXT: usize =
+    DEFERRED_TEXT_START.len() + 2 * MAX_DEFERRED_RECORD + DEFERRED_TEXT_END.len();
+
+/// A deferred message being encoded.
+pub struct DeferredRecord {
+    buffer: [u8; MAX_DEFERRED_RECORD],
+    len: usize,
+    // Set once an argument did not fit, so later ones are left out too.
+    full: bool,
+}
+
+impl DeferredRecord {
+    fn new(address: usize) -> DeferredRecord {
+        let mut record = DeferredRecord {
+            buffer: [0; MAX_DEFERRED_RECORD],
+            len: 0,
+            full: false,
+        };
+        let mut varint = [0; 10];
+        let len = encode_varint(address as u64, &mut varint);
+        record.push(&varint[..len]);
+        record
+    }
+
+    /// Add `bytes` if all of them fit.
+    fn push(&mut self, bytes: &[u8]) {
+        if self.full || self.len + bytes.len() > self.buffer.len() {
+            self.full = true;
+            return;
+        }
+        self.buffer[self.len..self.len + bytes.len()].copy_from_slice(bytes);
+        self.len += bytes.len();
+    }
+
+    fn push_unsigned(&mut self, tag: u8, value: u64) {
+        let mut argument = [0; 11];
+        argument[0] = tag;
+        let len = encode_varint(value, &mut argument[1..]);
+        self.push(&argument[..len + 1]);
+    }
+
+    fn push_signed(&mut self, value: i64) {
+        // Zigzag encoding keeps small negative values short.
+        self.push_unsigned(DEFERRED_TAG_SIGNED, ((value << 1) ^ (value >> 63)) as u64);
+    }
+
+    fn push_str(&mut self, value: &str) {
+        let mut header = [0; 11];
+        header[0] = DEFERRED_TAG_STR;
+        let len = encode_varint(value.len() as u64, &mut header[1..]);
+        if self.full || self.len + len + 1 + value.len() > self.buffer.len() {
+            self.full = true;
+            return;
+        }
+        self.push(&header[..len + 1]);
+        self.push(value.as_bytes());
+    }
+
+    /// COBS encode the record between two `0x00` bytes into `frame`, and
+    /// return its length.
+    fn frame(&self, frame: &mut [u8; MAX_DEFERRED_RECORD + 3]) -> usize {
+        frame[0] = 0;
+        let mut code_index = 1;
+        let mut out = 2;
+        let mut code = 1;
+        for &byte in self.buffer[..self.len].iter() {
+            if byte == 0 {
+                frame[code_index] = code;
+                code_index = out;
+                out += 1;
+                code = 1;
+            } else {
+                frame[out] = byte;
+                out += 1;
+                code += 1;
+            }
+        }
+        frame[code_index] = code;
+        frame[out] = 0;
+        out + 1
+    }
+
+    /// Write the record as a line of hex into `text`, and return its length.
+    fn text(&self, text: &mut [u8; MAX_DEFERRED_TEXT]) -> usize {
+        const HEX: &[u8; 16] = b"0123456789abcdef";
+        let mut out = DEFERRED_TEXT_START.len();
+        text[..out].copy_from_slice(DEFERRED_TEXT_START);
+        for &byte in self.buffer[..self.len].iter() {
+            text[out] = HEX[(byte >> 4) as usize];
+            text[out + 1] = HEX[(byte & 0xf) as usize];
+            out += 2;
+        }
+        text[out..out + DEFERRED_TEXT_END.len()].copy_from_slice(DEFERRED_TEXT_END);
+        out + DEFERRED_TEXT_END.len()
+    }
+}
+
+/// Write `value` to `out` as a LEB128 varint and return its length.
+fn encode_varint(mut value: u64, out: &mut [u8]) -> usize {
+    let mut len = 0;
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out[len] = byte;
+            return len + 1;
+        }
+        out[len] = byte | 0x80;
+        len += 1;
+    }
+}
+
+/// An argument of a `debug_deferred!()` message.
+pub trait DeferredArg {
+    fn encode(&self, record: &mut DeferredRecord);
+}
+
+macro_rules! deferred_unsigned {
+    ($($t:ty),*) => {$(
+        impl DeferredArg for $t {
+            fn encode(&self, record: &mut DeferredRecord) {
+                record.push_unsigned(DEFERRED_TAG_UNSIGNED, *self as u64);
+            }
+        }
+    )*};
+}
+
+macro_rules! deferred_signed {
+    ($($t:ty),*) => {$(
+        impl DeferredArg for $t {
+            fn encode(&self, record: &mut DeferredRecord) {
+                record.push_signed(*self as i64);
+            }
+        }
+    )*};
+}
+
+deferred_unsigned!(u8, u16, u32, u64, usize, bool);
+deferred_signed!(i8, i16, i32, i64, isize);
+
+impl DeferredArg for &str {
+    fn encode(&self, record: &mut DeferredRecord) {
+        record.push_str(self);
+    }
+}
+
+pub fn begin_deferred_fmt(module: &'static str, address: usize, args: &[&dyn DeferredArg]) {
+    unsafe {
+        let writer = get_debug_writer();
+        if !writer.begin_message(LogLevel::Debug, module) {
+            return;
+        }
+        let mut record = DeferredRecord::new(address);
+        for arg in args.iter() {
+            arg.encode(&mut record);
+        }
+        let mut frame = [0; MAX_DEFERRED_RECORD + 3];
+        let frame_len = record.frame(&mut frame);
+        let mut text = [0; MAX_DEFERRED_TEXT];
+        let text_len = record.text(&mut text);
+        writer.write_deferred(&frame[..frame_len], &text[..text_len]);
+        writer.publish_str();
+    }
+}
+
+/// In-kernel debugging that leaves formatting to the host.
+///
+/// Takes a string literal and the same arguments as `debug!()`, which can be
+/// integers, `bool`s and `&str`s. Instead of formatting the message, only
+/// the location of the format string and the arguments are sent, which is
+/// faster and keeps the format strings out of the kernel image. Use
+/// `tools/decode_deferred_log.py` to read the output.
+#[macro_export]
+#[allow_internal_unstable(const_raw_ptr_deref)]
+macro_rules! debug_deferred {
+    ($fmt:literal $(, $arg:expr)* $(,)?) => ({
+        // Check the arguments against the format string, without using
+        // them.
+        if false {
+            let _ = format_args!($fmt $(, $arg)*);
+        }
+        const FORMAT: &str = concat!($fmt, "\0");
+        #[link_section = ".tock_log_strings"]
+        #[used]
+        static INTERNED: [u8; FORMAT.len()] =
+            unsafe { *(FORMAT.as_ptr() as *const [u8; FORMAT.len()]) };
+        $crate::debug::begin_deferred_fmt(
+            module_path!(),
+            &INTERNED as *const _ as usize,
+            &[$(&$arg as &dyn $crate::debug::DeferredArg),*],
+        )
+    });
+}
+
 pub trait Debug {
     fn write(&self, buf: &'static mut [u8], len: usize);
 }
@@ -583,30 +1199,8 @@ impl Default for Debug {
     }
 }
 
+/// Synchronously write out the debug output that has not been sent yet, and
+/// the contents of any RAM log.
 pub unsafe fn flush<W: Write>(writer: &mut W) {
-    let debug_writer = get_debug_writer();
-
-    if let Some((head, mut tail, buffer)) = debug_writer.extract() {
-        if head != tail {
-            let _ = writer.write_str(
-                "\r\n---| Debug buffer not empty. Flushing. May repeat some of last message(s):\r\n",
-            );
-
-            if tail > head {
-                let start = buffer.as_mut_ptr().add(tail);
-                let len = buffer.len();
-                let slice = slice::from_raw_parts(start, len);
-                let s = str::from_utf8_unchecked(slice);
-                let _ = writer.write_str(s);
-                tail = 0;
-            }
-            if tail != head {
-                let start = buffer.as_mut_ptr().add(tail);
-                let len = head - tail;
-                let slice = slice::from_raw_parts(start, len);
-                let s = str::from_utf8_unchecked(slice);
-                let _ = writer.write_str(s);
-            }
-        }
-    }
+    get_debug_writer().flush(writer);
 }
@@ -10,7 +10,8 @@
 //!   * `subscribe` lets an application pass a callback to the driver to be
 //!   called later, when an event has occurred or data of interest is available.
 //!
-//!   * `command` tells the driver to do something immediately.
+//!   * `command` tells the driver to do something immediately. Its typed
+//!   variant, `typed_command`, can return several values at once.
 //!
 //!   * `allow` provides the driver access to an application buffer.
 //!
@@ -39,8 +40,9 @@
 //! understand its function and how it interacts with `subscribe`.
 
 use crate::callback::{AppId, Callback};
-use crate::mem::{AppSlice, Shared};
+use crate::mem::{AppSlice, ReadOnlyAppSlice, Shared};
 use crate::returncode::ReturnCode;
+use crate::syscall::SyscallReturn;
 
 /// `Driver`s implement the three driver-specific system calls: `subscribe`,
 /// `command` and `allow`.
@@ -96,6 +98,22 @@ pub trait Driver {
         ReturnCode::ENOSUPPORT
     }
 
+    /// `typed_command` is `command` for results that do not fit in a
+    /// `ReturnCode`, like a 64-bit timestamp or a length together with a
+    /// status. It takes the same arguments as `command`.
+    ///
+    /// By default it runs `command` and converts its result, so drivers only
+    /// need to implement it for the commands that return more than one value.
+    fn typed_command(
+        &self,
+        minor_num: usize,
+        r2: usize,
+        r3: usize,
+        caller_id: AppId,
+    ) -> SyscallReturn {
+        self.command(minor_num, r2, r3, caller_id).into()
+    }
+
     /// `allow` lets an application give the driver access to a buffer in the
     /// application's memory. This returns `ENOSUPPORT` if not used.
     ///
@@ -111,4 +129,20 @@ pub trait Driver {
     ) -> ReturnCode {
         ReturnCode::ENOSUPPORT
     }
+
+    /// `allow_read_only` lets an application give the driver read-only access
+    /// to a buffer in the application's memory or flash. This returns
+    /// `ENOSUPPORT` if not used.
+    ///
+    /// The allow numbers are separate from those of `allow`, though drivers
+    /// may use the same number for a buffer that can be shared either way.
+    #[allow(unused_variables)]
+    fn allow_read_only(
+        &self,
+        app: AppId,
+        minor_num: usize,
+        slice: Option<ReadOnlyAppSlice<u8>>,
+    ) -> ReturnCode {
+        ReturnCode::ENOSUPPORT
+    }
 }
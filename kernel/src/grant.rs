@@ -18,6 +18,7 @@ pub struct Grant<T: Default> {
 
 pub struct AppliedGrant<T> {
     appid: AppId,
+    grant_num: usize,
     grant: *mut T,
     _phantom: PhantomData<T>,
 }
@@ -28,7 +29,10 @@ impl<T> AppliedGrant<T> {
         F: FnOnce(&mut Owned<T>, &mut Allocator) -> R,
         R: Copy,
     {
-        let mut allocator = Allocator { appid: self.appid };
+        let mut allocator = Allocator {
+            appid: self.appid,
+            grant_num: self.grant_num,
+        };
         let mut root = unsafe { Owned::new(self.grant, self.appid) };
         fun(&mut root, &mut allocator)
     }
@@ -36,6 +40,8 @@ impl<T> AppliedGrant<T> {
 
 pub struct Allocator {
     appid: AppId,
+    /// The grant the allocated memory is accounted to.
+    grant_num: usize,
 }
 
 pub struct Owned<T: ?Sized> {
@@ -60,11 +66,9 @@ impl<T: ?Sized> Drop for Owned<T> {
     fn drop(&mut self) {
         unsafe {
             let data = self.data.as_ptr() as *mut u8;
-            self.appid
-                .kernel
-                .process_map_or((), self.appid.idx(), |process| {
-                    process.free(data);
-                });
+            self.appid.kernel.process_map_or((), self.appid, |process| {
+                process.free(data);
+            });
         }
     }
 }
@@ -87,17 +91,16 @@ impl Allocator {
         unsafe {
             self.appid
                 .kernel
-                .process_map_or(Err(Error::NoSuchApp), self.appid.idx(), |process| {
-                    process.alloc(size_of::<T>(), align_of::<T>()).map_or(
-                        Err(Error::OutOfMemory),
-                        |arr| {
+                .process_map_or(Err(Error::NoSuchApp), self.appid, |process| {
+                    process
+                        .alloc(size_of::<T>(), align_of::<T>(), self.grant_num)
+                        .map(|arr| {
                             let ptr = arr.as_mut_ptr() as *mut T;
                             // We use `ptr::write` to avoid `Drop`ping the uninitialized memory in
                             // case `T` implements the `Drop` trait.
                             write(ptr, data);
-                            Ok(Owned::new(ptr, self.appid))
-                        },
-                    )
+                            Owned::new(ptr, self.appid)
+                        })
                 })
         }
     }
@@ -145,13 +148,14 @@ impl<T: Default> Grant<T> {
 
     pub fn grant(&self, appid: AppId) -> Option<AppliedGrant<T>> {
         unsafe {
-            appid.kernel.process_map_or(None, appid.idx(), |process| {
+            appid.kernel.process_map_or(None, appid, |process| {
                 let cntr = *(process.grant_ptr(self.grant_num) as *mut *mut T);
                 if cntr.is_null() {
                     None
                 } else {
                     Some(AppliedGrant {
                         appid: appid,
+                        grant_num: self.grant_num,
                         grant: cntr,
                         _phantom: PhantomData,
                     })
@@ -168,7 +172,7 @@ impl<T: Default> Grant<T> {
         unsafe {
             appid
                 .kernel
-                .process_map_or(Err(Error::NoSuchApp), appid.idx(), |process| {
+                .process_map_or(Err(Error::NoSuchApp), appid, |process| {
                     // Here is an example of how the grants are laid out in a
                     // process's memory:
                     //
@@ -204,7 +208,7 @@ impl<T: Default> Grant<T> {
                     // memory needs to be allocated.
                     let new_grant = if (*ctr_ptr).is_null() {
                         process
-                            .alloc(size_of::<T>(), align_of::<T>())
+                            .alloc(size_of::<T>(), align_of::<T>(), self.grant_num)
                             .map(|root_arr| {
                                 let root_ptr = root_arr.as_mut_ptr() as *mut T;
                                 // Initialize the grant contents using ptr::write, to
@@ -216,18 +220,20 @@ impl<T: Default> Grant<T> {
                                 root_ptr
                             })
                     } else {
-                        Some(*ctr_ptr)
+                        Ok(*ctr_ptr)
                     };
 
                     // If the grant region already exists or there was enough
                     // memory to allocate it, call the passed in closure with
                     // the borrowed grant region.
-                    new_grant.map_or(Err(Error::OutOfMemory), move |root_ptr| {
+                    new_grant.map(move |root_ptr| {
                         let root_ptr = root_ptr as *mut T;
                         let mut root = Borrowed::new(&mut *root_ptr, appid);
-                        let mut allocator = Allocator { appid: appid };
-                        let res = fun(&mut root, &mut allocator);
-                        Ok(res)
+                        let mut allocator = Allocator {
+                            appid: appid,
+                            grant_num: self.grant_num,
+                        };
+                        fun(&mut root, &mut allocator)
                     })
                 })
         }
@@ -268,7 +274,10 @@ impl<T: Default> Iterator for Iter<'a, T> {
         while self.index < self.len {
             let idx = self.index;
             self.index += 1;
-            let res = self.grant.grant(AppId::new(self.grant.kernel, idx));
+            let res = self
+                .grant
+                .kernel
+                .process_slot_map_or(None, idx, |process| self.grant.grant(process.appid()));
             if res.is_some() {
                 return res;
             }
@@ -0,0 +1,198 @@
+//! Detects processes that stop making progress.
+//!
+//! A process asks to be monitored by telling the driver how often it will
+//! send a heartbeat. If it goes longer than that without one, for example
+//! because it is stuck waiting for a callback that never comes or spinning
+//! in a loop, the kernel faults it. What happens next is decided by its fault
+//! response, so a process that is restarted on faults is restarted instead of
+//! hanging silently.
+//!
+//! The kernel only checks heartbeats while it runs, so it also depends on the
+//! board's watchdog, see `Platform::watchdog()`, to recover if the kernel
+//! itself hangs.
+//!
+//! Usage
+//! -----
+//!
+//! ```ignore
+//! let heartbeat_alarm = static_init!(
+//!     VirtualMuxAlarm<'static, sam4l::ast::Ast>,
+//!     VirtualMuxAlarm::new(mux_alarm)
+//! );
+//! let heartbeat = static_init!(
+//!     kernel::heartbeat::Heartbeat<'static, VirtualMuxAlarm<'static, sam4l::ast::Ast>>,
+//!     kernel::heartbeat::Heartbeat::new(
+//!         board_kernel,
+//!         heartbeat_alarm,
+//!         &memory_allocation_capability,
+//!     )
+//! );
+//! heartbeat_alarm.set_client(heartbeat);
+//! ```
+
+use core::cell::Cell;
+
+use crate::callback::AppId;
+use crate::capabilities::MemoryAllocationCapability;
+use crate::driver::Driver;
+use crate::grant::Grant;
+use crate::hil::time::{self, Frequency};
+use crate::returncode::ReturnCode;
+use crate::sched::Kernel;
+
+/// Syscall number
+pub const DRIVER_NUM: usize = 0x10003;
+
+#[derive(Default)]
+pub struct HeartbeatData {
+    /// Whether the process asked to be monitored.
+    enabled: bool,
+    /// How long the process may go between heartbeats, in alarm tics.
+    period: u32,
+    /// When the process must send its next heartbeat, in alarm tics.
+    deadline: u32,
+}
+
+pub struct Heartbeat<'a, A: time::Alarm<'a>> {
+    kernel: &'static Kernel,
+    alarm: &'a A,
+    data: Grant<HeartbeatData>,
+}
+
+impl<A: time::Alarm<'a>> Heartbeat<'a, A> {
+    pub fn new(
+        kernel: &'static Kernel,
+        alarm: &'a A,
+        capability: &dyn MemoryAllocationCapability,
+    ) -> Heartbeat<'a, A> {
+        Heartbeat {
+            kernel: kernel,
+            alarm: alarm,
+            data: kernel.create_grant(capability),
+        }
+    }
+
+    /// Whether `deadline` has passed at `now`, allowing for the clock
+    /// wrapping around.
+    fn expired(now: u32, deadline: u32) -> bool {
+        (now.wrapping_sub(deadline) as i32) >= 0
+    }
+
+    /// Set the alarm for the earliest deadline of any monitored process, or
+    /// disable it if there are none.
+    fn reschedule(&self) {
+        let now = self.alarm.now();
+        let earliest: Cell<Option<u32>> = Cell::new(None);
+        self.data.each(|data| {
+            if data.enabled {
+                let remaining = if Self::expired(now, data.deadline) {
+                    0
+                } else {
+                    data.deadline.wrapping_sub(now)
+                };
+                if earliest.get().map_or(true, |earliest| remaining < earliest) {
+                    earliest.set(Some(remaining));
+                }
+            }
+        });
+        match earliest.get() {
+            Some(remaining) => self.alarm.set_alarm(now.wrapping_add(remaining.max(1))),
+            None => self.alarm.disable(),
+        }
+    }
+
+    /// Start monitoring `appid`, which will send a heartbeat at least every
+    /// `period_ms` milliseconds.
+    fn start(&self, appid: AppId, period_ms: usize) -> ReturnCode {
+        let frequency = <A::Frequency>::frequency() as u64;
+        let period = period_ms as u64 * frequency / 1000;
+        // Leave room to tell expired deadlines apart from future ones.
+        if period == 0 || period > core::i32::MAX as u64 {
+            return ReturnCode::EINVAL;
+        }
+        let rval = self
+            .data
+            .enter(appid, |data, _| {
+                data.enabled = true;
+                data.period = period as u32;
+                data.deadline = self.alarm.now().wrapping_add(data.period);
+                ReturnCode::SUCCESS
+            })
+            .unwrap_or_else(|err| err.into());
+        self.reschedule();
+        rval
+    }
+
+    /// Record a heartbeat from `appid`.
+    fn beat(&self, appid: AppId) -> ReturnCode {
+        self.data
+            .enter(appid, |data, _| {
+                if !data.enabled {
+                    return ReturnCode::EOFF;
+                }
+                data.deadline = self.alarm.now().wrapping_add(data.period);
+                ReturnCode::SUCCESS
+            })
+            .unwrap_or_else(|err| err.into())
+    }
+
+    fn stop(&self, appid: AppId) -> ReturnCode {
+        let rval = self
+            .data
+            .enter(appid, |data, _| {
+                data.enabled = false;
+                ReturnCode::SUCCESS
+            })
+            .unwrap_or_else(|err| err.into());
+        self.reschedule();
+        rval
+    }
+}
+
+impl<A: time::Alarm<'a>> time::AlarmClient for Heartbeat<'a, A> {
+    fn fired(&self) {
+        let now = self.alarm.now();
+        // Fault one process at a time, outside of `each()`, since faulting a
+        // process clears its grant region.
+        loop {
+            let missed: Cell<Option<AppId>> = Cell::new(None);
+            self.data.each(|data| {
+                // Only take the first expired process on each pass, so every
+                // disabled entry also gets faulted.
+                if missed.get().is_none() && data.enabled && Self::expired(now, data.deadline) {
+                    data.enabled = false;
+                    missed.set(Some(data.appid()));
+                }
+            });
+            match missed.get() {
+                Some(appid) => {
+                    self.kernel
+                        .process_map_or((), appid, |process| process.set_fault_state());
+                }
+                None => break,
+            }
+        }
+        self.reschedule();
+    }
+}
+
+impl<A: time::Alarm<'a>> Driver for Heartbeat<'a, A> {
+    /// Command interface.
+    ///
+    /// ### `command_num`
+    ///
+    /// - `0`: Driver check.
+    /// - `1`: Start monitoring this process, which will send a heartbeat at
+    ///        least every `data` milliseconds.
+    /// - `2`: Send a heartbeat.
+    /// - `3`: Stop monitoring this process.
+    fn command(&self, command_num: usize, data: usize, _: usize, appid: AppId) -> ReturnCode {
+        match command_num {
+            0 => ReturnCode::SUCCESS,
+            1 => self.start(appid, data),
+            2 => self.beat(appid),
+            3 => self.stop(appid),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
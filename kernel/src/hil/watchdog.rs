@@ -11,4 +11,23 @@ pub trait Watchdog {
     /// Service the watchdog to let the hardware know the application
     /// is still executing.
     fn tickle(&self);
+
+    /// Pause the watchdog while it cannot be serviced, for example while the
+    /// chip sleeps for longer than the period. By default this stops it.
+    fn suspend(&self) {
+        self.stop();
+    }
+
+    /// The period in milliseconds the watchdog was last started with, or
+    /// `None` if it was never started or the implementation does not keep it.
+    fn period(&self) -> Option<usize> {
+        None
+    }
+
+    /// Continue after `suspend()`, with the period the watchdog was last
+    /// started with. By default this starts it again with `period()`, and
+    /// does nothing if that is `None`.
+    fn resume(&self) {
+        self.period().map(|period| self.start(period));
+    }
 }
@@ -15,6 +15,7 @@ use core::cell::Cell;
 use crate::callback::AppId;
 use crate::capabilities::ProcessManagementCapability;
 use crate::common::cells::NumericCellExt;
+use crate::power::Residency;
 use crate::process;
 use crate::sched::Kernel;
 
@@ -78,7 +79,7 @@ impl KernelInfo {
         _capability: &dyn ProcessManagementCapability,
     ) -> &'static str {
         self.kernel
-            .process_map_or("unknown", app.idx(), |process| process.get_process_name())
+            .process_map_or("unknown", app, |process| process.get_process_name())
     }
 
     /// Returns the number of syscalls the app has called.
@@ -88,7 +89,7 @@ impl KernelInfo {
         _capability: &dyn ProcessManagementCapability,
     ) -> usize {
         self.kernel
-            .process_map_or(0, app.idx(), |process| process.debug_syscall_count())
+            .process_map_or(0, app, |process| process.debug_syscall_count())
     }
 
     /// Returns the number of dropped callbacks the app has experience.
@@ -99,9 +100,8 @@ impl KernelInfo {
         app: AppId,
         _capability: &dyn ProcessManagementCapability,
     ) -> usize {
-        self.kernel.process_map_or(0, app.idx(), |process| {
-            process.debug_dropped_callback_count()
-        })
+        self.kernel
+            .process_map_or(0, app, |process| process.debug_dropped_callback_count())
     }
 
     /// Returns the number of time this app has been restarted.
@@ -111,7 +111,7 @@ impl KernelInfo {
         _capability: &dyn ProcessManagementCapability,
     ) -> usize {
         self.kernel
-            .process_map_or(0, app.idx(), |process| process.debug_restart_count())
+            .process_map_or(0, app, |process| process.debug_restart_count())
     }
 
     /// Returns the number of time this app has exceeded its timeslice.
@@ -120,9 +120,8 @@ impl KernelInfo {
         app: AppId,
         _capability: &dyn ProcessManagementCapability,
     ) -> usize {
-        self.kernel.process_map_or(0, app.idx(), |process| {
-            process.debug_timeslice_expiration_count()
-        })
+        self.kernel
+            .process_map_or(0, app, |process| process.debug_timeslice_expiration_count())
     }
 
     /// Returns the total number of times all processes have exceeded
@@ -134,4 +133,114 @@ impl KernelInfo {
         });
         count.get()
     }
+
+    /// Returns how many microseconds of CPU time the app has used, including
+    /// time the kernel spent handling its system calls. Time is measured with
+    /// the system tick timer, so it is `None` on chips without one.
+    pub fn app_cpu_time_us(
+        &self,
+        app: AppId,
+        _capability: &dyn ProcessManagementCapability,
+    ) -> Option<u64> {
+        if !self.kernel.cpu_time_measured() {
+            return None;
+        }
+        Some(
+            self.kernel
+                .process_map_or(0, app, |process| process.debug_cpu_time_us()),
+        )
+    }
+
+    /// Returns how many microseconds of CPU time the kernel has used to
+    /// service interrupts and deferred calls, outside of any process, or
+    /// `None` on chips without a system tick timer.
+    pub fn kernel_cpu_time_us(&self, _capability: &dyn ProcessManagementCapability) -> Option<u64> {
+        if !self.kernel.cpu_time_measured() {
+            return None;
+        }
+        Some(self.kernel.kernel_time_us())
+    }
+
+    /// Returns the CPU time used by the kernel and all processes together, in
+    /// microseconds, or `None` on chips without a system tick timer. This
+    /// does not include time the chip spent sleeping.
+    pub fn total_cpu_time_us(&self, capability: &dyn ProcessManagementCapability) -> Option<u64> {
+        self.kernel_cpu_time_us(capability).map(|kernel_time_us| {
+            let time: Cell<u64> = Cell::new(kernel_time_us);
+            self.kernel
+                .process_each(|process| time.set(time.get() + process.debug_cpu_time_us()));
+            time.get()
+        })
+    }
+
+    /// Returns how many sleep states the chip has. This is zero until the chip
+    /// has slept for the first time.
+    pub fn number_sleep_states(&self, _capability: &dyn ProcessManagementCapability) -> usize {
+        self.kernel.sleep_states().len()
+    }
+
+    /// Returns the name of sleep state `state`, from zero for the lightest,
+    /// and how often and for how long the chip has slept in it.
+    pub fn sleep_state_residency(
+        &self,
+        state: usize,
+        _capability: &dyn ProcessManagementCapability,
+    ) -> Option<(&'static str, Residency)> {
+        let name = self.kernel.sleep_states().get(state)?.name;
+        self.kernel
+            .sleep_residency(state)
+            .map(|residency| (name, residency))
+    }
+
+    /// Returns how many grants capsules have created. Grants are numbered
+    /// from 0 in the order they were created with `Kernel::create_grant()`.
+    pub fn number_grants(&self, _capability: &dyn ProcessManagementCapability) -> usize {
+        self.kernel.number_of_grants()
+    }
+
+    /// Returns how many bytes of grant memory have been allocated for the app
+    /// by all capsules.
+    pub fn number_app_grant_bytes(
+        &self,
+        app: AppId,
+        _capability: &dyn ProcessManagementCapability,
+    ) -> usize {
+        self.kernel
+            .process_map_or(0, app, |process| process.grant_bytes_allocated())
+    }
+
+    /// Returns how many bytes of grant memory have been allocated for the app
+    /// through the grant with the given number, i.e. by the capsule that owns
+    /// that grant.
+    pub fn number_app_grant_bytes_in_grant(
+        &self,
+        app: AppId,
+        grant_num: usize,
+        _capability: &dyn ProcessManagementCapability,
+    ) -> usize {
+        self.kernel.process_map_or(0, app, |process| {
+            process.grant_bytes_allocated_for(grant_num)
+        })
+    }
+
+    /// Returns the most bytes of grant memory the app may allocate, or `None`
+    /// if it is not limited.
+    pub fn app_grant_quota(
+        &self,
+        app: AppId,
+        _capability: &dyn ProcessManagementCapability,
+    ) -> Option<usize> {
+        self.kernel
+            .process_map_or(None, app, |process| process.grant_quota())
+    }
+
+    /// Calls `f` with the most recent record in the board's process fault log,
+    /// if the board has a fault log and it holds a record.
+    pub fn map_last_process_fault(
+        &self,
+        f: &mut dyn FnMut(&[u8]),
+        _capability: &dyn ProcessManagementCapability,
+    ) {
+        self.kernel.map_last_fault(f);
+    }
 }
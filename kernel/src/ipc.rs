@@ -1,12 +1,16 @@
 //! Inter-process communication mechanism for Tock.
 //!
-//! This is a special syscall driver that allows userspace applications to
-//! share memory.
+//! There are two special syscall drivers for IPC. `IPC` allows userspace
+//! applications to share memory and notify each other. `Mailbox` has the
+//! kernel copy small messages from one process to another, so processes can
+//! exchange commands without sharing buffers.
+
+use core::cmp;
 
 use crate::callback::{AppId, Callback};
 use crate::capabilities::MemoryAllocationCapability;
 use crate::driver::Driver;
-use crate::grant::Grant;
+use crate::grant::{Grant, Owned};
 use crate::mem::{AppSlice, Shared};
 use crate::process;
 use crate::returncode::ReturnCode;
@@ -15,9 +19,13 @@ use crate::sched::Kernel;
 /// Syscall number
 pub const DRIVER_NUM: usize = 0x10000;
 
+/// Per-process IPC state. Both arrays are indexed by the other process's slot
+/// in the process table, and each entry remembers which process it was set
+/// up for, so that it is ignored once that slot holds a different process or
+/// a later run of the same one.
 struct IPCData {
-    shared_memory: [Option<AppSlice<Shared, u8>>; 8],
-    client_callbacks: [Option<Callback>; 8],
+    shared_memory: [Option<(AppId, AppSlice<Shared, u8>)>; 8],
+    client_callbacks: [Option<(AppId, Callback)>; 8],
     callback: Option<Callback>,
 }
 
@@ -42,6 +50,22 @@ impl IPC {
         }
     }
 
+    /// The IPC identifier userspace uses for a process. It is one more than
+    /// the unique identifier of the process's current run, since 0 selects
+    /// service discovery in `allow`.
+    fn ipc_id(appid: AppId) -> usize {
+        appid.id() + 1
+    }
+
+    /// The process that an IPC identifier from userspace names, if it still
+    /// exists. Identifiers of processes that have since exited or restarted
+    /// name no process.
+    fn lookup(&self, ipc_id: usize) -> Option<AppId> {
+        ipc_id
+            .checked_sub(1)
+            .and_then(|identifier| self.data.kernel.lookup_app_by_identifier(identifier))
+    }
+
     pub unsafe fn schedule_callback(
         &self,
         appid: AppId,
@@ -52,27 +76,25 @@ impl IPC {
             .enter(appid, |mydata, _| {
                 let callback = match cb_type {
                     process::IPCType::Service => mydata.callback,
-                    process::IPCType::Client => {
-                        *mydata.client_callbacks.get(otherapp.idx()).unwrap_or(&None)
-                    }
+                    process::IPCType::Client => match mydata.client_callbacks.get(otherapp.idx()) {
+                        Some(Some((service, callback))) if *service == otherapp => Some(*callback),
+                        _ => None,
+                    },
                 };
                 callback.map_or((), |mut callback| {
                     self.data
                         .enter(otherapp, |otherdata, _| {
-                            if appid.idx() >= otherdata.shared_memory.len() {
-                                return;
-                            }
-                            match otherdata.shared_memory[appid.idx()] {
-                                Some(ref slice) => {
+                            match otherdata.shared_memory.get(appid.idx()) {
+                                Some(Some((target, ref slice))) if *target == appid => {
                                     slice.expose_to(appid);
                                     callback.schedule(
-                                        otherapp.idx() + 1,
+                                        IPC::ipc_id(otherapp),
                                         slice.len(),
                                         slice.ptr() as usize,
                                     );
                                 }
-                                None => {
-                                    callback.schedule(otherapp.idx() + 1, 0, 0);
+                                _ => {
+                                    callback.schedule(IPC::ipc_id(otherapp), 0, 0);
                                 }
                             }
                         })
@@ -115,19 +137,20 @@ impl Driver for IPC {
             // a callback for a given service. The service number (passed
             // here as subscribe_num) is returned from the allow() call.
             // Once subscribed, the client will receive callbacks when the
-            // service process calls notify_client().
-            svc_id => {
-                if svc_id - 1 >= 8 {
-                    ReturnCode::EINVAL /* Maximum of 8 IPC's exceeded */
-                } else {
-                    self.data
-                        .enter(app_id, |data, _| {
-                            data.client_callbacks[svc_id - 1] = callback;
-                            ReturnCode::SUCCESS
-                        })
-                        .unwrap_or(ReturnCode::EBUSY)
-                }
-            }
+            // service process calls notify_client(). If the service has
+            // exited or restarted since the client looked it up, the
+            // service number is stale and EINVAL is returned.
+            svc_id => match self.lookup(svc_id) {
+                Some(service) if service.idx() < 8 => self
+                    .data
+                    .enter(app_id, |data, _| {
+                        data.client_callbacks[service.idx()] =
+                            callback.map(|callback| (service, callback));
+                        ReturnCode::SUCCESS
+                    })
+                    .unwrap_or(ReturnCode::EBUSY),
+                _ => ReturnCode::EINVAL,
+            },
         }
     }
 
@@ -137,7 +160,8 @@ impl Driver for IPC {
     /// In either case, the target_id is the same number as provided in a notify
     /// callback or as returned by allow.
     ///
-    /// Returns EINVAL if the other process doesn't exist.
+    /// Returns EINVAL if the other process doesn't exist, including if it has
+    /// exited or restarted since `target_id` was handed out.
     fn command(
         &self,
         target_id: usize,
@@ -151,15 +175,20 @@ impl Driver for IPC {
             process::IPCType::Client
         };
 
-        self.data
-            .kernel
-            .process_map_or(ReturnCode::EINVAL, target_id - 1, |target| {
-                let ret = target.enqueue_task(process::Task::IPC((appid, cb_type)));
-                match ret {
-                    true => ReturnCode::SUCCESS,
-                    false => ReturnCode::FAIL,
-                }
-            })
+        match self.lookup(target_id) {
+            Some(target_appid) => {
+                self.data
+                    .kernel
+                    .process_map_or(ReturnCode::EINVAL, target_appid, |target| {
+                        let ret = target.enqueue_task(process::Task::IPC((appid, cb_type)));
+                        match ret {
+                            true => ReturnCode::SUCCESS,
+                            false => ReturnCode::FAIL,
+                        }
+                    })
+            }
+            None => ReturnCode::EINVAL,
+        }
     }
 
     /// allow enables processes to discover IPC services on the platform or
@@ -169,7 +198,8 @@ impl Driver for IPC {
     /// call. The contents of the slice should be the string name of the IPC
     /// service. If this mechanism can find that service, allow will return
     /// an ID that can be used to notify that service. Otherwise an error will
-    /// be returned.
+    /// be returned. The ID names the service's current run, and stops working
+    /// if the service exits or restarts.
     ///
     /// If allow is called with target_id >= 1, it is a share command where the
     /// application is explicitly sharing a slice with an IPC service (as
@@ -191,7 +221,7 @@ impl Driver for IPC {
                             && s.iter().zip(slice_data.iter()).all(|(c1, c2)| c1 == c2)
                         {
                             ReturnCode::SuccessWithValue {
-                                value: (p.appid().idx() as usize) + 1,
+                                value: IPC::ipc_id(p.appid()),
                             }
                         } else {
                             ReturnCode::FAIL
@@ -206,16 +236,323 @@ impl Driver for IPC {
 
             return ReturnCode::EINVAL; /* AppSlice must have non-zero length */
         }
-        self.data
+        match self.lookup(target_id) {
+            Some(target) if target.idx() < 8 => self
+                .data
+                .enter(appid, |data, _| {
+                    data.shared_memory[target.idx()] = slice.map(|slice| (target, slice));
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or(ReturnCode::EBUSY),
+            _ => ReturnCode::EINVAL, /* Target process does not exist */
+        }
+    }
+}
+
+/// Syscall number of the mailbox driver.
+pub const MAILBOX_DRIVER_NUM: usize = 0x10002;
+
+/// Largest message, in bytes, the kernel copies from one mailbox to another.
+pub const MAX_MESSAGE_LEN: usize = 32;
+
+/// How many undelivered messages a mailbox holds before senders have to wait.
+pub const MAILBOX_DEPTH: usize = 4;
+
+#[derive(Clone, Copy, Default)]
+struct Message {
+    /// Identifier of the process that sent the message.
+    sender: usize,
+    len: usize,
+    data: [u8; MAX_MESSAGE_LEN],
+}
+
+#[derive(Default)]
+struct MailboxData {
+    /// Messages not yet delivered, oldest at `inbox_head`. This is allocated
+    /// from the process's grant memory when it first provides a receive
+    /// buffer, so processes that only send do not pay for it.
+    inbox: Option<Owned<[Message; MAILBOX_DEPTH]>>,
+    inbox_head: usize,
+    inbox_len: usize,
+    /// Whether a message was copied to `rx_buffer` that the process has not
+    /// acknowledged yet.
+    delivering: bool,
+    rx_buffer: Option<AppSlice<Shared, u8>>,
+    tx_buffer: Option<AppSlice<Shared, u8>>,
+    receive_callback: Option<Callback>,
+    ready_callback: Option<Callback>,
+    /// Identifier of the process whose full mailbox this process is waiting
+    /// to send to.
+    waiting_on: Option<usize>,
+}
+
+/// Mailbox IPC.
+///
+/// Each process that wants to receive messages has a mailbox, a queue of up
+/// to `MAILBOX_DEPTH` messages in its grant memory. Sending copies up to
+/// `MAX_MESSAGE_LEN` bytes from the sender's transmit buffer into the
+/// receiver's mailbox. Messages are delivered one at a time and in the order
+/// they were sent: the kernel copies the oldest message into the receiver's
+/// receive buffer and calls its receive callback, and delivers the next one
+/// once the receiver acknowledges it. If the mailbox is full the send fails
+/// with `EBUSY`, and the sender's ready callback is called when there is room
+/// again. A process can receive from any number of senders.
+///
+/// Processes are addressed by the unique identifier of their `AppId`, so
+/// messages are never delivered to a later run of the process they were
+/// meant for.
+pub struct Mailbox {
+    data: Grant<MailboxData>,
+}
+
+impl Mailbox {
+    pub fn new(kernel: &'static Kernel, capability: &dyn MemoryAllocationCapability) -> Mailbox {
+        Mailbox {
+            data: kernel.create_grant(capability),
+        }
+    }
+
+    /// Copy the oldest message in the mailbox of `appid` to its receive
+    /// buffer, unless the previous message has not been acknowledged, and let
+    /// senders waiting for room in the mailbox know there is some.
+    fn deliver(&self, appid: AppId) {
+        let delivered = self
+            .data
             .enter(appid, |data, _| {
-                data.shared_memory.get_mut(target_id - 1).map_or(
-                    ReturnCode::EINVAL, /* Target process does not exist */
-                    |smem| {
-                        *smem = slice;
+                if data.delivering || data.inbox_len == 0 {
+                    return false;
+                }
+                let message = match data.inbox {
+                    Some(ref inbox) => inbox[data.inbox_head],
+                    None => return false,
+                };
+                let mut callback = match data.receive_callback {
+                    Some(callback) => callback,
+                    None => return false,
+                };
+                let remaining = data.inbox_len - 1;
+                let rx_buffer = match data.rx_buffer {
+                    Some(ref mut rx_buffer) => rx_buffer,
+                    None => return false,
+                };
+                let len = cmp::min(message.len, rx_buffer.len());
+
+                // If the process's task queue is full, the callback is queued
+                // as soon as the process takes a task off it. Only one
+                // message is delivered at a time, so this fails only if the
+                // process can't run.
+                if !callback.schedule_when_room(message.sender, len, remaining) {
+                    return false;
+                }
+                rx_buffer.as_mut()[..len].copy_from_slice(&message.data[..len]);
+                data.inbox_head = (data.inbox_head + 1) % MAILBOX_DEPTH;
+                data.inbox_len = remaining;
+                data.delivering = true;
+                true
+            })
+            .unwrap_or(false);
+
+        if delivered {
+            self.data.each(|data| {
+                if data.waiting_on == Some(appid.id()) {
+                    data.waiting_on = None;
+                    data.ready_callback
+                        .map(|mut callback| callback.schedule(appid.id(), 0, 0));
+                }
+            });
+        }
+    }
+
+    /// Send the first `len` bytes of the transmit buffer of `appid` to the
+    /// process with identifier `target`.
+    fn send(&self, appid: AppId, target: usize, len: usize) -> ReturnCode {
+        let receiver = match self.data.kernel.lookup_app_by_identifier(target) {
+            Some(receiver) => receiver,
+            None => return ReturnCode::EINVAL,
+        };
+        if len > MAX_MESSAGE_LEN {
+            return ReturnCode::ESIZE;
+        }
+
+        let message = self
+            .data
+            .enter(appid, |data, _| match data.tx_buffer {
+                Some(ref tx_buffer) if len <= tx_buffer.len() => {
+                    let mut message = Message {
+                        sender: appid.id(),
+                        len: len,
+                        data: [0; MAX_MESSAGE_LEN],
+                    };
+                    message.data[..len].copy_from_slice(&tx_buffer.as_ref()[..len]);
+                    Ok(message)
+                }
+                Some(_) => Err(ReturnCode::ESIZE),
+                None => Err(ReturnCode::EINVAL),
+            })
+            .unwrap_or_else(|err| Err(err.into()));
+        let message = match message {
+            Ok(message) => message,
+            Err(err) => return err,
+        };
+
+        let rval = self
+            .data
+            .enter(receiver, |data, _| {
+                let full = data.inbox_len == MAILBOX_DEPTH;
+                let tail = (data.inbox_head + data.inbox_len) % MAILBOX_DEPTH;
+                match data.inbox {
+                    Some(_) if full => ReturnCode::EBUSY,
+                    Some(ref mut inbox) => {
+                        inbox[tail] = message;
+                        data.inbox_len += 1;
                         ReturnCode::SUCCESS
-                    },
-                )
+                    }
+                    // The receiver never provided a receive buffer.
+                    None => ReturnCode::EOFF,
+                }
             })
-            .unwrap_or(ReturnCode::EBUSY)
+            .unwrap_or_else(|err| err.into());
+
+        match rval {
+            ReturnCode::SUCCESS => self.deliver(receiver),
+            ReturnCode::EBUSY => {
+                let _ = self.data.enter(appid, |data, _| {
+                    data.waiting_on = Some(target);
+                });
+            }
+            _ => {}
+        }
+        rval
+    }
+}
+
+impl Driver for Mailbox {
+    /// Setup callbacks.
+    ///
+    /// ### `subscribe_num`
+    ///
+    /// - `0`: Message received. Called with the identifier of the sender, the
+    ///        length of the message copied to the receive buffer, and how many
+    ///        more messages are waiting.
+    /// - `1`: Ready to send. Called with the identifier of the receiver after
+    ///        a send to it failed with `EBUSY` and its mailbox has room again.
+    fn subscribe(
+        &self,
+        subscribe_num: usize,
+        callback: Option<Callback>,
+        appid: AppId,
+    ) -> ReturnCode {
+        let rval = self
+            .data
+            .enter(appid, |data, _| {
+                match subscribe_num {
+                    0 => data.receive_callback = callback,
+                    1 => data.ready_callback = callback,
+                    _ => return ReturnCode::ENOSUPPORT,
+                }
+                ReturnCode::SUCCESS
+            })
+            .unwrap_or_else(|err| err.into());
+        if subscribe_num == 0 {
+            self.deliver(appid);
+        }
+        rval
+    }
+
+    /// Mailbox control.
+    ///
+    /// ### `command_num`
+    ///
+    /// - `0`: Driver check.
+    /// - `1`: Returns the identifier other processes send messages to this
+    ///        process with.
+    /// - `2`: Send the first `arg2` bytes of the transmit buffer to the
+    ///        process with identifier `arg1`. Returns `EINVAL` if there is no
+    ///        such process, `EOFF` if it does not receive messages, and
+    ///        `EBUSY` if its mailbox is full.
+    /// - `3`: Acknowledge the message in the receive buffer, so the next one
+    ///        can be delivered.
+    fn command(&self, command_num: usize, arg1: usize, arg2: usize, appid: AppId) -> ReturnCode {
+        match command_num {
+            0 => ReturnCode::SUCCESS,
+            1 => ReturnCode::SuccessWithValue { value: appid.id() },
+            2 => self.send(appid, arg1, arg2),
+            3 => {
+                let rval = self
+                    .data
+                    .enter(appid, |data, _| {
+                        if data.delivering {
+                            data.delivering = false;
+                            ReturnCode::SUCCESS
+                        } else {
+                            ReturnCode::EALREADY
+                        }
+                    })
+                    .unwrap_or_else(|err| err.into());
+                self.deliver(appid);
+                rval
+            }
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    /// Pass buffers to the kernel, or look up a process.
+    ///
+    /// ### `allow_num`
+    ///
+    /// - `0`: Receive buffer. Messages are copied here. Providing it the
+    ///        first time allocates the mailbox.
+    /// - `1`: Transmit buffer. Messages are sent from here.
+    /// - `2`: Look up the process whose package name is the contents of the
+    ///        buffer and return its identifier.
+    fn allow(
+        &self,
+        appid: AppId,
+        allow_num: usize,
+        slice: Option<AppSlice<Shared, u8>>,
+    ) -> ReturnCode {
+        match allow_num {
+            0 => {
+                let rval = self
+                    .data
+                    .enter(appid, |data, allocator| {
+                        if data.inbox.is_none() {
+                            match allocator.alloc([Message::default(); MAILBOX_DEPTH]) {
+                                Ok(inbox) => data.inbox = Some(inbox),
+                                Err(err) => return err.into(),
+                            }
+                        }
+                        data.rx_buffer = slice;
+                        ReturnCode::SUCCESS
+                    })
+                    .unwrap_or_else(|err| err.into());
+                self.deliver(appid);
+                rval
+            }
+            1 => self
+                .data
+                .enter(appid, |data, _| {
+                    data.tx_buffer = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            2 => slice.map_or(ReturnCode::EINVAL, |name| {
+                let rval = self.data.kernel.process_until(|process| {
+                    if process.get_process_name().as_bytes() == name.as_ref() {
+                        ReturnCode::SuccessWithValue {
+                            value: process.appid().id(),
+                        }
+                    } else {
+                        ReturnCode::FAIL
+                    }
+                });
+                if rval == ReturnCode::FAIL {
+                    ReturnCode::EINVAL
+                } else {
+                    rval
+                }
+            }),
+            _ => ReturnCode::ENOSUPPORT,
+        }
     }
 }
@@ -10,6 +10,7 @@
 #![feature(panic_info_message)]
 #![feature(in_band_lifetimes, crate_visibility_modifier)]
 #![feature(associated_type_defaults)]
+#![feature(allow_internal_unstable)]
 #![warn(unreachable_pub)]
 #![no_std]
 
@@ -19,9 +20,11 @@ pub mod common;
 pub mod component;
 #[macro_use]
 pub mod debug;
+pub mod heartbeat;
 pub mod hil;
 pub mod introspection;
 pub mod ipc;
+pub mod power;
 pub mod syscall;
 
 mod callback;
@@ -31,6 +34,9 @@ mod mem;
 mod memop;
 mod platform;
 mod process;
+mod process_checker;
+mod process_fault_log;
+mod process_loader;
 mod returncode;
 mod sched;
 mod tbfheader;
@@ -38,12 +44,13 @@ mod tbfheader;
 pub use crate::callback::{AppId, Callback};
 pub use crate::driver::Driver;
 pub use crate::grant::Grant;
-pub use crate::mem::{AppPtr, AppSlice, Private, Shared};
+pub use crate::mem::{AppPtr, AppSlice, Private, ReadOnlyAppSlice, Shared};
 pub use crate::platform::systick::SysTick;
-pub use crate::platform::{mpu, Chip, Platform};
+pub use crate::platform::{mpu, Chip, Platform, SyscallFilterResponse};
 pub use crate::platform::{ClockInterface, NoClockControl, NO_CLOCK_CONTROL};
 pub use crate::returncode::ReturnCode;
-pub use crate::sched::Kernel;
+pub use crate::sched::{Kernel, Scheduler, SchedulingDecision, StoppedExecutingReason};
+pub use crate::syscall::SyscallReturn;
 
 // Export only select items from the process module. To remove the name conflict
 // this cannot be called `process`, so we use a shortened version. These
@@ -51,5 +58,22 @@ pub use crate::sched::Kernel;
 // processes.
 /// Publicly available process-related objects.
 pub mod procs {
-    pub use crate::process::{load_processes, FaultResponse, FunctionCall, Process, ProcessType};
+    pub use crate::process::{
+        load_and_check_processes, load_processes, FaultResponse, FunctionCall, FunctionCallSource,
+        Process, ProcessFaultPolicy, ProcessType, State, SyscallRecord,
+    };
+    pub use crate::process_checker::{AppCredentialsChecker, CheckResult, CredentialsFailure};
+    pub use crate::process_fault_log::{ProcessFault, ProcessFaultLog};
+    pub use crate::process_loader::DynamicProcessLoader;
+    pub use crate::tbfheader::{TbfFooterV2Credentials, TbfFooterV2CredentialsType};
+}
+
+/// Scheduler implementations provided by the kernel. Boards choose one of
+/// these, or provide their own implementation of `Scheduler`, and pass it to
+/// `Kernel::kernel_loop()`.
+pub mod schedulers {
+    pub use crate::sched::cooperative::CooperativeSched;
+    pub use crate::sched::mlfq::{MLFQProcessNode, MLFQSched};
+    pub use crate::sched::priority::PrioritySched;
+    pub use crate::sched::round_robin::RoundRobinSched;
 }
@@ -51,7 +51,7 @@ impl<L, T> Drop for AppPtr<L, T> {
     fn drop(&mut self) {
         self.process
             .kernel
-            .process_map_or((), self.process.idx(), |process| unsafe {
+            .process_map_or((), self.process, |process| unsafe {
                 process.free(self.ptr.as_ptr() as *mut u8)
             })
     }
@@ -89,11 +89,11 @@ impl<L, T> AppSlice<L, T> {
     /// Provide access to one app's AppSlice to another app. This is used for
     /// IPC.
     crate unsafe fn expose_to(&self, appid: AppId) -> bool {
-        if appid.idx() != self.ptr.process.idx() {
+        if appid != self.ptr.process {
             self.ptr
                 .process
                 .kernel
-                .process_map_or(false, appid.idx(), |process| {
+                .process_map_or(false, appid, |process| {
                     process
                         .add_mpu_region(self.ptr() as *const u8, self.len(), self.len())
                         .is_some()
@@ -131,3 +131,55 @@ impl<L, T> AsMut<[T]> for AppSlice<L, T> {
         unsafe { slice::from_raw_parts_mut(self.ptr.ptr.as_mut(), self.len) }
     }
 }
+
+/// Buffer of memory shared from an app to the kernel that the kernel can only
+/// read.
+///
+/// This is the type created after an app calls the read-only allow syscall.
+/// Unlike an `AppSlice`, the buffer may be in the app's flash as well as its
+/// RAM, so apps can share constant data without copying it to RAM first.
+pub struct ReadOnlyAppSlice<T> {
+    ptr: *const T,
+    len: usize,
+    process: AppId,
+}
+
+impl<T> ReadOnlyAppSlice<T> {
+    crate fn new(ptr: *const T, len: usize, appid: AppId) -> ReadOnlyAppSlice<T> {
+        ReadOnlyAppSlice {
+            ptr: ptr,
+            len: len,
+            process: appid,
+        }
+    }
+
+    /// Number of bytes in the `ReadOnlyAppSlice`.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Get the raw pointer to the buffer. This will be a pointer inside of the
+    /// app's memory or flash region.
+    pub fn ptr(&self) -> *const T {
+        self.ptr
+    }
+
+    /// The app that shared the buffer.
+    pub fn appid(&self) -> AppId {
+        self.process
+    }
+
+    pub fn iter(&self) -> slice::Iter<T> {
+        self.as_ref().iter()
+    }
+
+    pub fn chunks(&self, size: usize) -> slice::Chunks<T> {
+        self.as_ref().chunks(size)
+    }
+}
+
+impl<T> AsRef<[T]> for ReadOnlyAppSlice<T> {
+    fn as_ref(&self) -> &[T] {
+        unsafe { slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
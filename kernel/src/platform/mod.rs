@@ -1,6 +1,10 @@
 //! Interface for chips and boards.
 
 use crate::driver::Driver;
+use crate::hil;
+use crate::power;
+use crate::process;
+use crate::returncode::ReturnCode;
 use crate::syscall;
 
 pub mod mpu;
@@ -13,6 +17,41 @@ pub trait Platform {
     fn with_driver<F, R>(&self, driver_num: usize, f: F) -> R
     where
         F: FnOnce(Option<&dyn Driver>) -> R;
+
+    /// Check whether `process` may make the system call `syscall`.
+    ///
+    /// The kernel calls this for every system call, after checking the driver
+    /// permissions in the process's TBF header. Boards can override it to
+    /// restrict which processes use which drivers, e.g. to keep untrusted
+    /// apps away from storage or the radio. Returning an error prevents the
+    /// call from being handled. The default allows everything.
+    fn filter_syscall(
+        &self,
+        _process: &dyn process::ProcessType,
+        _syscall: &syscall::Syscall,
+    ) -> Result<(), SyscallFilterResponse> {
+        Ok(())
+    }
+
+    /// The watchdog the kernel services, if the board has one.
+    ///
+    /// The board starts the watchdog. The kernel tickles it every time around
+    /// its main loop, and suspends it while the chip sleeps. Its period must
+    /// be longer than the kernel can take between iterations, which includes
+    /// the longest timeslice a process runs for.
+    fn watchdog(&self) -> Option<&dyn hil::watchdog::Watchdog> {
+        None
+    }
+}
+
+/// How the kernel handles a system call that a syscall filter denies.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SyscallFilterResponse {
+    /// Do not handle the system call and return this error code to the
+    /// process instead.
+    Error(ReturnCode),
+    /// Fault the process. What happens next is decided by its `FaultResponse`.
+    Fault,
 }
 
 /// Interface for individual MCUs.
@@ -27,6 +66,20 @@ pub trait Chip {
     fn systick(&self) -> &Self::SysTick;
     fn userspace_kernel_boundary(&self) -> &Self::UserspaceKernelBoundary;
     fn sleep(&self);
+
+    /// The states the chip can sleep in, from the lightest to the deepest.
+    /// Chips without a table are put to sleep with `sleep()`.
+    fn sleep_states(&self) -> &'static [power::SleepState] {
+        &[]
+    }
+
+    /// Sleep in `state`, an index into `sleep_states()`, or a lighter state
+    /// if the hardware is not ready for it. Returns the state the chip slept
+    /// in.
+    fn sleep_in(&self, _state: usize) -> usize {
+        self.sleep();
+        0
+    }
     unsafe fn atomic<F, R>(&self, f: F) -> R
     where
         F: FnOnce() -> R;
@@ -15,15 +15,38 @@ pub trait SysTick {
     ///
     /// Callers can assume at least a 24-bit wide clock. Specific timing is
     /// dependent on the driving clock. In practice, increments of 10ms are most
-    /// accurate and values up to 400ms are valid.
+    /// accurate. Intervals longer than `max_timer_us()` are shortened to it.
     fn set_timer(&self, us: u32);
 
+    /// Returns the longest interval, in microseconds, that `set_timer` can
+    /// count down, which depends on the clock driving the timer.
+    fn max_timer_us(&self) -> u32 {
+        u32::max_value()
+    }
+
+    /// Returns false if the timer does not count down at all, so that
+    /// `get_value` can't be used to measure time.
+    fn measures_time(&self) -> bool {
+        true
+    }
+
     /// Returns if there is at least `us` microseconds left
     fn greater_than(&self, us: u32) -> bool;
 
     /// Returns true if the timer has expired
+    ///
+    /// On some chips, such as Cortex-M, this clears the flag that records the
+    /// expiration, so a later call returns false. Callers that need the answer
+    /// more than once must remember it.
     fn overflowed(&self) -> bool;
 
+    /// Returns the number of microseconds left before the timer expires.
+    ///
+    /// The kernel uses this to measure how long a process ran for. If the
+    /// timer has already expired the returned value is unspecified, callers
+    /// should check `overflowed()` first.
+    fn get_value(&self) -> u32;
+
     /// Resets the timer
     ///
     /// Resets the timer to 0 and disables it
@@ -41,7 +64,8 @@ pub trait SysTick {
 /// A dummy `SysTick` implementation in which the timer never expires.
 ///
 /// Using this implementation is functional, but will mean the scheduler cannot
-/// interrupt non-yielding processes.
+/// interrupt non-yielding processes, and that the CPU time of processes and
+/// the kernel is unavailable.
 impl SysTick for () {
     fn reset(&self) {}
 
@@ -49,10 +73,18 @@ impl SysTick for () {
 
     fn enable(&self, _: bool) {}
 
+    fn measures_time(&self) -> bool {
+        false
+    }
+
     fn overflowed(&self) -> bool {
         false
     }
 
+    fn get_value(&self) -> u32 {
+        u32::max_value()
+    }
+
     fn greater_than(&self, _: u32) -> bool {
         true
     }
@@ -0,0 +1,176 @@
+//! Kernel power management.
+//!
+//! Chips describe the states they can sleep in with a table of `SleepState`s,
+//! from the lightest to the deepest, and what each keeps working. Chip
+//! drivers that need something kept working while the chip sleeps, for
+//! example a UART in the middle of a transfer, hold a `PowerConstraint` for
+//! as long as they need it. When the kernel has nothing to do it puts the chip
+//! in the deepest state that keeps every required constraint.
+//!
+//! Constraints are counted globally, like deferred calls, so chip drivers
+//! that have no reference to the kernel can use them:
+//!
+//! ```ignore
+//! static UART_ACTIVE: PowerConstraint = PowerConstraint::new(Constraint::UartActive);
+//!
+//! fn transmit(&self) {
+//!     UART_ACTIVE.require();
+//!     // ...
+//! }
+//!
+//! fn transmit_done(&self) {
+//!     UART_ACTIVE.release();
+//!     // ...
+//! }
+//! ```
+//!
+//! The kernel counts how often and, if the board gives it a `SleepClock`, for
+//! how long the chip sleeps in each state. `introspection::KernelInfo`
+//! reports these.
+
+use core::cell::Cell;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::hil::time::{Frequency, Time};
+
+/// Something that a driver needs kept working while the chip sleeps.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Constraint {
+    /// The high speed clock source stays running and locked.
+    HighSpeedClock = 0,
+    /// Peripheral bus clocks keep running, e.g. for a DMA transfer.
+    PeripheralClocks = 1,
+    /// UARTs can keep sending and receiving.
+    UartActive = 2,
+    /// The radio can keep sending and receiving.
+    RadioActive = 3,
+}
+
+const NUM_CONSTRAINTS: usize = 4;
+
+/// How many `PowerConstraint`s require each constraint.
+static REQUIRED: [AtomicUsize; NUM_CONSTRAINTS] = [
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+    AtomicUsize::new(0),
+];
+
+/// One driver's vote for a constraint.
+///
+/// Requiring or releasing a constraint more than once in a row has no
+/// further effect, so a driver does not need to track whether it holds it.
+pub struct PowerConstraint {
+    constraint: Constraint,
+    required: Cell<bool>,
+}
+
+// Constraints are only changed from the kernel's thread, never from interrupt
+// handlers, so sharing them in statics is safe.
+unsafe impl Sync for PowerConstraint {}
+
+impl PowerConstraint {
+    pub const fn new(constraint: Constraint) -> PowerConstraint {
+        PowerConstraint {
+            constraint: constraint,
+            required: Cell::new(false),
+        }
+    }
+
+    /// Keep the constraint satisfied until `release()` is called.
+    pub fn require(&self) {
+        if !self.required.get() {
+            self.required.set(true);
+            let count = &REQUIRED[self.constraint as usize];
+            count.store(count.load(Ordering::Relaxed) + 1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn release(&self) {
+        if self.required.get() {
+            self.required.set(false);
+            let count = &REQUIRED[self.constraint as usize];
+            count.store(count.load(Ordering::Relaxed) - 1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn is_required(&self) -> bool {
+        self.required.get()
+    }
+}
+
+/// Whether any driver currently requires `constraint`.
+pub fn is_required(constraint: Constraint) -> bool {
+    REQUIRED[constraint as usize].load(Ordering::Relaxed) != 0
+}
+
+/// A state the chip can sleep in.
+pub struct SleepState {
+    /// Name shown in statistics, e.g. the datasheet's name for the mode.
+    pub name: &'static str,
+    /// The constraints that hold while the chip sleeps in this state.
+    pub keeps: &'static [Constraint],
+}
+
+impl SleepState {
+    fn allowed(&self) -> bool {
+        [
+            Constraint::HighSpeedClock,
+            Constraint::PeripheralClocks,
+            Constraint::UartActive,
+            Constraint::RadioActive,
+        ]
+        .iter()
+        .all(|&constraint| !is_required(constraint) || self.keeps.contains(&constraint))
+    }
+}
+
+/// The deepest of `states` that keeps every required constraint. The first,
+/// lightest, state is used if none does.
+crate fn deepest_allowed_state(states: &[SleepState]) -> usize {
+    states
+        .iter()
+        .rposition(|state| state.allowed())
+        .unwrap_or(0)
+}
+
+/// The most sleep states the kernel keeps statistics for.
+pub const MAX_SLEEP_STATES: usize = 8;
+
+/// How much the chip has slept in one state.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Residency {
+    /// How many times the chip went to sleep in the state.
+    pub entries: usize,
+    /// How long the chip has slept in the state, in microseconds. Only
+    /// counted if the board set a `SleepClock`.
+    pub time_us: u64,
+}
+
+/// A clock that keeps counting in every sleep state, which the kernel reads
+/// before and after sleeping to measure residency. Any alarm or timer that
+/// wakes the chip up works.
+pub trait SleepClock {
+    /// The current tick count, which wraps around after `max_tics()`.
+    fn now(&self) -> u32;
+
+    /// The largest tick count, one less than a power of two.
+    fn max_tics(&self) -> u32;
+
+    /// Ticks per second.
+    fn frequency(&self) -> u32;
+}
+
+impl<T: Time> SleepClock for T {
+    fn now(&self) -> u32 {
+        Time::now(self)
+    }
+
+    fn max_tics(&self) -> u32 {
+        Time::max_tics(self)
+    }
+
+    fn frequency(&self) -> u32 {
+        T::Frequency::frequency()
+    }
+}
@@ -1,22 +1,24 @@
 //! Support for creating and running userspace applications.
 
 use core::cell::Cell;
-use core::fmt::Write;
+use core::fmt::{self, Write};
 use core::ptr::write_volatile;
 use core::{mem, ptr, slice, str};
 
 use crate::callback::{AppId, CallbackId};
 use crate::capabilities::ProcessManagementCapability;
-use crate::common::cells::MapCell;
+use crate::common::cells::{MapCell, NumericCellExt};
 use crate::common::{Queue, RingBuffer};
-use crate::mem::{AppSlice, Shared};
+use crate::mem::{AppSlice, ReadOnlyAppSlice, Shared};
 use crate::platform::mpu::{self, MPU};
 use crate::platform::Chip;
+use crate::process_checker::{self, AppCredentialsChecker, CredentialsFailure};
+use crate::process_fault_log::ProcessFault;
 use crate::returncode::ReturnCode;
-use crate::sched::Kernel;
-use crate::syscall::{self, Syscall, UserspaceKernelBoundary};
+use crate::sched::{Kernel, MAX_TASK_QUEUE_DEPTH};
+use crate::syscall::{self, Syscall, SyscallReturn, UserspaceKernelBoundary};
 use crate::tbfheader;
-use core::cmp::max;
+use core::cmp::{self, max};
 
 /// Helper function to load processes from flash into an array of active
 /// processes. This is the default template for loading processes, but a board
@@ -24,22 +26,69 @@ use core::cmp::max;
 ///
 /// Processes are found in flash starting from the given address and iterating
 /// through Tock Binary Format headers. Processes are given memory out of the
-/// `app_memory` buffer until either the memory is exhausted or the allocated
-/// number of processes are created, with process structures placed in the
-/// provided array. How process faults are handled by the kernel is also
+/// `app_memory` buffer until either the memory is exhausted or all of the
+/// process slots the kernel was created with are filled. The kernel keeps the
+/// buffer, so that a `DynamicProcessLoader` can later hand out the parts of it
+/// no process uses. How process faults are handled by the kernel is also
 /// selected.
 pub fn load_processes<C: Chip>(
     kernel: &'static Kernel,
     chip: &'static C,
     start_of_flash: *const u8,
-    app_memory: &mut [u8],
-    procs: &'static mut [Option<&'static dyn ProcessType>],
+    app_memory: &'static mut [u8],
     fault_response: FaultResponse,
     _capability: &dyn ProcessManagementCapability,
+) {
+    load_processes_inner(
+        kernel,
+        chip,
+        start_of_flash,
+        app_memory,
+        fault_response,
+        None,
+    );
+}
+
+/// Like `load_processes()`, but only load processes whose credentials satisfy
+/// the board-supplied `checker`.
+///
+/// Apps that the checker refuses are skipped and their flash is left
+/// untouched, or, if the checker's `failure_response()` asks for it, loaded in
+/// the `CredentialsFailed` state. Either way they never run. See the
+/// `process_checker` module for how credentials are checked.
+pub fn load_and_check_processes<C: Chip>(
+    kernel: &'static Kernel,
+    chip: &'static C,
+    start_of_flash: *const u8,
+    app_memory: &'static mut [u8],
+    fault_response: FaultResponse,
+    checker: &'static dyn AppCredentialsChecker,
+    _capability: &dyn ProcessManagementCapability,
+) {
+    load_processes_inner(
+        kernel,
+        chip,
+        start_of_flash,
+        app_memory,
+        fault_response,
+        Some(checker),
+    );
+}
+
+fn load_processes_inner<C: Chip>(
+    kernel: &'static Kernel,
+    chip: &'static C,
+    start_of_flash: *const u8,
+    app_memory: &'static mut [u8],
+    fault_response: FaultResponse,
+    checker: Option<&'static dyn AppCredentialsChecker>,
 ) {
     let mut apps_in_flash_ptr = start_of_flash;
     let mut app_memory_ptr = app_memory.as_mut_ptr();
     let mut app_memory_size = app_memory.len();
+    kernel.set_app_memory(app_memory);
+
+    let procs = kernel.process_slots();
     for i in 0..procs.len() {
         unsafe {
             let (process, flash_offset, memory_offset) = Process::create(
@@ -49,19 +98,21 @@ pub fn load_processes<C: Chip>(
                 app_memory_ptr,
                 app_memory_size,
                 fault_response,
+                checker,
                 i,
             );
 
             if process.is_none() {
                 // We did not get a valid process, but we may have gotten a disabled
-                // process or padding. Therefore we want to skip this chunk of flash
-                // and see if there is a valid app there. However, if we cannot
-                // advance the flash pointer, then we are done.
+                // process, padding, or a process that failed its credentials
+                // check. Therefore we want to skip this chunk of flash and see
+                // if there is a valid app there. However, if we cannot advance
+                // the flash pointer, then we are done.
                 if flash_offset == 0 && memory_offset == 0 {
                     break;
                 }
             } else {
-                procs[i] = process;
+                procs[i].set(process);
             }
 
             apps_in_flash_ptr = apps_in_flash_ptr.add(flash_offset);
@@ -85,6 +136,12 @@ pub trait ProcessType {
     /// this is passed to the capsule that tried to schedule the `Task`.
     fn enqueue_task(&self, task: Task) -> bool;
 
+    /// Queue a `Task` like `enqueue_task()`, except that if the queue is full
+    /// the task is held back and queued as soon as the process takes a task
+    /// off its queue. A process holds at most one task like this, so this
+    /// returns `false` if one is already held, or if the process can't run.
+    fn enqueue_task_when_room(&self, task: Task) -> bool;
+
     /// Remove the scheduled operation from the front of the queue and return it
     /// to be handled by the scheduler.
     ///
@@ -100,6 +157,11 @@ pub trait ProcessType {
     /// or "yielded".
     fn get_state(&self) -> State;
 
+    /// Returns whether this process is ready to execute, either because it is
+    /// already running or because it has a `Task` waiting to be handled.
+    /// Schedulers use this to decide which process to run next.
+    fn ready(&self) -> bool;
+
     /// Move this process from the running state to the yielded state.
     fn set_yielded_state(&self);
 
@@ -110,12 +172,37 @@ pub trait ProcessType {
     fn resume(&self);
 
     /// Put this process in the fault state. This will trigger the
-    /// `FaultResponse` for this process to occur.
+    /// `FaultResponse` for this process to occur. Does nothing if the process
+    /// is `Terminated` or failed its credentials check.
     fn set_fault_state(&self);
 
+    /// Reset this process's memory and grant regions and schedule it to run
+    /// again from its init function, as if it was just loaded. This is how
+    /// the kernel restarts a faulted process. Does nothing if the process is
+    /// `Terminated` or failed its credentials check.
+    fn restart(&self);
+
+    /// Stop this process for good, for example because it is being unloaded.
+    /// All of its pending tasks are dropped and its grant regions are cleared.
+    fn terminate(&self);
+
     /// Get the name of the process. Used for IPC.
     fn get_process_name(&self) -> &'static str;
 
+    /// Get the scheduling priority requested in the TBF header, if any. Lower
+    /// values are higher priority.
+    fn get_priority(&self) -> Option<u32>;
+
+    /// Get the timeslice in microseconds requested in the TBF header, if any.
+    /// Schedulers that preempt processes use this instead of their default
+    /// timeslice.
+    fn get_timeslice_us(&self) -> Option<u32>;
+
+    /// Check whether the TBF header allows this process to use the driver with
+    /// number `driver_num`. Processes whose header does not restrict the
+    /// drivers they use may use all of them.
+    fn driver_permitted(&self, driver_num: usize) -> bool;
+
     // memop operations
 
     /// Change the location of the program break and reallocate the MPU region
@@ -174,6 +261,21 @@ pub trait ProcessType {
         size: usize,
     ) -> Result<Option<AppSlice<Shared, u8>>, ReturnCode>;
 
+    /// Creates a `ReadOnlyAppSlice` from the given offset and size in process
+    /// memory or flash.
+    ///
+    /// ## Returns
+    ///
+    /// If the buffer is null, return None, signaling the capsule to delete the
+    /// entry. If the buffer is within the process's accessible memory or its
+    /// flash, returns a ReadOnlyAppSlice wrapping that buffer. Otherwise,
+    /// returns an error `ReturnCode`.
+    fn allow_read_only(
+        &self,
+        buf_start_addr: *const u8,
+        size: usize,
+    ) -> Result<Option<ReadOnlyAppSlice<u8>>, ReturnCode>;
+
     /// Get the first address of process's flash that isn't protected by the
     /// kernel. The protected range of flash contains the TBF header and
     /// potentially other state the kernel is storing on behalf of the process,
@@ -196,21 +298,43 @@ pub trait ProcessType {
 
     // grants
 
-    /// Create new memory in the grant region, and check that the MPU region
-    /// covering program memory does not extend past the kernel memory break.
-    unsafe fn alloc(&self, size: usize, align: usize) -> Option<&mut [u8]>;
+    /// Create new memory in the grant region for the grant `grant_num`, and
+    /// check that the MPU region covering program memory does not extend past
+    /// the kernel memory break. The memory is counted against the process's
+    /// grant quota.
+    unsafe fn alloc(&self, size: usize, align: usize, grant_num: usize)
+        -> Result<&mut [u8], Error>;
 
     unsafe fn free(&self, _: *mut u8);
 
     /// Get a pointer to the grant pointer for this grant number.
     unsafe fn grant_ptr(&self, grant_num: usize) -> *mut *mut u8;
 
+    /// How many bytes of grant memory have been allocated for this process,
+    /// summed over all grants. This includes padding for alignment.
+    fn grant_bytes_allocated(&self) -> usize;
+
+    /// How many bytes of grant memory have been allocated for this process
+    /// while the grant `grant_num` was entered.
+    fn grant_bytes_allocated_for(&self, grant_num: usize) -> usize;
+
+    /// The most bytes of grant memory this process may allocate, if it is
+    /// limited.
+    fn grant_quota(&self) -> Option<usize>;
+
+    /// Set the process's own grant quota. `None` makes the process use the
+    /// kernel's default quota.
+    fn set_grant_quota(&self, quota: Option<usize>);
+
     // functions for processes that are architecture specific
 
     /// Set the return value the process should see when it begins executing
     /// again after the syscall.
     unsafe fn set_syscall_return_value(&self, return_value: isize);
 
+    /// Set the values a typed command returns to the process.
+    unsafe fn set_syscall_return(&self, return_value: SyscallReturn);
+
     /// Set the function that is to be executed when the process is resumed.
     unsafe fn set_process_function(&self, callback: FunctionCall);
 
@@ -220,11 +344,26 @@ pub trait ProcessType {
     unsafe fn fault_fmt(&self, writer: &mut dyn Write);
     unsafe fn process_detail_fmt(&self, writer: &mut dyn Write);
 
+    /// Print only the architecture specific state of the process, e.g. its
+    /// CPU registers.
+    unsafe fn registers_fmt(&self, writer: &mut dyn Write);
+
     // debug
 
     /// Returns how many syscalls this app has called.
     fn debug_syscall_count(&self) -> usize;
 
+    /// Note that the process called `syscall` after using `cpu_time_us`
+    /// microseconds of CPU time. This counts the call and, if the kernel
+    /// traces syscalls, adds it to the process's syscall trace. The return
+    /// value is added to the trace when it is set.
+    fn debug_syscall_called(&self, syscall: Syscall, cpu_time_us: u64);
+
+    /// Call `f` on each syscall in the process's syscall trace, oldest first.
+    /// The trace is empty unless the kernel was asked to trace syscalls before
+    /// the process was loaded.
+    fn debug_syscall_trace(&self, f: &mut dyn FnMut(&SyscallRecord));
+
     /// Returns how many callbacks for this process have been dropped.
     fn debug_dropped_callback_count(&self) -> usize;
 
@@ -235,6 +374,13 @@ pub trait ProcessType {
     fn debug_timeslice_expiration_count(&self) -> usize;
 
     fn debug_timeslice_expired(&self);
+
+    /// Returns how many microseconds of CPU time the process has used,
+    /// including time the kernel spent handling its system calls.
+    fn debug_cpu_time_us(&self) -> u64;
+
+    /// Charge `us` microseconds of CPU time to the process.
+    fn debug_add_cpu_time(&self, us: u32);
 }
 
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
@@ -242,6 +388,10 @@ pub enum Error {
     NoSuchApp,
     OutOfMemory,
     AddressOutOfBounds,
+    /// Allocating the memory would take the process over its grant quota.
+    /// Processes see this as `ERESERVE`, so they can tell it apart from the
+    /// kernel running out of memory.
+    QuotaExceeded,
     KernelError, // This likely indicates a bug in the kernel and that some
                  // state is inconsistent in the kernel.
 }
@@ -250,6 +400,7 @@ impl From<Error> for ReturnCode {
     fn from(err: Error) -> ReturnCode {
         match err {
             Error::OutOfMemory => ReturnCode::ENOMEM,
+            Error::QuotaExceeded => ReturnCode::ERESERVE,
             Error::AddressOutOfBounds => ReturnCode::EINVAL,
             Error::NoSuchApp => ReturnCode::EINVAL,
             Error::KernelError => ReturnCode::FAIL,
@@ -295,6 +446,15 @@ pub enum State {
     /// processes yet. It can also happen if an process is terminated and all
     /// of its state is reset as if it has not been executed yet.
     Unstarted,
+
+    /// The process has been unloaded from the kernel and will never run
+    /// again. Its memory may be handed to a different process.
+    Terminated,
+
+    /// The app's credentials did not satisfy the board's checker, which chose
+    /// to keep the process loaded anyway. It is never scheduled, restarted, or
+    /// given callbacks.
+    CredentialsFailed,
 }
 
 /// The reaction the kernel should take when an app encounters a fault.
@@ -302,7 +462,8 @@ pub enum State {
 /// When an exception occurs during an app's execution (a common example is an
 /// app trying to access memory outside of its allowed regions) the system will
 /// trap back to the kernel, and the kernel has to decide what to do with the
-/// app at that point.
+/// app at that point. Boards that need more than a fixed response, for example
+/// to stop an app that keeps crashing, can set a `ProcessFaultPolicy`.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum FaultResponse {
     /// Generate a `panic!()` call and crash the entire system. This is useful
@@ -319,6 +480,20 @@ pub enum FaultResponse {
     Stop,
 }
 
+/// A board-defined policy that decides how the kernel responds to each process
+/// fault, instead of the fixed `FaultResponse` processes were loaded with.
+///
+/// The policy is a hook for a supervisor: it learns about every fault and can
+/// keep its own history of them. A policy that stops a process can restart it
+/// later, e.g. after a backoff delay, with `Kernel::restart_process()`.
+pub trait ProcessFaultPolicy {
+    /// Decide how to respond to `process` faulting. The process is in the
+    /// `Fault` state when this is called. Processes that are `Terminated` or
+    /// failed their credentials check can't fault, so the policy is never
+    /// asked about them and can't restart them.
+    fn action(&self, process: &dyn ProcessType) -> FaultResponse;
+}
+
 #[derive(Copy, Clone, Debug)]
 pub enum IPCType {
     Service,
@@ -360,6 +535,84 @@ pub struct FunctionCall {
     pub pc: usize,
 }
 
+/// One system call a process made, as recorded in its syscall trace.
+#[derive(Copy, Clone, Debug)]
+pub struct SyscallRecord {
+    /// The system call and its arguments.
+    pub syscall: Syscall,
+    /// What the kernel returned to the process, or `None` for calls that do
+    /// not return a value, like yield, or that have not returned yet. Typed
+    /// commands only record their error code or first value.
+    pub return_value: Option<isize>,
+    /// How many microseconds of CPU time the process had used when it made
+    /// the call.
+    pub cpu_time_us: u64,
+}
+
+impl fmt::Display for SyscallRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:>10}us ", self.cpu_time_us)?;
+        match self.syscall {
+            Syscall::YIELD => write!(f, "yield()")?,
+            Syscall::SUBSCRIBE {
+                driver_number,
+                subdriver_number,
+                callback_ptr,
+                appdata,
+            } => write!(
+                f,
+                "subscribe({:#x}, {}, {:p}, {:#x})",
+                driver_number, subdriver_number, callback_ptr, appdata
+            )?,
+            Syscall::COMMAND {
+                driver_number,
+                subdriver_number,
+                arg0,
+                arg1,
+            } => write!(
+                f,
+                "command({:#x}, {}, {:#x}, {:#x})",
+                driver_number, subdriver_number, arg0, arg1
+            )?,
+            Syscall::TYPED_COMMAND {
+                driver_number,
+                subdriver_number,
+                arg0,
+                arg1,
+            } => write!(
+                f,
+                "typed_command({:#x}, {}, {:#x}, {:#x})",
+                driver_number, subdriver_number, arg0, arg1
+            )?,
+            Syscall::ALLOW {
+                driver_number,
+                subdriver_number,
+                allow_address,
+                allow_size,
+            } => write!(
+                f,
+                "allow({:#x}, {}, {:p}, {})",
+                driver_number, subdriver_number, allow_address, allow_size
+            )?,
+            Syscall::MEMOP { operand, arg0 } => write!(f, "memop({}, {:#x})", operand, arg0)?,
+            Syscall::READ_ONLY_ALLOW {
+                driver_number,
+                subdriver_number,
+                allow_address,
+                allow_size,
+            } => write!(
+                f,
+                "allow_read_only({:#x}, {}, {:p}, {})",
+                driver_number, subdriver_number, allow_address, allow_size
+            )?,
+        }
+        match self.return_value {
+            Some(value) => write!(f, " = {}", value),
+            None => Ok(()),
+        }
+    }
+}
+
 /// State for helping with debugging apps.
 ///
 /// These pointers and counters are not strictly required for kernel operation,
@@ -393,6 +646,10 @@ struct ProcessDebug {
     /// How many times this process has been paused because it exceeded its
     /// timeslice.
     timeslice_expiration_count: usize,
+
+    /// How many microseconds the process has run for, as measured by the
+    /// system tick timer.
+    cpu_time_us: u64,
 }
 
 pub struct Process<'a, C: 'static + Chip> {
@@ -401,6 +658,12 @@ pub struct Process<'a, C: 'static + Chip> {
     /// Corresponds to AppId
     app_idx: usize,
 
+    /// Unique identifier of this run of the process, never reused. The
+    /// process gets a new one when it restarts.
+    ///
+    /// Corresponds to AppId
+    identifier: Cell<usize>,
+
     /// Pointer to the main Kernel struct.
     kernel: &'static Kernel,
 
@@ -440,6 +703,13 @@ pub struct Process<'a, C: 'static + Chip> {
     /// Pointer to the end of the allocated (and MPU protected) grant region.
     kernel_memory_break: Cell<*const u8>,
 
+    /// Bytes of grant memory allocated for each grant, stored in the grant
+    /// region just below the grant pointers.
+    grant_usage: &'static [Cell<usize>],
+
+    /// This process's own limit on grant memory, if it has one.
+    grant_quota: Cell<Option<usize>>,
+
     /// Copy of where the kernel memory break is when the app is first started.
     /// This is handy if the app is restarted so we know where to reset
     /// the kernel_memory break to without having to recalculate it.
@@ -484,6 +754,13 @@ pub struct Process<'a, C: 'static + Chip> {
     /// process.
     tasks: MapCell<RingBuffer<'a, Task>>,
 
+    /// A task that did not fit in `tasks`, which is queued as soon as there
+    /// is room.
+    held_task: Cell<Option<Task>>,
+
+    /// The most recent syscalls of the process, if the kernel traces them.
+    syscall_trace: MapCell<RingBuffer<'a, SyscallRecord>>,
+
     /// Name of the app.
     process_name: &'static str,
 
@@ -493,19 +770,53 @@ pub struct Process<'a, C: 'static + Chip> {
 
 impl<C: Chip> ProcessType for Process<'a, C> {
     fn appid(&self) -> AppId {
-        AppId::new(self.kernel, self.app_idx)
+        AppId::new(self.kernel, self.identifier.get(), self.app_idx)
     }
 
     fn enqueue_task(&self, task: Task) -> bool {
-        // If this app is in the `Fault` state then we shouldn't schedule
-        // any work for it.
-        if self.state.get() == State::Fault {
-            return false;
+        // If this app is in the `Fault`, `Terminated` or `CredentialsFailed`
+        // state then we shouldn't schedule any work for it.
+        match self.state.get() {
+            State::Fault | State::Terminated | State::CredentialsFailed => return false,
+            _ => {}
         }
 
         self.kernel.increment_work();
 
-        let ret = self.tasks.map_or(false, |tasks| tasks.enqueue(task));
+        let ret = self.tasks.map_or(false, |tasks| {
+            if tasks.enqueue(task) {
+                return true;
+            }
+
+            // The queue is full. If the app asked for it, drop the oldest
+            // pending callback for the same subscription to make room, so
+            // the app sees the most recent one rather than losing it.
+            let callback_id = match task {
+                Task::FunctionCall(FunctionCall {
+                    source: FunctionCallSource::Driver(id),
+                    ..
+                }) if self.header.coalesce_callbacks() => id,
+                _ => return false,
+            };
+            let mut replaced = false;
+            tasks.retain(|pending| match pending {
+                Task::FunctionCall(FunctionCall {
+                    source: FunctionCallSource::Driver(id),
+                    ..
+                }) if !replaced && *id == callback_id => {
+                    replaced = true;
+                    false
+                }
+                _ => true,
+            });
+            if replaced {
+                // The replaced callback no longer needs to be run.
+                self.kernel.decrement_work();
+                tasks.enqueue(task)
+            } else {
+                false
+            }
+        });
 
         // Make a note that we lost this callback if the enqueue function
         // fails.
@@ -518,7 +829,33 @@ impl<C: Chip> ProcessType for Process<'a, C> {
         ret
     }
 
+    fn enqueue_task_when_room(&self, task: Task) -> bool {
+        match self.state.get() {
+            State::Fault | State::Terminated | State::CredentialsFailed => return false,
+            _ => {}
+        }
+        if self.held_task.get().is_some() {
+            return false;
+        }
+
+        if self.tasks.map_or(false, |tasks| tasks.enqueue(task)) {
+            self.kernel.increment_work();
+        } else {
+            self.held_task.set(Some(task));
+        }
+        true
+    }
+
     fn remove_pending_callbacks(&self, callback_id: CallbackId) {
+        if let Some(Task::FunctionCall(FunctionCall {
+            source: FunctionCallSource::Driver(id),
+            ..
+        })) = self.held_task.get()
+        {
+            if id == callback_id {
+                self.held_task.set(None);
+            }
+        }
         self.tasks.map(|tasks| {
             tasks.retain(|task| match task {
                 // Remove only tasks that are function calls with an id equal
@@ -536,6 +873,16 @@ impl<C: Chip> ProcessType for Process<'a, C> {
         self.state.get()
     }
 
+    fn ready(&self) -> bool {
+        match self.state.get() {
+            State::Running => true,
+            State::Yielded | State::Unstarted => {
+                self.tasks.map_or(false, |tasks| tasks.has_elements())
+            }
+            _ => false,
+        }
+    }
+
     fn set_yielded_state(&self) {
         if self.state.get() == State::Running {
             self.state.set(State::Yielded);
@@ -560,74 +907,35 @@ impl<C: Chip> ProcessType for Process<'a, C> {
     }
 
     fn set_fault_state(&self) {
+        // A process that may never run can't fault. Faulting it anyway, e.g.
+        // from `hardfault_all_apps()`, must not hand it to the fault policy,
+        // which could restart it.
+        match self.state.get() {
+            State::CredentialsFailed | State::Terminated => return,
+            _ => {}
+        }
+
+        // A running process counts as outstanding work. Without this the
+        // kernel would never see all processes blocked again, and would not
+        // sleep while a stopped process waits to be restarted.
+        match self.state.get() {
+            State::Running | State::StoppedRunning => self.kernel.decrement_work(),
+            _ => {}
+        }
         self.state.set(State::Fault);
 
-        match self.fault_response {
+        // Keep a record of the fault before the fault response changes the
+        // process's state.
+        self.kernel.log_fault(&ProcessFault::new(self));
+
+        // The board's fault policy, if it has one, decides what happens next.
+        match self.kernel.fault_response(self, self.fault_response) {
             FaultResponse::Panic => {
                 // process faulted. Panic and print status
                 panic!("Process {} had a fault", self.process_name);
             }
             FaultResponse::Restart => {
-                // Remove the tasks that were scheduled for the app from the
-                // amount of work queue.
-                let tasks_len = self.tasks.map_or(0, |tasks| tasks.len());
-                for _ in 0..tasks_len {
-                    self.kernel.decrement_work();
-                }
-
-                // And remove those tasks
-                self.tasks.map(|tasks| {
-                    tasks.empty();
-                });
-
-                // Update debug information
-                self.debug.map(|debug| {
-                    // Mark that we restarted this process.
-                    debug.restart_count += 1;
-
-                    // Reset some state for the process.
-                    debug.syscall_count = 0;
-                    debug.last_syscall = None;
-                    debug.dropped_callback_count = 0;
-                });
-
-                // We are going to start this process over again, so need
-                // the init_fn location.
-                let app_flash_address = self.flash_start();
-                let init_fn = unsafe {
-                    app_flash_address.offset(self.header.get_init_function_offset() as isize)
-                        as usize
-                };
-                self.state.set(State::Unstarted);
-
-                // Need to reset the grant region.
-                unsafe {
-                    self.grant_ptrs_reset();
-                }
-                self.kernel_memory_break
-                    .set(self.original_kernel_memory_break);
-
-                // Reset other memory pointers.
-                self.app_break.set(self.original_app_break);
-                self.current_stack_pointer.set(self.original_stack_pointer);
-
-                // And queue up this app to be restarted.
-                let flash_protected_size = self.header.get_protected_size() as usize;
-                let flash_app_start = app_flash_address as usize + flash_protected_size;
-
-                self.tasks.map(|tasks| {
-                    tasks.empty();
-                    tasks.enqueue(Task::FunctionCall(FunctionCall {
-                        source: FunctionCallSource::Kernel,
-                        pc: init_fn,
-                        argument0: flash_app_start,
-                        argument1: self.memory.as_ptr() as usize,
-                        argument2: self.memory.len() as usize,
-                        argument3: self.app_break.get() as usize,
-                    }));
-                });
-
-                self.kernel.increment_work();
+                self.restart();
             }
             FaultResponse::Stop => {
                 // This looks a lot like restart, except we just leave the app
@@ -659,10 +967,115 @@ impl<C: Chip> ProcessType for Process<'a, C> {
         }
     }
 
+    fn restart(&self) {
+        // Restarting would queue the init function of an app that must never
+        // run.
+        match self.state.get() {
+            State::CredentialsFailed | State::Terminated => return,
+            _ => {}
+        }
+
+        // Remove the tasks that were scheduled for the app from the
+        // amount of work queue.
+        let tasks_len = self.tasks.map_or(0, |tasks| tasks.len());
+        for _ in 0..tasks_len {
+            self.kernel.decrement_work();
+        }
+
+        // And remove those tasks
+        self.tasks.map(|tasks| {
+            tasks.empty();
+        });
+        self.held_task.set(None);
+
+        // Update debug information
+        self.debug.map(|debug| {
+            // Mark that we restarted this process.
+            debug.restart_count += 1;
+
+            // Reset some state for the process.
+            debug.syscall_count = 0;
+            debug.last_syscall = None;
+            debug.dropped_callback_count = 0;
+        });
+        self.syscall_trace.map(|trace| trace.empty());
+
+        // We are going to start this process over again, so need
+        // the init_fn location.
+        let app_flash_address = self.flash_start();
+        let init_fn = unsafe {
+            app_flash_address.offset(self.header.get_init_function_offset() as isize) as usize
+        };
+        self.state.set(State::Unstarted);
+
+        // The restarted process is a new run of the app, so `AppId`s naming
+        // the previous run must no longer refer to it.
+        self.identifier.set(self.kernel.create_process_identifier());
+
+        // Need to reset the grant region.
+        unsafe {
+            self.grant_ptrs_reset();
+        }
+        self.kernel_memory_break
+            .set(self.original_kernel_memory_break);
+
+        // Reset other memory pointers.
+        self.app_break.set(self.original_app_break);
+        self.current_stack_pointer.set(self.original_stack_pointer);
+
+        // And queue up this app to be restarted.
+        let flash_protected_size = self.header.get_protected_size() as usize;
+        let flash_app_start = app_flash_address as usize + flash_protected_size;
+
+        self.tasks.map(|tasks| {
+            tasks.empty();
+            tasks.enqueue(Task::FunctionCall(FunctionCall {
+                source: FunctionCallSource::Kernel,
+                pc: init_fn,
+                argument0: flash_app_start,
+                argument1: self.memory.as_ptr() as usize,
+                argument2: self.memory.len() as usize,
+                argument3: self.app_break.get() as usize,
+            }));
+        });
+
+        self.kernel.increment_work();
+    }
+
+    fn terminate(&self) {
+        // A running process counts as outstanding work, as does each of its
+        // queued tasks.
+        match self.state.get() {
+            State::Running | State::StoppedRunning => self.kernel.decrement_work(),
+            _ => {}
+        }
+        let tasks_len = self.tasks.map_or(0, |tasks| tasks.len());
+        for _ in 0..tasks_len {
+            self.kernel.decrement_work();
+        }
+        self.tasks.map(|tasks| {
+            tasks.empty();
+        });
+        self.held_task.set(None);
+
+        // Clear any grant regions this app has setup with any capsules.
+        unsafe {
+            self.grant_ptrs_reset();
+        }
+
+        self.state.set(State::Terminated);
+    }
+
     fn dequeue_task(&self) -> Option<Task> {
         self.tasks.map_or(None, |tasks| {
             tasks.dequeue().map(|cb| {
                 self.kernel.decrement_work();
+                // There is room for the held task now.
+                self.held_task.take().map(|held| {
+                    if tasks.enqueue(held) {
+                        self.kernel.increment_work();
+                    }
+                });
                 cb
             })
         })
@@ -809,27 +1222,74 @@ impl<C: Chip> ProcessType for Process<'a, C> {
         }
     }
 
-    unsafe fn alloc(&self, size: usize, align: usize) -> Option<&mut [u8]> {
-        self.mpu_config.and_then(|mut config| {
-            let new_break_unaligned = self.kernel_memory_break.get().offset(-(size as isize));
-            // The alignment must be a power of two, 2^a. The expression `!(align - 1)` then
-            // returns a mask with leading ones, followed by `a` trailing zeros.
-            let alignment_mask = !(align - 1);
-            let new_break = (new_break_unaligned as usize & alignment_mask) as *const u8;
-            if new_break < self.app_break.get() {
-                None
-            } else if let Err(_) = self.chip.mpu().update_app_memory_region(
-                self.app_break.get(),
-                new_break,
-                mpu::Permissions::ReadWriteOnly,
-                &mut config,
-            ) {
-                None
-            } else {
-                self.kernel_memory_break.set(new_break);
-                Some(slice::from_raw_parts_mut(new_break as *mut u8, size))
-            }
-        })
+    fn allow_read_only(
+        &self,
+        buf_start_addr: *const u8,
+        size: usize,
+    ) -> Result<Option<ReadOnlyAppSlice<u8>>, ReturnCode> {
+        if buf_start_addr == ptr::null() {
+            Ok(None)
+        } else if self.in_app_owned_memory(buf_start_addr, size) {
+            // The buffer must stay in app memory while it is shared, like a
+            // writable one.
+            let buf_end_addr = buf_start_addr.wrapping_add(size);
+            let new_water_mark = max(self.allow_high_water_mark.get(), buf_end_addr);
+            self.allow_high_water_mark.set(new_water_mark);
+            Ok(Some(ReadOnlyAppSlice::new(
+                buf_start_addr,
+                size,
+                self.appid(),
+            )))
+        } else if self.in_app_flash(buf_start_addr, size) {
+            Ok(Some(ReadOnlyAppSlice::new(
+                buf_start_addr,
+                size,
+                self.appid(),
+            )))
+        } else {
+            Err(ReturnCode::EINVAL)
+        }
+    }
+
+    unsafe fn alloc(
+        &self,
+        size: usize,
+        align: usize,
+        grant_num: usize,
+    ) -> Result<&mut [u8], Error> {
+        let usage = match self.grant_usage.get(grant_num) {
+            Some(usage) => usage,
+            None => return Err(Error::KernelError),
+        };
+        self.mpu_config
+            .map_or(Err(Error::KernelError), |mut config| {
+                let new_break_unaligned = self.kernel_memory_break.get().offset(-(size as isize));
+                // The alignment must be a power of two, 2^a. The expression `!(align - 1)` then
+                // returns a mask with leading ones, followed by `a` trailing zeros.
+                let alignment_mask = !(align - 1);
+                let new_break = (new_break_unaligned as usize & alignment_mask) as *const u8;
+                // Count the padding for alignment too, as it is just as unusable.
+                let allocated = self.kernel_memory_break.get() as usize - new_break as usize;
+                let over_quota = self.grant_quota().map_or(false, |quota| {
+                    self.grant_bytes_allocated() + allocated > quota
+                });
+                if over_quota {
+                    Err(Error::QuotaExceeded)
+                } else if new_break < self.app_break.get() {
+                    Err(Error::OutOfMemory)
+                } else if let Err(_) = self.chip.mpu().update_app_memory_region(
+                    self.app_break.get(),
+                    new_break,
+                    mpu::Permissions::ReadWriteOnly,
+                    &mut config,
+                ) {
+                    Err(Error::OutOfMemory)
+                } else {
+                    self.kernel_memory_break.set(new_break);
+                    usage.add(allocated);
+                    Ok(slice::from_raw_parts_mut(new_break as *mut u8, size))
+                }
+            })
     }
 
     unsafe fn free(&self, _: *mut u8) {}
@@ -840,16 +1300,72 @@ impl<C: Chip> ProcessType for Process<'a, C> {
         (self.mem_end() as *mut *mut u8).offset(-(grant_num + 1))
     }
 
+    fn grant_bytes_allocated(&self) -> usize {
+        self.grant_usage.iter().map(|usage| usage.get()).sum()
+    }
+
+    fn grant_bytes_allocated_for(&self, grant_num: usize) -> usize {
+        self.grant_usage
+            .get(grant_num)
+            .map_or(0, |usage| usage.get())
+    }
+
+    fn grant_quota(&self) -> Option<usize> {
+        self.grant_quota
+            .get()
+            .or_else(|| self.kernel.default_grant_quota())
+    }
+
+    fn set_grant_quota(&self, quota: Option<usize>) {
+        self.grant_quota.set(quota);
+    }
+
     fn get_process_name(&self) -> &'static str {
         self.process_name
     }
 
+    fn get_priority(&self) -> Option<u32> {
+        self.header.get_priority()
+    }
+
+    fn get_timeslice_us(&self) -> Option<u32> {
+        self.header.get_timeslice_us()
+    }
+
+    fn driver_permitted(&self, driver_num: usize) -> bool {
+        self.header.driver_permitted(driver_num)
+    }
+
     unsafe fn set_syscall_return_value(&self, return_value: isize) {
         let mut stored_state = self.stored_state.get();
         self.chip
             .userspace_kernel_boundary()
             .set_syscall_return_value(self.sp(), &mut stored_state, return_value);
         self.stored_state.set(stored_state);
+
+        self.syscall_trace.map(|trace| {
+            trace
+                .iter_mut()
+                .last()
+                .map(|record| record.return_value = Some(return_value));
+        });
+    }
+
+    unsafe fn set_syscall_return(&self, return_value: SyscallReturn) {
+        let mut stored_state = self.stored_state.get();
+        self.chip.userspace_kernel_boundary().set_syscall_return(
+            self.sp(),
+            &mut stored_state,
+            return_value,
+        );
+        self.stored_state.set(stored_state);
+
+        self.syscall_trace.map(|trace| {
+            trace
+                .iter_mut()
+                .last()
+                .map(|record| record.return_value = Some(return_value.summary()));
+        });
     }
 
     unsafe fn set_process_function(&self, callback: FunctionCall) {
@@ -940,6 +1456,32 @@ impl<C: Chip> ProcessType for Process<'a, C> {
         self.debug.map_or(0, |debug| debug.syscall_count)
     }
 
+    fn debug_syscall_called(&self, syscall: Syscall, cpu_time_us: u64) {
+        self.debug.map(|debug| {
+            debug.syscall_count += 1;
+            debug.last_syscall = Some(syscall);
+        });
+        self.syscall_trace.map(|trace| {
+            // Make room by forgetting the oldest call.
+            if trace.is_full() {
+                trace.dequeue();
+            }
+            trace.enqueue(SyscallRecord {
+                syscall: syscall,
+                return_value: None,
+                cpu_time_us: cpu_time_us,
+            });
+        });
+    }
+
+    fn debug_syscall_trace(&self, f: &mut dyn FnMut(&SyscallRecord)) {
+        self.syscall_trace.map(|trace| {
+            for record in trace.iter() {
+                f(record);
+            }
+        });
+    }
+
     fn debug_dropped_callback_count(&self) -> usize {
         self.debug.map_or(0, |debug| debug.dropped_callback_count)
     }
@@ -958,10 +1500,26 @@ impl<C: Chip> ProcessType for Process<'a, C> {
             .map(|debug| debug.timeslice_expiration_count += 1);
     }
 
+    fn debug_cpu_time_us(&self) -> u64 {
+        self.debug.map_or(0, |debug| debug.cpu_time_us)
+    }
+
+    fn debug_add_cpu_time(&self, us: u32) {
+        self.debug.map(|debug| debug.cpu_time_us += us as u64);
+    }
+
     unsafe fn fault_fmt(&self, writer: &mut dyn Write) {
         self.chip.userspace_kernel_boundary().fault_fmt(writer);
     }
 
+    unsafe fn registers_fmt(&self, writer: &mut dyn Write) {
+        self.chip.userspace_kernel_boundary().process_detail_fmt(
+            self.sp(),
+            &self.stored_state.get(),
+            writer,
+        );
+    }
+
     unsafe fn process_detail_fmt(&self, writer: &mut dyn Write) {
         // Flash
         let flash_end = self.flash.as_ptr().add(self.flash.len()) as usize;
@@ -1085,11 +1643,7 @@ impl<C: Chip> ProcessType for Process<'a, C> {
             flash_start
         ));
 
-        self.chip.userspace_kernel_boundary().process_detail_fmt(
-            self.sp(),
-            &self.stored_state.get(),
-            writer,
-        );
+        self.registers_fmt(writer);
 
         let _ = writer.write_fmt(format_args!(
             "\
@@ -1109,6 +1663,7 @@ impl<C: 'static + Chip> Process<'a, C> {
         remaining_app_memory: *mut u8,
         remaining_app_memory_size: usize,
         fault_response: FaultResponse,
+        checker: Option<&dyn AppCredentialsChecker>,
         index: usize,
     ) -> (Option<&'static dyn ProcessType>, usize, usize) {
         if let Some(tbf_header) = tbfheader::parse_and_validate_tbf_header(app_flash_address) {
@@ -1120,6 +1675,20 @@ impl<C: 'static + Chip> Process<'a, C> {
                 return (None, app_flash_size, 0);
             }
 
+            // If the board has a credentials policy, the app must satisfy it
+            // before we give it any resources, unless the policy keeps failed
+            // apps loaded but unstarted.
+            let mut credentials_failed = false;
+            if let Some(checker) = checker {
+                if !process_checker::check_app_credentials(checker, app_flash_address, &tbf_header)
+                {
+                    match checker.failure_response() {
+                        CredentialsFailure::Refuse => return (None, app_flash_size, 0),
+                        CredentialsFailure::KeepUnstarted => credentials_failed = true,
+                    }
+                }
+            }
+
             // Otherwise, actually load the app.
             let mut min_app_ram_size = tbf_header.get_minimum_app_ram_size() as usize;
             let process_name = tbf_header.get_package_name();
@@ -1149,18 +1718,56 @@ impl<C: 'static + Chip> Process<'a, C> {
             let grant_ptrs_num = kernel.get_grant_count_and_finalize();
             let grant_ptrs_offset = grant_ptrs_num * grant_ptr_size;
 
-            // Allocate memory for callback ring buffer.
+            // Make room for counting how much memory each grant uses.
+            let grant_usage_offset = grant_ptrs_num * mem::size_of::<Cell<usize>>();
+
+            // Allocate memory for callback ring buffer. Like the syscall
+            // trace below, it needs room for one more task than it holds. The
+            // app can't have a deeper queue than any other process could.
             let callback_size = mem::size_of::<Task>();
-            let callback_len = 10;
-            let callbacks_offset = callback_len * callback_size;
+            let callback_len = cmp::min(
+                tbf_header
+                    .get_task_queue_depth()
+                    .map_or(kernel.task_queue_depth(), |depth| depth as usize),
+                MAX_TASK_QUEUE_DEPTH,
+            ) + 1;
+            let callbacks_offset = match callback_len.checked_mul(callback_size) {
+                Some(offset) => offset,
+                None => return (None, app_flash_size, 0),
+            };
 
-            // Make room to store this process's metadata.
-            let process_struct_offset = mem::size_of::<Process<C>>();
+            // Make room for the syscall trace, if there is one.
+            let syscall_trace_len = match kernel.syscall_trace_length() {
+                0 => 0,
+                len => len.saturating_add(1),
+            };
+            // The records hold 64-bit times, so leave room to align them too.
+            let syscall_trace_offset = match syscall_trace_len
+                .checked_mul(mem::size_of::<SyscallRecord>())
+                .and_then(|size| size.checked_add(mem::align_of::<SyscallRecord>() - 1))
+            {
+                Some(offset) => offset,
+                None => return (None, app_flash_size, 0),
+            };
+
+            // Make room to store this process's metadata. It holds 64-bit
+            // counters, so leave room to align it too.
+            let process_struct_offset =
+                mem::size_of::<Process<C>>() + mem::align_of::<Process<C>>() - 1;
 
             // Initial sizes of the app-owned and kernel-owned parts of process memory.
             // Provide the app with plenty of initial process accessible memory.
-            let initial_kernel_memory_size =
-                grant_ptrs_offset + callbacks_offset + process_struct_offset;
+            // The sizes depend on the TBF header and the board, so refuse to
+            // load the process rather than let them overflow.
+            let initial_kernel_memory_size = match grant_ptrs_offset
+                .checked_add(grant_usage_offset)
+                .and_then(|size| size.checked_add(callbacks_offset))
+                .and_then(|size| size.checked_add(syscall_trace_offset))
+                .and_then(|size| size.checked_add(process_struct_offset))
+            {
+                Some(size) => size,
+                None => return (None, app_flash_size, 0),
+            };
             let initial_app_memory_size = 3 * 1024;
 
             if min_app_ram_size < initial_app_memory_size {
@@ -1168,7 +1775,11 @@ impl<C: 'static + Chip> Process<'a, C> {
             }
 
             // Minimum memory size for the process.
-            let min_total_memory_size = min_app_ram_size + initial_kernel_memory_size;
+            let min_total_memory_size =
+                match min_app_ram_size.checked_add(initial_kernel_memory_size) {
+                    Some(size) => size,
+                    None => return (None, app_flash_size, 0),
+                };
 
             // Determine where process memory will go and allocate MPU region for app-owned memory.
             let (memory_start, memory_size) = match chip.mpu().allocate_app_memory_region(
@@ -1211,6 +1822,14 @@ impl<C: 'static + Chip> Process<'a, C> {
                 *opt = ptr::null()
             }
 
+            // Below them go the grant usage counters, which start at zero.
+            kernel_memory_break = kernel_memory_break.offset(-(grant_usage_offset as isize));
+            let grant_usage =
+                slice::from_raw_parts_mut(kernel_memory_break as *mut Cell<usize>, grant_ptrs_num);
+            for usage in grant_usage.iter_mut() {
+                ptr::write(usage, Cell::new(0));
+            }
+
             // Now that we know we have the space we can setup the memory
             // for the callbacks.
             kernel_memory_break = kernel_memory_break.offset(-(callbacks_offset as isize));
@@ -1220,8 +1839,36 @@ impl<C: 'static + Chip> Process<'a, C> {
                 slice::from_raw_parts_mut(kernel_memory_break as *mut Task, callback_len);
             let tasks = RingBuffer::new(callback_buf);
 
-            // Last thing is the process struct.
-            kernel_memory_break = kernel_memory_break.offset(-(process_struct_offset as isize));
+            // Then the syscall trace. The tasks above it are only word
+            // aligned, so round down to the records' alignment.
+            kernel_memory_break = kernel_memory_break
+                .offset(-((syscall_trace_len * mem::size_of::<SyscallRecord>()) as isize));
+            kernel_memory_break = kernel_memory_break
+                .sub(kernel_memory_break as usize % mem::align_of::<SyscallRecord>());
+            debug_assert_eq!(
+                kernel_memory_break as usize % mem::align_of::<SyscallRecord>(),
+                0
+            );
+            let syscall_trace = if syscall_trace_len > 0 {
+                let trace_buf = slice::from_raw_parts_mut(
+                    kernel_memory_break as *mut SyscallRecord,
+                    syscall_trace_len,
+                );
+                MapCell::new(RingBuffer::new(trace_buf))
+            } else {
+                MapCell::empty()
+            };
+
+            // Last thing is the process struct. The memory above it is only
+            // word aligned, so round down to the struct's alignment.
+            kernel_memory_break =
+                kernel_memory_break.offset(-(mem::size_of::<Process<C>>() as isize));
+            kernel_memory_break = kernel_memory_break
+                .sub(kernel_memory_break as usize % mem::align_of::<Process<C>>());
+            debug_assert_eq!(
+                kernel_memory_break as usize % mem::align_of::<Process<C>>(),
+                0
+            );
             let process_struct_memory_location = kernel_memory_break;
 
             // Determine the debug information to the best of our
@@ -1235,10 +1882,13 @@ impl<C: 'static + Chip> Process<'a, C> {
                 &mut *(process_struct_memory_location as *mut Process<'static, C>);
 
             process.app_idx = index;
+            process.identifier = Cell::new(kernel.create_process_identifier());
             process.kernel = kernel;
             process.chip = chip;
             process.memory = app_memory;
             process.header = tbf_header;
+            process.grant_usage = grant_usage;
+            process.grant_quota = Cell::new(None);
             process.kernel_memory_break = Cell::new(kernel_memory_break);
             process.original_kernel_memory_break = kernel_memory_break;
             process.app_break = Cell::new(initial_sbrk_pointer);
@@ -1263,6 +1913,8 @@ impl<C: 'static + Chip> Process<'a, C> {
                 Cell::new(None),
             ];
             process.tasks = MapCell::new(tasks);
+            process.held_task = Cell::new(None);
+            process.syscall_trace = syscall_trace;
             process.process_name = process_name;
 
             process.debug = MapCell::new(ProcessDebug {
@@ -1274,21 +1926,28 @@ impl<C: 'static + Chip> Process<'a, C> {
                 dropped_callback_count: 0,
                 restart_count: 0,
                 timeslice_expiration_count: 0,
+                cpu_time_us: 0,
             });
 
             let flash_protected_size = process.header.get_protected_size() as usize;
             let flash_app_start = app_flash_address as usize + flash_protected_size;
 
-            process.tasks.map(|tasks| {
-                tasks.enqueue(Task::FunctionCall(FunctionCall {
-                    source: FunctionCallSource::Kernel,
-                    pc: init_fn,
-                    argument0: flash_app_start,
-                    argument1: process.memory.as_ptr() as usize,
-                    argument2: process.memory.len() as usize,
-                    argument3: process.app_break.get() as usize,
-                }));
-            });
+            // A process that failed its credentials check is kept, but never
+            // gets its init function call.
+            if credentials_failed {
+                process.state.set(State::CredentialsFailed);
+            } else {
+                process.tasks.map(|tasks| {
+                    tasks.enqueue(Task::FunctionCall(FunctionCall {
+                        source: FunctionCallSource::Kernel,
+                        pc: init_fn,
+                        argument0: flash_app_start,
+                        argument1: process.memory.as_ptr() as usize,
+                        argument2: process.memory.len() as usize,
+                        argument3: process.app_break.get() as usize,
+                    }));
+                });
+            }
 
             // Handle any architecture-specific requirements for a new process
             let mut stored_state = process.stored_state.get();
@@ -1310,7 +1969,9 @@ impl<C: 'static + Chip> Process<'a, C> {
             };
 
             // Mark this process as having something to do (it has to start!)
-            kernel.increment_work();
+            if !credentials_failed {
+                kernel.increment_work();
+            }
 
             return (
                 Some(process),
@@ -1339,9 +2000,22 @@ impl<C: 'static + Chip> Process<'a, C> {
             && buf_end_addr <= self.app_break.get()
     }
 
-    /// Reset all `grant_ptr`s to NULL.
+    /// Checks if the buffer represented by the passed in base pointer and size
+    /// is within the process's flash region.
+    fn in_app_flash(&self, buf_start_addr: *const u8, size: usize) -> bool {
+        let buf_end_addr = buf_start_addr.wrapping_add(size);
+
+        buf_end_addr >= buf_start_addr
+            && buf_start_addr >= self.flash_start()
+            && buf_end_addr <= self.flash_end()
+    }
+
+    /// Reset all `grant_ptr`s to NULL and the grant usage counters to zero.
     #[allow(clippy::cast_ptr_alignment)]
     unsafe fn grant_ptrs_reset(&self) {
+        for usage in self.grant_usage.iter() {
+            usage.set(0);
+        }
         let grant_ptrs_num = self.kernel.get_grant_count_and_finalize();
         for grant_num in 0..grant_ptrs_num {
             let grant_num = grant_num as isize;
@@ -0,0 +1,121 @@
+//! Checking the credentials of process images before they are loaded.
+//!
+//! A TBF app may carry one or more credentials footers after its program
+//! binary, each holding a hash or signature over the app's integrity region
+//! (the TBF header and the program binary, i.e. everything from the start of
+//! the app up to its binary end offset). When processes are loaded with
+//! `load_and_check_processes()`, the board supplies an `AppCredentialsChecker`
+//! that is shown each credential in turn and decides whether the app may run.
+//!
+//! The kernel does not implement any cryptography itself: the checker decides
+//! which credential formats and keys it trusts. A process is loaded if the
+//! checker accepts one of its credentials, or if none are accepted or rejected
+//! and the checker does not require credentials. Otherwise the checker's
+//! `failure_response()` decides what happens: by default the process is refused
+//! and its flash skipped, exactly like a disabled app, but a checker can instead
+//! keep it loaded in the `CredentialsFailed` state, where it is listed by the
+//! process console but never started.
+//!
+//! ```ignore
+//! struct Sha256Only;
+//!
+//! impl AppCredentialsChecker for Sha256Only {
+//!     fn require_credentials(&self) -> bool {
+//!         true
+//!     }
+//!
+//!     fn check_credentials(
+//!         &self,
+//!         credentials: TbfFooterV2Credentials,
+//!         integrity_region: &[u8],
+//!     ) -> CheckResult {
+//!         match credentials.format() {
+//!             TbfFooterV2CredentialsType::Sha256 => {
+//!                 if sha256(integrity_region) == credentials.data() {
+//!                     CheckResult::Accept
+//!                 } else {
+//!                     CheckResult::Reject
+//!                 }
+//!             }
+//!             _ => CheckResult::Pass,
+//!         }
+//!     }
+//! }
+//! ```
+
+use core::slice;
+
+use crate::tbfheader::{TbfFooterCredentialsIter, TbfFooterV2Credentials, TbfHeader};
+
+/// The decision of an `AppCredentialsChecker` about a single credential.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CheckResult {
+    /// The credential is valid and trusted; the process may be loaded. No
+    /// further credentials are checked.
+    Accept,
+    /// The checker has no opinion on this credential, for example because it
+    /// does not support its format or does not know the signing key. The next
+    /// credential is checked.
+    Pass,
+    /// The credential is invalid, for example because the hash does not match.
+    /// The process is refused and no further credentials are checked.
+    Reject,
+}
+
+/// What the kernel does with a process whose credentials the checker does not
+/// accept.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CredentialsFailure {
+    /// Skip the app and its flash, as if it were disabled. The process does
+    /// not use any RAM.
+    Refuse,
+    /// Load the process, but never start or schedule it. This keeps the app
+    /// visible, for example in the process console, at the cost of its RAM.
+    KeepUnstarted,
+}
+
+/// Board-supplied policy for deciding which processes may be loaded.
+pub trait AppCredentialsChecker {
+    /// Whether a process needs an accepted credential to be loaded. If this is
+    /// `false`, processes without credentials or with only credentials the
+    /// checker passes on are loaded as well.
+    fn require_credentials(&self) -> bool;
+
+    /// Check one credential of a process. `integrity_region` is the region of
+    /// flash the credential covers.
+    fn check_credentials(
+        &self,
+        credentials: TbfFooterV2Credentials,
+        integrity_region: &[u8],
+    ) -> CheckResult;
+
+    /// What to do with processes that fail the check. Refusing them is the
+    /// default.
+    fn failure_response(&self) -> CredentialsFailure {
+        CredentialsFailure::Refuse
+    }
+}
+
+/// Run the checker over all credentials of the app at `app_flash_address` and
+/// return whether the app may be loaded.
+///
+/// The caller must ensure that `header` was parsed from `app_flash_address`
+/// and that the entire app lies in readable memory.
+crate unsafe fn check_app_credentials(
+    checker: &dyn AppCredentialsChecker,
+    app_flash_address: *const u8,
+    header: &TbfHeader,
+) -> bool {
+    let integrity_region =
+        slice::from_raw_parts(app_flash_address, header.get_binary_end() as usize);
+
+    for credentials in TbfFooterCredentialsIter::new(app_flash_address, header) {
+        match checker.check_credentials(credentials, integrity_region) {
+            CheckResult::Accept => return true,
+            CheckResult::Reject => return false,
+            CheckResult::Pass => {}
+        }
+    }
+
+    !checker.require_credentials()
+}
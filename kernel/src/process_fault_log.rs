@@ -0,0 +1,64 @@
+//! Recording process faults so they can be inspected later.
+//!
+//! When a process faults, the kernel hands a `ProcessFault` to the board's
+//! `ProcessFaultLog`, if one is set with `Kernel::set_fault_log()`, before it
+//! applies the process's `FaultResponse`. The log decides where to keep the
+//! record; keeping it in retained RAM or flash lets it be read back after the
+//! board reboots, e.g. because the fault response was `Panic`.
+
+use core::fmt::Write;
+
+use crate::process::ProcessType;
+
+/// Storage for records of process faults.
+pub trait ProcessFaultLog {
+    /// Record a fault. This is called in the kernel right after the process
+    /// faulted, so the fault status registers still describe this fault.
+    fn log_fault(&self, fault: &ProcessFault);
+
+    /// Call `f` with the most recently recorded fault, which may be from
+    /// before the last reboot. `f` is not called if there is no record.
+    fn map_last_fault(&self, f: &mut dyn FnMut(&[u8]));
+}
+
+/// A process that just faulted.
+pub struct ProcessFault<'a> {
+    process: &'a dyn ProcessType,
+}
+
+impl ProcessFault<'a> {
+    crate fn new(process: &'a dyn ProcessType) -> ProcessFault<'a> {
+        ProcessFault { process: process }
+    }
+
+    pub fn process_name(&self) -> &'static str {
+        self.process.get_process_name()
+    }
+
+    /// Start of the process's image in flash. Unlike the name, which the app
+    /// picks in its TBF header, this is where the kernel found the image, and
+    /// no two processes have the same one.
+    pub fn flash_start(&self) -> *const u8 {
+        self.process.flash_start()
+    }
+
+    /// How many times the process has been restarted before this fault.
+    pub fn restart_count(&self) -> usize {
+        self.process.debug_restart_count()
+    }
+
+    /// Write a text record of the fault: the app name, its restart count, the
+    /// fault status registers, and the process's registers.
+    pub fn fmt(&self, writer: &mut dyn Write) {
+        let _ = writer.write_fmt(format_args!(
+            "App: {}   Restart Count: {}\r\n",
+            self.process_name(),
+            self.restart_count()
+        ));
+        unsafe {
+            self.process.fault_fmt(writer);
+            self.process.registers_fmt(writer);
+        }
+        let _ = writer.write_fmt(format_args!("\r\n"));
+    }
+}
@@ -0,0 +1,243 @@
+//! Loading, unloading and replacing processes while the kernel is running.
+//!
+//! At boot, `load_processes()` fills the process array from the apps in flash.
+//! A `DynamicProcessLoader` lets trusted code change that set afterwards,
+//! without rebooting the board: it can load a TBF image that was written to
+//! flash (e.g. by an update capsule) into a free process slot, unload a
+//! process and hand its RAM back, or replace a process with a new image.
+//!
+//! The loader uses the process slots of the kernel and the app memory buffer
+//! that was passed to `load_processes()`, so it must be used after processes
+//! are loaded at boot. Memory is handed out from the parts of the app memory
+//! buffer that no loaded process is using, so RAM of unloaded processes is
+//! reused by later loads.
+//!
+//! ```ignore
+//! let loader = static_init!(
+//!     kernel::procs::DynamicProcessLoader<sam4l::chip::Sam4l>,
+//!     kernel::procs::DynamicProcessLoader::new(board_kernel, chip, FAULT_RESPONSE, None)
+//! );
+//! ```
+//!
+//! Unloading a process clears its slot, so capsules must not keep using the
+//! `AppId` of a process after it has been unloaded. The loader must not be
+//! used to unload or replace the process whose system call the kernel is
+//! currently handling.
+
+use core::cell::Cell;
+use core::cmp;
+
+use crate::callback::AppId;
+use crate::capabilities::ProcessLoadingCapability;
+use crate::platform::Chip;
+use crate::process::{FaultResponse, Process, ProcessType};
+use crate::process_checker::{self, AppCredentialsChecker};
+use crate::returncode::ReturnCode;
+use crate::sched::Kernel;
+use crate::tbfheader;
+
+/// Loads and unloads processes at runtime.
+pub struct DynamicProcessLoader<C: 'static + Chip> {
+    kernel: &'static Kernel,
+    chip: &'static C,
+    fault_response: FaultResponse,
+    checker: Option<&'static dyn AppCredentialsChecker>,
+}
+
+impl<C: 'static + Chip> DynamicProcessLoader<C> {
+    pub fn new(
+        kernel: &'static Kernel,
+        chip: &'static C,
+        fault_response: FaultResponse,
+        checker: Option<&'static dyn AppCredentialsChecker>,
+    ) -> DynamicProcessLoader<C> {
+        DynamicProcessLoader {
+            kernel: kernel,
+            chip: chip,
+            fault_response: fault_response,
+            checker: checker,
+        }
+    }
+
+    /// Load the TBF app starting at `app_flash_address` into a free process
+    /// slot and start it.
+    ///
+    /// Returns the `AppId` of the new process, or:
+    ///
+    /// - `EINVAL` if there is no valid, enabled app at the address.
+    /// - `EBUSY` if the app overlaps the flash of a loaded process.
+    /// - `FAIL` if the app does not satisfy the board's credentials checker.
+    /// - `ENOMEM` if there is no free process slot or not enough free memory,
+    ///   or if `load_processes()` has not been called yet.
+    pub fn load(
+        &self,
+        app_flash_address: *const u8,
+        _capability: &dyn ProcessLoadingCapability,
+    ) -> Result<AppId, ReturnCode> {
+        unsafe { self.load_into(app_flash_address) }
+    }
+
+    /// Stop the process with the given `AppId` and remove it from the kernel.
+    /// Its memory becomes available to processes loaded later.
+    pub fn unload(&self, appid: AppId, _capability: &dyn ProcessLoadingCapability) -> ReturnCode {
+        match self.process_slot(appid) {
+            Some((slot, process)) => {
+                slot.set(None);
+                process.terminate();
+                ReturnCode::SUCCESS
+            }
+            None => ReturnCode::EINVAL,
+        }
+    }
+
+    /// Replace the process with the given `AppId` with the app starting at
+    /// `app_flash_address`.
+    ///
+    /// The new image is validated before the old process is unloaded. If the
+    /// new process cannot be created, the old one is loaded again from its
+    /// flash. Returns the `AppId` of the new process, or the same errors as
+    /// `load()`.
+    pub fn replace(
+        &self,
+        appid: AppId,
+        app_flash_address: *const u8,
+        _capability: &dyn ProcessLoadingCapability,
+    ) -> Result<AppId, ReturnCode> {
+        let (slot, old) = match self.process_slot(appid) {
+            Some(found) => found,
+            None => return Err(ReturnCode::EINVAL),
+        };
+        unsafe {
+            self.check_image(app_flash_address)?;
+
+            // Once the old process is terminated its RAM, which holds its
+            // `Process` struct, may be reused by the new process, so `old`
+            // must not be used after this.
+            let old_flash_start = old.flash_start();
+            slot.set(None);
+            old.terminate();
+
+            self.load_into(app_flash_address).or_else(|err| {
+                // Put the old app back so the board keeps running it.
+                let _ = self.load_into(old_flash_start);
+                Err(err)
+            })
+        }
+    }
+
+    /// Find the slot of the process `appid` names, if it is still loaded.
+    fn process_slot(
+        &self,
+        appid: AppId,
+    ) -> Option<(
+        &'static Cell<Option<&'static dyn ProcessType>>,
+        &'static dyn ProcessType,
+    )> {
+        let slot = self.kernel.process_slots().get(appid.idx())?;
+        match slot.get() {
+            Some(process) if process.appid() == appid => Some((slot, process)),
+            _ => None,
+        }
+    }
+
+    /// Check that there is a valid, enabled app at `app_flash_address` that
+    /// the credentials checker accepts. Returns the app's size in flash.
+    unsafe fn check_image(&self, app_flash_address: *const u8) -> Result<usize, ReturnCode> {
+        let header = match tbfheader::parse_and_validate_tbf_header(app_flash_address) {
+            Some(header) => header,
+            None => return Err(ReturnCode::EINVAL),
+        };
+        if !header.is_app() || !header.enabled() {
+            return Err(ReturnCode::EINVAL);
+        }
+        let accepted = self.checker.map_or(true, |checker| {
+            process_checker::check_app_credentials(checker, app_flash_address, &header)
+        });
+        if !accepted {
+            return Err(ReturnCode::FAIL);
+        }
+        Ok(header.get_total_size() as usize)
+    }
+
+    unsafe fn load_into(&self, app_flash_address: *const u8) -> Result<AppId, ReturnCode> {
+        let app_flash_size = self.check_image(app_flash_address)?;
+        let procs = self.kernel.process_slots();
+
+        // Don't run two processes out of the same flash.
+        let app_flash_end = app_flash_address.add(app_flash_size);
+        let overlaps = procs.iter().any(|slot| {
+            slot.get().map_or(false, |p| {
+                app_flash_address < p.flash_end() && p.flash_start() < app_flash_end
+            })
+        });
+        if overlaps {
+            return Err(ReturnCode::EBUSY);
+        }
+
+        let index = match procs.iter().position(|slot| slot.get().is_none()) {
+            Some(index) => index,
+            None => return Err(ReturnCode::ENOMEM),
+        };
+        let (app_memory_start, app_memory_len) = match self.kernel.app_memory() {
+            Some(app_memory) => app_memory,
+            None => return Err(ReturnCode::ENOMEM),
+        };
+
+        // Try each free region of app memory until the process fits. The MPU
+        // may need the process memory to be aligned, so whether it fits
+        // depends on where the region is, not just its size.
+        let memory_end = app_memory_start.add(app_memory_len) as *const u8;
+        let mut region_start = app_memory_start as *const u8;
+        while let Some((start, len)) = next_free_region(procs, region_start, memory_end) {
+            // The image was checked above, so don't check it again.
+            let (process, _, _) = Process::<C>::create(
+                self.kernel,
+                self.chip,
+                app_flash_address,
+                start as *mut u8,
+                len,
+                self.fault_response,
+                None,
+                index,
+            );
+            if let Some(process) = process {
+                procs[index].set(Some(process));
+                return Ok(process.appid());
+            }
+            region_start = start.add(len);
+        }
+        Err(ReturnCode::ENOMEM)
+    }
+}
+
+/// Find the first region of app memory between `from` and `memory_end` that no
+/// loaded process is using, and return its start and length.
+fn next_free_region(
+    procs: &[Cell<Option<&'static dyn ProcessType>>],
+    from: *const u8,
+    memory_end: *const u8,
+) -> Option<(*const u8, usize)> {
+    let mut start = from;
+    while start < memory_end {
+        // If a process uses the memory at `start`, skip past it.
+        let used_until = procs
+            .iter()
+            .filter_map(|slot| slot.get())
+            .find(|p| p.mem_start() <= start && start < p.mem_end())
+            .map(|p| p.mem_end());
+        if let Some(used_until) = used_until {
+            start = used_until;
+            continue;
+        }
+
+        // Otherwise the region is free until the next process.
+        let end = procs
+            .iter()
+            .filter_map(|slot| slot.get())
+            .map(|p| p.mem_start())
+            .filter(|&mem_start| mem_start > start)
+            .fold(memory_end, cmp::min);
+        return Some((start, end as usize - start as usize));
+    }
+    None
+}
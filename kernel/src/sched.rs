@@ -1,34 +1,126 @@
 //! Tock core scheduler.
+//!
+//! The `Kernel` struct owns the main loop, but the policy for choosing which
+//! process runs next, and for how long, is provided by an implementation of
+//! the `Scheduler` trait. Boards pick the scheduler they want and pass it to
+//! `Kernel::kernel_loop()`. The kernel provides several implementations in
+//! the submodules of this module.
+
+crate mod cooperative;
+crate mod mlfq;
+crate mod priority;
+crate mod round_robin;
 
 use core::cell::Cell;
+use core::cmp;
 use core::ptr::NonNull;
 
-use crate::callback::{Callback, CallbackId};
+use crate::callback::{AppId, Callback, CallbackId};
 use crate::capabilities;
-use crate::common::cells::NumericCellExt;
+use crate::common::cells::{NumericCellExt, OptionalCell};
 use crate::common::dynamic_deferred_call::DynamicDeferredCall;
 use crate::grant::Grant;
 use crate::ipc;
 use crate::memop;
 use crate::platform::mpu::MPU;
 use crate::platform::systick::SysTick;
-use crate::platform::{Chip, Platform};
-use crate::process::{self, Task};
+use crate::platform::{Chip, Platform, SyscallFilterResponse};
+use crate::power::{self, Residency, SleepClock, SleepState};
+use crate::process::{self, FaultResponse, ProcessFaultPolicy, Task};
+use crate::process_fault_log::{ProcessFault, ProcessFaultLog};
 use crate::returncode::ReturnCode;
-use crate::syscall::{ContextSwitchReason, Syscall};
+use crate::syscall::{ContextSwitchReason, Syscall, SyscallReturn};
 
-/// The time a process is permitted to run before being pre-empted
-const KERNEL_TICK_DURATION_US: u32 = 10000;
 /// Skip re-scheduling a process if its quanta is nearly exhausted
 const MIN_QUANTA_THRESHOLD_US: u32 = 500;
 
+/// How long the system tick timer runs for when it only measures CPU time,
+/// i.e. while the kernel services interrupts and while a process runs without
+/// a timeslice. Longer stretches are counted as this long.
+const CPU_TIME_INTERVAL_US: u32 = 100_000;
+
+/// How many callbacks can be queued for each process unless the board or the
+/// app chooses otherwise.
+const DEFAULT_TASK_QUEUE_DEPTH: usize = 10;
+
+/// The deepest task queue a process can have, whatever the board or the app
+/// asks for.
+crate const MAX_TASK_QUEUE_DEPTH: usize = 128;
+
+/// Trait which any scheduler must implement.
+///
+/// The kernel main loop asks the scheduler which process to run next by
+/// calling `next()`, runs that process, and then reports back why the process
+/// stopped executing by calling `result()`. Between those calls the kernel
+/// handles interrupts and deferred calls, so a scheduler only has to decide
+/// the order and length of process executions.
+pub trait Scheduler {
+    /// Decide which process to run next.
+    ///
+    /// The scheduler must return `SchedulingDecision::RunProcess` with the
+    /// `AppId` of a process that is ready to run, optionally with a timeslice
+    /// in microseconds. If no timeslice is given the process will run until
+    /// it yields or is interrupted by a hardware event. If no process is ready
+    /// the scheduler should return `SchedulingDecision::TrySleep`.
+    fn next(&self, kernel: &Kernel) -> SchedulingDecision;
+
+    /// Inform the scheduler why the last process it chose stopped executing,
+    /// and for how long it ran. The execution time is `None` if the process
+    /// was not run with a timeslice, as then the kernel has no timer running
+    /// to measure it.
+    fn result(&self, result: StoppedExecutingReason, execution_time_us: Option<u32>);
+}
+
+/// Enum representing the actions the scheduler can request in each call to
+/// `scheduler.next()`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SchedulingDecision {
+    /// Tell the kernel to run the specified process with the passed timeslice.
+    /// If `None` is passed as a timeslice, the process will be run
+    /// cooperatively.
+    RunProcess((AppId, Option<u32>)),
+
+    /// Tell the kernel to go to sleep. The kernel only sleeps if there is no
+    /// outstanding kernel or process work, otherwise it will call `next()`
+    /// again.
+    TrySleep,
+}
+
+/// Why the kernel stopped executing a process.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StoppedExecutingReason {
+    /// The process returned because it is no longer ready to run.
+    NoWorkLeft,
+
+    /// The process faulted, and the board restart policy was configured such
+    /// that it was not restarted and there was not a kernel panic.
+    StoppedFaulted,
+
+    /// The kernel stopped the process.
+    Stopped,
+
+    /// The process was preempted because its timeslice expired.
+    TimesliceExpired,
+
+    /// The process returned because it was preempted by the kernel. This can
+    /// mean that kernel work became ready (most likely because an interrupt
+    /// fired and the kernel thread needs to execute the bottom half of the
+    /// interrupt).
+    KernelPreemption,
+}
+
 /// Main object for the kernel. Each board will need to create one.
 pub struct Kernel {
     /// How many "to-do" items exist at any given time. These include
     /// outstanding callbacks and processes in the Running state.
     work: Cell<usize>,
-    /// This holds a pointer to the static array of Process pointers.
-    processes: &'static [Option<&'static dyn process::ProcessType>],
+    /// This holds a pointer to the static array of Process pointers. Process
+    /// loaders fill and clear its slots through the kernel, so it is shared
+    /// through cells rather than held mutably anywhere else.
+    processes: &'static [Cell<Option<&'static dyn process::ProcessType>>],
+    /// The start and length of the RAM that processes are allocated from,
+    /// once `load_processes()` has been given it.
+    app_memory: Cell<Option<(*mut u8, usize)>>,
     /// How many grant regions have been setup. This is incremented on every
     /// call to `create_grant()`. We need to explicitly track this so that when
     /// processes are created they can allocated pointers for each grant.
@@ -38,15 +130,56 @@ pub struct Kernel {
     /// created and the data structures for grants have already been
     /// established.
     grants_finalized: Cell<bool>,
+    /// Where to record process faults, if the board keeps a fault log.
+    fault_log: OptionalCell<&'static dyn ProcessFaultLog>,
+    /// Decides how to respond to process faults, if the board has a policy.
+    fault_policy: OptionalCell<&'static dyn ProcessFaultPolicy>,
+    /// Most bytes of grant memory a process may allocate, for processes
+    /// without a quota of their own.
+    grant_quota: Cell<Option<usize>>,
+    /// How many microseconds the kernel has spent servicing interrupts and
+    /// deferred calls.
+    kernel_time_us: Cell<u64>,
+    /// Whether the chip's system tick timer measures time, so that the CPU
+    /// time of the kernel and processes is known.
+    cpu_time_measured: Cell<bool>,
+    /// The identifier the next process that is loaded or restarted will get.
+    process_identifier_max: Cell<usize>,
+    /// How many system calls of each process to trace.
+    syscall_trace_len: Cell<usize>,
+    /// How many callbacks can be queued for each process that does not ask
+    /// for a specific number.
+    task_queue_depth: Cell<usize>,
+    /// Measures how long the chip sleeps, if the board provides a clock.
+    sleep_clock: OptionalCell<&'static dyn SleepClock>,
+    /// The chip's sleep states, once it has slept.
+    sleep_states: Cell<&'static [SleepState]>,
+    /// How much the chip has slept in each of its sleep states.
+    sleep_residency: [Cell<Residency>; power::MAX_SLEEP_STATES],
 }
 
 impl Kernel {
-    pub fn new(processes: &'static [Option<&'static dyn process::ProcessType>]) -> Kernel {
+    /// Create the kernel with the board's array of process slots. The kernel
+    /// takes over the array; processes are placed in it by
+    /// `load_processes()`.
+    pub fn new(processes: &'static mut [Option<&'static dyn process::ProcessType>]) -> Kernel {
         Kernel {
             work: Cell::new(0),
-            processes: processes,
+            processes: Cell::from_mut(processes).as_slice_of_cells(),
+            app_memory: Cell::new(None),
             grant_counter: Cell::new(0),
             grants_finalized: Cell::new(false),
+            fault_log: OptionalCell::empty(),
+            fault_policy: OptionalCell::empty(),
+            grant_quota: Cell::new(None),
+            kernel_time_us: Cell::new(0),
+            cpu_time_measured: Cell::new(false),
+            process_identifier_max: Cell::new(0),
+            syscall_trace_len: Cell::new(0),
+            task_queue_depth: Cell::new(DEFAULT_TASK_QUEUE_DEPTH),
+            sleep_clock: OptionalCell::empty(),
+            sleep_states: Cell::new(&[]),
+            sleep_residency: Default::default(),
         }
     }
 
@@ -67,18 +200,65 @@ impl Kernel {
         self.work.get() == 0
     }
 
-    /// Run a closure on a specific process if it exists. If the process does
-    /// not exist (i.e. it is `None` in the `processes` array) then `default`
-    /// will be returned. Otherwise the closure will executed and passed a
-    /// reference to the process.
-    crate fn process_map_or<F, R>(&self, default: R, process_index: usize, closure: F) -> R
+    /// Run a closure on the process `appid` names, if it still exists. If it
+    /// does not (the slot is empty, or holds a different process or a later
+    /// run of the same process) then `default` will be returned. Otherwise
+    /// the closure will executed and passed a reference to the process.
+    crate fn process_map_or<F, R>(&self, default: R, appid: AppId, closure: F) -> R
     where
         F: FnOnce(&dyn process::ProcessType) -> R,
     {
-        if process_index > self.processes.len() {
-            return default;
+        match self.processes.get(appid.idx()).and_then(|slot| slot.get()) {
+            Some(process) if process.appid() == appid => closure(process),
+            _ => default,
         }
-        self.processes[process_index].map_or(default, |process| closure(process))
+    }
+
+    /// Run a closure on whichever process is in the given slot of the process
+    /// table, if any. If the slot is empty or out of range `default` will be
+    /// returned.
+    crate fn process_slot_map_or<F, R>(
+        &self,
+        default: R,
+        process_index: usize,
+        closure: F,
+    ) -> R
+    where
+        F: FnOnce(&dyn process::ProcessType) -> R,
+    {
+        self.processes
+            .get(process_index)
+            .and_then(|slot| slot.get())
+            .map_or(default, |process| closure(process))
+    }
+
+    /// Returns the `AppId` of the process whose current run has the given
+    /// unique identifier, if there is one.
+    crate fn lookup_app_by_identifier(&self, identifier: usize) -> Option<AppId> {
+        self.processes
+            .iter()
+            .filter_map(|process| process.get().map(|p| p.appid()))
+            .find(|appid| appid.id() == identifier)
+    }
+
+    /// Create a new identifier for a process. Identifiers are never reused,
+    /// so a process gets a new one each time it is loaded or restarted.
+    crate fn create_process_identifier(&self) -> usize {
+        let identifier = self.process_identifier_max.get();
+        self.process_identifier_max.increment();
+        identifier
+    }
+
+    /// Returns the `AppId` of the process in the given slot if that process
+    /// exists and is ready to run.
+    crate fn ready_process(&self, process_index: usize) -> Option<AppId> {
+        self.process_slot_map_or(None, process_index, |process| {
+            if process.ready() {
+                Some(process.appid())
+            } else {
+                None
+            }
+        })
     }
 
     /// Run a closure on every valid process. This will iterate the array of
@@ -88,9 +268,9 @@ impl Kernel {
         F: Fn(&dyn process::ProcessType),
     {
         for process in self.processes.iter() {
-            match process {
+            match process.get() {
                 Some(p) => {
-                    closure(*p);
+                    closure(p);
                 }
                 None => {}
             }
@@ -106,12 +286,12 @@ impl Kernel {
         _capability: &dyn capabilities::ProcessManagementCapability,
         closure: F,
     ) where
-        F: Fn(usize, &dyn process::ProcessType),
+        F: Fn(usize, &'static dyn process::ProcessType),
     {
         for (i, process) in self.processes.iter().enumerate() {
-            match process {
+            match process.get() {
                 Some(p) => {
-                    closure(i, *p);
+                    closure(i, p);
                 }
                 None => {}
             }
@@ -127,9 +307,9 @@ impl Kernel {
         F: Fn(&dyn process::ProcessType) -> ReturnCode,
     {
         for process in self.processes.iter() {
-            match process {
+            match process.get() {
                 Some(p) => {
-                    let ret = closure(*p);
+                    let ret = closure(p);
                     if ret != ReturnCode::FAIL {
                         return ret;
                     }
@@ -145,6 +325,26 @@ impl Kernel {
         self.processes.len()
     }
 
+    /// The process slots, for the process loaders to fill and clear.
+    crate fn process_slots(
+        &self,
+    ) -> &'static [Cell<Option<&'static dyn process::ProcessType>>] {
+        self.processes
+    }
+
+    /// Record the RAM that processes are allocated from, so that processes
+    /// loaded later can be given the parts no process uses.
+    crate fn set_app_memory(&self, app_memory: &'static mut [u8]) {
+        self.app_memory
+            .set(Some((app_memory.as_mut_ptr(), app_memory.len())));
+    }
+
+    /// The start and length of the RAM that processes are allocated from, if
+    /// processes have been loaded.
+    crate fn app_memory(&self) -> Option<(*mut u8, usize)> {
+        self.app_memory.get()
+    }
+
     /// Create a new grant. This is used in board initialization to setup grants
     /// that capsules use to interact with processes.
     ///
@@ -182,6 +382,211 @@ impl Kernel {
         self.grant_counter.get()
     }
 
+    /// Returns how many microseconds the kernel has spent servicing
+    /// interrupts and deferred calls.
+    crate fn kernel_time_us(&self) -> u64 {
+        self.kernel_time_us.get()
+    }
+
+    /// Returns whether CPU time is measured, which needs a system tick timer
+    /// that counts down. Without one all CPU times stay 0.
+    crate fn cpu_time_measured(&self) -> bool {
+        self.cpu_time_measured.get()
+    }
+
+    /// Set the clock used to measure how long the chip sleeps in each sleep
+    /// state. Without one, the kernel only counts how often it sleeps in
+    /// each.
+    pub fn set_sleep_clock(
+        &self,
+        clock: &'static dyn SleepClock,
+        _capability: &dyn capabilities::MainLoopCapability,
+    ) {
+        self.sleep_clock.set(clock);
+    }
+
+    /// The chip's sleep states, or an empty list if the chip has none or has
+    /// not slept yet.
+    crate fn sleep_states(&self) -> &'static [SleepState] {
+        self.sleep_states.get()
+    }
+
+    crate fn sleep_residency(&self, state: usize) -> Option<Residency> {
+        self.sleep_residency
+            .get(state)
+            .map(|residency| residency.get())
+    }
+
+    /// Put the chip in the deepest sleep state that the drivers' power
+    /// constraints allow, and record how long it slept.
+    fn sleep<C: Chip>(&self, chip: &C) {
+        let states = chip.sleep_states();
+        let start = self.sleep_clock.map(|clock| clock.now());
+        let state = chip.sleep_in(power::deepest_allowed_state(states));
+
+        self.sleep_states.set(states);
+        if state < states.len() {
+            self.sleep_residency.get(state).map(|residency| {
+                let mut updated = residency.get();
+                updated.entries += 1;
+                self.sleep_clock.map(|clock| {
+                    start.map(|start| {
+                        let ticks = (clock.now().wrapping_sub(start) & clock.max_tics()) as u64;
+                        updated.time_us += ticks * 1_000_000 / clock.frequency() as u64;
+                    });
+                });
+                residency.set(updated);
+            });
+        }
+    }
+
+    /// Returns how many grants have been created, without finalizing them.
+    crate fn number_of_grants(&self) -> usize {
+        self.grant_counter.get()
+    }
+
+    /// Set the log that process faults are recorded in.
+    ///
+    /// Only callers with the `ProcessManagementCapability` can set the log, as
+    /// the log sees the state of every process that faults.
+    pub fn set_fault_log(
+        &self,
+        fault_log: &'static dyn ProcessFaultLog,
+        _capability: &dyn capabilities::ProcessManagementCapability,
+    ) {
+        self.fault_log.set(fault_log);
+    }
+
+    /// Record a process fault in the fault log, if there is one.
+    crate fn log_fault(&self, fault: &ProcessFault) {
+        self.fault_log.map(|fault_log| fault_log.log_fault(fault));
+    }
+
+    /// Call `f` with the most recent record in the fault log, if any.
+    crate fn map_last_fault(&self, f: &mut dyn FnMut(&[u8])) {
+        self.fault_log.map(|fault_log| fault_log.map_last_fault(f));
+    }
+
+    /// Set the policy that decides how the kernel responds to process faults.
+    /// Without a policy each process uses the `FaultResponse` it was loaded
+    /// with.
+    pub fn set_fault_policy(
+        &self,
+        fault_policy: &'static dyn ProcessFaultPolicy,
+        _capability: &dyn capabilities::ProcessManagementCapability,
+    ) {
+        self.fault_policy.set(fault_policy);
+    }
+
+    /// Decide how to respond to `process` faulting, falling back to `default`
+    /// if the board has no fault policy.
+    crate fn fault_response(
+        &self,
+        process: &dyn process::ProcessType,
+        default: FaultResponse,
+    ) -> FaultResponse {
+        self.fault_policy
+            .map_or(default, |fault_policy| fault_policy.action(process))
+    }
+
+    /// Restart a process that was stopped after it faulted.
+    ///
+    /// Returns `EINVAL` if there is no such process and `EALREADY` if the
+    /// process is not stopped after a fault.
+    pub fn restart_process(
+        &self,
+        appid: AppId,
+        _capability: &dyn capabilities::ProcessManagementCapability,
+    ) -> ReturnCode {
+        self.process_map_or(ReturnCode::EINVAL, appid, |process| {
+            if process.get_state() == process::State::StoppedFaulted {
+                process.restart();
+                ReturnCode::SUCCESS
+            } else {
+                ReturnCode::EALREADY
+            }
+        })
+    }
+
+    /// Limit how many bytes of grant memory each process may allocate. This
+    /// applies to every process that has not been given its own quota with
+    /// `set_grant_quota()`. `None` removes the limit.
+    ///
+    /// Once a process reaches its quota, entering a grant it has not used yet
+    /// fails with `Error::QuotaExceeded` instead of taking memory the process
+    /// may need for other capsules.
+    pub fn set_default_grant_quota(
+        &self,
+        quota: Option<usize>,
+        _capability: &dyn capabilities::ProcessManagementCapability,
+    ) {
+        self.grant_quota.set(quota);
+    }
+
+    /// Limit how many bytes of grant memory the process with the given `AppId`
+    /// may allocate, overriding the default quota. `None` makes the process
+    /// use the default quota again.
+    ///
+    /// Returns `EINVAL` if there is no such process.
+    pub fn set_grant_quota(
+        &self,
+        appid: AppId,
+        quota: Option<usize>,
+        _capability: &dyn capabilities::ProcessManagementCapability,
+    ) -> ReturnCode {
+        self.process_map_or(ReturnCode::EINVAL, appid, |process| {
+            process.set_grant_quota(quota);
+            ReturnCode::SUCCESS
+        })
+    }
+
+    /// The grant quota of processes that do not have their own.
+    crate fn default_grant_quota(&self) -> Option<usize> {
+        self.grant_quota.get()
+    }
+
+    /// Keep a trace of the last `len` system calls of each process, with
+    /// their arguments and return values, for debugging. `0`, the default,
+    /// turns tracing off.
+    ///
+    /// The trace is kept in process memory, so this only applies to
+    /// processes loaded after it is called, and costs each of them
+    /// `len + 1` records of memory.
+    pub fn set_syscall_trace_length(
+        &self,
+        len: usize,
+        _capability: &dyn capabilities::ProcessManagementCapability,
+    ) {
+        self.syscall_trace_len.set(len);
+    }
+
+    /// How many system calls of each process are traced.
+    crate fn syscall_trace_length(&self) -> usize {
+        self.syscall_trace_len.get()
+    }
+
+    /// Set how many callbacks can be queued for each process before further
+    /// ones are dropped. Apps can ask for a different depth in their TBF
+    /// header.
+    ///
+    /// This only applies to processes loaded after it is called. Each queued
+    /// callback costs a few words of process memory. Depths above
+    /// `MAX_TASK_QUEUE_DEPTH` are capped to it.
+    pub fn set_task_queue_depth(
+        &self,
+        depth: usize,
+        _capability: &dyn capabilities::ProcessManagementCapability,
+    ) {
+        self.task_queue_depth
+            .set(cmp::min(depth, MAX_TASK_QUEUE_DEPTH));
+    }
+
+    /// How many callbacks can be queued for processes that do not ask for a
+    /// specific number.
+    crate fn task_queue_depth(&self) -> usize {
+        self.task_queue_depth.get()
+    }
+
     /// Cause all apps to fault.
     ///
     /// This will call `set_fault_state()` on each app, causing the app to enter
@@ -194,68 +599,141 @@ impl Kernel {
     /// apps.
     pub fn hardfault_all_apps<C: capabilities::ProcessManagementCapability>(&self, _c: &C) {
         for p in self.processes.iter() {
-            p.map(|process| {
+            p.get().map(|process| {
                 process.set_fault_state();
             });
         }
     }
 
     /// Main loop.
-    pub fn kernel_loop<P: Platform, C: Chip>(
+    ///
+    /// The `scheduler` chooses which process runs next and for how long.
+    pub fn kernel_loop<P: Platform, C: Chip, S: Scheduler>(
         &'static self,
         platform: &P,
         chip: &C,
         ipc: Option<&ipc::IPC>,
-        _capability: &dyn capabilities::MainLoopCapability,
+        scheduler: &S,
+        capability: &dyn capabilities::MainLoopCapability,
     ) {
         loop {
-            unsafe {
-                chip.service_pending_interrupts();
-                DynamicDeferredCall::call_global_instance_while(|| !chip.has_pending_interrupts());
+            self.kernel_loop_operation(platform, chip, ipc, scheduler, capability);
+        }
+    }
 
-                for p in self.processes.iter() {
-                    p.map(|process| {
-                        self.do_process(platform, chip, process, ipc);
+    /// One iteration of the main loop: service interrupts and deferred calls,
+    /// then run one process or put the chip to sleep.
+    ///
+    /// Boards should call `kernel_loop()`. This is for environments that need
+    /// to get control back between iterations, such as tests running the
+    /// kernel on an emulated chip.
+    pub fn kernel_loop_operation<P: Platform, C: Chip, S: Scheduler>(
+        &'static self,
+        platform: &P,
+        chip: &C,
+        ipc: Option<&ipc::IPC>,
+        scheduler: &S,
+        _capability: &dyn capabilities::MainLoopCapability,
+    ) {
+        platform.watchdog().map(|watchdog| watchdog.tickle());
+
+        let systick = chip.systick();
+        self.cpu_time_measured.set(systick.measures_time());
+        unsafe {
+            // Measure how long the kernel spends on its own work.
+            let timer = SliceTimer::start(systick, CPU_TIME_INTERVAL_US);
+            chip.service_pending_interrupts();
+            DynamicDeferredCall::call_global_instance_while(|| !chip.has_pending_interrupts());
+            let kernel_time_us = timer.elapsed_us();
+            self.kernel_time_us
+                .set(self.kernel_time_us.get() + kernel_time_us as u64);
+            systick.reset();
+
+            if chip.has_pending_interrupts()
+                || DynamicDeferredCall::global_instance_calls_pending().unwrap_or(false)
+            {
+                // Handle kernel work before running any process.
+                return;
+            }
+
+            match scheduler.next(self) {
+                SchedulingDecision::RunProcess((appid, timeslice_us)) => {
+                    self.process_map_or((), appid, |process| {
+                        let (reason, execution_time_us) =
+                            self.do_process(platform, chip, process, ipc, timeslice_us);
+                        scheduler.result(reason, execution_time_us);
+                    });
+                }
+                SchedulingDecision::TrySleep => {
+                    chip.atomic(|| {
+                        if !chip.has_pending_interrupts()
+                            && !DynamicDeferredCall::global_instance_calls_pending()
+                                .unwrap_or(false)
+                            && self.processes_blocked()
+                        {
+                            // The chip may sleep for longer than the
+                            // watchdog's period.
+                            platform.watchdog().map(|watchdog| watchdog.suspend());
+                            self.sleep(chip);
+                            platform.watchdog().map(|watchdog| watchdog.resume());
+                        }
                     });
-                    if chip.has_pending_interrupts()
-                        || DynamicDeferredCall::global_instance_calls_pending().unwrap_or(false)
-                    {
-                        break;
-                    }
                 }
+            }
+        }
+    }
 
-                chip.atomic(|| {
-                    if !chip.has_pending_interrupts()
-                        && !DynamicDeferredCall::global_instance_calls_pending().unwrap_or(false)
-                        && self.processes_blocked()
-                    {
-                        chip.sleep();
-                    }
-                });
-            };
+    /// Check a syscall against the driver permissions in the process's TBF
+    /// header and then against the platform's filter.
+    fn filter_syscall<P: Platform>(
+        &self,
+        platform: &P,
+        process: &dyn process::ProcessType,
+        syscall: &Syscall,
+    ) -> Result<(), SyscallFilterResponse> {
+        if let Some(driver_number) = syscall.driver_number() {
+            if !process.driver_permitted(driver_number) {
+                return Err(SyscallFilterResponse::Error(ReturnCode::ENOSUPPORT));
+            }
         }
+        platform.filter_syscall(process, syscall)
     }
 
+    /// Run a process until it stops being ready, its timeslice expires, or
+    /// the kernel has work to do.
+    ///
+    /// If `timeslice_us` is `None` the process is run cooperatively, without
+    /// being preempted by the system tick timer.
+    ///
+    /// Returns why the process stopped executing and, if it was given a
+    /// timeslice, for how many microseconds it ran.
     unsafe fn do_process<P: Platform, C: Chip>(
         &self,
         platform: &P,
         chip: &C,
         process: &dyn process::ProcessType,
         ipc: Option<&crate::ipc::IPC>,
-    ) {
+        timeslice_us: Option<u32>,
+    ) -> (StoppedExecutingReason, Option<u32>) {
         let appid = process.appid();
         let systick = chip.systick();
-        systick.reset();
-        systick.set_timer(KERNEL_TICK_DURATION_US);
-        systick.enable(false);
+        // Without a timeslice the timer still runs, without an interrupt, to
+        // measure the CPU time the process uses.
+        let timer_us = timeslice_us.unwrap_or(CPU_TIME_INTERVAL_US);
+        let timer = SliceTimer::start(systick, timer_us);
 
+        let mut return_reason = StoppedExecutingReason::NoWorkLeft;
         loop {
             if chip.has_pending_interrupts() {
+                return_reason = StoppedExecutingReason::KernelPreemption;
                 break;
             }
 
-            if systick.overflowed() || !systick.greater_than(MIN_QUANTA_THRESHOLD_US) {
+            if timeslice_us.is_some()
+                && (timer.expired() || !systick.greater_than(MIN_QUANTA_THRESHOLD_US))
+            {
                 process.debug_timeslice_expired();
+                return_reason = StoppedExecutingReason::TimesliceExpired;
                 break;
             }
 
@@ -266,9 +744,13 @@ impl Kernel {
                     // the process.
                     process.setup_mpu();
                     chip.mpu().enable_mpu();
-                    systick.enable(true);
+                    if timeslice_us.is_some() {
+                        systick.enable(true);
+                    }
                     let context_switch_reason = process.switch_to();
-                    systick.enable(false);
+                    if timeslice_us.is_some() {
+                        systick.enable(false);
+                    }
                     chip.mpu().disable_mpu();
 
                     // Now the process has returned back to the kernel. Check
@@ -279,6 +761,28 @@ impl Kernel {
                             process.set_fault_state();
                         }
                         Some(ContextSwitchReason::SyscallFired { syscall }) => {
+                            let cpu_time_us =
+                                process.debug_cpu_time_us() + timer.elapsed_us() as u64;
+                            process.debug_syscall_called(syscall, cpu_time_us);
+
+                            // Only handle syscalls the process is allowed to
+                            // make.
+                            if let Err(response) = self.filter_syscall(platform, process, &syscall)
+                            {
+                                match response {
+                                    SyscallFilterResponse::Error(err) => match syscall {
+                                        Syscall::TYPED_COMMAND { .. } => {
+                                            process.set_syscall_return(SyscallReturn::Failure(err));
+                                        }
+                                        _ => process.set_syscall_return_value(err.into()),
+                                    },
+                                    SyscallFilterResponse::Fault => {
+                                        process.set_fault_state();
+                                    }
+                                }
+                                continue;
+                            }
+
                             // Handle each of the syscalls.
                             match syscall {
                                 Syscall::MEMOP { operand, arg0 } => {
@@ -338,6 +842,29 @@ impl Kernel {
                                         );
                                     process.set_syscall_return_value(res.into());
                                 }
+                                Syscall::TYPED_COMMAND {
+                                    driver_number,
+                                    subdriver_number,
+                                    arg0,
+                                    arg1,
+                                } => {
+                                    let res =
+                                        platform.with_driver(
+                                            driver_number,
+                                            |driver| match driver {
+                                                Some(d) => d.typed_command(
+                                                    subdriver_number,
+                                                    arg0,
+                                                    arg1,
+                                                    appid,
+                                                ),
+                                                None => {
+                                                    SyscallReturn::Failure(ReturnCode::ENODEVICE)
+                                                }
+                                            },
+                                        );
+                                    process.set_syscall_return(res);
+                                }
                                 Syscall::ALLOW {
                                     driver_number,
                                     subdriver_number,
@@ -359,14 +886,41 @@ impl Kernel {
                                     });
                                     process.set_syscall_return_value(res.into());
                                 }
+                                Syscall::READ_ONLY_ALLOW {
+                                    driver_number,
+                                    subdriver_number,
+                                    allow_address,
+                                    allow_size,
+                                } => {
+                                    let res = platform.with_driver(driver_number, |driver| {
+                                        match driver {
+                                            Some(d) => {
+                                                match process
+                                                    .allow_read_only(allow_address, allow_size)
+                                                {
+                                                    Ok(oslice) => d.allow_read_only(
+                                                        appid,
+                                                        subdriver_number,
+                                                        oslice,
+                                                    ),
+                                                    Err(err) => err, /* memory not valid */
+                                                }
+                                            }
+                                            None => ReturnCode::ENODEVICE,
+                                        }
+                                    });
+                                    process.set_syscall_return_value(res.into());
+                                }
                             }
                         }
                         Some(ContextSwitchReason::TimesliceExpired) => {
                             // break to handle other processes.
+                            return_reason = StoppedExecutingReason::TimesliceExpired;
                             break;
                         }
                         Some(ContextSwitchReason::Interrupted) => {
                             // break to handle other processes.
+                            return_reason = StoppedExecutingReason::KernelPreemption;
                             break;
                         }
                         None => {
@@ -406,20 +960,77 @@ impl Kernel {
                     // We should never be scheduling a process in fault.
                     panic!("Attempted to schedule a faulty process");
                 }
-                process::State::StoppedRunning => {
+                process::State::StoppedRunning | process::State::StoppedYielded => {
+                    return_reason = StoppedExecutingReason::Stopped;
                     break;
-                    // Do nothing
                 }
-                process::State::StoppedYielded => {
+                process::State::StoppedFaulted => {
+                    return_reason = StoppedExecutingReason::StoppedFaulted;
                     break;
-                    // Do nothing
                 }
-                process::State::StoppedFaulted => {
+                process::State::Terminated | process::State::CredentialsFailed => {
+                    return_reason = StoppedExecutingReason::Stopped;
                     break;
-                    // Do nothing
                 }
             }
         }
+
+        // Work out how long the process ran for. If the timeslice expired the
+        // timer may have already wrapped, so charge the whole timeslice.
+        let elapsed_us = if return_reason == StoppedExecutingReason::TimesliceExpired {
+            timer_us
+        } else {
+            timer.elapsed_us()
+        };
         systick.reset();
+        process.debug_add_cpu_time(elapsed_us);
+
+        (return_reason, timeslice_us.map(|_| elapsed_us))
+    }
+}
+
+/// The system tick timer while it counts down one timeslice or measurement.
+///
+/// On Cortex-M, reading whether the timer expired clears the flag that records
+/// it, and the timer reloads and keeps counting. Checking the flag through
+/// this remembers an expiration, so measuring the time a process used does not
+/// hide that its timeslice is over.
+struct SliceTimer<'a, S: SysTick> {
+    systick: &'a S,
+    timer_us: u32,
+    expired: Cell<bool>,
+}
+
+impl<S: SysTick> SliceTimer<'a, S> {
+    /// Start the timer counting down from `timer_us`, without its interrupt.
+    /// Intervals longer than the timer can count are shortened to the
+    /// longest it can.
+    fn start(systick: &'a S, timer_us: u32) -> SliceTimer<'a, S> {
+        let timer_us = cmp::min(timer_us, systick.max_timer_us());
+        systick.reset();
+        systick.set_timer(timer_us);
+        systick.enable(false);
+        SliceTimer {
+            systick: systick,
+            timer_us: timer_us,
+            expired: Cell::new(false),
+        }
+    }
+
+    fn expired(&self) -> bool {
+        if !self.expired.get() && self.systick.overflowed() {
+            self.expired.set(true);
+        }
+        self.expired.get()
+    }
+
+    /// Microseconds since the timer started. Once it expired, all of
+    /// `timer_us` has passed.
+    fn elapsed_us(&self) -> u32 {
+        if self.expired() {
+            self.timer_us
+        } else {
+            self.timer_us.saturating_sub(self.systick.get_value())
+        }
     }
 }
@@ -0,0 +1,58 @@
+//! Cooperative scheduler for Tock.
+//!
+//! This scheduler runs all processes in a round-robin fashion, but does not
+//! use a scheduler timer to enforce process timeslices. That is, all processes
+//! are run cooperatively. Processes are run until they yield or stop
+//! executing (i.e. they crash or exit).
+//!
+//! When hardware interrupts occur while a process is executing, the kernel
+//! handles the interrupt and then resumes the same process, so a process only
+//! gives up the CPU to another process when it has no work left.
+
+use core::cell::Cell;
+
+use crate::sched::{Kernel, Scheduler, SchedulingDecision, StoppedExecutingReason};
+
+/// Cooperative scheduler.
+pub struct CooperativeSched {
+    /// Index of the process slot that was scheduled most recently.
+    current: Cell<usize>,
+    /// Whether the current process was preempted by the kernel and should be
+    /// resumed before moving on to the next process.
+    last_rescheduled: Cell<bool>,
+}
+
+impl CooperativeSched {
+    pub const fn new() -> CooperativeSched {
+        CooperativeSched {
+            current: Cell::new(0),
+            last_rescheduled: Cell::new(false),
+        }
+    }
+}
+
+impl Scheduler for CooperativeSched {
+    fn next(&self, kernel: &Kernel) -> SchedulingDecision {
+        let num_slots = kernel.number_of_process_slots();
+        if num_slots == 0 {
+            return SchedulingDecision::TrySleep;
+        }
+
+        // If the last process was interrupted give it the first chance to run
+        // again, otherwise start with the slot after it.
+        let first_offset = if self.last_rescheduled.get() { 0 } else { 1 };
+        for offset in first_offset..first_offset + num_slots {
+            let index = (self.current.get() + offset) % num_slots;
+            if let Some(appid) = kernel.ready_process(index) {
+                self.current.set(index);
+                return SchedulingDecision::RunProcess((appid, None));
+            }
+        }
+        SchedulingDecision::TrySleep
+    }
+
+    fn result(&self, result: StoppedExecutingReason, _: Option<u32>) {
+        self.last_rescheduled
+            .set(result == StoppedExecutingReason::KernelPreemption);
+    }
+}
@@ -0,0 +1,202 @@
+//! Multilevel feedback queue scheduler for Tock.
+//!
+//! Based on the MLFQ rules described in "Operating Systems: Three Easy Pieces"
+//! by Remzi H. Arpaci-Dusseau and Andrea C. Arpaci-Dusseau.
+//!
+//! The scheduler keeps processes in one of several queues. Processes in higher
+//! priority queues are always run first, and processes within the same queue
+//! are run round-robin with the timeslice of that queue:
+//!
+//! 1. If Priority(A) > Priority(B), A runs (B doesn't).
+//! 2. If Priority(A) = Priority(B), A & B run in round-robin fashion using the
+//!    time slice (quantum length) of the given queue.
+//! 3. When a job enters the system, it is placed at the highest priority (the
+//!    topmost queue).
+//! 4. Once a job uses up its time allotment at a given level (regardless of
+//!    how many times it has given up the CPU), its priority is reduced (i.e.,
+//!    it moves down one queue).
+//! 5. After some time period S, move all the jobs in the system to the topmost
+//!    queue.
+//!
+//! This lets interactive processes that mostly wait for events stay in the top
+//! queue and get low latency, while compute-heavy processes sink to the lower
+//! queues. Tock does not keep a wall clock in the kernel, so the period `S`
+//! is measured in process execution time rather than real time. Because the
+//! scheduler manages priorities itself, it ignores any scheduling parameters
+//! in process TBF headers.
+//!
+//! The scheduler needs to keep some state for every process. Boards must
+//! provide one `MLFQProcessNode` per process slot. A node is reset when a
+//! different process, or a restarted one, runs in its slot, so the new
+//! process starts in the top queue. For example:
+//!
+//! ```ignore
+//! static mut MLFQ_NODES: [kernel::schedulers::MLFQProcessNode; 4] = [
+//!     kernel::schedulers::MLFQProcessNode::new(),
+//!     kernel::schedulers::MLFQProcessNode::new(),
+//!     kernel::schedulers::MLFQProcessNode::new(),
+//!     kernel::schedulers::MLFQProcessNode::new(),
+//! ];
+//!
+//! let scheduler = static_init!(
+//!     kernel::schedulers::MLFQSched,
+//!     kernel::schedulers::MLFQSched::new(&MLFQ_NODES)
+//! );
+//! ```
+
+use core::cell::Cell;
+
+use crate::callback::AppId;
+use crate::sched::{Kernel, Scheduler, SchedulingDecision, StoppedExecutingReason};
+
+/// Timeslice of each queue, highest priority queue first.
+const QUEUE_TIMESLICES_US: [u32; 3] = [10000, 20000, 50000];
+
+/// How much process execution time passes between moving all processes back
+/// to the highest priority queue.
+const PRIORITY_REFRESH_PERIOD_US: u32 = 5_000_000;
+
+/// Per-process scheduler state.
+pub struct MLFQProcessNode {
+    /// Unique identifier of the process run the state belongs to.
+    process_id: Cell<Option<usize>>,
+    /// The queue the process is in. 0 is the highest priority.
+    queue: Cell<usize>,
+    /// How much of its allotment for the current queue the process has used.
+    us_used_this_queue: Cell<u32>,
+}
+
+impl MLFQProcessNode {
+    pub const fn new() -> MLFQProcessNode {
+        MLFQProcessNode {
+            process_id: Cell::new(None),
+            queue: Cell::new(0),
+            us_used_this_queue: Cell::new(0),
+        }
+    }
+}
+
+/// Multilevel feedback queue scheduler.
+pub struct MLFQSched {
+    /// State for each process slot, indexed the same as the processes array.
+    nodes: &'static [MLFQProcessNode],
+    /// Index of the process slot that was scheduled most recently.
+    current: Cell<usize>,
+    /// Whether the current process was preempted by the kernel and should be
+    /// resumed before moving on to the next process.
+    last_rescheduled: Cell<bool>,
+    /// Process execution time since all processes were last moved to the
+    /// highest priority queue.
+    us_since_refresh: Cell<u32>,
+}
+
+impl MLFQSched {
+    pub const fn new(nodes: &'static [MLFQProcessNode]) -> MLFQSched {
+        MLFQSched {
+            nodes: nodes,
+            current: Cell::new(0),
+            last_rescheduled: Cell::new(false),
+            us_since_refresh: Cell::new(0),
+        }
+    }
+
+    /// Get the queue the process `appid` in slot `index` is in. If the
+    /// slot's node was used by another process, it is reset first, which
+    /// puts the process in the highest priority queue. Processes without a
+    /// node are treated as being in the lowest priority queue.
+    fn queue_of(&self, index: usize, appid: AppId) -> usize {
+        self.nodes
+            .get(index)
+            .map_or(QUEUE_TIMESLICES_US.len() - 1, |node| {
+                if node.process_id.get() != Some(appid.id()) {
+                    node.process_id.set(Some(appid.id()));
+                    node.queue.set(0);
+                    node.us_used_this_queue.set(0);
+                }
+                node.queue.get()
+            })
+    }
+
+    /// Move every process back to the highest priority queue.
+    fn refresh_queues(&self) {
+        for node in self.nodes.iter() {
+            node.queue.set(0);
+            node.us_used_this_queue.set(0);
+        }
+        self.us_since_refresh.set(0);
+    }
+}
+
+impl Scheduler for MLFQSched {
+    fn next(&self, kernel: &Kernel) -> SchedulingDecision {
+        if self.us_since_refresh.get() >= PRIORITY_REFRESH_PERIOD_US {
+            self.refresh_queues();
+        }
+
+        let num_slots = kernel.number_of_process_slots();
+        if num_slots == 0 {
+            return SchedulingDecision::TrySleep;
+        }
+
+        // Find the ready process in the highest priority queue. Within a
+        // queue, start searching after the process that ran last so processes
+        // at the same level are run round-robin, unless the last process was
+        // interrupted by the kernel in which case it should continue.
+        let first_offset = if self.last_rescheduled.get() { 0 } else { 1 };
+        let mut next = None;
+        for offset in first_offset..first_offset + num_slots {
+            let index = (self.current.get() + offset) % num_slots;
+            if let Some(appid) = kernel.ready_process(index) {
+                let queue = self.queue_of(index, appid);
+                match next {
+                    Some((_, _, best_queue)) if best_queue <= queue => {}
+                    _ => next = Some((index, appid, queue)),
+                }
+            }
+        }
+
+        match next {
+            Some((index, appid, queue)) => {
+                self.current.set(index);
+                let used = self
+                    .nodes
+                    .get(index)
+                    .map_or(0, |node| node.us_used_this_queue.get());
+                let timeslice = QUEUE_TIMESLICES_US[queue].saturating_sub(used);
+                SchedulingDecision::RunProcess((appid, Some(timeslice)))
+            }
+            None => SchedulingDecision::TrySleep,
+        }
+    }
+
+    fn result(&self, result: StoppedExecutingReason, execution_time_us: Option<u32>) {
+        let execution_time_us = execution_time_us.unwrap_or(0);
+        self.us_since_refresh.set(
+            self.us_since_refresh
+                .get()
+                .saturating_add(execution_time_us),
+        );
+        self.last_rescheduled
+            .set(result == StoppedExecutingReason::KernelPreemption);
+
+        if let Some(node) = self.nodes.get(self.current.get()) {
+            let queue = node.queue.get();
+            let used = node
+                .us_used_this_queue
+                .get()
+                .saturating_add(execution_time_us);
+            if used >= QUEUE_TIMESLICES_US[queue]
+                || result == StoppedExecutingReason::TimesliceExpired
+            {
+                // The process used up its allotment for this queue, so move it
+                // down a level.
+                if queue + 1 < QUEUE_TIMESLICES_US.len() {
+                    node.queue.set(queue + 1);
+                }
+                node.us_used_this_queue.set(0);
+            } else {
+                node.us_used_this_queue.set(used);
+            }
+        }
+    }
+}
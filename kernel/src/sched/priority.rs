@@ -0,0 +1,54 @@
+//! Fixed priority scheduler for Tock.
+//!
+//! This scheduler always runs the highest priority process that is ready.
+//! Processes set their priority with the scheduling element of their TBF
+//! header, where lower values are higher priority. Processes that do not
+//! specify a priority are lower priority than all processes that do, and
+//! among themselves have priority based on their order in the processes array,
+//! such that processes earlier in the array have higher priority. Processes
+//! with the same priority are also ordered by their position in the array.
+//!
+//! The chosen process runs until it yields or the kernel has work to do,
+//! unless it requested a timeslice in its TBF header, in which case it is
+//! also preempted when that expires. After the kernel handles interrupts the
+//! scheduler checks again, so a higher priority process that became ready
+//! preempts a lower priority one.
+//!
+//! Lower priority processes can be starved if a higher priority process never
+//! yields.
+
+use crate::sched::{Kernel, Scheduler, SchedulingDecision, StoppedExecutingReason};
+
+/// Fixed priority scheduler.
+pub struct PrioritySched {}
+
+impl PrioritySched {
+    pub const fn new() -> PrioritySched {
+        PrioritySched {}
+    }
+}
+
+impl Scheduler for PrioritySched {
+    fn next(&self, kernel: &Kernel) -> SchedulingDecision {
+        let mut next = None;
+        for index in 0..kernel.number_of_process_slots() {
+            if let Some(appid) = kernel.ready_process(index) {
+                let (priority, timeslice) = kernel.process_map_or((None, None), appid, |process| {
+                    (process.get_priority(), process.get_timeslice_us())
+                });
+                let priority = priority.unwrap_or(u32::max_value());
+                match next {
+                    Some((best_priority, _, _)) if best_priority <= priority => {}
+                    _ => next = Some((priority, appid, timeslice)),
+                }
+            }
+        }
+
+        match next {
+            Some((_, appid, timeslice)) => SchedulingDecision::RunProcess((appid, timeslice)),
+            None => SchedulingDecision::TrySleep,
+        }
+    }
+
+    fn result(&self, _: StoppedExecutingReason, _: Option<u32>) {}
+}
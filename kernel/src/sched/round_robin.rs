@@ -0,0 +1,94 @@
+//! Round robin scheduler for Tock.
+//!
+//! This is the classic Tock scheduler. Each ready process is given a fixed
+//! timeslice in turn. When hardware interrupts occur while a process is
+//! executing the kernel stops the process to handle the bottom half of the
+//! interrupt. To keep the use of timeslices fair, the interrupted process is
+//! then resumed with whatever was left of its timeslice, rather than moving on
+//! to the next process.
+//!
+//! Processes that request a timeslice in their TBF header are given that
+//! timeslice instead of the scheduler's default.
+
+use core::cell::Cell;
+
+use crate::callback::AppId;
+use crate::sched::{Kernel, Scheduler, SchedulingDecision, StoppedExecutingReason};
+
+/// The time a process is permitted to run before being pre-empted.
+const DEFAULT_TIMESLICE_US: u32 = 10000;
+
+/// Round robin scheduler.
+pub struct RoundRobinSched {
+    /// Length of the timeslice given to processes that do not request one.
+    timeslice_us: u32,
+    /// Index of the process slot that was scheduled most recently.
+    current: Cell<usize>,
+    /// Time left in the timeslice of the current process.
+    time_remaining: Cell<u32>,
+    /// Whether the current process was preempted by the kernel and should be
+    /// resumed before moving on to the next process.
+    last_rescheduled: Cell<bool>,
+}
+
+impl RoundRobinSched {
+    /// Create a round robin scheduler with the default 10 ms timeslice.
+    pub const fn new() -> RoundRobinSched {
+        RoundRobinSched::new_with_timeslice(DEFAULT_TIMESLICE_US)
+    }
+
+    /// Create a round robin scheduler with a specific default timeslice.
+    pub const fn new_with_timeslice(timeslice_us: u32) -> RoundRobinSched {
+        RoundRobinSched {
+            timeslice_us: timeslice_us,
+            current: Cell::new(0),
+            time_remaining: Cell::new(timeslice_us),
+            last_rescheduled: Cell::new(false),
+        }
+    }
+
+    /// Get the timeslice for the given process.
+    fn timeslice_for(&self, kernel: &Kernel, appid: AppId) -> u32 {
+        kernel.process_map_or(self.timeslice_us, appid, |process| {
+            process.get_timeslice_us().unwrap_or(self.timeslice_us)
+        })
+    }
+}
+
+impl Scheduler for RoundRobinSched {
+    fn next(&self, kernel: &Kernel) -> SchedulingDecision {
+        // Resume an interrupted process if it still has work to do.
+        if self.last_rescheduled.get() {
+            let current = self.current.get();
+            if let Some(appid) = kernel.ready_process(current) {
+                return SchedulingDecision::RunProcess((appid, Some(self.time_remaining.get())));
+            }
+        }
+
+        // Otherwise start with the slot after the last process that ran.
+        let num_slots = kernel.number_of_process_slots();
+        if num_slots == 0 {
+            return SchedulingDecision::TrySleep;
+        }
+        for offset in 1..=num_slots {
+            let index = (self.current.get() + offset) % num_slots;
+            if let Some(appid) = kernel.ready_process(index) {
+                let timeslice = self.timeslice_for(kernel, appid);
+                self.current.set(index);
+                self.time_remaining.set(timeslice);
+                return SchedulingDecision::RunProcess((appid, Some(timeslice)));
+            }
+        }
+        SchedulingDecision::TrySleep
+    }
+
+    fn result(&self, result: StoppedExecutingReason, execution_time_us: Option<u32>) {
+        let rescheduled = result == StoppedExecutingReason::KernelPreemption;
+        if rescheduled {
+            let used = execution_time_us.unwrap_or(0);
+            self.time_remaining
+                .set(self.time_remaining.get().saturating_sub(used));
+        }
+        self.last_rescheduled.set(rescheduled);
+    }
+}
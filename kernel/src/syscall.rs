@@ -3,9 +3,11 @@
 use core::fmt::Write;
 
 use crate::process;
+use crate::returncode::ReturnCode;
 
 /// The syscall number assignments.
 #[derive(Copy, Clone, Debug, PartialEq)]
+#[allow(non_camel_case_types)]
 pub enum Syscall {
     /// Return to the kernel to allow other processes to execute or to wait for
     /// interrupts and callbacks.
@@ -47,6 +49,128 @@ pub enum Syscall {
     ///
     /// SVC_NUM = 4
     MEMOP { operand: usize, arg0: usize },
+
+    /// Share a memory buffer with the kernel that the kernel may only read.
+    /// The buffer can be in the process's flash as well as its RAM.
+    ///
+    /// SVC_NUM = 5
+    READ_ONLY_ALLOW {
+        driver_number: usize,
+        subdriver_number: usize,
+        allow_address: *const u8,
+        allow_size: usize,
+    },
+
+    /// Like `COMMAND`, but the capsule answers with a `SyscallReturn` that
+    /// can carry up to three values.
+    ///
+    /// SVC_NUM = 6
+    TYPED_COMMAND {
+        driver_number: usize,
+        subdriver_number: usize,
+        arg0: usize,
+        arg1: usize,
+    },
+}
+
+impl Syscall {
+    /// The number of the driver this syscall is directed at, if any. Yield and
+    /// memop are handled by the kernel itself and have no driver.
+    pub fn driver_number(&self) -> Option<usize> {
+        match *self {
+            Syscall::SUBSCRIBE { driver_number, .. }
+            | Syscall::COMMAND { driver_number, .. }
+            | Syscall::TYPED_COMMAND { driver_number, .. }
+            | Syscall::ALLOW { driver_number, .. }
+            | Syscall::READ_ONLY_ALLOW { driver_number, .. } => Some(driver_number),
+            Syscall::YIELD | Syscall::MEMOP { .. } => None,
+        }
+    }
+}
+
+/// What a typed command returns to the process.
+///
+/// The kernel passes the result back in four registers: the first holds the
+/// variant's tag, listed below, and the rest hold its values in order. 64-bit
+/// values take two registers, low word first. A failure's `ReturnCode` is
+/// passed as the same negative number a plain command would return.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SyscallReturn {
+    /// Tag 0.
+    Failure(ReturnCode),
+    /// Tag 1.
+    FailureU32(ReturnCode, u32),
+    /// Tag 2.
+    FailureU32U32(ReturnCode, u32, u32),
+    /// Tag 3.
+    FailureU64(ReturnCode, u64),
+    /// Tag 128.
+    Success,
+    /// Tag 129.
+    SuccessU32(u32),
+    /// Tag 130.
+    SuccessU32U32(u32, u32),
+    /// Tag 131.
+    SuccessU64(u64),
+    /// Tag 132.
+    SuccessU32U32U32(u32, u32, u32),
+    /// Tag 133.
+    SuccessU64U32(u64, u32),
+}
+
+impl SyscallReturn {
+    /// The values of the four return registers.
+    pub fn encode(&self) -> [u32; 4] {
+        fn code(rc: ReturnCode) -> u32 {
+            isize::from(rc) as u32
+        }
+        fn low(value: u64) -> u32 {
+            value as u32
+        }
+        fn high(value: u64) -> u32 {
+            (value >> 32) as u32
+        }
+        match *self {
+            SyscallReturn::Failure(rc) => [0, code(rc), 0, 0],
+            SyscallReturn::FailureU32(rc, a) => [1, code(rc), a, 0],
+            SyscallReturn::FailureU32U32(rc, a, b) => [2, code(rc), a, b],
+            SyscallReturn::FailureU64(rc, a) => [3, code(rc), low(a), high(a)],
+            SyscallReturn::Success => [128, 0, 0, 0],
+            SyscallReturn::SuccessU32(a) => [129, a, 0, 0],
+            SyscallReturn::SuccessU32U32(a, b) => [130, a, b, 0],
+            SyscallReturn::SuccessU64(a) => [131, low(a), high(a), 0],
+            SyscallReturn::SuccessU32U32U32(a, b, c) => [132, a, b, c],
+            SyscallReturn::SuccessU64U32(a, b) => [133, low(a), high(a), b],
+        }
+    }
+
+    /// The value a plain command with the same result would have returned,
+    /// dropping any values after the first. Used for syscall traces.
+    crate fn summary(&self) -> isize {
+        match *self {
+            SyscallReturn::Failure(rc)
+            | SyscallReturn::FailureU32(rc, _)
+            | SyscallReturn::FailureU32U32(rc, _, _)
+            | SyscallReturn::FailureU64(rc, _) => rc.into(),
+            SyscallReturn::Success => 0,
+            SyscallReturn::SuccessU32(a)
+            | SyscallReturn::SuccessU32U32(a, _)
+            | SyscallReturn::SuccessU32U32U32(a, _, _) => a as isize,
+            SyscallReturn::SuccessU64(a) | SyscallReturn::SuccessU64U32(a, _) => a as isize,
+        }
+    }
+}
+
+/// Lets capsules answer a typed command with the result of their plain
+/// command.
+impl From<ReturnCode> for SyscallReturn {
+    fn from(rc: ReturnCode) -> SyscallReturn {
+        match rc {
+            ReturnCode::SUCCESS => SyscallReturn::Success,
+            ReturnCode::SuccessWithValue { value } => SyscallReturn::SuccessU32(value as u32),
+            _ => SyscallReturn::Failure(rc),
+        }
+    }
 }
 
 /// Why the process stopped executing and execution returned to the kernel.
@@ -96,6 +220,17 @@ pub trait UserspaceKernelBoundary {
         return_value: isize,
     );
 
+    /// Set the values a typed command returns to the process, in the four
+    /// registers its arguments were passed in. Like
+    /// `set_syscall_return_value`, this is only called after a process has
+    /// called a syscall.
+    unsafe fn set_syscall_return(
+        &self,
+        stack_pointer: *const usize,
+        state: &mut Self::StoredState,
+        return_value: SyscallReturn,
+    );
+
     /// Set the function that the process should execute when it is resumed.
     /// This has two major uses: 1) sets up the initial function call to
     /// `_start` when the process is started for the very first time; 2) tells
@@ -198,6 +333,18 @@ pub fn arguments_to_syscall(
             operand: r0,
             arg0: r1,
         }),
+        5 => Some(Syscall::READ_ONLY_ALLOW {
+            driver_number: r0,
+            subdriver_number: r1,
+            allow_address: r2 as *const u8,
+            allow_size: r3,
+        }),
+        6 => Some(Syscall::TYPED_COMMAND {
+            driver_number: r0,
+            subdriver_number: r1,
+            arg0: r2,
+            arg1: r3,
+        }),
         _ => None,
     }
 }
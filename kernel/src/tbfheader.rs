@@ -28,7 +28,11 @@ crate enum TbfHeaderTypes {
     TbfHeaderMain = 1,
     TbfHeaderWriteableFlashRegions = 2,
     TbfHeaderPackageName = 3,
-    Unused = 5,
+    TbfHeaderScheduling = 5,
+    TbfHeaderBinaryEnd = 6,
+    TbfHeaderDriverPermissions = 7,
+    TbfHeaderTaskQueue = 8,
+    Unused = 9,
 }
 
 /// The TLV header (T and L).
@@ -62,6 +66,97 @@ crate struct TbfHeaderV2WriteableFlashRegion {
     writeable_flash_region_size: u32,
 }
 
+/// Scheduling parameters the app requests from the kernel.
+///
+/// A `timeslice_us` of 0 means the app does not request a specific timeslice
+/// and the scheduler's default is used.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+crate struct TbfHeaderV2Scheduling {
+    priority: u32,
+    timeslice_us: u32,
+}
+
+/// Offset from the start of the app's flash region to the end of the program
+/// binary.
+///
+/// Everything after this offset, up to `total_size`, is a sequence of footer
+/// TLVs. Apps without this element have no footers.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+crate struct TbfHeaderV2BinaryEnd {
+    binary_end_offset: u32,
+}
+
+/// How the kernel queues callbacks for the app.
+///
+/// A `depth` of 0 means the app does not request a specific depth and the
+/// board's default is used.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+crate struct TbfHeaderV2TaskQueue {
+    depth: u32,
+    flags: u32,
+}
+
+/// Flag in `TbfHeaderV2TaskQueue` that makes a new callback replace a pending
+/// callback for the same subscription when the queue is full.
+const TASK_QUEUE_FLAG_COALESCE: u32 = 0x1;
+
+/// Types in TLV structures for each footer after the program binary.
+#[repr(u16)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+crate enum TbfFooterTypes {
+    TbfFooterCredentials = 128,
+}
+
+/// Formats of credentials that can be stored in a credentials footer.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TbfFooterV2CredentialsType {
+    /// SHA-256 hash of the integrity region (32 bytes).
+    Sha256 = 1,
+    // 2 is reserved for ECDSA NIST P-256 signatures, which no checker
+    // supports yet.
+    /// Ed25519 signature of the integrity region (64 bytes).
+    Ed25519 = 3,
+}
+
+impl TbfFooterV2CredentialsType {
+    fn from_u32(format: u32) -> Option<TbfFooterV2CredentialsType> {
+        match format {
+            1 => Some(TbfFooterV2CredentialsType::Sha256),
+            3 => Some(TbfFooterV2CredentialsType::Ed25519),
+            _ => None,
+        }
+    }
+
+    /// Number of bytes of credential data this format requires.
+    fn data_len(&self) -> usize {
+        match *self {
+            TbfFooterV2CredentialsType::Sha256 => 32,
+            TbfFooterV2CredentialsType::Ed25519 => 64,
+        }
+    }
+}
+
+/// A credentials footer, e.g. a hash or signature covering the app's
+/// integrity region (its header and program binary).
+#[derive(Clone, Copy, Debug)]
+pub struct TbfFooterV2Credentials {
+    format: TbfFooterV2CredentialsType,
+    data: &'static [u8],
+}
+
+impl TbfFooterV2Credentials {
+    pub fn format(&self) -> TbfFooterV2CredentialsType {
+        self.format
+    }
+
+    pub fn data(&self) -> &'static [u8] {
+        self.data
+    }
+}
+
 /// Single header that can contain all parts of a v2 header.
 #[derive(Clone, Copy, Debug)]
 crate struct TbfHeaderV2 {
@@ -69,6 +164,10 @@ crate struct TbfHeaderV2 {
     main: Option<&'static TbfHeaderV2Main>,
     package_name: Option<&'static str>,
     writeable_regions: Option<&'static [TbfHeaderV2WriteableFlashRegion]>,
+    scheduling: Option<&'static TbfHeaderV2Scheduling>,
+    binary_end: Option<&'static TbfHeaderV2BinaryEnd>,
+    driver_permissions: Option<&'static [u32]>,
+    task_queue: Option<&'static TbfHeaderV2TaskQueue>,
 }
 
 /// Type that represents the fields of the Tock Binary Format header.
@@ -152,6 +251,76 @@ impl TbfHeader {
         }
     }
 
+    /// Get the scheduling priority the app requested, if any. Lower values are
+    /// higher priority.
+    crate fn get_priority(&self) -> Option<u32> {
+        match *self {
+            TbfHeader::TbfHeaderV2(hd) => hd.scheduling.map(|s| s.priority),
+            _ => None,
+        }
+    }
+
+    /// Get the timeslice in microseconds the app requested, if any.
+    crate fn get_timeslice_us(&self) -> Option<u32> {
+        match *self {
+            TbfHeader::TbfHeaderV2(hd) => hd.scheduling.and_then(|s| {
+                if s.timeslice_us == 0 {
+                    None
+                } else {
+                    Some(s.timeslice_us)
+                }
+            }),
+            _ => None,
+        }
+    }
+
+    /// Get the number of callbacks the app requested the kernel be able to
+    /// queue for it, if any.
+    crate fn get_task_queue_depth(&self) -> Option<u32> {
+        match *self {
+            TbfHeader::TbfHeaderV2(hd) => {
+                hd.task_queue
+                    .and_then(|q| if q.depth == 0 { None } else { Some(q.depth) })
+            }
+            _ => None,
+        }
+    }
+
+    /// Check whether the app asked that a new callback replace a pending one
+    /// for the same subscription, rather than be dropped, when its task queue
+    /// is full.
+    crate fn coalesce_callbacks(&self) -> bool {
+        match *self {
+            TbfHeader::TbfHeaderV2(hd) => hd
+                .task_queue
+                .map_or(false, |q| q.flags & TASK_QUEUE_FLAG_COALESCE != 0),
+            _ => false,
+        }
+    }
+
+    /// Get the offset from the beginning of the app's flash region to the end
+    /// of the program binary. The header and binary before this offset are
+    /// covered by the app's credentials; the footers follow it.
+    crate fn get_binary_end(&self) -> u32 {
+        match *self {
+            TbfHeader::TbfHeaderV2(hd) => hd
+                .binary_end
+                .map_or(hd.base.total_size, |b| b.binary_end_offset),
+            _ => 0,
+        }
+    }
+
+    /// Check whether the app may use the driver with number `driver_num`. Apps
+    /// that do not list the drivers they use may use all of them.
+    crate fn driver_permitted(&self, driver_num: usize) -> bool {
+        match *self {
+            TbfHeader::TbfHeaderV2(hd) => hd.driver_permissions.map_or(true, |permitted| {
+                permitted.iter().any(|&num| num as usize == driver_num)
+            }),
+            _ => false,
+        }
+    }
+
     /// Get the number of flash regions this app has specified in its header.
     crate fn number_writeable_flash_regions(&self) -> usize {
         match *self {
@@ -244,6 +413,10 @@ crate unsafe fn parse_and_validate_tbf_header(address: *const u8) -> Option<TbfH
                 // options.
                 let mut main_pointer: Option<&TbfHeaderV2Main> = None;
                 let mut wfr_pointer: Option<&'static [TbfHeaderV2WriteableFlashRegion]> = None;
+                let mut scheduling_pointer: Option<&TbfHeaderV2Scheduling> = None;
+                let mut binary_end_pointer: Option<&TbfHeaderV2BinaryEnd> = None;
+                let mut driver_permissions_pointer: Option<&'static [u32]> = None;
+                let mut task_queue_pointer: Option<&TbfHeaderV2TaskQueue> = None;
                 let mut app_name_str = "";
 
                 // Loop through the header looking for known options.
@@ -303,6 +476,68 @@ crate unsafe fn parse_and_validate_tbf_header(address: *const u8) -> Option<TbfH
                                         });
                                 }
                             }
+                            TbfHeaderTypes::TbfHeaderScheduling =>
+                            /* Scheduling */
+                            {
+                                if remaining_length >= mem::size_of::<TbfHeaderV2Scheduling>()
+                                    && tbf_tlv_header.length as usize
+                                        == mem::size_of::<TbfHeaderV2Scheduling>()
+                                {
+                                    let tbf_scheduling =
+                                        &*(address.offset(offset) as *const TbfHeaderV2Scheduling);
+                                    scheduling_pointer = Some(tbf_scheduling);
+                                }
+                            }
+                            TbfHeaderTypes::TbfHeaderBinaryEnd =>
+                            /* Binary End */
+                            {
+                                if remaining_length >= mem::size_of::<TbfHeaderV2BinaryEnd>()
+                                    && tbf_tlv_header.length as usize
+                                        == mem::size_of::<TbfHeaderV2BinaryEnd>()
+                                {
+                                    let tbf_binary_end =
+                                        &*(address.offset(offset) as *const TbfHeaderV2BinaryEnd);
+                                    // The binary must end after the header,
+                                    // inside the app, and footers must be word
+                                    // aligned.
+                                    let end = tbf_binary_end.binary_end_offset;
+                                    if end >= tbf_header_base.header_size as u32
+                                        && end <= tbf_header_base.total_size
+                                        && end % 4 == 0
+                                    {
+                                        binary_end_pointer = Some(tbf_binary_end);
+                                    }
+                                }
+                            }
+                            TbfHeaderTypes::TbfHeaderDriverPermissions =>
+                            /* Driver Permissions */
+                            {
+                                // Length must be a multiple of the size of a
+                                // driver number.
+                                if remaining_length >= tbf_tlv_header.length as usize
+                                    && tbf_tlv_header.length as usize % mem::size_of::<u32>() == 0
+                                {
+                                    let number_drivers =
+                                        tbf_tlv_header.length as usize / mem::size_of::<u32>();
+                                    let drivers = slice::from_raw_parts(
+                                        address.offset(offset) as *const u32,
+                                        number_drivers,
+                                    );
+                                    driver_permissions_pointer = Some(drivers);
+                                }
+                            }
+                            TbfHeaderTypes::TbfHeaderTaskQueue =>
+                            /* Task Queue */
+                            {
+                                if remaining_length >= mem::size_of::<TbfHeaderV2TaskQueue>()
+                                    && tbf_tlv_header.length as usize
+                                        == mem::size_of::<TbfHeaderV2TaskQueue>()
+                                {
+                                    let tbf_task_queue =
+                                        &*(address.offset(offset) as *const TbfHeaderV2TaskQueue);
+                                    task_queue_pointer = Some(tbf_task_queue);
+                                }
+                            }
                             TbfHeaderTypes::Unused => {}
                         }
                     }
@@ -318,6 +553,10 @@ crate unsafe fn parse_and_validate_tbf_header(address: *const u8) -> Option<TbfH
                     main: main_pointer,
                     package_name: Some(app_name_str),
                     writeable_regions: wfr_pointer,
+                    scheduling: scheduling_pointer,
+                    binary_end: binary_end_pointer,
+                    driver_permissions: driver_permissions_pointer,
+                    task_queue: task_queue_pointer,
                 };
 
                 Some(TbfHeader::TbfHeaderV2(tbf_header))
@@ -329,3 +568,76 @@ crate unsafe fn parse_and_validate_tbf_header(address: *const u8) -> Option<TbfH
         _ => None,
     }
 }
+
+/// Iterator over the credentials footers of an app.
+///
+/// Footers are TLVs laid out between the end of the program binary and the end
+/// of the app. Footers of unknown type and credentials in unknown formats or
+/// with the wrong length are skipped. Iteration stops at the first malformed
+/// TLV.
+crate struct TbfFooterCredentialsIter {
+    address: *const u8,
+    offset: usize,
+    end: usize,
+}
+
+impl TbfFooterCredentialsIter {
+    /// Create an iterator over the footers of the app at `address` described
+    /// by `header`.
+    ///
+    /// The caller must ensure that the header was parsed from `address` and
+    /// that the entire app lies in readable memory.
+    crate unsafe fn new(address: *const u8, header: &TbfHeader) -> TbfFooterCredentialsIter {
+        TbfFooterCredentialsIter {
+            address: address,
+            offset: header.get_binary_end() as usize,
+            end: header.get_total_size() as usize,
+        }
+    }
+}
+
+impl Iterator for TbfFooterCredentialsIter {
+    type Item = TbfFooterV2Credentials;
+
+    #[allow(clippy::cast_ptr_alignment)]
+    fn next(&mut self) -> Option<TbfFooterV2Credentials> {
+        while self.offset + mem::size_of::<TbfHeaderTlv>() <= self.end {
+            // Footer TLVs share the header TLV layout, but their type is read
+            // as a raw `u16` since footer types are not `TbfHeaderTypes`.
+            let (tipe, length) = unsafe {
+                let tlv = self.address.add(self.offset) as *const u16;
+                (*tlv, *tlv.offset(1) as usize)
+            };
+            let data_offset = self.offset + mem::size_of::<TbfHeaderTlv>();
+            if data_offset + length > self.end {
+                // Malformed footer, stop looking for more.
+                self.offset = self.end;
+                return None;
+            }
+            self.offset = data_offset + align4!(length);
+
+            if tipe != TbfFooterTypes::TbfFooterCredentials as u16 || length < mem::size_of::<u32>()
+            {
+                continue;
+            }
+
+            let format = unsafe { *(self.address.add(data_offset) as *const u32) };
+            if let Some(format) = TbfFooterV2CredentialsType::from_u32(format) {
+                let data_len = length - mem::size_of::<u32>();
+                if data_len == format.data_len() {
+                    let data = unsafe {
+                        slice::from_raw_parts(
+                            self.address.add(data_offset + mem::size_of::<u32>()),
+                            data_len,
+                        )
+                    };
+                    return Some(TbfFooterV2Credentials {
+                        format: format,
+                        data: data,
+                    });
+                }
+            }
+        }
+        None
+    }
+}
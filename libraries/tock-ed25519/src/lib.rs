@@ -0,0 +1,401 @@
+//! Ed25519 signature verification.
+//!
+//! This crate verifies Ed25519 signatures as described in RFC 8032. It can't
+//! create signatures. The arithmetic is ported from TweetNaCl; see the README
+//! for the upstream source and for the notes of the constant-time review.
+
+#![no_std]
+
+mod sha512;
+
+use crate::sha512::Sha512;
+
+// Arithmetic modulo 2^255 - 19. An element is 16 limbs of 16 bits, kept in
+// i64s so that products can be summed before carrying.
+
+type Gf = [i64; 16];
+
+const GF0: Gf = [0; 16];
+const GF1: Gf = [1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+
+/// The curve constant d.
+const D: Gf = [
+    0x78a3, 0x1359, 0x4dca, 0x75eb, 0xd8ab, 0x4141, 0x0a4d, 0x0070, 0xe898, 0x7779, 0x4079, 0x8cc7,
+    0xfe73, 0x2b6f, 0x6cee, 0x5203,
+];
+
+/// 2 * d.
+const D2: Gf = [
+    0xf159, 0x26b2, 0x9b94, 0xebd6, 0xb156, 0x8283, 0x149a, 0x00e0, 0xd130, 0xeef3, 0x80f2, 0x198e,
+    0xfce7, 0x56df, 0xd9dc, 0x2406,
+];
+
+/// The x coordinate of the base point.
+const X: Gf = [
+    0xd51a, 0x8f25, 0x2d60, 0xc956, 0xa7b2, 0x9525, 0xc760, 0x692c, 0xdc5c, 0xfdd6, 0xe231, 0xc0a4,
+    0x53fe, 0xcd6e, 0x36d3, 0x2169,
+];
+
+/// The y coordinate of the base point.
+const Y: Gf = [
+    0x6658, 0x6666, 0x6666, 0x6666, 0x6666, 0x6666, 0x6666, 0x6666, 0x6666, 0x6666, 0x6666, 0x6666,
+    0x6666, 0x6666, 0x6666, 0x6666,
+];
+
+/// A square root of -1.
+const I: Gf = [
+    0xa0b0, 0x4a0e, 0x1b27, 0xc4ee, 0xe478, 0xad2f, 0x1806, 0x2f43, 0xd7a7, 0x3dfb, 0x0099, 0x2b4d,
+    0xdf0b, 0x4fc1, 0x2480, 0x2b83,
+];
+
+/// The order of the base point, little endian.
+const L: [i64; 32] = [
+    0xed, 0xd3, 0xf5, 0x5c, 0x1a, 0x63, 0x12, 0x58, 0xd6, 0x9c, 0xf7, 0xa2, 0xde, 0xf9, 0xde, 0x14,
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x10,
+];
+
+fn car25519(o: &mut Gf) {
+    for i in 0..16 {
+        o[i] += 1 << 16;
+        let c = o[i] >> 16;
+        if i < 15 {
+            o[i + 1] += c - 1;
+        } else {
+            o[0] += 38 * (c - 1);
+        }
+        o[i] -= c << 16;
+    }
+}
+
+/// Swap `p` and `q` if `b` is 1, in constant time.
+fn sel25519(p: &mut Gf, q: &mut Gf, b: i64) {
+    let c = !(b - 1);
+    for i in 0..16 {
+        let t = c & (p[i] ^ q[i]);
+        p[i] ^= t;
+        q[i] ^= t;
+    }
+}
+
+fn pack25519(n: &Gf) -> [u8; 32] {
+    let mut t = *n;
+    car25519(&mut t);
+    car25519(&mut t);
+    car25519(&mut t);
+    for _ in 0..2 {
+        let mut m = GF0;
+        m[0] = t[0] - 0xffed;
+        for i in 1..15 {
+            m[i] = t[i] - 0xffff - ((m[i - 1] >> 16) & 1);
+            m[i - 1] &= 0xffff;
+        }
+        m[15] = t[15] - 0x7fff - ((m[14] >> 16) & 1);
+        let b = (m[15] >> 16) & 1;
+        m[14] &= 0xffff;
+        sel25519(&mut t, &mut m, 1 - b);
+    }
+    let mut o = [0u8; 32];
+    for i in 0..16 {
+        o[2 * i] = t[i] as u8;
+        o[2 * i + 1] = (t[i] >> 8) as u8;
+    }
+    o
+}
+
+fn neq25519(a: &Gf, b: &Gf) -> bool {
+    pack25519(a) != pack25519(b)
+}
+
+fn par25519(a: &Gf) -> u8 {
+    pack25519(a)[0] & 1
+}
+
+fn unpack25519(n: &[u8; 32]) -> Gf {
+    let mut o = GF0;
+    for i in 0..16 {
+        o[i] = n[2 * i] as i64 + ((n[2 * i + 1] as i64) << 8);
+    }
+    o[15] &= 0x7fff;
+    o
+}
+
+fn add25519(a: &Gf, b: &Gf) -> Gf {
+    let mut o = GF0;
+    for i in 0..16 {
+        o[i] = a[i] + b[i];
+    }
+    o
+}
+
+fn sub25519(a: &Gf, b: &Gf) -> Gf {
+    let mut o = GF0;
+    for i in 0..16 {
+        o[i] = a[i] - b[i];
+    }
+    o
+}
+
+fn mul25519(a: &Gf, b: &Gf) -> Gf {
+    let mut t = [0i64; 31];
+    for i in 0..16 {
+        for j in 0..16 {
+            t[i + j] += a[i] * b[j];
+        }
+    }
+    for i in 0..15 {
+        t[i] += 38 * t[i + 16];
+    }
+    let mut o = GF0;
+    o.copy_from_slice(&t[..16]);
+    car25519(&mut o);
+    car25519(&mut o);
+    o
+}
+
+fn square25519(a: &Gf) -> Gf {
+    mul25519(a, a)
+}
+
+fn inv25519(i: &Gf) -> Gf {
+    let mut c = *i;
+    for a in (0..254).rev() {
+        c = square25519(&c);
+        if a != 2 && a != 4 {
+            c = mul25519(&c, i);
+        }
+    }
+    c
+}
+
+fn pow2523(i: &Gf) -> Gf {
+    let mut c = *i;
+    for a in (0..251).rev() {
+        c = square25519(&c);
+        if a != 1 {
+            c = mul25519(&c, i);
+        }
+    }
+    c
+}
+
+// Points on the curve in extended coordinates (X, Y, Z, T).
+
+type Point = [Gf; 4];
+
+fn add(p: &mut Point, q: &Point) {
+    let a = mul25519(&sub25519(&p[1], &p[0]), &sub25519(&q[1], &q[0]));
+    let b = mul25519(&add25519(&p[0], &p[1]), &add25519(&q[0], &q[1]));
+    let c = mul25519(&mul25519(&p[3], &q[3]), &D2);
+    let d = mul25519(&p[2], &q[2]);
+    let d = add25519(&d, &d);
+    let e = sub25519(&b, &a);
+    let f = sub25519(&d, &c);
+    let g = add25519(&d, &c);
+    let h = add25519(&b, &a);
+
+    p[0] = mul25519(&e, &f);
+    p[1] = mul25519(&h, &g);
+    p[2] = mul25519(&g, &f);
+    p[3] = mul25519(&e, &h);
+}
+
+fn cswap(p: &mut Point, q: &mut Point, b: u8) {
+    for i in 0..4 {
+        sel25519(&mut p[i], &mut q[i], b as i64);
+    }
+}
+
+fn pack(p: &Point) -> [u8; 32] {
+    let zi = inv25519(&p[2]);
+    let tx = mul25519(&p[0], &zi);
+    let ty = mul25519(&p[1], &zi);
+    let mut r = pack25519(&ty);
+    r[31] ^= par25519(&tx) << 7;
+    r
+}
+
+/// Compute `s * q`, where `s` is a little endian scalar.
+fn scalarmult(q: &Point, s: &[u8; 32]) -> Point {
+    let mut p = [GF0, GF1, GF1, GF0];
+    let mut q = *q;
+    for i in (0..256).rev() {
+        let b = (s[i / 8] >> (i & 7)) & 1;
+        cswap(&mut p, &mut q, b);
+        add(&mut q, &p);
+        let p2 = p;
+        add(&mut p, &p2);
+        cswap(&mut p, &mut q, b);
+    }
+    p
+}
+
+fn scalarbase(s: &[u8; 32]) -> Point {
+    scalarmult(&[X, Y, GF1, mul25519(&X, &Y)], s)
+}
+
+/// Reduce the 512-bit little endian number `x` modulo `L`.
+fn mod_l(x: &mut [i64; 64]) -> [u8; 32] {
+    for i in (32..64).rev() {
+        let mut carry = 0;
+        for j in (i - 32)..(i - 12) {
+            x[j] += carry - 16 * x[i] * L[j - (i - 32)];
+            carry = (x[j] + 128) >> 8;
+            x[j] -= carry << 8;
+        }
+        x[i - 12] += carry;
+        x[i] = 0;
+    }
+    let mut carry = 0;
+    for j in 0..32 {
+        x[j] += carry - (x[31] >> 4) * L[j];
+        carry = x[j] >> 8;
+        x[j] &= 255;
+    }
+    for j in 0..32 {
+        x[j] -= carry * L[j];
+    }
+    let mut r = [0u8; 32];
+    for i in 0..32 {
+        x[i + 1] += x[i] >> 8;
+        r[i] = x[i] as u8;
+    }
+    r
+}
+
+/// Decode the public key `p` and negate it, as verification needs -A.
+fn unpackneg(p: &[u8; 32]) -> Option<Point> {
+    let mut r = [GF0, unpack25519(p), GF1, GF0];
+
+    let num = square25519(&r[1]);
+    let den = mul25519(&num, &D);
+    let num = sub25519(&num, &r[2]);
+    let den = add25519(&r[2], &den);
+
+    let den2 = square25519(&den);
+    let den4 = square25519(&den2);
+    let den6 = mul25519(&den4, &den2);
+    let t = mul25519(&mul25519(&den6, &num), &den);
+
+    let t = pow2523(&t);
+    let t = mul25519(&mul25519(&mul25519(&t, &num), &den), &den);
+    r[0] = mul25519(&t, &den);
+
+    if neq25519(&mul25519(&square25519(&r[0]), &den), &num) {
+        r[0] = mul25519(&r[0], &I);
+    }
+    if neq25519(&mul25519(&square25519(&r[0]), &den), &num) {
+        return None;
+    }
+
+    if par25519(&r[0]) == (p[31] >> 7) {
+        r[0] = sub25519(&GF0, &r[0]);
+    }
+    r[3] = mul25519(&r[0], &r[1]);
+    Some(r)
+}
+
+/// Whether the scalar `s` is less than `L`, as RFC 8032 requires of the second
+/// half of a signature.
+fn is_canonical(s: &[u8]) -> bool {
+    for i in (0..32).rev() {
+        if (s[i] as i64) < L[i] {
+            return true;
+        } else if (s[i] as i64) > L[i] {
+            return false;
+        }
+    }
+    false
+}
+
+/// Verify the Ed25519 `signature` of `message` under `public_key`.
+pub fn verify(public_key: &[u8; 32], message: &[u8], signature: &[u8; 64]) -> bool {
+    if !is_canonical(&signature[32..]) {
+        return false;
+    }
+    let q = match unpackneg(public_key) {
+        Some(q) => q,
+        None => return false,
+    };
+
+    let mut sha = Sha512::new();
+    sha.update(&signature[..32]);
+    sha.update(public_key);
+    sha.update(message);
+    let h = sha.finish();
+    let mut x = [0i64; 64];
+    for i in 0..64 {
+        x[i] = h[i] as i64;
+    }
+    let h = mod_l(&mut x);
+
+    // Check that [S]B - [h]A equals R.
+    let mut s = [0u8; 32];
+    s.copy_from_slice(&signature[32..]);
+    let mut p = scalarmult(&q, &h);
+    add(&mut p, &scalarbase(&s));
+    pack(&p)[..] == signature[..32]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from_hex(s: &str, out: &mut [u8]) {
+        for (i, byte) in out.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&s[2 * i..2 * i + 2], 16).unwrap();
+        }
+    }
+
+    /// Check a test vector from RFC 8032, section 7.1, and that changing the
+    /// message or signature makes it fail.
+    fn check_vector(public_key: &str, message: &str, signature: &str) {
+        let mut pk = [0u8; 32];
+        let mut msg = [0u8; 2];
+        let mut sig = [0u8; 64];
+        from_hex(public_key, &mut pk);
+        from_hex(message, &mut msg[..message.len() / 2]);
+        from_hex(signature, &mut sig);
+        let msg = &mut msg[..message.len() / 2];
+
+        assert!(verify(&pk, msg, &sig));
+
+        let mut bad_sig = sig;
+        bad_sig[10] ^= 1;
+        assert!(!verify(&pk, msg, &bad_sig));
+
+        if !msg.is_empty() {
+            msg[0] ^= 1;
+            assert!(!verify(&pk, msg, &sig));
+        }
+    }
+
+    #[test]
+    fn rfc8032_test_1() {
+        check_vector(
+            "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a",
+            "",
+            "e5564300c360ac729086e2cc806e828a84877f1eb8e5d974d873e06522490155\
+             5fb8821590a33bacc61e39701cf9b46bd25bf5f0595bbe24655141438e7a100b",
+        );
+    }
+
+    #[test]
+    fn rfc8032_test_2() {
+        check_vector(
+            "3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c",
+            "72",
+            "92a009a9f0d4cab8720e820b5f642540a2b27b5416503f8fb3762223ebdb69da\
+             085ac1e43e15996e458f3613d0f11d8c387b2eaeb4302aeeb00d291612bb0c00",
+        );
+    }
+
+    #[test]
+    fn rfc8032_test_3() {
+        check_vector(
+            "fc51cd8e6218a1a38da47ed00230f0580816ed13ba3303ac5deb911548908025",
+            "af82",
+            "6291d657deec24024827e69c3abe01a30ce548a284743a445e3680d7db5ac3ac\
+             18ff9b538d16f290ae67f760984dc6594a7c15e9716ed28dc027beceea1ec40a",
+        );
+    }
+}
@@ -0,0 +1,242 @@
+#!/usr/bin/env python3
+
+'''
+Decode kernel debug output that contains `debug_deferred!()` messages.
+
+Deferred messages are sent as the offset of their format string in the
+kernel's `.tock_log_strings` section plus the raw arguments, so they need the
+kernel ELF file the board is running to be read. Everything else in the
+output, such as `debug!()` text, is passed through unchanged.
+
+Usage:
+
+    decode_deferred_log.py target/thumbv7em-none-eabihf/release/hail.elf /dev/ttyUSB0
+    decode_deferred_log.py hail.elf capture.bin
+    cat capture.bin | decode_deferred_log.py hail.elf
+
+See the `debug_deferred!()` section of `kernel/src/debug.rs` for the
+encoding.
+'''
+
+import argparse
+import os
+import re
+import struct
+import sys
+
+SECTION = '.tock_log_strings'
+
+TAG_UNSIGNED = 0
+TAG_SIGNED = 1
+TAG_STR = 2
+
+# Text sinks, such as the RAM log printed when the kernel panics, hold each
+# record as a line of hex rather than as a COBS frame.
+TEXT_RECORD = re.compile(r'\[deferred ([0-9a-f]*)\]\r?\n')
+
+
+def read_format_strings(elf_path):
+    '''
+    Return a map from the address of each format string in the ELF file's
+    `.tock_log_strings` section to the string.
+    '''
+    with open(elf_path, 'rb') as f:
+        elf = f.read()
+
+    if elf[:4] != b'\x7fELF':
+        sys.exit('{} is not an ELF file'.format(elf_path))
+    is_64 = elf[4] == 2
+    endian = '<' if elf[5] == 1 else '>'
+
+    if is_64:
+        shoff, = struct.unpack_from(endian + 'Q', elf, 0x28)
+        shentsize, shnum, shstrndx = struct.unpack_from(endian + 'HHH', elf, 0x3a)
+        section_format = endian + 'IIQQQQ'
+    else:
+        shoff, = struct.unpack_from(endian + 'I', elf, 0x20)
+        shentsize, shnum, shstrndx = struct.unpack_from(endian + 'HHH', elf, 0x2e)
+        section_format = endian + 'IIIIII'
+
+    sections = []
+    for i in range(shnum):
+        name, _type, _flags, addr, offset, size = struct.unpack_from(
+            section_format, elf, shoff + i * shentsize)
+        sections.append((name, addr, offset, size))
+
+    _, _, names_offset, _ = sections[shstrndx]
+    for name, addr, offset, size in sections:
+        end = elf.index(b'\0', names_offset + name)
+        if elf[names_offset + name:end].decode() != SECTION:
+            continue
+
+        # The section is a sequence of null terminated strings.
+        strings = {}
+        start = 0
+        data = elf[offset:offset + size]
+        while start < len(data):
+            end = data.index(b'\0', start)
+            strings[addr + start] = data[start:end].decode('utf-8', 'replace')
+            start = end + 1
+        return strings
+
+    sys.exit('{} has no {} section'.format(elf_path, SECTION))
+
+
+def cobs_decode(encoded):
+    record = bytearray()
+    i = 0
+    while i < len(encoded):
+        code = encoded[i]
+        if code == 0 or i + code > len(encoded):
+            raise ValueError('bad COBS encoding')
+        record += encoded[i + 1:i + code]
+        i += code
+        if i < len(encoded):
+            record.append(0)
+    return bytes(record)
+
+
+def read_varint(record, i):
+    value = 0
+    shift = 0
+    while True:
+        byte = record[i]
+        i += 1
+        value |= (byte & 0x7f) << shift
+        if byte & 0x80 == 0:
+            return value, i
+        shift += 7
+
+
+def decode_arguments(record, i):
+    args = []
+    while i < len(record):
+        tag = record[i]
+        value, i = read_varint(record, i + 1)
+        if tag == TAG_UNSIGNED:
+            args.append(value)
+        elif tag == TAG_SIGNED:
+            args.append((value >> 1) ^ -(value & 1))
+        elif tag == TAG_STR:
+            args.append(record[i:i + value].decode('utf-8', 'replace'))
+            i += value
+        else:
+            raise ValueError('unknown argument tag {}'.format(tag))
+    return args
+
+
+def format_message(fmt, args):
+    '''
+    Format `args` with a Rust format string. Format specs such as `{:x}`,
+    `{:#x}` and `{:08}` mean the same in Python; `?` is ignored. Arguments
+    that did not fit in the record are shown as `?`.
+    '''
+    out = []
+    next_arg = 0
+    i = 0
+    while i < len(fmt):
+        c = fmt[i]
+        if c in '{}' and fmt[i + 1:i + 2] == c:
+            out.append(c)
+            i += 2
+        elif c == '{':
+            end = fmt.index('}', i)
+            name, _, spec = fmt[i + 1:end].partition(':')
+            i = end + 1
+            if name:
+                index = int(name)
+            else:
+                index = next_arg
+                next_arg += 1
+            if index >= len(args):
+                out.append('?')
+                continue
+            try:
+                out.append(format(args[index], spec.replace('?', '')))
+            except ValueError:
+                out.append(str(args[index]))
+        else:
+            out.append(c)
+            i += 1
+    return ''.join(out)
+
+
+def decode_record(strings, record):
+    address, i = read_varint(record, 0)
+    fmt = strings.get(address)
+    if fmt is None:
+        return '<deferred message with unknown format string {:#x}>'.format(address)
+    return format_message(fmt, decode_arguments(record, i))
+
+
+def decode_or_note(strings, record):
+    try:
+        return decode_record(strings, record) + '\r\n'
+    except (ValueError, IndexError):
+        return '<undecodable deferred message>\r\n'
+
+
+def decode_text(strings, text):
+    def replace(match):
+        return decode_or_note(strings, bytes.fromhex(match.group(1)))
+    return TEXT_RECORD.sub(replace, text)
+
+
+def decode_stream(strings, fd, out):
+    frame = None
+    # Text that may be the start of a hex encoded record.
+    pending = ''
+    while True:
+        data = os.read(fd, 4096)
+        if not data:
+            break
+        text = bytearray()
+        for byte in data:
+            if frame is None:
+                if byte == 0:
+                    frame = bytearray()
+                else:
+                    text.append(byte)
+            elif byte == 0:
+                out.write(decode_text(strings, pending + text.decode('utf-8', 'replace')))
+                pending = ''
+                text = bytearray()
+                try:
+                    out.write(decode_or_note(strings, cobs_decode(bytes(frame))))
+                except ValueError:
+                    out.write('<undecodable deferred message>\r\n')
+                frame = None
+            else:
+                frame.append(byte)
+        text = decode_text(strings, pending + text.decode('utf-8', 'replace'))
+        # Hold back a record whose line has not all arrived yet.
+        start = text.rfind('[deferred ')
+        if start == -1 or '\n' in text[start:]:
+            start = len(text)
+        out.write(text[:start])
+        pending = text[start:]
+        out.flush()
+    out.write(pending)
+
+
+def main():
+    parser = argparse.ArgumentParser(
+        description='Decode kernel debug output with deferred messages.')
+    parser.add_argument('elf', help='kernel ELF file the board is running')
+    parser.add_argument('input', nargs='?',
+                        help='captured output or serial device (default: stdin)')
+    args = parser.parse_args()
+
+    strings = read_format_strings(args.elf)
+    if args.input:
+        fd = os.open(args.input, os.O_RDONLY)
+    else:
+        fd = sys.stdin.fileno()
+    try:
+        decode_stream(strings, fd, sys.stdout)
+    except KeyboardInterrupt:
+        pass
+
+
+if __name__ == '__main__':
+    main()
@@ -0,0 +1,41 @@
+#!/usr/bin/env bash
+
+# Fetch libtock-c and build the apps that the QEMU tests load, so that
+# `qemu_test.py --apps-dir tools/qemu-test/libtock-c` runs every test.
+#
+# Needs git, `arm-none-eabi-gcc` with newlib, and `elf2tab`, which is
+# installed with cargo if it is missing.
+#
+# Usage: build_apps.sh [LIBTOCK_C_DIR]
+#
+# LIBTOCK_C_DIR defaults to tools/qemu-test/libtock-c. An existing checkout
+# there is used as is. Otherwise libtock-c is cloned and checked out at the
+# last commit before LIBTOCK_C_DATE, since later versions of libtock-c target
+# a newer system call interface than this kernel's. Set LIBTOCK_C_URL,
+# LIBTOCK_C_DATE or LIBTOCK_C_REV to fetch another repository or revision.
+
+set -e
+
+QEMU_TEST_DIR=$(cd "$(dirname "$0")" && pwd)
+LIBTOCK_C_DIR=${1:-$QEMU_TEST_DIR/libtock-c}
+LIBTOCK_C_URL=${LIBTOCK_C_URL:-https://github.com/tock/libtock-c}
+LIBTOCK_C_DATE=${LIBTOCK_C_DATE:-2019-10-01}
+
+# The architectures of the boards in qemu_test.py that run apps, and the
+# apps that the tests in tests/ load.
+ARCHS="cortex-m0 cortex-m3"
+APPS="examples/c_hello examples/tests/whileone"
+
+if [ ! -d "$LIBTOCK_C_DIR" ]; then
+	git clone --quiet "$LIBTOCK_C_URL" "$LIBTOCK_C_DIR"
+	LIBTOCK_C_REV=${LIBTOCK_C_REV:-$(git -C "$LIBTOCK_C_DIR" rev-list -n 1 --before="$LIBTOCK_C_DATE" HEAD)}
+	git -C "$LIBTOCK_C_DIR" checkout --quiet "$LIBTOCK_C_REV"
+fi
+
+if ! command -v elf2tab > /dev/null; then
+	cargo install elf2tab --version 0.4.0
+fi
+
+for app in $APPS; do
+	make -C "$LIBTOCK_C_DIR/$app" TOCK_ARCHS="$ARCHS"
+done
@@ -0,0 +1,277 @@
+#!/usr/bin/env python3
+
+'''
+Run Tock kernel integration tests in QEMU.
+
+Each test boots a board's kernel, optionally with TBF apps, in QEMU and
+follows a script of steps against the board's console: wait for output that
+matches a regular expression, or send input. A test passes if every step
+completes before the test's timeout and no failure pattern (by default a
+kernel panic) is printed.
+
+Tests are JSON files in the `tests` directory next to this script:
+
+    {
+        "description": "c_hello prints to the console",
+        "boards": ["mps2_an385"],
+        "apps": ["examples/c_hello/build/{arch}/{arch}.tbf"],
+        "timeout": 10,
+        "steps": [
+            {"expect": "Hello World!"},
+            {"send": "list\\r"},
+            {"expect": "c_hello"}
+        ]
+    }
+
+`apps` are paths relative to `--apps-dir`, usually a libtock-c checkout,
+where `{arch}` is replaced with the board's app architecture. Tests that need
+apps are skipped if `--apps-dir` is not given or an app is missing, except
+when the `CI` environment variable is `true`, where they fail so that CI
+cannot pass without running them. `build_apps.sh` fetches libtock-c and
+builds the apps. `fail_on` replaces the default list of failure patterns.
+
+Usage:
+
+    qemu_test.py                          # all tests on all boards
+    qemu_test.py --board mps2_an385 boot  # one test on one board
+    qemu_test.py --apps-dir ../libtock-c  # include tests with apps
+    build_apps.sh && qemu_test.py --apps-dir tools/qemu-test/libtock-c
+
+The kernels are built with `make` first, unless `--no-build` is given.
+'''
+
+import argparse
+import glob
+import json
+import os
+import re
+import select
+import subprocess
+import sys
+import tempfile
+import time
+
+TOCK_ROOT = os.path.abspath(os.path.join(os.path.dirname(__file__), '..', '..'))
+TESTS_DIR = os.path.join(os.path.dirname(os.path.abspath(__file__)), 'tests')
+
+# How to run each board in QEMU. `app_address` is the start of app flash,
+# which must match the board's linker script.
+BOARDS = {
+    'hifive1': {
+        'target': 'riscv32imac-unknown-none-elf',
+        'arch': 'rv32imac',
+        'qemu': ['qemu-system-riscv32', '-machine', 'sifive_e'],
+        'app_address': 0x20430000,
+    },
+    'mps2_an385': {
+        'target': 'thumbv7m-none-eabi',
+        'arch': 'cortex-m3',
+        'qemu': ['qemu-system-arm', '-machine', 'mps2-an385'],
+        'app_address': 0x00040000,
+    },
+    'microbit': {
+        'target': 'thumbv6m-none-eabi',
+        'arch': 'cortex-m0',
+        'qemu': ['qemu-system-arm', '-machine', 'microbit'],
+        'app_address': 0x00030000,
+    },
+}
+
+DEFAULT_FAIL_ON = ['Kernel panic']
+DEFAULT_TIMEOUT = 10
+
+
+class TestFailure(Exception):
+    pass
+
+
+class TestSkipped(Exception):
+    pass
+
+
+def missing_apps(reason):
+    '''
+    The exception for a test whose apps are missing: a skip, or in CI, where
+    every test must run, a failure.
+    '''
+    if os.environ.get('CI') == 'true':
+        return TestFailure(reason, '')
+    return TestSkipped(reason)
+
+
+def kernel_path(board):
+    return os.path.join(TOCK_ROOT, 'boards', board, 'target',
+                        BOARDS[board]['target'], 'release', board + '.elf')
+
+
+def build_kernel(board):
+    subprocess.check_call(['make', '-C', os.path.join(TOCK_ROOT, 'boards', board)])
+
+
+def load_test(path):
+    with open(path) as f:
+        test = json.load(f)
+    test['name'] = os.path.splitext(os.path.basename(path))[0]
+    return test
+
+
+def app_image(test, board, apps_dir, tmpdir):
+    '''
+    Return the path of one file holding all of the test's apps back to back,
+    in the order given, or None if the test has no apps.
+    '''
+    if not test.get('apps'):
+        return None
+    if apps_dir is None:
+        raise missing_apps('needs --apps-dir')
+
+    image = os.path.join(tmpdir, 'apps.tbf')
+    with open(image, 'wb') as out:
+        for app in test['apps']:
+            path = os.path.join(apps_dir, app.format(arch=BOARDS[board]['arch']))
+            if not os.path.exists(path):
+                raise missing_apps('{} not found'.format(path))
+            with open(path, 'rb') as f:
+                out.write(f.read())
+    return image
+
+
+class Qemu:
+    '''
+    A running QEMU with the board's first UART on its stdin and stdout.
+    '''
+
+    def __init__(self, board, apps):
+        config = BOARDS[board]
+        command = config['qemu'] + [
+            '-display', 'none', '-monitor', 'none', '-serial', 'stdio',
+            '-kernel', kernel_path(board),
+        ]
+        if apps is not None:
+            command += ['-device', 'loader,file={},addr={:#x}'.format(
+                apps, config['app_address'])]
+
+        try:
+            self.process = subprocess.Popen(command, stdin=subprocess.PIPE,
+                                            stdout=subprocess.PIPE,
+                                            stderr=subprocess.STDOUT)
+        except FileNotFoundError:
+            raise TestFailure('{} not found'.format(command[0]), '')
+        self.output = ''
+
+    def read(self, until):
+        '''
+        Wait for more output, up to the time `until`. Returns False if QEMU
+        has exited.
+        '''
+        remaining = until - time.monotonic()
+        if remaining <= 0:
+            return True
+        ready, _, _ = select.select([self.process.stdout], [], [], remaining)
+        if ready:
+            data = os.read(self.process.stdout.fileno(), 4096)
+            if not data:
+                return False
+            self.output += data.decode('utf-8', errors='replace')
+        return True
+
+    def send(self, text):
+        self.process.stdin.write(text.encode('utf-8'))
+        self.process.stdin.flush()
+
+    def stop(self):
+        self.process.kill()
+        self.process.wait()
+
+
+def run_test(test, board, apps_dir):
+    '''
+    Run `test` on `board`. Returns the console output, or raises TestFailure
+    with the output so far.
+    '''
+    fail_on = [re.compile(p) for p in test.get('fail_on', DEFAULT_FAIL_ON)]
+    deadline = time.monotonic() + test.get('timeout', DEFAULT_TIMEOUT)
+
+    with tempfile.TemporaryDirectory() as tmpdir:
+        qemu = Qemu(board, app_image(test, board, apps_dir, tmpdir))
+        try:
+            # Expectations are matched in order, each after the end of the
+            # previous match.
+            position = 0
+            for step in test['steps']:
+                if 'send' in step:
+                    qemu.send(step['send'])
+                    continue
+
+                expect = re.compile(step['expect'])
+                while True:
+                    for pattern in fail_on:
+                        if pattern.search(qemu.output):
+                            raise TestFailure('printed "{}"'.format(pattern.pattern),
+                                              qemu.output)
+                    match = expect.search(qemu.output, position)
+                    if match:
+                        position = match.end()
+                        break
+                    if time.monotonic() >= deadline:
+                        raise TestFailure('timed out waiting for "{}"'.format(
+                            expect.pattern), qemu.output)
+                    if not qemu.read(deadline):
+                        raise TestFailure('QEMU exited waiting for "{}"'.format(
+                            expect.pattern), qemu.output)
+            return qemu.output
+        finally:
+            qemu.stop()
+
+
+def main():
+    parser = argparse.ArgumentParser(description=__doc__.split('\n\n')[0].strip())
+    parser.add_argument('tests', nargs='*',
+                        help='names of the tests to run, default all')
+    parser.add_argument('--board', action='append', choices=sorted(BOARDS),
+                        help='board to test on, can be repeated, default all')
+    parser.add_argument('--apps-dir',
+                        help='directory that app paths in tests are relative to')
+    parser.add_argument('--no-build', action='store_true',
+                        help='use the kernels that are already built')
+    parser.add_argument('--verbose', '-v', action='store_true',
+                        help='print the console output of every test')
+    args = parser.parse_args()
+
+    tests = [load_test(path) for path in sorted(glob.glob(os.path.join(TESTS_DIR, '*.json')))]
+    if args.tests:
+        unknown = set(args.tests) - set(t['name'] for t in tests)
+        if unknown:
+            parser.error('unknown tests: {}'.format(', '.join(sorted(unknown))))
+        tests = [t for t in tests if t['name'] in args.tests]
+
+    boards = args.board or sorted(BOARDS)
+    runs = [(test, board) for test in tests for board in test['boards'] if board in boards]
+
+    if not args.no_build:
+        for board in sorted(set(board for _, board in runs)):
+            build_kernel(board)
+
+    results = {'PASS': 0, 'FAIL': 0, 'SKIP': 0}
+    for test, board in runs:
+        try:
+            output = run_test(test, board, args.apps_dir)
+            result, reason = 'PASS', ''
+        except TestSkipped as e:
+            output, result, reason = '', 'SKIP', str(e)
+        except TestFailure as e:
+            output, result, reason = e.args[1], 'FAIL', e.args[0]
+
+        results[result] += 1
+        print('{}  {:<12} {:<24} {}'.format(result, board, test['name'], reason).rstrip())
+        if output and (result == 'FAIL' or args.verbose):
+            for line in output.splitlines():
+                print('    | ' + line)
+
+    print('\n{} passed, {} failed, {} skipped'.format(
+        results['PASS'], results['FAIL'], results['SKIP']))
+    return 1 if results['FAIL'] else 0
+
+
+if __name__ == '__main__':
+    sys.exit(main())
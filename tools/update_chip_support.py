@@ -19,7 +19,8 @@ import os
 import re
 
 # Static info of chip crates that just support other chips.
-SUBSUMES = {'nrf52': ['nrf5x'],
+SUBSUMES = {'nrf51': ['nrf5x'],
+            'nrf52': ['nrf5x'],
             'e310x': ['sifive'],
             'arty_e21': ['sifive']}
 